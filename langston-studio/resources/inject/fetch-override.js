@@ -0,0 +1,144 @@
+// Injected into OpenCode's HTML by the proxy (see scripts.json).
+//
+// Overrides window.fetch for mutating HTTP methods (POST, PUT, PATCH,
+// DELETE) so those requests are relayed via postMessage to the parent Tauri
+// webview. The parent executes them through Rust's reqwest with a long
+// timeout, completely bypassing WKWebView's ~60s idle connection kill.
+// GET/HEAD/OPTIONS requests continue through native fetch (they're fast and
+// don't trigger the timeout issue).
+//
+// Response bodies come back as tauri-fetch-chunk messages as they arrive
+// and are fed into a ReadableStream, so a streamed reply (sending a message
+// and reading the answer as it's generated) is readable incrementally.
+// Chunks can overtake the tauri-fetch-response carrying the status and
+// headers; the stream buffers them until it's read.
+//
+// The timeout placeholder below is filled in with the proxy's read timeout.
+(function() {
+  var _origFetch = window.fetch;
+  var _pending = {};
+  var _timeoutSecs = __READ_TIMEOUT_SECS__;
+
+  function decodeChunk(data) {
+    var raw = atob(data);
+    var bytes = new Uint8Array(raw.length);
+    for (var i = 0; i < raw.length; i++) bytes[i] = raw.charCodeAt(i);
+    return bytes;
+  }
+
+  // Forget a request once both its head and the end of its body are in.
+  function settle(id) {
+    var p = _pending[id];
+    if (p && p.head && p.done) delete _pending[id];
+  }
+
+  window.addEventListener('message', function(e) {
+    if (!e.data || !e.data.id) return;
+    var p = _pending[e.data.id];
+    if (!p) return;
+    if (e.data.type === 'tauri-fetch-response') {
+      p.head = true;
+      if (!e.data.streamed) p.done = true;
+      p.resolve(e.data);
+      settle(e.data.id);
+    }
+    if (e.data.type === 'tauri-fetch-chunk') {
+      try {
+        if (e.data.data) p.controller.enqueue(decodeChunk(e.data.data));
+        if (e.data.done) {
+          if (e.data.error) p.controller.error(new Error(e.data.error));
+          else p.controller.close();
+        }
+      } catch (ex) {
+        // The page cancelled the stream; drop the rest.
+      }
+      if (e.data.done) {
+        p.done = true;
+        settle(e.data.id);
+      }
+    }
+    if (e.data.type === 'tauri-fetch-error') {
+      p.reject(new Error(e.data.error));
+      try { p.controller.error(new Error(e.data.error)); } catch (ex) {}
+      delete _pending[e.data.id];
+    }
+  });
+
+  window.fetch = function(input, init) {
+    var method = (init && init.method) ? init.method.toUpperCase() : 'GET';
+    // Only intercept mutating methods — these are the ones that can block
+    // for minutes while the LLM processes. GETs are fast or use SSE (streaming).
+    if (method === 'GET' || method === 'HEAD' || method === 'OPTIONS') {
+      return _origFetch.call(window, input, init);
+    }
+
+    // If we're not in an iframe (no parent), fall back to native fetch
+    if (window === window.parent) {
+      return _origFetch.call(window, input, init);
+    }
+
+    var url = typeof input === 'string' ? input : (input && input.url ? input.url : String(input));
+    // Make relative URLs absolute
+    if (url.startsWith('/')) {
+      url = window.location.origin + url;
+    }
+
+    var id = Math.random().toString(36).slice(2) + Date.now().toString(36);
+    var headers = {};
+    if (init && init.headers) {
+      try {
+        var h = new Headers(init.headers);
+        h.forEach(function(v, k) { headers[k] = v; });
+      } catch(ex) {
+        // If headers aren't iterable, skip
+      }
+    }
+
+    var body = (init && init.body) ? init.body : null;
+    // Convert body to string if it's not already
+    if (body && typeof body !== 'string') {
+      try { body = JSON.stringify(body); } catch(ex) { body = String(body); }
+    }
+
+    console.log('[tauri-fetch] Relaying ' + method + ' ' + url + ' via postMessage (id: ' + id + ')');
+
+    var stream;
+    return new Promise(function(resolve, reject) {
+      var entry = { resolve: resolve, reject: reject, controller: null, head: false, done: false };
+      stream = new ReadableStream({
+        start: function(controller) { entry.controller = controller; }
+      });
+      _pending[id] = entry;
+
+      window.parent.postMessage({
+        type: 'tauri-fetch',
+        id: id,
+        method: method,
+        url: url,
+        body: body,
+        headers: headers
+      }, '*');
+
+      // Safety timeout for the response head (matches the Rust-side read
+      // timeout); a stream may run for longer.
+      setTimeout(function() {
+        if (_pending[id] && !_pending[id].head) {
+          console.error('[tauri-fetch] Timeout for ' + method + ' ' + url + ' (id: ' + id + ')');
+          delete _pending[id];
+          reject(new Error('tauri-fetch timeout after ' + _timeoutSecs + 's'));
+        }
+      }, _timeoutSecs * 1000);
+    }).then(function(data) {
+      console.log('[tauri-fetch] Got response for ' + method + ' ' + url + ': ' + data.status);
+      // These statuses can't have a body, and Response refuses one.
+      var noBody = [101, 204, 205, 304].indexOf(data.status) !== -1;
+      var body = noBody ? null : (data.streamed ? stream : data.body);
+      return new Response(body, {
+        status: data.status,
+        headers: data.headers
+      });
+    });
+  };
+
+  console.log('[tauri-fetch] Fetch override active: POST/PUT/PATCH/DELETE -> Tauri relay');
+})();
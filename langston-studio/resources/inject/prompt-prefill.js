@@ -0,0 +1,49 @@
+// Injected into OpenCode's HTML by the proxy (see scripts.json).
+//
+// Types a prompt from a langston-studio:// link into OpenCode's prompt box
+// without sending it. The parent Tauri webview posts
+// { type: 'langston-prefill-prompt', text } once the page has loaded; the
+// box may not be rendered yet, so it's looked for again for a while.
+(function() {
+  var ATTEMPTS = 30;
+  var INTERVAL_MS = 500;
+
+  function findPromptBox() {
+    var boxes = document.querySelectorAll('textarea, [contenteditable="true"]');
+    for (var i = 0; i < boxes.length; i++) {
+      if (boxes[i].offsetParent !== null) return boxes[i];
+    }
+    return null;
+  }
+
+  function fill(box, text) {
+    box.focus();
+    if (box.tagName === 'TEXTAREA') {
+      // Through the native setter, so frameworks tracking the value see it.
+      var setter = Object.getOwnPropertyDescriptor(HTMLTextAreaElement.prototype, 'value').set;
+      setter.call(box, text);
+    } else {
+      box.textContent = text;
+    }
+    box.dispatchEvent(new Event('input', { bubbles: true }));
+  }
+
+  window.addEventListener('message', function(event) {
+    if (event.source !== window.parent) return;
+    var msg = event.data;
+    if (!msg || msg.type !== 'langston-prefill-prompt' || typeof msg.text !== 'string') return;
+    var attempts = 0;
+    (function tryFill() {
+      var box = findPromptBox();
+      if (box) {
+        fill(box, msg.text);
+        return;
+      }
+      if (++attempts < ATTEMPTS) {
+        setTimeout(tryFill, INTERVAL_MS);
+      } else {
+        console.warn('[langston] No prompt box found to fill in');
+      }
+    })();
+  });
+})();
@@ -0,0 +1,47 @@
+// Injected into OpenCode's HTML by the proxy (see scripts.json).
+//
+// Opens the session that was open when the app was last closed (see
+// sessions.rs). The parent Tauri webview posts
+// { type: 'langston-open-session', id } once the page has loaded. The
+// session list may not be rendered yet, so its link is looked for again for
+// a while; if it never shows up, the session's route is loaded directly.
+(function() {
+  var ATTEMPTS = 20;
+  var INTERVAL_MS = 500;
+
+  function findLink(id) {
+    var links = document.querySelectorAll('a[href*="/session/"]');
+    for (var i = 0; i < links.length; i++) {
+      var href = links[i].getAttribute('href') || '';
+      if (href.split('?')[0].split('/').pop() === id) return links[i];
+    }
+    return null;
+  }
+
+  function sessionPath(id) {
+    var path = window.location.pathname;
+    var at = path.indexOf('/session');
+    var base = at >= 0 ? path.slice(0, at) : path.replace(/\/$/, '');
+    return base + '/session/' + encodeURIComponent(id);
+  }
+
+  window.addEventListener('message', function(event) {
+    if (event.source !== window.parent) return;
+    var msg = event.data;
+    if (!msg || msg.type !== 'langston-open-session' || typeof msg.id !== 'string') return;
+    if (window.location.pathname.split('/').pop() === msg.id) return;
+    var attempts = 0;
+    (function tryOpen() {
+      var link = findLink(msg.id);
+      if (link) {
+        link.click();
+        return;
+      }
+      if (++attempts < ATTEMPTS) {
+        setTimeout(tryOpen, INTERVAL_MS);
+      } else {
+        window.location.assign(sessionPath(msg.id));
+      }
+    })();
+  });
+})();
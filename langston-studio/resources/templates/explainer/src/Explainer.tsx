@@ -0,0 +1,113 @@
+import {
+    AbsoluteFill,
+    interpolate,
+    Sequence,
+    spring,
+    useCurrentFrame,
+    useVideoConfig,
+} from "remotion";
+import {z} from "zod";
+
+const FONT_FAMILY = '-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif';
+
+export const ExplainerSchema = z.object({
+  title: z.string(),
+  steps: z.array(z.object({heading: z.string(), caption: z.string()})).min(1).max(8),
+  summary: z.string(),
+  sceneDurationSec: z.number().min(2).max(15).default(4),
+  background: z.string().default("#1a1a2e"),
+  accent: z.string().default("#73C1BD"),
+});
+
+const Title: React.FC<{text: string; accent: string}> = ({text, accent}) => {
+  const frame = useCurrentFrame();
+  const {fps} = useVideoConfig();
+  const rise = spring({frame, fps, config: {damping: 200}});
+  return (
+    <AbsoluteFill style={{justifyContent: "center", alignItems: "center"}}>
+      <div
+        style={{
+          fontFamily: FONT_FAMILY,
+          fontSize: 96,
+          fontWeight: 700,
+          color: "white",
+          borderBottom: `8px solid ${accent}`,
+          paddingBottom: 16,
+          transform: `translateY(${interpolate(rise, [0, 1], [40, 0])}px)`,
+          opacity: rise,
+        }}
+      >
+        {text}
+      </div>
+    </AbsoluteFill>
+  );
+};
+
+const Step: React.FC<{number: number; heading: string; caption: string; accent: string}> = ({
+  number,
+  heading,
+  caption,
+  accent,
+}) => {
+  const frame = useCurrentFrame();
+  const {fps} = useVideoConfig();
+  const enter = spring({frame, fps, config: {damping: 200}});
+  const captionOpacity = interpolate(frame, [fps * 0.5, fps], [0, 1], {
+    extrapolateLeft: "clamp",
+    extrapolateRight: "clamp",
+  });
+  return (
+    <AbsoluteFill style={{justifyContent: "center", padding: 160, fontFamily: FONT_FAMILY}}>
+      <div style={{display: "flex", alignItems: "center", gap: 48, opacity: enter}}>
+        <div
+          style={{
+            width: 140,
+            height: 140,
+            borderRadius: 70,
+            background: accent,
+            color: "#1a1a2e",
+            fontSize: 80,
+            fontWeight: 800,
+            display: "flex",
+            justifyContent: "center",
+            alignItems: "center",
+            transform: `scale(${enter})`,
+          }}
+        >
+          {number}
+        </div>
+        <div style={{fontSize: 80, fontWeight: 700, color: "white"}}>{heading}</div>
+      </div>
+      <div style={{fontSize: 48, color: "#d0d0d8", marginTop: 48, opacity: captionOpacity}}>
+        {caption}
+      </div>
+    </AbsoluteFill>
+  );
+};
+
+export const Explainer: React.FC<z.infer<typeof ExplainerSchema>> = ({
+  title,
+  steps,
+  summary,
+  sceneDurationSec,
+  background,
+  accent,
+}) => {
+  const {fps} = useVideoConfig();
+  const scene = Math.round(sceneDurationSec * fps);
+  return (
+    <AbsoluteFill style={{background}}>
+      <Sequence durationInFrames={scene}>
+        <Title text={title} accent={accent} />
+      </Sequence>
+      {steps.map((step, i) => (
+        <Sequence key={i} from={scene * (i + 1)} durationInFrames={scene}>
+          <Step number={i + 1} heading={step.heading} caption={step.caption} accent={accent} />
+        </Sequence>
+      ))}
+      <Sequence from={scene * (steps.length + 1)}>
+        <Title text={summary} accent={accent} />
+      </Sequence>
+    </AbsoluteFill>
+  );
+};
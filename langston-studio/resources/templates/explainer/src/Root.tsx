@@ -0,0 +1,72 @@
+import { Composition } from "remotion";
+import { Welcome, WelcomeSchema } from "./Welcome";
+import { Explainer, ExplainerSchema } from "./Explainer";
+import { WorkspaceChallenge, WorkspaceChallengeSchema } from "./WorkspaceChallenge";
+
+export const RemotionRoot = () => {
+  return (
+    <>
+      <Composition
+        id="Explainer"
+        component={Explainer}
+        durationInFrames={600}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={ExplainerSchema}
+        defaultProps={{
+          title: "How it works",
+          steps: [
+            { heading: "Describe it", caption: "Tell the assistant what your video should show." },
+            { heading: "Preview it", caption: "Watch it come together in Remotion Studio." },
+            { heading: "Render it", caption: "Export an MP4 when you're happy with it." },
+          ],
+          summary: "That's all there is to it",
+          sceneDurationSec: 4,
+          background: "#1a1a2e",
+          accent: "#73C1BD",
+        }}
+        calculateMetadata={({ props }) => ({
+          durationInFrames: Math.round((props.steps.length + 2) * props.sceneDurationSec * 30),
+        })}
+      />
+      <Composition
+        id="Welcome"
+        component={Welcome}
+        durationInFrames={780}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={WelcomeSchema}
+        defaultProps={{
+          sceneDurationSec: 4,
+          transitionDurationSec: 0.5,
+          welcomeTitle: "Welcome to Langston Videos!",
+          welcomeSubtitle: "Your AI-powered video creation studio",
+          welcomeEmoji: "🎬",
+          setupTitle: "You're All Set Up!",
+          setupEmoji: "✅",
+          howItWorksIntro: "To create a video, just describe what you want...",
+          typewriterText: "Create a welcome video with animated text",
+          aiResponse: "I'll create that for you! Setting up a new composition with spring animations...",
+          workflowTitle: "Your Workflow",
+          ctaTitle: "Let's Create Something!",
+          ctaSubtitle: "Just ask: 'Create a 10-second promo video for...'",
+          ctaEmoji: "🚀",
+        }}
+      />
+      <Composition
+        id="WorkspaceChallenge"
+        component={WorkspaceChallenge}
+        durationInFrames={900}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={WorkspaceChallengeSchema}
+        defaultProps={{
+          transitionDurationSec: 0.5,
+        }}
+      />
+    </>
+  );
+};
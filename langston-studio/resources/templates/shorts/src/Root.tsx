@@ -0,0 +1,68 @@
+import { Composition } from "remotion";
+import { Welcome, WelcomeSchema } from "./Welcome";
+import { Short, ShortSchema } from "./Short";
+import { WorkspaceChallenge, WorkspaceChallengeSchema } from "./WorkspaceChallenge";
+
+export const RemotionRoot = () => {
+  return (
+    <>
+      <Composition
+        id="Short"
+        component={Short}
+        durationInFrames={450}
+        fps={30}
+        width={1080}
+        height={1920}
+        schema={ShortSchema}
+        defaultProps={{
+          hook: "Stop scrolling 👋",
+          beats: ["Describe your video", "Langston builds it", "You post it"],
+          callToAction: "Follow for more",
+          beatDurationSec: 2.5,
+          background: "#155F6C",
+          accent: "#FFDD6F",
+        }}
+        calculateMetadata={({ props }) => ({
+          durationInFrames: Math.round((props.beats.length + 2) * props.beatDurationSec * 30),
+        })}
+      />
+      <Composition
+        id="Welcome"
+        component={Welcome}
+        durationInFrames={780}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={WelcomeSchema}
+        defaultProps={{
+          sceneDurationSec: 4,
+          transitionDurationSec: 0.5,
+          welcomeTitle: "Welcome to Langston Videos!",
+          welcomeSubtitle: "Your AI-powered video creation studio",
+          welcomeEmoji: "🎬",
+          setupTitle: "You're All Set Up!",
+          setupEmoji: "✅",
+          howItWorksIntro: "To create a video, just describe what you want...",
+          typewriterText: "Create a welcome video with animated text",
+          aiResponse: "I'll create that for you! Setting up a new composition with spring animations...",
+          workflowTitle: "Your Workflow",
+          ctaTitle: "Let's Create Something!",
+          ctaSubtitle: "Just ask: 'Create a 10-second promo video for...'",
+          ctaEmoji: "🚀",
+        }}
+      />
+      <Composition
+        id="WorkspaceChallenge"
+        component={WorkspaceChallenge}
+        durationInFrames={900}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={WorkspaceChallengeSchema}
+        defaultProps={{
+          transitionDurationSec: 0.5,
+        }}
+      />
+    </>
+  );
+};
@@ -0,0 +1,72 @@
+import {
+    AbsoluteFill,
+    interpolate,
+    Sequence,
+    spring,
+    useCurrentFrame,
+    useVideoConfig,
+} from "remotion";
+import {z} from "zod";
+
+const FONT_FAMILY = '-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif';
+
+export const ShortSchema = z.object({
+  hook: z.string(),
+  beats: z.array(z.string()).min(1).max(6),
+  callToAction: z.string(),
+  beatDurationSec: z.number().min(1).max(10).default(2.5),
+  background: z.string().default("#155F6C"),
+  accent: z.string().default("#FFDD6F"),
+});
+
+const Card: React.FC<{text: string; accent: string; size: number}> = ({text, accent, size}) => {
+  const frame = useCurrentFrame();
+  const {fps} = useVideoConfig();
+  const pop = spring({frame, fps, config: {damping: 12}});
+  return (
+    <AbsoluteFill style={{justifyContent: "center", alignItems: "center", padding: 80}}>
+      <div
+        style={{
+          fontFamily: FONT_FAMILY,
+          fontSize: size,
+          fontWeight: 800,
+          color: "white",
+          textAlign: "center",
+          lineHeight: 1.15,
+          transform: `scale(${interpolate(pop, [0, 1], [0.8, 1])})`,
+          opacity: pop,
+          textShadow: `0 8px 0 ${accent}`,
+        }}
+      >
+        {text}
+      </div>
+    </AbsoluteFill>
+  );
+};
+
+export const Short: React.FC<z.infer<typeof ShortSchema>> = ({
+  hook,
+  beats,
+  callToAction,
+  beatDurationSec,
+  background,
+  accent,
+}) => {
+  const {fps} = useVideoConfig();
+  const beat = Math.round(beatDurationSec * fps);
+  return (
+    <AbsoluteFill style={{background}}>
+      <Sequence durationInFrames={beat}>
+        <Card text={hook} accent={accent} size={110} />
+      </Sequence>
+      {beats.map((text, i) => (
+        <Sequence key={i} from={beat * (i + 1)} durationInFrames={beat}>
+          <Card text={text} accent={accent} size={90} />
+        </Sequence>
+      ))}
+      <Sequence from={beat * (beats.length + 1)}>
+        <Card text={callToAction} accent={accent} size={100} />
+      </Sequence>
+    </AbsoluteFill>
+  );
+};
@@ -0,0 +1,73 @@
+import { Composition } from "remotion";
+import { Welcome, WelcomeSchema } from "./Welcome";
+import { Slideshow, SlideshowSchema, slideshowDuration } from "./Slideshow";
+import { WorkspaceChallenge, WorkspaceChallengeSchema } from "./WorkspaceChallenge";
+
+export const RemotionRoot = () => {
+  return (
+    <>
+      <Composition
+        id="Slideshow"
+        component={Slideshow}
+        durationInFrames={210}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={SlideshowSchema}
+        defaultProps={{
+          slides: [
+            { image: "langston-logo-full.png", caption: "Put your images in public/" },
+            { image: "opencode-mark-192x192.png", caption: "and list them here" },
+          ],
+          slideDurationSec: 4,
+          transitionDurationSec: 1,
+        }}
+        calculateMetadata={({ props }) => ({
+          durationInFrames: slideshowDuration(
+            props.slides.length,
+            props.slideDurationSec,
+            props.transitionDurationSec,
+            30,
+          ),
+        })}
+      />
+      <Composition
+        id="Welcome"
+        component={Welcome}
+        durationInFrames={780}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={WelcomeSchema}
+        defaultProps={{
+          sceneDurationSec: 4,
+          transitionDurationSec: 0.5,
+          welcomeTitle: "Welcome to Langston Videos!",
+          welcomeSubtitle: "Your AI-powered video creation studio",
+          welcomeEmoji: "🎬",
+          setupTitle: "You're All Set Up!",
+          setupEmoji: "✅",
+          howItWorksIntro: "To create a video, just describe what you want...",
+          typewriterText: "Create a welcome video with animated text",
+          aiResponse: "I'll create that for you! Setting up a new composition with spring animations...",
+          workflowTitle: "Your Workflow",
+          ctaTitle: "Let's Create Something!",
+          ctaSubtitle: "Just ask: 'Create a 10-second promo video for...'",
+          ctaEmoji: "🚀",
+        }}
+      />
+      <Composition
+        id="WorkspaceChallenge"
+        component={WorkspaceChallenge}
+        durationInFrames={900}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={WorkspaceChallengeSchema}
+        defaultProps={{
+          transitionDurationSec: 0.5,
+        }}
+      />
+    </>
+  );
+};
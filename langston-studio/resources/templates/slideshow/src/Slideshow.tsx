@@ -0,0 +1,94 @@
+import {
+    AbsoluteFill,
+    Img,
+    interpolate,
+    staticFile,
+    useCurrentFrame,
+    useVideoConfig,
+} from "remotion";
+import {linearTiming, TransitionSeries} from "@remotion/transitions";
+import {fade} from "@remotion/transitions/fade";
+import {z} from "zod";
+
+const FONT_FAMILY = '-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", Arial, sans-serif';
+
+export const SlideshowSchema = z.object({
+  slides: z
+    .array(z.object({image: z.string(), caption: z.string().default("")}))
+    .min(1),
+  slideDurationSec: z.number().min(3).max(20).default(4),
+  transitionDurationSec: z.number().min(0.1).max(2).default(1),
+});
+
+const Slide: React.FC<{image: string; caption: string; index: number}> = ({image, caption, index}) => {
+  const frame = useCurrentFrame();
+  const {durationInFrames} = useVideoConfig();
+  // Alternate the pan direction so consecutive slides don't feel repetitive.
+  const progress = interpolate(frame, [0, durationInFrames], [0, 1]);
+  const scale = interpolate(progress, [0, 1], [1.05, 1.15]);
+  const x = interpolate(progress, [0, 1], index % 2 === 0 ? [-20, 20] : [20, -20]);
+  return (
+    <AbsoluteFill style={{background: "black"}}>
+      <Img
+        src={staticFile(image)}
+        style={{
+          width: "100%",
+          height: "100%",
+          objectFit: "cover",
+          transform: `scale(${scale}) translateX(${x}px)`,
+        }}
+      />
+      {caption ? (
+        <AbsoluteFill style={{justifyContent: "flex-end", padding: 80}}>
+          <div
+            style={{
+              fontFamily: FONT_FAMILY,
+              fontSize: 56,
+              color: "white",
+              textShadow: "0 2px 12px rgba(0,0,0,0.8)",
+            }}
+          >
+            {caption}
+          </div>
+        </AbsoluteFill>
+      ) : null}
+    </AbsoluteFill>
+  );
+};
+
+export const slideshowDuration = (
+  slides: number,
+  slideDurationSec: number,
+  transitionDurationSec: number,
+  fps: number,
+) =>
+  slides * Math.round(slideDurationSec * fps) -
+  (slides - 1) * Math.round(transitionDurationSec * fps);
+
+export const Slideshow: React.FC<z.infer<typeof SlideshowSchema>> = ({
+  slides,
+  slideDurationSec,
+  transitionDurationSec,
+}) => {
+  const {fps} = useVideoConfig();
+  const slide = Math.round(slideDurationSec * fps);
+  const transition = Math.round(transitionDurationSec * fps);
+  return (
+    <TransitionSeries>
+      {slides.flatMap((s, i) => [
+        <TransitionSeries.Sequence key={`slide-${i}`} durationInFrames={slide}>
+          <Slide image={s.image} caption={s.caption} index={i} />
+        </TransitionSeries.Sequence>,
+        ...(i < slides.length - 1
+          ? [
+              <TransitionSeries.Transition
+                key={`fade-${i}`}
+                presentation={fade()}
+                timing={linearTiming({durationInFrames: transition})}
+              />,
+            ]
+          : []),
+      ])}
+    </TransitionSeries>
+  );
+};
@@ -4,3 +4,13 @@ Config.setVideoImageFormat("jpeg");
 Config.setStudioPort(7500);
 Config.setShouldOpenBrowser(false);
 Config.setWebpackPollingInMilliseconds(1000);
+
+// Langston Studio keeps webpack's cache outside node_modules so it survives
+// reinstalls.
+Config.overrideWebpackConfig((config) => {
+  const cacheDirectory = process.env.LANGSTON_WEBPACK_CACHE_DIR;
+  if (!cacheDirectory || typeof config.cache !== "object") {
+    return config;
+  }
+  return { ...config, cache: { ...config.cache, cacheDirectory } };
+});
@@ -0,0 +1,18 @@
+#!/bin/sh
+# Fill resources/npm-cache with every package the workspace template needs,
+# so first-run setup can install them offline. Run before `tauri build`.
+set -eu
+
+root="$(cd "$(dirname "$0")/.." && pwd)"
+template="$root/resources/workspace-template"
+cache="$root/resources/npm-cache"
+work="$(mktemp -d)"
+trap 'rm -rf "$work"' EXIT
+
+find "$cache" -mindepth 1 ! -name README.md ! -name .gitignore -exec rm -rf {} +
+
+cp "$template/package.json" "$template/package-lock.json" "$work/"
+cd "$work"
+npm ci --cache "$cache" --no-audit --no-fund --ignore-scripts
+
+echo "npm cache written to $cache"
@@ -0,0 +1,561 @@
+//! Importing media into the workspace.
+//!
+//! `import_assets` copies files the user dropped onto the window into
+//! `public/assets`, where Remotion compositions can load them with
+//! `staticFile("assets/<name>")`. Names are made URL-safe and never
+//! overwrite an existing asset (`clip.mp4`, `clip-1.mp4`, ...). Videos over
+//! `MAX_VIDEO_BYTES` are transcoded to 1080p H.264 with ffmpeg when it's
+//! installed, since the studio preview chokes on multi-gigabyte camera
+//! files; without ffmpeg they are copied as-is.
+//!
+//! Every import is recorded in `.langston-assets.json` at the workspace root
+//! (original name and path, size, when it was imported, whether it was
+//! transcoded). Files over `PROGRESS_THRESHOLD` report progress as
+//! `asset-import-progress` events.
+//!
+//! `list_assets` walks `public/assets` for the media browser and returns each
+//! file's size, MIME type and, where it can tell, image dimensions and video
+//! or audio duration, frame rate and codec. Media is inspected with ffprobe
+//! when it's installed; without it only images get dimensions.
+
+use crate::error::StudioError;
+use crate::{get_path_env, platform, resolve_workspace_dir};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tauri::{AppHandle, Emitter};
+
+/// Directory under the workspace that imported assets go into.
+pub const ASSETS_DIR: &str = "public/assets";
+const MANIFEST_FILE: &str = ".langston-assets.json";
+/// Videos larger than this are transcoded when ffmpeg is available.
+const MAX_VIDEO_BYTES: u64 = 500 * 1024 * 1024;
+/// Files larger than this send progress events while importing.
+const PROGRESS_THRESHOLD: u64 = 10 * 1024 * 1024;
+const COPY_BUFFER: usize = 1024 * 1024;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v", "webm", "mkv", "avi"];
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav", "m4a", "aac", "ogg", "flac"];
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "avif"];
+
+/// "video", "audio", "image" or "other", from the file extension.
+pub fn kind_of(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    if VIDEO_EXTENSIONS.contains(&ext.as_str()) {
+        "video"
+    } else if AUDIO_EXTENSIONS.contains(&ext.as_str()) {
+        "audio"
+    } else if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        "image"
+    } else {
+        "other"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetRecord {
+    /// Path relative to `public/`, as passed to `staticFile()`.
+    pub file: String,
+    pub original_name: String,
+    pub source_path: String,
+    pub kind: String,
+    pub size: u64,
+    pub imported_at: String,
+    pub transcoded: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    assets: Vec<AssetRecord>,
+}
+
+fn read_manifest(workspace: &Path) -> Manifest {
+    std::fs::read_to_string(workspace.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn write_manifest(workspace: &Path, manifest: &Manifest) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    std::fs::write(workspace.join(MANIFEST_FILE), json + "\n")
+        .map_err(|e| format!("Failed to update {}: {}", MANIFEST_FILE, e))
+}
+
+/// A URL-safe version of a file name: lowercase ASCII letters, digits, `-`,
+/// `_` and `.`, with everything else turned into `-`.
+fn safe_file_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        let c = c.to_ascii_lowercase();
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches(|c| c == '-' || c == '.').to_string();
+    if out.is_empty() {
+        "asset".to_string()
+    } else {
+        out
+    }
+}
+
+/// `dir/stem.ext`, or `dir/stem-N.ext` for the first N that isn't taken.
+fn unique_path(dir: &Path, stem: &str, ext: &str) -> PathBuf {
+    let name = |suffix: String| {
+        if ext.is_empty() {
+            format!("{}{}", stem, suffix)
+        } else {
+            format!("{}{}.{}", stem, suffix, ext)
+        }
+    };
+    let mut candidate = dir.join(name(String::new()));
+    let mut n = 1;
+    while candidate.exists() {
+        candidate = dir.join(name(format!("-{}", n)));
+        n += 1;
+    }
+    candidate
+}
+
+fn emit_progress(app: &AppHandle, source: &Path, file: &str, stage: &str, progress: f64) {
+    let _ = app.emit(
+        "asset-import-progress",
+        serde_json::json!({
+            "source": source.to_string_lossy(),
+            "file": file,
+            "stage": stage,
+            "progress": progress,
+        }),
+    );
+}
+
+fn copy_with_progress(app: &AppHandle, src: &Path, dst: &Path, file: &str) -> Result<(), String> {
+    let total = std::fs::metadata(src).map(|m| m.len()).unwrap_or(0);
+    let report = total > PROGRESS_THRESHOLD;
+    let mut reader =
+        File::open(src).map_err(|e| format!("Failed to open {}: {}", src.display(), e))?;
+    let mut writer =
+        File::create(dst).map_err(|e| format!("Failed to create {}: {}", dst.display(), e))?;
+
+    let mut buffer = vec![0; COPY_BUFFER];
+    let mut copied: u64 = 0;
+    let mut last_percent = 0;
+    loop {
+        let n = reader
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", src.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        writer
+            .write_all(&buffer[..n])
+            .map_err(|e| format!("Failed to write {}: {}", dst.display(), e))?;
+        copied += n as u64;
+        if report {
+            let percent = copied * 100 / total;
+            if percent != last_percent {
+                last_percent = percent;
+                emit_progress(app, src, file, "copying", percent as f64 / 100.0);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Duration of a media file in seconds, via ffprobe.
+fn probe_duration(ffprobe: &Path, path: &Path) -> Option<f64> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Transcode `src` to a 1080p H.264 MP4 at `dst`.
+fn transcode(
+    app: &AppHandle,
+    ffmpeg: &Path,
+    src: &Path,
+    dst: &Path,
+    file: &str,
+) -> Result<(), String> {
+    let path_env = get_path_env();
+    let duration = platform::which("ffprobe", &path_env).and_then(|p| probe_duration(&p, src));
+
+    let mut child = Command::new(ffmpeg)
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-i"])
+        .arg(src)
+        .args(["-vf", "scale='min(1920,iw)':-2", "-c:v", "libx264"])
+        .args(["-preset", "veryfast", "-crf", "20", "-pix_fmt", "yuv420p"])
+        .args(["-c:a", "aac", "-b:a", "192k", "-movflags", "+faststart"])
+        .args(["-progress", "pipe:1", "-nostats"])
+        .arg(dst)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let Some(micros) = line.strip_prefix("out_time_us=") else {
+                continue;
+            };
+            if let (Ok(micros), Some(total)) = (micros.trim().parse::<f64>(), duration) {
+                if total > 0.0 {
+                    let progress = (micros / 1_000_000.0 / total).clamp(0.0, 1.0);
+                    emit_progress(app, src, file, "transcoding", progress);
+                }
+            }
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let _ = std::fs::remove_file(dst);
+        Err(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn import_one(app: &AppHandle, workspace: &Path, source: &Path) -> Result<AssetRecord, String> {
+    if !source.is_file() {
+        return Err(format!("Not a file: {}", source.display()));
+    }
+    let size = std::fs::metadata(source).map(|m| m.len()).unwrap_or(0);
+    let kind = kind_of(source);
+    let original_name = source
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let dir = workspace.join(ASSETS_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let stem = safe_file_name(&source.file_stem().unwrap_or_default().to_string_lossy());
+    let ext = source
+        .extension()
+        .map(|e| safe_file_name(&e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let ffmpeg = (kind == "video" && size > MAX_VIDEO_BYTES)
+        .then(|| platform::which("ffmpeg", &get_path_env()))
+        .flatten();
+    let (dest, transcoded) = match ffmpeg {
+        Some(ffmpeg) => {
+            let dest = unique_path(&dir, &stem, "mp4");
+            let file = dest
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            crate::logging::log_line(
+                "INFO",
+                &format!(
+                    "[assets] Transcoding {} ({} MB) to {}",
+                    original_name,
+                    size / 1024 / 1024,
+                    file
+                ),
+            );
+            transcode(app, &ffmpeg, source, &dest, &file)?;
+            (dest, true)
+        }
+        None => {
+            if kind == "video" && size > MAX_VIDEO_BYTES {
+                crate::logging::log_line(
+                    "WARN",
+                    &format!(
+                        "[assets] {} is {} MB but ffmpeg isn't installed; copying as-is",
+                        original_name,
+                        size / 1024 / 1024
+                    ),
+                );
+            }
+            let dest = unique_path(&dir, &stem, &ext);
+            let file = dest
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if let Err(e) = copy_with_progress(app, source, &dest, &file) {
+                let _ = std::fs::remove_file(&dest);
+                return Err(e);
+            }
+            (dest, false)
+        }
+    };
+
+    let file_name = dest.file_name().unwrap_or_default().to_string_lossy();
+    emit_progress(app, source, &file_name, "done", 1.0);
+    Ok(AssetRecord {
+        file: format!("assets/{}", file_name),
+        original_name,
+        source_path: source.to_string_lossy().to_string(),
+        kind: kind.to_string(),
+        size: std::fs::metadata(&dest).map(|m| m.len()).unwrap_or(size),
+        imported_at: Local::now().to_rfc3339(),
+        transcoded,
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub imported: Vec<AssetRecord>,
+    /// One message per file that couldn't be imported.
+    pub errors: Vec<String>,
+}
+
+fn import_blocking(app: &AppHandle, paths: &[String]) -> Result<ImportResult, String> {
+    let workspace = resolve_workspace_dir();
+    if !workspace.exists() {
+        return Err(format!("Workspace not found: {}", workspace.display()));
+    }
+
+    let mut result = ImportResult {
+        imported: Vec::new(),
+        errors: Vec::new(),
+    };
+    for path in paths {
+        match import_one(app, &workspace, Path::new(path)) {
+            Ok(record) => {
+                crate::logging::log_line(
+                    "INFO",
+                    &format!("[assets] Imported {} as {}", path, record.file),
+                );
+                result.imported.push(record);
+            }
+            Err(e) => {
+                crate::logging::log_line("WARN", &format!("[assets] {}", e));
+                result.errors.push(e);
+            }
+        }
+    }
+
+    if !result.imported.is_empty() {
+        let mut manifest = read_manifest(&workspace);
+        manifest.assets.extend(result.imported.iter().cloned());
+        write_manifest(&workspace, &manifest)?;
+    }
+    Ok(result)
+}
+
+/// Copy files into the workspace's `public/assets` folder.
+#[tauri::command]
+pub async fn import_assets(
+    app: AppHandle,
+    paths: Vec<String>,
+) -> Result<ImportResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || import_blocking(&app, &paths))
+        .await?
+        .map_err(Into::into)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetInfo {
+    /// Path relative to `public/`, as passed to `staticFile()`.
+    pub file: String,
+    pub name: String,
+    pub kind: String,
+    pub mime_type: String,
+    pub size: u64,
+    /// Last modified, RFC 3339.
+    pub modified: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Seconds, for video and audio.
+    pub duration: Option<f64>,
+    pub fps: Option<f64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    /// From the import manifest, if the file was imported through the app.
+    pub original_name: Option<String>,
+    pub imported_at: Option<String>,
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        "avi" => "video/x-msvideo",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "avif" => "image/avif",
+        "json" => "application/json",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+/// "30000/1001" -> 29.97.
+fn parse_rate(rate: &str) -> Option<f64> {
+    let (num, den) = rate.split_once('/')?;
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    (den > 0.0 && num > 0.0).then(|| num / den)
+}
+
+/// Fill in what ffprobe reports about `path`.
+fn probe_media(ffprobe: &Path, path: &Path, info: &mut AssetInfo) {
+    let Ok(output) = Command::new(ffprobe)
+        .args(["-v", "error", "-print_format", "json"])
+        .args(["-show_format", "-show_streams"])
+        .arg(path)
+        .output()
+    else {
+        return;
+    };
+    let Ok(probe) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+        return;
+    };
+
+    info.duration = probe["format"]["duration"]
+        .as_str()
+        .and_then(|d| d.parse().ok());
+    let streams = probe["streams"].as_array().cloned().unwrap_or_default();
+    for stream in &streams {
+        let codec = stream["codec_name"].as_str().map(str::to_string);
+        match stream["codec_type"].as_str() {
+            Some("video") if info.video_codec.is_none() => {
+                info.video_codec = codec;
+                info.width = stream["width"].as_u64().map(|w| w as u32).or(info.width);
+                info.height = stream["height"].as_u64().map(|h| h as u32).or(info.height);
+                // Still images show up as a single-frame video stream.
+                if info.kind == "video" {
+                    info.fps = stream["avg_frame_rate"]
+                        .as_str()
+                        .and_then(parse_rate)
+                        .or_else(|| stream["r_frame_rate"].as_str().and_then(parse_rate));
+                }
+            }
+            Some("audio") if info.audio_codec.is_none() => info.audio_codec = codec,
+            _ => {}
+        }
+    }
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, out);
+        } else {
+            out.push(path);
+        }
+    }
+}
+
+fn list_blocking() -> Result<Vec<AssetInfo>, String> {
+    let workspace = resolve_workspace_dir();
+    let public = workspace.join("public");
+    let mut files = Vec::new();
+    collect_files(&workspace.join(ASSETS_DIR), &mut files);
+
+    let manifest = read_manifest(&workspace);
+    let ffprobe = platform::which("ffprobe", &get_path_env());
+
+    let mut assets: Vec<AssetInfo> = files
+        .into_iter()
+        .map(|path| {
+            let meta = std::fs::metadata(&path).ok();
+            let file = path
+                .strip_prefix(&public)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let record = manifest.assets.iter().rev().find(|a| a.file == file);
+            let kind = kind_of(&path);
+            let mut info = AssetInfo {
+                name: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                kind: kind.to_string(),
+                mime_type: mime_type(&path).to_string(),
+                size: meta.as_ref().map_or(0, |m| m.len()),
+                modified: meta
+                    .and_then(|m| m.modified().ok())
+                    .map(|t| chrono::DateTime::<Local>::from(t).to_rfc3339()),
+                width: None,
+                height: None,
+                duration: None,
+                fps: None,
+                video_codec: None,
+                audio_codec: None,
+                original_name: record.map(|r| r.original_name.clone()),
+                imported_at: record.map(|r| r.imported_at.clone()),
+                file,
+            };
+
+            if kind == "image" {
+                if let Ok(size) = imagesize::size(&path) {
+                    info.width = Some(size.width as u32);
+                    info.height = Some(size.height as u32);
+                }
+            }
+            if let Some(ffprobe) = &ffprobe {
+                if kind == "video" || kind == "audio" {
+                    probe_media(ffprobe, &path, &mut info);
+                }
+            }
+            info
+        })
+        .collect();
+    assets.sort_by(|a, b| a.file.cmp(&b.file));
+    Ok(assets)
+}
+
+/// Files in the workspace's `public/assets` folder, with media metadata.
+#[tauri::command]
+pub async fn list_assets() -> Result<Vec<AssetInfo>, StudioError> {
+    tauri::async_runtime::spawn_blocking(list_blocking)
+        .await?
+        .map_err(Into::into)
+}
@@ -0,0 +1,109 @@
+//! Periodic auto-save while a session is running.
+//!
+//! The workspace used to be committed only when a session started, so a
+//! crash late in a session lost everything the agent had done since. Setup
+//! now also starts a background thread that commits the workspace every
+//! `autoSaveIntervalMins` (10 by default; 0 turns it off) when it has
+//! changes.
+//!
+//! A save waits until the watcher has seen no changes for `QUIET_PERIOD`, so
+//! a burst of edits lands in one commit instead of being cut in half, and
+//! it's held off while a render is running, setup is working on the
+//! workspace or an experiment is being merged or discarded. During an
+//! experiment the saves go to its branch (see `experiments`) and say so in
+//! their message. Each commit is announced with an `auto-saved` event,
+//! `{ commit, message }`, and pushed to the backup remote if there is one.
+
+use crate::{
+    backup, experiments, git, read_config_file, render, resolve_workspace_dir, setup, AppConfig,
+};
+use chrono::Local;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+const DEFAULT_INTERVAL_MINS: u64 = 10;
+/// How often the thread checks whether a save is due.
+const TICK: Duration = Duration::from_secs(15);
+/// How long the workspace has to be left alone before it's saved.
+const QUIET_PERIOD: Duration = Duration::from_secs(30);
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+/// When the watcher last reported a change.
+static LAST_CHANGE: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoSaved {
+    commit: String,
+    message: String,
+}
+
+/// Minutes between saves; 0 when auto-save is off.
+pub fn interval_mins(config: &AppConfig) -> u64 {
+    config
+        .auto_save_interval_mins
+        .unwrap_or(DEFAULT_INTERVAL_MINS)
+}
+
+/// The workspace just changed; put the next save off until it's quiet.
+pub fn note_change() {
+    *LAST_CHANGE.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+}
+
+fn quiet() -> bool {
+    LAST_CHANGE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .map_or(true, |at| at.elapsed() >= QUIET_PERIOD)
+}
+
+fn save(app: &AppHandle) {
+    let workspace = resolve_workspace_dir();
+    if !workspace.join(".git").exists() {
+        return;
+    }
+    let mut message = format!("Auto-save {}", Local::now().format("%Y-%m-%d %H:%M"));
+    if let Some(experiment) = experiments::current(&workspace) {
+        message.push_str(&format!(" (experiment: {})", experiment.name));
+    }
+    match git::commit_all(&workspace, &message) {
+        Ok(Some(commit)) => {
+            crate::logging::log_line("INFO", &format!("[autosave] Saved changes as {}", commit));
+            backup::schedule_push(app);
+            let _ = app.emit("auto-saved", AutoSaved { commit, message });
+        }
+        Ok(None) => {}
+        Err(e) => crate::logging::log_line("WARN", &format!("[autosave] Couldn't save: {}", e)),
+    }
+}
+
+/// Start the auto-save thread. It runs for the rest of the app's life and
+/// always saves whichever workspace is open, so later calls do nothing.
+pub fn start(app: &AppHandle) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut last_save = Instant::now();
+        loop {
+            std::thread::sleep(TICK);
+            let mins = interval_mins(&read_config_file());
+            if mins == 0 || last_save.elapsed() < Duration::from_secs(mins * 60) {
+                continue;
+            }
+            if !quiet()
+                || render::is_rendering(&app)
+                || setup::running()
+                || experiments::switching()
+            {
+                continue;
+            }
+            last_save = Instant::now();
+            save(&app);
+        }
+    });
+}
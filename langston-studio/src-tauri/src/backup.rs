@@ -0,0 +1,283 @@
+//! Optional off-machine backup of the workspace history.
+//!
+//! When `backupRemote` is set in config.json, every auto-save commit is
+//! pushed there in the background. Each workspace goes to a branch named
+//! after its folder, so several projects can share one backup repository;
+//! an experiment goes to `<folder>-<experiment>` next to it.
+//! Pushes never prompt for credentials (the user's git credential helper or
+//! SSH agent has to be set up already) and failures are retried a few times;
+//! being offline is expected and only logged, since the next commit or a
+//! manual `push_backup` will catch up.
+
+use crate::diagnostics::redact_url_credentials;
+use crate::error::StudioError;
+use crate::{get_path_env, read_config_file, resolve_workspace_dir};
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Name of the git remote we manage in each workspace.
+const REMOTE_NAME: &str = "langston-backup";
+
+/// Delays before each background push attempt.
+const RETRY_DELAYS: &[Duration] = &[
+    Duration::from_secs(0),
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(300),
+];
+
+/// A background push is running.
+static PUSH_RUNNING: AtomicBool = AtomicBool::new(false);
+/// Another commit happened while a push was running; push again afterwards.
+static PUSH_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Whether `url` looks like something `git push` accepts. The value ends up
+/// on a git command line, so anything resembling an option is rejected.
+pub fn valid_remote_url(url: &str) -> bool {
+    let has_scheme = ["https://", "http://", "ssh://", "git://", "file://"]
+        .iter()
+        .any(|s| url.starts_with(s));
+    // scp-like syntax: user@host:path
+    let scp_like = url
+        .split_once(':')
+        .map(|(host, path)| host.contains('@') && !host.contains('/') && !path.is_empty())
+        .unwrap_or(false);
+    !url.starts_with('-') && !url.chars().any(char::is_whitespace) && (has_scheme || scp_like)
+}
+
+fn configured_remote() -> Option<String> {
+    read_config_file()
+        .backup_remote
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// Branch the workspace is pushed to: its folder name, limited to characters
+/// that are always valid in a ref.
+fn branch_name(workspace: &Path) -> String {
+    let name: String = workspace
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('-');
+    if name.is_empty() {
+        "workspace".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+fn git(workspace: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(workspace)
+        .env("PATH", get_path_env())
+        // Never block on a username/password or host key prompt.
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env(
+            "GIT_SSH_COMMAND",
+            "ssh -o BatchMode=yes -o ConnectTimeout=15",
+        )
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        // Errors often quote the remote, credentials and all.
+        Err(redact_url_credentials(
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ))
+    }
+}
+
+/// Point the managed remote at `url`, adding it if needed.
+fn ensure_remote(workspace: &Path, url: &str) -> Result<(), String> {
+    match git(workspace, &["remote", "get-url", REMOTE_NAME]) {
+        Ok(current) if current.trim() == url => Ok(()),
+        Ok(_) => git(workspace, &["remote", "set-url", REMOTE_NAME, url]).map(|_| ()),
+        Err(_) => git(workspace, &["remote", "add", REMOTE_NAME, url]).map(|_| ()),
+    }
+}
+
+/// Whether a push error means the remote couldn't be reached at all, as
+/// opposed to being rejected.
+fn is_offline_error(err: &str) -> bool {
+    let err = err.to_lowercase();
+    [
+        "could not resolve host",
+        "could not resolve hostname",
+        "network is unreachable",
+        "connection timed out",
+        "operation timed out",
+        "connection refused",
+        "failed to connect",
+    ]
+    .iter()
+    .any(|s| err.contains(s))
+}
+
+/// Push the workspace's current branch to the backup remote once.
+fn push_once(workspace: &Path, url: &str) -> Result<String, String> {
+    if !workspace.join(".git").exists() {
+        return Err("Workspace is not a git repository".to_string());
+    }
+    ensure_remote(workspace, url)?;
+
+    let mut branch = branch_name(workspace);
+    if let Some(experiment) = crate::experiments::current(workspace) {
+        branch = format!("{}-{}", branch, crate::snapshots::slug(&experiment.name));
+    }
+    let refspec = format!("HEAD:refs/heads/{}", branch);
+    git(
+        workspace,
+        &[
+            // Give up on stalled HTTPS transfers instead of hanging forever.
+            "-c",
+            "http.lowSpeedLimit=1000",
+            "-c",
+            "http.lowSpeedTime=30",
+            "push",
+            "--quiet",
+            REMOTE_NAME,
+            &refspec,
+        ],
+    )?;
+    Ok(branch)
+}
+
+fn emit_backup_status(app: &AppHandle, status: &str, message: &str) {
+    let _ = app.emit(
+        "backup-status",
+        serde_json::json!({
+            "status": status,
+            "message": message,
+        }),
+    );
+}
+
+/// Push in the background after a commit. Does nothing unless a backup
+/// remote is configured. Commits made while a push is running are picked up
+/// by one more push when it finishes.
+pub fn schedule_push(app: &AppHandle) {
+    if configured_remote().is_none() {
+        return;
+    }
+    PUSH_PENDING.store(true, Ordering::SeqCst);
+    if PUSH_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        while PUSH_PENDING.swap(false, Ordering::SeqCst) {
+            push_with_retry(&app);
+        }
+        PUSH_RUNNING.store(false, Ordering::SeqCst);
+        // A commit may have slipped in between the last check and clearing
+        // the flag.
+        if PUSH_PENDING.load(Ordering::SeqCst) {
+            schedule_push(&app);
+        }
+    });
+}
+
+fn push_with_retry(app: &AppHandle) {
+    let mut last_error = String::new();
+
+    for (attempt, delay) in RETRY_DELAYS.iter().enumerate() {
+        std::thread::sleep(*delay);
+        // Re-read each time: the remote may have been changed or removed
+        // while we were waiting.
+        let Some(url) = configured_remote() else {
+            return;
+        };
+        let workspace = resolve_workspace_dir();
+
+        match push_once(&workspace, &url) {
+            Ok(branch) => {
+                crate::logging::log_line(
+                    "INFO",
+                    &format!(
+                        "Backed up workspace to {} ({})",
+                        redact_url_credentials(&url),
+                        branch
+                    ),
+                );
+                emit_backup_status(app, "pushed", &format!("Backed up to {}", branch));
+                return;
+            }
+            Err(e) => {
+                let offline = is_offline_error(&e);
+                crate::logging::log_line(
+                    if offline { "INFO" } else { "WARN" },
+                    &format!(
+                        "Backup push attempt {}/{} failed{}: {}",
+                        attempt + 1,
+                        RETRY_DELAYS.len(),
+                        if offline { " (offline)" } else { "" },
+                        e
+                    ),
+                );
+                last_error = e;
+            }
+        }
+    }
+
+    if is_offline_error(&last_error) {
+        emit_backup_status(
+            app,
+            "offline",
+            "Backup remote unreachable; will retry after the next save",
+        );
+    } else {
+        emit_backup_status(app, "failed", &format!("Backup failed: {}", last_error));
+    }
+}
+
+/// Push the workspace to the backup remote now. Returns the branch pushed to.
+#[tauri::command]
+pub async fn push_backup(app: AppHandle) -> Result<String, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let url = configured_remote().ok_or("No backup remote configured")?;
+        let workspace = resolve_workspace_dir();
+        match push_once(&workspace, &url) {
+            Ok(branch) => {
+                crate::logging::log_line(
+                    "INFO",
+                    &format!(
+                        "Backed up workspace to {} ({})",
+                        redact_url_credentials(&url),
+                        branch
+                    ),
+                );
+                emit_backup_status(&app, "pushed", &format!("Backed up to {}", branch));
+                Ok(branch)
+            }
+            Err(e) => {
+                crate::logging::log_line("WARN", &format!("Backup push failed: {}", e));
+                if is_offline_error(&e) {
+                    Err(StudioError::Network(format!(
+                        "Backup remote unreachable: {}",
+                        e
+                    )))
+                } else {
+                    Err(StudioError::Other(format!("Backup failed: {}", e)))
+                }
+            }
+        }
+    })
+    .await?
+}
@@ -0,0 +1,219 @@
+//! Opt-in capture of proxied traffic.
+//!
+//! When a user reports that the AI stopped responding, the logs only say
+//! that a request was sent and how many bytes came back. With capture on,
+//! the proxy also records every non-static exchange — method, URI, headers,
+//! status, timings and the first `MAX_BODY_BYTES` of each body — as one JSON
+//! object per line in `<session>.capture.ndjson` next to the session's logs,
+//! so it's purged with them and included in diagnostics bundles.
+//!
+//! Credentials in headers are redacted. Bodies are not, so capture is off
+//! by default and only lasts until it's disabled or the app quits.
+
+use crate::error::StudioError;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Most of each body kept in a capture entry.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+const CAPTURE_SUFFIX: &str = "capture.ndjson";
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Serializes appends so entries from concurrent requests don't interleave.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn capture_path() -> Option<PathBuf> {
+    crate::logging::session_file(CAPTURE_SUFFIX)
+}
+
+/// Headers as a map, with credentials redacted.
+pub fn headers(map: &reqwest::header::HeaderMap) -> BTreeMap<String, String> {
+    map.iter()
+        .map(|(name, value)| {
+            let name = name.as_str().to_string();
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).to_string()
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+/// A body as text, truncated to `MAX_BODY_BYTES`.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Body {
+    bytes: u64,
+    text: String,
+    truncated: bool,
+}
+
+impl Body {
+    fn append(&mut self, chunk: &[u8]) {
+        self.bytes += chunk.len() as u64;
+        let room = MAX_BODY_BYTES.saturating_sub(self.text.len());
+        if chunk.len() > room {
+            self.truncated = true;
+        }
+        self.text
+            .push_str(&String::from_utf8_lossy(&chunk[..chunk.len().min(room)]));
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    ts: String,
+    request_id: u64,
+    upstream: &'static str,
+    method: String,
+    uri: String,
+    request_headers: BTreeMap<String, String>,
+    request_body: Body,
+    status: Option<u16>,
+    response_headers: BTreeMap<String, String>,
+    response_body: Body,
+    ttfb_ms: Option<u64>,
+    duration_ms: u64,
+    error: Option<String>,
+}
+
+/// One request/response pair being captured. The proxy fills it in as the
+/// exchange progresses and calls `finish` when the response has ended; if
+/// the client goes away first, it's written when dropped.
+pub struct Exchange {
+    started: Instant,
+    entry: Mutex<Entry>,
+    written: AtomicBool,
+}
+
+impl Exchange {
+    /// Start capturing a request, or `None` if capture is off.
+    pub fn start(
+        request_id: u64,
+        upstream: &'static str,
+        method: &str,
+        uri: &str,
+        request_headers: BTreeMap<String, String>,
+    ) -> Option<Exchange> {
+        if !enabled() {
+            return None;
+        }
+        Some(Exchange {
+            started: Instant::now(),
+            entry: Mutex::new(Entry {
+                ts: Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+                request_id,
+                upstream,
+                method: method.to_string(),
+                uri: uri.to_string(),
+                request_headers,
+                request_body: Body::default(),
+                status: None,
+                response_headers: BTreeMap::new(),
+                response_body: Body::default(),
+                ttfb_ms: None,
+                duration_ms: 0,
+                error: None,
+            }),
+            written: AtomicBool::new(false),
+        })
+    }
+
+    pub fn request_chunk(&self, chunk: &[u8]) {
+        if let Ok(mut entry) = self.entry.lock() {
+            entry.request_body.append(chunk);
+        }
+    }
+
+    pub fn response(&self, status: u16, headers: BTreeMap<String, String>) {
+        if let Ok(mut entry) = self.entry.lock() {
+            entry.status = Some(status);
+            entry.response_headers = headers;
+            entry.ttfb_ms = Some(self.started.elapsed().as_millis() as u64);
+        }
+    }
+
+    pub fn response_chunk(&self, chunk: &[u8]) {
+        if let Ok(mut entry) = self.entry.lock() {
+            entry.response_body.append(chunk);
+        }
+    }
+
+    pub fn error(&self, error: &str) {
+        if let Ok(mut entry) = self.entry.lock() {
+            entry.error = Some(error.to_string());
+        }
+    }
+
+    /// Append the entry to the capture file. Only the first call writes.
+    pub fn finish(&self) {
+        if self.written.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Ok(mut entry) = self.entry.lock() else {
+            return;
+        };
+        entry.duration_ms = self.started.elapsed().as_millis() as u64;
+        let (Some(path), Ok(line)) = (capture_path(), serde_json::to_string(&*entry)) else {
+            return;
+        };
+        let _guard = WRITE_LOCK.lock();
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+impl Drop for Exchange {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Start capturing proxied traffic. Returns the capture file's path.
+#[tauri::command]
+pub fn enable_capture() -> Result<String, StudioError> {
+    let path = capture_path().ok_or("Logging isn't set up yet")?;
+    ENABLED.store(true, Ordering::Relaxed);
+    crate::logging::log_line(
+        "INFO",
+        &format!("[proxy] Capture enabled, writing to {:?}", path),
+    );
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn disable_capture() {
+    if ENABLED.swap(false, Ordering::Relaxed) {
+        crate::logging::log_line("INFO", "[proxy] Capture disabled");
+    }
+}
+
+/// The capture file, if capture is on or has been this session.
+#[tauri::command]
+pub fn get_capture_path() -> Option<String> {
+    capture_path()
+        .filter(|path| enabled() || path.exists())
+        .map(|path| path.to_string_lossy().to_string())
+}
@@ -0,0 +1,89 @@
+//! Checkpoints for creating a workspace.
+//!
+//! A first setup that failed halfway through `npm install` used to start
+//! over on the next launch, copying the template again over whatever was
+//! already there. `setup_workspace` now records its progress in
+//! `.langston-setup.json` in the workspace: the template version it copied
+//! and the steps it finished. A later run, or `retry_setup`, skips the
+//! finished steps, and the file is removed once the workspace is complete.
+//! A workspace with a package.json and no checkpoint is a finished one.
+
+use crate::error::StudioError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CHECKPOINT_FILE: &str = ".langston-setup.json";
+
+/// Steps of creating a workspace, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Step {
+    /// The template has been copied in full.
+    Template,
+    /// `npm install` succeeded.
+    Dependencies,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// Version of the template that was copied.
+    pub template_version: u32,
+    #[serde(default)]
+    pub completed: Vec<Step>,
+}
+
+fn path(workspace: &Path) -> PathBuf {
+    workspace.join(CHECKPOINT_FILE)
+}
+
+impl Checkpoint {
+    pub fn new(template_version: u32) -> Checkpoint {
+        Checkpoint {
+            template_version,
+            completed: Vec::new(),
+        }
+    }
+
+    /// The checkpoint of an unfinished setup in `workspace`, if there is
+    /// one. An unreadable checkpoint counts as none.
+    pub fn load(workspace: &Path) -> Option<Checkpoint> {
+        let contents = fs::read_to_string(path(workspace)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn is_done(&self, step: Step) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// The first step that hasn't finished yet.
+    pub fn next(&self) -> Option<Step> {
+        [Step::Template, Step::Dependencies]
+            .into_iter()
+            .find(|step| !self.is_done(*step))
+    }
+
+    pub fn save(&self, workspace: &Path) -> Result<(), StudioError> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let path = path(workspace);
+        fs::write(&path, json + "\n").map_err(|e| StudioError::io_at("Failed to write", &path, e))
+    }
+
+    /// Record `step` as finished.
+    pub fn complete(&mut self, workspace: &Path, step: Step) -> Result<(), StudioError> {
+        if !self.is_done(step) {
+            self.completed.push(step);
+        }
+        self.save(workspace)
+    }
+}
+
+/// Remove the checkpoint once the workspace is complete.
+pub fn clear(workspace: &Path) -> Result<(), StudioError> {
+    match fs::remove_file(path(workspace)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(StudioError::io_at("Failed to remove", &path(workspace), e)),
+    }
+}
@@ -0,0 +1,134 @@
+//! Copying directory trees: the workspace template and the bundled npm
+//! cache.
+//!
+//! `copy_dir_recursive` used to copy file by file with nothing to show for
+//! it, which took a while for templates with large assets. `copy_dir` counts
+//! what it's about to copy first and calls back with a `Progress` as it
+//! goes. Junk files (`.DS_Store`, AppleDouble `._*` files and the like) are
+//! skipped.
+//!
+//! With `Mode::LinkDependencies`, files inside `node_modules` are
+//! hard-linked when the destination is on the same volume and copied
+//! otherwise. npm replaces packages rather than writing into them, so that's
+//! safe there; anywhere else an edit to the copy would also change the
+//! source, so the rest is copied. Copies already clone on APFS, since
+//! `std::fs::copy` uses `fclonefileat` on macOS.
+
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Files that are never copied.
+const JUNK: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini", ".localized"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Copy,
+    /// Hard-link what's in `node_modules`; see the module docs.
+    LinkDependencies,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub files_copied: u64,
+    pub files_total: u64,
+    pub bytes_copied: u64,
+    pub bytes_total: u64,
+}
+
+impl Progress {
+    /// Share of the bytes copied so far, 0 to 100.
+    pub fn percent(&self) -> u64 {
+        (self.bytes_copied * 100)
+            .checked_div(self.bytes_total)
+            .unwrap_or(100)
+    }
+}
+
+fn is_junk(name: &str) -> bool {
+    JUNK.contains(&name) || name.starts_with("._")
+}
+
+/// Files and bytes under `src` that `copy_dir` would copy.
+fn measure(src: &Path, total: &mut Progress) -> io::Result<()> {
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        if is_junk(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            measure(&entry.path(), total)?;
+        } else {
+            total.files_total += 1;
+            total.bytes_total += entry.metadata()?.len();
+        }
+    }
+    Ok(())
+}
+
+fn copy_file(src: &Path, dst: &Path, link: bool) -> io::Result<()> {
+    if link {
+        // Across volumes (or on filesystems without hard links) this fails
+        // and the file is copied instead.
+        let _ = fs::remove_file(dst);
+        if fs::hard_link(src, dst).is_ok() {
+            return Ok(());
+        }
+    }
+    fs::copy(src, dst).map(|_| ())
+}
+
+fn copy_tree(
+    src: &Path,
+    dst: &Path,
+    mode: Mode,
+    in_node_modules: bool,
+    progress: &mut Progress,
+    on_progress: &mut dyn FnMut(&Progress),
+) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        if is_junk(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        if file_type.is_dir() {
+            let in_node_modules = in_node_modules || entry.file_name() == "node_modules";
+            copy_tree(
+                &src_path,
+                &dst_path,
+                mode,
+                in_node_modules,
+                progress,
+                on_progress,
+            )?;
+        } else {
+            let link = in_node_modules && mode == Mode::LinkDependencies;
+            copy_file(&src_path, &dst_path, link)?;
+            progress.files_copied += 1;
+            progress.bytes_copied += entry.metadata()?.len();
+            on_progress(progress);
+        }
+    }
+    Ok(())
+}
+
+/// Copy everything under `src` into `dst`, calling `on_progress` after
+/// every file. Returns the final progress.
+pub fn copy_dir(
+    src: &Path,
+    dst: &Path,
+    mode: Mode,
+    mut on_progress: impl FnMut(&Progress),
+) -> io::Result<Progress> {
+    let mut progress = Progress::default();
+    measure(src, &mut progress)?;
+    copy_tree(src, dst, mode, false, &mut progress, &mut on_progress)?;
+    Ok(progress)
+}
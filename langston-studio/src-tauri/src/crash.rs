@@ -0,0 +1,224 @@
+//! Crash reports for the OpenCode and Remotion servers.
+//!
+//! When a server died, all we had was "exited unexpectedly with code None":
+//! no signal, and its last words were somewhere in a rotated log. The last
+//! `OUTPUT_LINES` lines each server printed are now kept in memory, and when
+//! the supervisor sees one exit it didn't ask for, `report` writes a crash
+//! report to `crashes/` in the logs folder with the exit code, the signal
+//! that killed it (Unix only), how long it had been up and that output. The
+//! report is attached to the Sentry event, which is still only sent while
+//! telemetry is enabled, and goes into the diagnostics bundle. Only the
+//! newest `MAX_REPORTS` reports are kept.
+
+use crate::supervisor::Service;
+use crate::{get_logs_dir, telemetry};
+use chrono::Local;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Output lines kept per server for the report.
+const OUTPUT_LINES: usize = 200;
+const MAX_REPORTS: usize = 20;
+
+static OUTPUT: Mutex<[VecDeque<String>; 2]> = Mutex::new([VecDeque::new(), VecDeque::new()]);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Crash {
+    pub service: &'static str,
+    pub pid: u32,
+    pub exit_code: Option<i32>,
+    /// The signal that ended it, on Unix.
+    pub signal: Option<i32>,
+    pub signal_name: Option<&'static str>,
+    pub uptime_secs: u64,
+    pub at: String,
+    /// Oldest first, each prefixed with its stream.
+    pub output_tail: Vec<String>,
+}
+
+impl Crash {
+    /// How it ended, for log lines: "code 1", "signal 9 (SIGKILL)".
+    pub fn describe(&self) -> String {
+        match (self.exit_code, self.signal) {
+            (Some(code), _) => format!("code {}", code),
+            (None, Some(signal)) => match self.signal_name {
+                Some(name) => format!("signal {} ({})", signal, name),
+                None => format!("signal {}", signal),
+            },
+            (None, None) => "an unknown status".to_string(),
+        }
+    }
+}
+
+fn index(service: Service) -> usize {
+    match service {
+        Service::OpenCode => 0,
+        Service::Remotion => 1,
+    }
+}
+
+fn service_of(name: &str) -> Option<Service> {
+    Service::ALL.into_iter().find(|s| s.id() == name)
+}
+
+/// Keep a line a server printed, e.g. `record_output("remotion", "stderr", line)`.
+pub fn record_output(name: &str, stream: &str, line: &str) {
+    let Some(service) = service_of(name) else {
+        return;
+    };
+    if let Ok(mut output) = OUTPUT.lock() {
+        let lines = &mut output[index(service)];
+        if lines.len() == OUTPUT_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(format!("[{}] {}", stream, line));
+    }
+}
+
+/// Forget a server's output; called when a new process is spawned.
+pub fn clear_output(name: &str) {
+    let Some(service) = service_of(name) else {
+        return;
+    };
+    if let Ok(mut output) = OUTPUT.lock() {
+        output[index(service)].clear();
+    }
+}
+
+#[cfg(unix)]
+fn signal_of(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal_of(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
+
+pub fn crashes_dir() -> PathBuf {
+    get_logs_dir().join("crashes")
+}
+
+fn render(crash: &Crash) -> String {
+    let mut report = format!(
+        "Service: {}\nPID: {}\nExited with: {}\nUptime: {}s\nTime: {}\nPlatform: {} {}\nApp version: {}\n\n\
+         Last {} lines of output:\n",
+        crash.service,
+        crash.pid,
+        crash.describe(),
+        crash.uptime_secs,
+        crash.at,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        env!("CARGO_PKG_VERSION"),
+        crash.output_tail.len(),
+    );
+    for line in &crash.output_tail {
+        report.push_str(line);
+        report.push('\n');
+    }
+    report
+}
+
+/// Reports in `crashes_dir`, newest first.
+pub fn recent_reports() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(crashes_dir()) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".txt"))
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    reports.sort_by_key(|report| std::cmp::Reverse(report.0));
+    reports.into_iter().map(|(_, path)| path).collect()
+}
+
+fn write_report(crash: &Crash, contents: &str) -> std::io::Result<PathBuf> {
+    let dir = crashes_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{}_{}_{}.txt",
+        crash.service,
+        Local::now().format("%Y-%m-%d_%H-%M-%S"),
+        crash.pid
+    ));
+    std::fs::write(&path, contents)?;
+    for old in recent_reports().into_iter().skip(MAX_REPORTS) {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(path)
+}
+
+/// Describe how `service` exited, write the crash report and send it to
+/// Sentry. Returns the crash and where the report was written, if it could
+/// be.
+pub fn report(
+    service: Service,
+    pid: u32,
+    status: ExitStatus,
+    uptime: Duration,
+) -> (Crash, Result<PathBuf, String>) {
+    let signal = signal_of(&status);
+    let output_tail = OUTPUT
+        .lock()
+        .map(|output| output[index(service)].iter().cloned().collect())
+        .unwrap_or_default();
+    let crash = Crash {
+        service: service.id(),
+        pid,
+        exit_code: status.code(),
+        signal,
+        signal_name: signal.and_then(signal_name),
+        uptime_secs: uptime.as_secs(),
+        at: Local::now().to_rfc3339(),
+        output_tail,
+    };
+    let contents = render(&crash);
+    let path =
+        write_report(&crash, &contents).map_err(|e| format!("Failed to write crash report: {}", e));
+
+    let file_name = path
+        .as_ref()
+        .ok()
+        .and_then(|p| p.file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| format!("{}-crash.txt", crash.service));
+    telemetry::report_crash(
+        "supervisor",
+        &format!(
+            "{} exited unexpectedly with {}",
+            service.label(),
+            crash.describe()
+        ),
+        &[
+            ("service", crash.service.to_string()),
+            ("exit", crash.describe()),
+        ],
+        &file_name,
+        contents.into_bytes(),
+    );
+    (crash, path)
+}
@@ -0,0 +1,145 @@
+//! `langston-studio://` links.
+//!
+//! Links on the web dashboard can open the app on a project, with a prompt
+//! for the agent already typed in:
+//!
+//! ```text
+//! langston-studio://open?project=<id>&prompt=<text>
+//! ```
+//!
+//! Both parameters are optional; `langston-studio://open` just brings the
+//! window forward. The deep-link plugin registers the scheme with the OS and
+//! passes the links on, both the one the app was launched with and those
+//! opened while it runs (on Windows and Linux those arrive as a second
+//! launch, which the single-instance plugin forwards; see `instance`).
+//!
+//! Each link is sent to the UI as a `deep-link` event, `{ projectId,
+//! prompt }`, once it's listening. A link never does anything by itself: the
+//! UI asks before switching projects, and a prompt is only typed in, not
+//! sent.
+
+use crate::{frontend, tray};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Url};
+
+pub const SCHEME: &str = "langston-studio";
+/// Longer prompts are cut short rather than pasted whole.
+const MAX_PROMPT_LEN: usize = 10_000;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeepLink {
+    pub project_id: Option<String>,
+    pub prompt: Option<String>,
+}
+
+/// What `url` asks for.
+fn parse(url: &Url) -> Result<DeepLink, String> {
+    if url.scheme() != SCHEME {
+        return Err(format!("not a {}:// link", SCHEME));
+    }
+    // `langston-studio://open` has the action as its host, though some
+    // platforms hand it over as `langston-studio:open`.
+    let action = url
+        .host_str()
+        .unwrap_or_else(|| url.path())
+        .trim_matches('/');
+    if action != "open" {
+        return Err(format!("unknown action \"{}\"", action));
+    }
+    let mut link = DeepLink::default();
+    for (key, value) in url.query_pairs() {
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match key.as_ref() {
+            "project" => link.project_id = Some(value.to_string()),
+            "prompt" => link.prompt = Some(value.chars().take(MAX_PROMPT_LEN).collect()),
+            _ => {}
+        }
+    }
+    Ok(link)
+}
+
+/// Pass `urls` on to the UI, waiting for it on the one the app was launched
+/// with.
+pub fn handle(app: &AppHandle, urls: Vec<Url>) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if !frontend::is_ready() {
+            frontend::wait_until_ready();
+        }
+        for url in urls {
+            match parse(&url) {
+                Ok(link) => {
+                    crate::logging::log_line(
+                        "INFO",
+                        &format!(
+                            "[deep-link] Opened (project: {}, prompt: {})",
+                            link.project_id.as_deref().unwrap_or("-"),
+                            if link.prompt.is_some() { "yes" } else { "no" }
+                        ),
+                    );
+                    tray::show_main_window(&app);
+                    let _ = app.emit("deep-link", link);
+                }
+                Err(e) => crate::logging::log_line(
+                    "WARN",
+                    &format!("[deep-link] Ignoring {}: {}", url, e),
+                ),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(url: &str) -> Result<DeepLink, String> {
+        parse(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn open_with_a_project_and_a_prompt() {
+        let link =
+            parse_str("langston-studio://open?project=p_123&prompt=Make%20the%20intro%20blue")
+                .unwrap();
+        assert_eq!(link.project_id.as_deref(), Some("p_123"));
+        assert_eq!(link.prompt.as_deref(), Some("Make the intro blue"));
+    }
+
+    #[test]
+    fn parameters_are_optional() {
+        for url in [
+            "langston-studio://open",
+            "langston-studio://open/",
+            "langston-studio:open",
+            "langston-studio://open?project=&prompt=%20%20&other=1",
+        ] {
+            let link = parse_str(url).unwrap();
+            assert_eq!(link.project_id, None, "{}", url);
+            assert_eq!(link.prompt, None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn long_prompts_are_cut_short() {
+        let url = format!(
+            "langston-studio://open?prompt={}",
+            "é".repeat(MAX_PROMPT_LEN + 5)
+        );
+        let prompt = parse_str(&url).unwrap().prompt.unwrap();
+        assert_eq!(prompt.chars().count(), MAX_PROMPT_LEN);
+    }
+
+    #[test]
+    fn other_schemes_and_actions_are_refused() {
+        assert!(parse_str("https://open?project=p_123").is_err());
+        assert_eq!(
+            parse_str("langston-studio://delete?project=p_123").unwrap_err(),
+            "unknown action \"delete\""
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Diagnostics bundle for support.
+//!
+//! `create_diagnostics_bundle` collects everything support usually asks for
+//! into one zip in the logs folder:
+//!
+//! - `logs/`: this session's log files and those of the last few sessions
+//! - `crashes/`: the server crash reports (see `crash`)
+//! - `config.json`: config.json with API keys, tokens and credentials in
+//!   URLs redacted
+//! - `environment.json`: app version, OS, PATH and the node, npm and
+//!   opencode versions found on it
+//! - `ports.json`: the ports in use and which processes hold them
+//! - `git-status.txt`: status and recent history of the open workspace
+
+use crate::error::StudioError;
+use crate::{
+    get_config_path, get_logs_dir, get_path_env, git, load_config, platform, ports, providers,
+    resolve_workspace_dir,
+};
+use chrono::Local;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tauri::AppHandle;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Sessions whose logs are included, counting the current one.
+const LOG_SESSIONS: usize = 5;
+/// Logs beyond this total size are left out.
+const MAX_LOG_BYTES: u64 = 50 * 1024 * 1024;
+/// Every session log starts with this; see `logging`.
+const LOG_PREFIX: &str = "langston-studio_";
+const REDACTED: &str = "<redacted>";
+
+/// Whether a config field or environment variable holds a secret, going by
+/// its name.
+pub fn is_secret_field(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["key", "token", "secret", "password", "webhook"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// Strip `user:password@` from every URL in `text`, such as a git remote or
+/// an error message quoting one.
+pub(crate) fn redact_url_credentials(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find("://") {
+        let (head, tail) = rest.split_at(i + 3);
+        out.push_str(head);
+        let authority_end = tail
+            .find(|c: char| c == '/' || c.is_whitespace() || matches!(c, '\'' | '"' | '>' | ')'))
+            .unwrap_or(tail.len());
+        rest = match tail[..authority_end].rfind('@') {
+            Some(at) => {
+                out.push_str(REDACTED);
+                out.push('@');
+                &tail[at + 1..]
+            }
+            None => tail,
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (name, field) in map.iter_mut() {
+                if is_secret_field(name) && !field.is_null() {
+                    *field = serde_json::json!(REDACTED);
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        serde_json::Value::String(s) => *s = redact_url_credentials(s),
+        _ => {}
+    }
+}
+
+/// config.json with secrets replaced, plus whether the keys are set at all
+/// (they usually live in the keychain rather than the file).
+fn sanitized_config() -> serde_json::Value {
+    let path = get_config_path();
+    let mut file = std::fs::read_to_string(&path)
+        .ok()
+        .map(|contents| {
+            serde_json::from_str(&contents)
+                .unwrap_or_else(|e| serde_json::json!({ "parseError": e.to_string() }))
+        })
+        .unwrap_or(serde_json::Value::Null);
+    redact(&mut file);
+
+    let config = load_config();
+    serde_json::json!({
+        "configPath": path.to_string_lossy(),
+        "configExists": path.exists(),
+        "hasAnthropicKey": config.anthropic_api_key.is_some(),
+        "hasOpenaiKey": config.openai_api_key.is_some(),
+        "providers": providers::statuses(&config),
+        "file": file,
+    })
+}
+
+/// Where `binary` is on PATH and what `--version` says.
+fn tool_version(binary: &str, path_env: &str) -> serde_json::Value {
+    let Some(path) = platform::which(binary, path_env) else {
+        return serde_json::json!({ "found": false });
+    };
+    let version = match Command::new(&path)
+        .arg("--version")
+        .env("PATH", path_env)
+        .output()
+    {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).trim().to_string(),
+        Err(e) => format!("failed to run: {}", e),
+    };
+    serde_json::json!({
+        "found": true,
+        "path": path.to_string_lossy(),
+        "version": version,
+    })
+}
+
+fn environment(app: &AppHandle) -> serde_json::Value {
+    let path_env = get_path_env();
+    serde_json::json!({
+        "appVersion": app.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "createdAt": Local::now().to_rfc3339(),
+        "path": path_env,
+        "node": tool_version("node", &path_env),
+        "npm": tool_version("npm", &path_env),
+        "opencode": tool_version("opencode", &path_env),
+    })
+}
+
+fn port_status(app: &AppHandle) -> serde_json::Value {
+    let current = ports::current(app);
+    let describe = |port: u16| {
+        serde_json::json!({
+            "port": port,
+            "inUse": platform::port_in_use(port),
+            "pids": platform::pids_on_port(port),
+        })
+    };
+    serde_json::json!({
+        "remotion": describe(current.remotion),
+        "opencode": describe(current.opencode),
+        "proxy": describe(current.proxy),
+        "remotionProxy": describe(current.remotion_proxy),
+    })
+}
+
+fn git_status() -> String {
+    let workspace = resolve_workspace_dir();
+    let mut out = format!("Workspace: {}\n\n", workspace.display());
+    if !workspace.join(".git").exists() {
+        out.push_str("Not a git repository\n");
+        return out;
+    }
+    for args in [
+        &["status", "--short", "--branch"][..],
+        &["log", "--oneline", "-n", "20"][..],
+        &["remote", "-v"][..],
+    ] {
+        out.push_str(&format!("$ git {}\n", args.join(" ")));
+        match git::run_git(&workspace, args) {
+            Ok(output) if args[0] == "remote" => {
+                for line in output.lines() {
+                    out.push_str(&redact_url_credentials(line));
+                    out.push('\n');
+                }
+            }
+            Ok(output) => out.push_str(&output),
+            Err(e) => out.push_str(&format!("{}\n", e)),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Log files of the newest `LOG_SESSIONS` sessions, newest first, up to
+/// `MAX_LOG_BYTES` in total.
+fn recent_logs(logs_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(logs_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(std::time::SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(LOG_PREFIX))
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            Some((meta.modified().ok()?, meta.len(), e.path()))
+        })
+        .collect();
+    files.sort_by_key(|file| std::cmp::Reverse(file.0));
+
+    let session_of = |path: &Path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        name.split('.').next().unwrap_or_default().to_string()
+    };
+    let mut sessions: Vec<String> = Vec::new();
+    let mut total = 0;
+    let mut picked = Vec::new();
+    for (_, size, path) in files {
+        let session = session_of(&path);
+        if !sessions.contains(&session) {
+            if sessions.len() == LOG_SESSIONS {
+                continue;
+            }
+            sessions.push(session);
+        }
+        if total + size > MAX_LOG_BYTES {
+            continue;
+        }
+        total += size;
+        picked.push(path);
+    }
+    picked
+}
+
+fn add_file(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    contents: &[u8],
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    zip.start_file(name, options)
+        .map_err(|e| format!("Failed to add {}: {}", name, e))?;
+    zip.write_all(contents)
+        .map_err(|e| format!("Failed to add {}: {}", name, e))
+}
+
+fn create_bundle(app: &AppHandle) -> Result<PathBuf, String> {
+    crate::logging::flush();
+    let logs_dir = get_logs_dir();
+    std::fs::create_dir_all(&logs_dir)
+        .map_err(|e| format!("Failed to create {}: {}", logs_dir.display(), e))?;
+    let path = logs_dir.join(format!(
+        "langston-studio-diagnostics_{}.zip",
+        Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    let file =
+        File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let pretty = |value: serde_json::Value| serde_json::to_vec_pretty(&value).unwrap_or_default();
+
+    add_file(
+        &mut zip,
+        "config.json",
+        &pretty(sanitized_config()),
+        options,
+    )?;
+    add_file(
+        &mut zip,
+        "environment.json",
+        &pretty(environment(app)),
+        options,
+    )?;
+    add_file(&mut zip, "ports.json", &pretty(port_status(app)), options)?;
+    add_file(&mut zip, "git-status.txt", git_status().as_bytes(), options)?;
+    for log in recent_logs(&logs_dir) {
+        // A log can disappear between listing and reading (rotation); skip it.
+        let Ok(contents) = std::fs::read(&log) else {
+            continue;
+        };
+        // Older logs may quote a backup remote with its credentials.
+        let contents = redact_url_credentials(&String::from_utf8_lossy(&contents));
+        let name = log.file_name().unwrap_or_default().to_string_lossy();
+        add_file(
+            &mut zip,
+            &format!("logs/{}", name),
+            contents.as_bytes(),
+            options,
+        )?;
+    }
+    for report in crate::crash::recent_reports() {
+        let Ok(contents) = std::fs::read(&report) else {
+            continue;
+        };
+        let name = report.file_name().unwrap_or_default().to_string_lossy();
+        add_file(&mut zip, &format!("crashes/{}", name), &contents, options)?;
+    }
+
+    zip.finish()
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Write a diagnostics zip to the logs folder and return its path.
+#[tauri::command]
+pub async fn create_diagnostics_bundle(app: AppHandle) -> Result<String, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = create_bundle(&app)?;
+        crate::logging::log_line("INFO", &format!("Diagnostics bundle written to {:?}", path));
+        Ok(path.to_string_lossy().to_string())
+    })
+    .await?
+}
@@ -0,0 +1,206 @@
+//! Where the workspace's disk space goes, and getting some of it back.
+//!
+//! `get_disk_usage` breaks the open workspace down into node_modules, .git,
+//! renders (`out/`), imported assets and caches (`node_modules/.cache` and
+//! Remotion's webpack cache, which is kept outside the workspace; see
+//! `prewarm`). `clean_workspace` removes the targets that can be rebuilt or
+//! re-rendered:
+//!
+//! - `caches`: Remotion is stopped while its cache is removed
+//! - `renders`: refused while a render is queued or running
+//! - `nodeModules`: both servers are stopped, node_modules is removed and
+//!   reinstalled, and the servers come back up
+//!
+//! `.git` and assets are the user's work and are only reported.
+
+use crate::error::StudioError;
+use crate::supervisor::{self, Service};
+use crate::{assets, get_path_env, npm, prewarm, render, resolve_workspace_dir};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+const CLEANABLE: &[&str] = &["caches", "renders", "nodeModules"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskUsage {
+    pub workspace: String,
+    /// Everything in node_modules except the caches.
+    pub node_modules: u64,
+    pub git: u64,
+    pub renders: u64,
+    pub assets: u64,
+    pub caches: u64,
+    /// The project's own files.
+    pub other: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanResult {
+    pub cleaned: Vec<String>,
+    pub reclaimed_bytes: u64,
+    pub usage: DiskUsage,
+}
+
+/// Size of everything under `path`. Symlinks count as themselves and aren't
+/// followed.
+fn size_of(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries.flatten().map(|entry| size_of(&entry.path())).sum()
+}
+
+fn caches_dir(workspace: &Path) -> PathBuf {
+    workspace.join("node_modules").join(".cache")
+}
+
+fn usage(workspace: &Path) -> DiskUsage {
+    let webpack_cache = size_of(&prewarm::cache_dir(workspace));
+    let total = size_of(workspace) + webpack_cache;
+    let node_modules_cache = size_of(&caches_dir(workspace));
+    let caches = node_modules_cache + webpack_cache;
+    let node_modules = size_of(&workspace.join("node_modules")).saturating_sub(node_modules_cache);
+    let git = size_of(&workspace.join(".git"));
+    let renders = size_of(&workspace.join("out"));
+    let assets = size_of(&workspace.join(assets::ASSETS_DIR));
+    DiskUsage {
+        workspace: workspace.to_string_lossy().to_string(),
+        node_modules,
+        git,
+        renders,
+        assets,
+        caches,
+        other: total.saturating_sub(node_modules + caches + git + renders + assets),
+        total,
+    }
+}
+
+/// Remove what's inside `dir`, keeping `dir` itself.
+fn empty_dir(dir: &Path) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let result = match entry.file_type() {
+            Ok(t) if t.is_dir() => fs::remove_dir_all(&path),
+            _ => fs::remove_file(&path),
+        };
+        result.map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+fn remove_dir(dir: &Path) -> Result<(), String> {
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {}", dir.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn remove_caches(workspace: &Path) -> Result<(), String> {
+    remove_dir(&caches_dir(workspace))?;
+    remove_dir(&prewarm::cache_dir(workspace))
+}
+
+fn clean(app: &AppHandle, workspace: &Path, target: &str) -> Result<(), StudioError> {
+    match target {
+        "caches" => supervisor::relaunch(
+            app,
+            &[Service::Remotion],
+            "Clearing Remotion's cache",
+            |_| remove_caches(workspace),
+        )
+        .map_err(Into::into),
+        "renders" => {
+            if render::is_busy(app) {
+                return Err(StudioError::InvalidInput(
+                    "Wait for the running renders to finish first".to_string(),
+                ));
+            }
+            Ok(empty_dir(&workspace.join("out"))?)
+        }
+        "nodeModules" => {
+            supervisor::relaunch(app, &Service::ALL, "Reinstalling dependencies", |app| {
+                remove_dir(&workspace.join("node_modules"))?;
+                Ok(npm::install(app, workspace, &get_path_env())?)
+            })
+            .map_err(Into::into)
+        }
+        other => Err(StudioError::InvalidInput(format!(
+            "Unknown target: {}",
+            other
+        ))),
+    }
+}
+
+fn clean_blocking(app: &AppHandle, targets: &[String]) -> Result<CleanResult, StudioError> {
+    if let Some(target) = targets.iter().find(|t| !CLEANABLE.contains(&t.as_str())) {
+        return Err(StudioError::InvalidInput(format!(
+            "{} can't be cleaned; choose from {}",
+            target,
+            CLEANABLE.join(", ")
+        )));
+    }
+    let workspace = resolve_workspace_dir();
+    let before = usage(&workspace);
+
+    let mut cleaned = Vec::new();
+    // node_modules includes the caches, so clean the caches first and don't
+    // restart Remotion twice.
+    for target in CLEANABLE {
+        if !targets.iter().any(|t| t == target) {
+            continue;
+        }
+        if *target == "caches" && targets.iter().any(|t| t == "nodeModules") {
+            remove_caches(&workspace)?;
+        } else {
+            clean(app, &workspace, target)?;
+        }
+        crate::logging::log_line("INFO", &format!("Cleaned {} in {:?}", target, workspace));
+        cleaned.push(target.to_string());
+    }
+
+    let after = usage(&workspace);
+    Ok(CleanResult {
+        cleaned,
+        reclaimed_bytes: before.total.saturating_sub(after.total),
+        usage: after,
+    })
+}
+
+/// Disk space used by the open workspace, by category.
+#[tauri::command]
+pub async fn get_disk_usage() -> Result<DiskUsage, StudioError> {
+    Ok(tauri::async_runtime::spawn_blocking(|| usage(&resolve_workspace_dir())).await?)
+}
+
+/// Remove `targets` ("caches", "renders", "nodeModules") from the open
+/// workspace and report how much space that freed.
+#[tauri::command]
+pub async fn clean_workspace(
+    app: AppHandle,
+    targets: Vec<String>,
+) -> Result<CleanResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = clean_blocking(&app, &targets);
+        if let Err(e) = &result {
+            crate::logging::log_line("ERROR", &format!("Cleanup failed: {}", e));
+        }
+        result
+    })
+    .await?
+}
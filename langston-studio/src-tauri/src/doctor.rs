@@ -0,0 +1,229 @@
+//! Dependency preflight ("doctor").
+//!
+//! Before setup runs, each tool it needs is looked up on the same PATH the
+//! servers are started with and its version checked against a minimum. The
+//! result is sent as a `preflight-report` event, and `run_doctor` produces
+//! the same report on demand, so the UI can say exactly what's missing and
+//! how to fix it instead of failing later with "Failed to run npm install".
+//!
+//! node and npm that are missing or too old don't block setup when nvm is
+//! installed: setup runs `nvm install`, which provides the version pinned in
+//! the workspace's .nvmrc. Without nvm, setup installs a managed Node.js
+//! (see `runtime`) and checks again. A missing opencode doesn't block setup
+//! either, since setup installs it.
+
+use crate::error::StudioError;
+use crate::runtime::{self, parse_version};
+use crate::{get_path_env, has_nvm, platform};
+use serde::Serialize;
+use std::process::Command;
+use tauri::AppHandle;
+
+/// A tool setup depends on.
+struct Requirement {
+    name: &'static str,
+    /// Oldest supported version, as (major, minor).
+    min_version: (u64, u64),
+    /// Whether nvm can stand in for it.
+    nvm_provides: bool,
+    /// Whether setup installs it when it's missing.
+    installed_by_setup: bool,
+    fix: &'static str,
+}
+
+const REQUIREMENTS: &[Requirement] = &[
+    Requirement {
+        name: "node",
+        min_version: (runtime::MIN_NODE_MAJOR, 0),
+        nvm_provides: true,
+        installed_by_setup: false,
+        fix: "Install Node.js 18 or newer from https://nodejs.org (the LTS installer is fine), then restart Langston Studio.",
+    },
+    Requirement {
+        name: "npm",
+        min_version: (8, 0),
+        nvm_provides: true,
+        installed_by_setup: false,
+        fix: "npm comes with Node.js. Reinstall Node.js 18 or newer from https://nodejs.org, then restart Langston Studio.",
+    },
+    Requirement {
+        name: "git",
+        min_version: (2, 20),
+        nvm_provides: false,
+        installed_by_setup: false,
+        fix: if cfg!(target_os = "macos") {
+            "Run `xcode-select --install` in Terminal to install git, then restart Langston Studio."
+        } else {
+            "Install git from https://git-scm.com/downloads, then restart Langston Studio."
+        },
+    },
+    Requirement {
+        name: "opencode",
+        min_version: (0, 0),
+        nvm_provides: false,
+        installed_by_setup: true,
+        fix: "Setup will install the opencode CLI automatically.",
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Check {
+    pub name: String,
+    /// "ok", "missing", "outdated" or "unknownVersion".
+    pub status: String,
+    /// Whether this problem stops setup from running.
+    pub blocking: bool,
+    pub path: Option<String>,
+    pub version: Option<String>,
+    pub min_version: String,
+    /// What's wrong, for anything but "ok".
+    pub message: Option<String>,
+    /// How to fix it, for anything but "ok".
+    pub fix: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    /// False if any check is blocking.
+    pub ok: bool,
+    pub nvm_available: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Whether node or npm is what's blocking; the managed runtime can
+    /// provide both.
+    pub fn needs_node(&self) -> bool {
+        self.blocked_by("node") || self.blocked_by("npm")
+    }
+
+    /// Whether the check for `name` is blocking.
+    pub fn blocked_by(&self, name: &str) -> bool {
+        self.checks.iter().any(|c| c.blocking && c.name == name)
+    }
+
+    /// The blocking problems as an error, coded by the tool that's missing.
+    pub fn error(&self) -> StudioError {
+        let summary = self.summary();
+        if self.needs_node() {
+            StudioError::NodeMissing(summary)
+        } else if self.blocked_by("git") {
+            StudioError::GitMissing(summary)
+        } else if self.blocked_by("opencode") {
+            StudioError::OpencodeMissing(summary)
+        } else {
+            StudioError::Other(summary)
+        }
+    }
+
+    /// One line per blocking problem, for logs and the setup error.
+    pub fn summary(&self) -> String {
+        self.checks
+            .iter()
+            .filter(|c| c.blocking)
+            .map(|c| {
+                format!(
+                    "{} {}",
+                    c.message.as_deref().unwrap_or(&c.name),
+                    c.fix.as_deref().unwrap_or("")
+                )
+                .trim()
+                .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn check(req: &Requirement, path_env: &str, nvm_available: bool) -> Check {
+    let min_version = format!("{}.{}", req.min_version.0, req.min_version.1);
+    let mut result = Check {
+        name: req.name.to_string(),
+        status: "ok".to_string(),
+        blocking: false,
+        path: None,
+        version: None,
+        min_version,
+        message: None,
+        fix: None,
+    };
+
+    let Some(path) = platform::which(req.name, path_env) else {
+        result.status = "missing".to_string();
+        result.message = Some(format!("{} was not found.", req.name));
+        result.fix = Some(req.fix.to_string());
+        result.blocking = !(req.installed_by_setup || req.nvm_provides && nvm_available);
+        return result;
+    };
+    result.path = Some(path.to_string_lossy().to_string());
+
+    let output = Command::new(&path)
+        .arg("--version")
+        .env("PATH", path_env)
+        .output();
+    let version = output
+        .ok()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|v| !v.is_empty());
+    let parsed = version.as_deref().and_then(parse_version);
+    result.version = version;
+
+    match parsed {
+        None => {
+            // Found but wouldn't say its version; let setup try it.
+            result.status = "unknownVersion".to_string();
+            result.message = Some(format!("Couldn't determine the {} version.", req.name));
+            result.fix = Some(req.fix.to_string());
+        }
+        Some(v) => {
+            let major = v.first().copied().unwrap_or(0);
+            let minor = v.get(1).copied().unwrap_or(0);
+            if (major, minor) < req.min_version {
+                result.status = "outdated".to_string();
+                result.message = Some(format!(
+                    "{} {}.{} is too old; {} or newer is required.",
+                    req.name, major, minor, result.min_version
+                ));
+                result.fix = Some(req.fix.to_string());
+                result.blocking = !(req.nvm_provides && nvm_available);
+            }
+        }
+    }
+    result
+}
+
+/// Check every tool setup needs.
+pub fn run() -> Report {
+    let path_env = get_path_env();
+    let nvm_available = has_nvm();
+    let checks: Vec<Check> = REQUIREMENTS
+        .iter()
+        .map(|req| check(req, &path_env, nvm_available))
+        .collect();
+    Report {
+        ok: !checks.iter().any(|c| c.blocking),
+        nvm_available,
+        checks,
+    }
+}
+
+/// Run the checks, log any problems and send the `preflight-report` event.
+pub fn preflight(app: &AppHandle) -> Report {
+    let report = run();
+    for check in &report.checks {
+        if let Some(message) = &check.message {
+            let level = if check.blocking { "ERROR" } else { "WARN" };
+            crate::logging::log_line(level, &format!("[doctor] {}", message));
+        }
+    }
+    crate::frontend::emit(app, "preflight-report", &report);
+    report
+}
+
+/// Check the tools setup needs and report what's missing.
+#[tauri::command]
+pub async fn run_doctor(app: AppHandle) -> Result<Report, StudioError> {
+    Ok(tauri::async_runtime::spawn_blocking(move || preflight(&app)).await?)
+}
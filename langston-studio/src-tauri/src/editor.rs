@@ -0,0 +1,139 @@
+//! "Jump to file" links for workspace files.
+//!
+//! `open_in_finder` reveals a file and `open_in_editor` opens it at a line
+//! in VS Code or Cursor. Paths come from the AI panel, so they're resolved
+//! against the open workspace and rejected if they end up outside it
+//! (`..`, absolute paths elsewhere, symlinks pointing out).
+//!
+//! The editor is the `editor` setting if set, otherwise the first of
+//! `EDITORS` found on PATH or in /Applications. Without either the file is
+//! opened with its default app.
+
+use crate::error::StudioError;
+use crate::{get_path_env, platform, read_config_file, resolve_workspace_dir};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub struct Editor {
+    /// Value of the `editor` setting, and the name of its CLI.
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Where the CLI lives inside the macOS app bundle, for installs that
+    /// never ran "Install 'code' command in PATH".
+    bundled_cli: &'static str,
+}
+
+pub static EDITORS: [Editor; 2] = [
+    Editor {
+        id: "code",
+        name: "VS Code",
+        bundled_cli: "/Applications/Visual Studio Code.app/Contents/Resources/app/bin/code",
+    },
+    Editor {
+        id: "cursor",
+        name: "Cursor",
+        bundled_cli: "/Applications/Cursor.app/Contents/Resources/app/bin/cursor",
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenedIn {
+    pub path: String,
+    /// The editor used, or "default app".
+    pub app: String,
+}
+
+/// The CLI for the editor with this `id`, if it's installed.
+pub fn find(id: &str) -> Option<(&'static Editor, PathBuf)> {
+    let editor = EDITORS.iter().find(|editor| editor.id == id)?;
+    let cli = platform::which(editor.id, &get_path_env()).or_else(|| {
+        let bundled = Path::new(editor.bundled_cli);
+        (cfg!(target_os = "macos") && bundled.is_file()).then(|| bundled.to_path_buf())
+    })?;
+    Some((editor, cli))
+}
+
+fn preferred_editor() -> Option<(&'static Editor, PathBuf)> {
+    read_config_file()
+        .editor
+        .and_then(|id| find(&id))
+        .or_else(|| EDITORS.iter().find_map(|editor| find(editor.id)))
+}
+
+/// `path` (relative to the workspace, or absolute) as a canonical path,
+/// if it exists and is inside the workspace.
+fn resolve_in_workspace(path: &str) -> Result<PathBuf, StudioError> {
+    let workspace = resolve_workspace_dir();
+    let workspace = workspace
+        .canonicalize()
+        .map_err(|_| StudioError::NotFound(format!("Workspace {}", workspace.display())))?;
+    let full = workspace.join(path.trim());
+    let full = full
+        .canonicalize()
+        .map_err(|_| StudioError::NotFound(path.to_string()))?;
+    if !full.starts_with(&workspace) {
+        return Err(StudioError::InvalidInput(format!(
+            "{} is outside the workspace",
+            path
+        )));
+    }
+    Ok(full)
+}
+
+/// Show a workspace file or folder in Finder.
+#[tauri::command]
+pub fn open_in_finder(path: String) -> Result<OpenedIn, StudioError> {
+    let full = resolve_in_workspace(&path)?;
+    platform::reveal_path(&full)
+        .map_err(|e| StudioError::io(format!("Failed to open {}", path), e))?;
+    crate::logging::log_line("INFO", &format!("Revealed {}", path));
+    let file_manager = if cfg!(target_os = "macos") {
+        "Finder"
+    } else {
+        "file manager"
+    };
+    Ok(OpenedIn {
+        path: full.to_string_lossy().to_string(),
+        app: file_manager.to_string(),
+    })
+}
+
+/// Open a workspace file in the editor, at `line` if given. The workspace
+/// is opened with it so the editor's search and git views work.
+#[tauri::command]
+pub fn open_in_editor(path: String, line: Option<u32>) -> Result<OpenedIn, StudioError> {
+    let full = resolve_in_workspace(&path)?;
+
+    let Some((editor, cli)) = preferred_editor() else {
+        platform::open_path(&full)
+            .map_err(|e| StudioError::io(format!("Failed to open {}", path), e))?;
+        crate::logging::log_line(
+            "INFO",
+            &format!("No editor found, opened {} with its default app", path),
+        );
+        return Ok(OpenedIn {
+            path: full.to_string_lossy().to_string(),
+            app: "default app".to_string(),
+        });
+    };
+
+    let target = match line {
+        Some(line) if line > 0 => format!("{}:{}", full.display(), line),
+        _ => full.to_string_lossy().to_string(),
+    };
+    Command::new(&cli)
+        .arg(resolve_workspace_dir())
+        .arg("--goto")
+        .arg(&target)
+        .env("PATH", get_path_env())
+        .spawn()
+        .map_err(|e| StudioError::io(format!("Failed to start {}", editor.name), e))?;
+
+    crate::logging::log_line("INFO", &format!("Opened {} in {}", target, editor.name));
+    Ok(OpenedIn {
+        path: full.to_string_lossy().to_string(),
+        app: editor.name.to_string(),
+    })
+}
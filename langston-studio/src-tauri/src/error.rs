@@ -0,0 +1,228 @@
+//! Errors returned to the frontend.
+//!
+//! Commands used to fail with a bare string, so the UI could only print it.
+//! They now return a `StudioError`, which reaches the frontend as
+//!
+//! ```json
+//! {
+//!   "code": "PORT_IN_USE",
+//!   "message": "Port 4096 is in use...",
+//!   "hint": "Quit...",
+//!   "retryable": false
+//! }
+//! ```
+//!
+//! `code` is stable and meant for branching on; `hint` says what the user
+//! can do about it, and `retryable` whether it may pass on its own (it has
+//! already been retried where `retry` is used). `setup-error` events carry
+//! the same object. Helpers that still return `Result<_, String>` convert
+//! into `Other`, so only the failures that have a known cause need mapping.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StudioError {
+    /// Node.js or npm is missing or too old.
+    #[error("{0}")]
+    NodeMissing(String),
+    #[error("{0}")]
+    GitMissing(String),
+    /// The opencode CLI isn't installed and installing it failed.
+    #[error("{0}")]
+    OpencodeMissing(String),
+    #[error("Port {port} is in use and no free port was found near it")]
+    PortInUse { port: u16 },
+    #[error("Not enough disk space for {0}")]
+    DiskFull(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    Network(String),
+    #[error("npm install failed: {0}")]
+    NpmInstall(String),
+    #[error("{what} timed out after {secs}s")]
+    Timeout { what: String, secs: u64 },
+    #[error("{0} cancelled")]
+    Cancelled(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Keychain error: {0}")]
+    Keychain(String),
+    #[error("{0}")]
+    Config(String),
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+    /// An experiment branch couldn't be merged back cleanly.
+    #[error("Merging the experiment conflicts with other changes in {}", files.join(", "))]
+    MergeConflict { files: Vec<String> },
+    /// An S3-compatible bucket turned a request down with error `code`.
+    #[error("the bucket said {code}: {message}")]
+    Bucket { code: String, message: String },
+    /// Another running copy of the app has the workspace open.
+    #[error("The workspace is open in another Langston Studio window (process {pid})")]
+    WorkspaceLocked { pid: u32 },
+    #[error("{0}")]
+    Other(String),
+}
+
+impl StudioError {
+    /// Classify an I/O failure while doing `context`, so a full disk or a
+    /// permission problem gets its own code.
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        let context = context.into();
+        // ENOSPC on unix, ERROR_DISK_FULL on Windows.
+        let disk_full = if cfg!(windows) { 112 } else { 28 };
+        if source.raw_os_error() == Some(disk_full) {
+            return StudioError::DiskFull(context);
+        }
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => StudioError::PermissionDenied(context),
+            _ => StudioError::Io { context, source },
+        }
+    }
+
+    /// `io` for a failure on `path`.
+    pub fn io_at(action: &str, path: &Path, source: io::Error) -> Self {
+        Self::io(format!("{} {}", action, path.display()), source)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            StudioError::NodeMissing(_) => "NODE_MISSING",
+            StudioError::GitMissing(_) => "GIT_MISSING",
+            StudioError::OpencodeMissing(_) => "OPENCODE_MISSING",
+            StudioError::PortInUse { .. } => "PORT_IN_USE",
+            StudioError::DiskFull(_) => "DISK_FULL",
+            StudioError::PermissionDenied(_) => "PERMISSION_DENIED",
+            StudioError::Network(_) => "NETWORK",
+            StudioError::NpmInstall(_) => "NPM_INSTALL_FAILED",
+            StudioError::Timeout { .. } => "TIMEOUT",
+            StudioError::Cancelled(_) => "CANCELLED",
+            StudioError::InvalidInput(_) => "INVALID_INPUT",
+            StudioError::NotFound(_) => "NOT_FOUND",
+            StudioError::Keychain(_) => "KEYCHAIN",
+            StudioError::Config(_) => "CONFIG",
+            StudioError::Io { .. } => "IO",
+            StudioError::MergeConflict { .. } => "MERGE_CONFLICT",
+            StudioError::Bucket { .. } => "BUCKET",
+            StudioError::WorkspaceLocked { .. } => "WORKSPACE_LOCKED",
+            StudioError::Other(_) => "UNKNOWN",
+        }
+    }
+
+    /// Whether trying again may help: the network, a slow step or npm
+    /// rather than something that needs fixing first.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            StudioError::Network(_) | StudioError::Timeout { .. } | StudioError::NpmInstall(_)
+        )
+    }
+
+    /// What the user can do about it, if there's anything to suggest.
+    pub fn hint(&self) -> Option<&'static str> {
+        let hint = match self {
+            StudioError::NodeMissing(_) => {
+                "Install Node.js 18 or newer from https://nodejs.org, or run the dependency check \
+                 again to let Langston Studio install it."
+            }
+            StudioError::GitMissing(_) => {
+                if cfg!(target_os = "macos") {
+                    "Run `xcode-select --install` in Terminal, then restart Langston Studio."
+                } else {
+                    "Install git from https://git-scm.com/downloads, then restart Langston Studio."
+                }
+            }
+            StudioError::OpencodeMissing(_) => {
+                "Check your internet connection and restart Langston Studio to retry the install, \
+                 or install it yourself with `npm install -g opencode-ai`."
+            }
+            StudioError::PortInUse { .. } => {
+                "Quit other development servers or apps using these ports, then restart the \
+                 services."
+            }
+            StudioError::DiskFull(_) => {
+                "Free up disk space, for example with Clean Workspace in Settings, then try again."
+            }
+            StudioError::PermissionDenied(_) => {
+                "Check that your user can write to this folder, or choose a different workspace \
+                 location."
+            }
+            StudioError::Network(_) => "Check your internet connection and try again.",
+            StudioError::NpmInstall(_) => {
+                "Open the logs for npm's output. Restarting Langston Studio retries the install."
+            }
+            StudioError::Timeout { .. } => {
+                "This can happen on slow connections. Restart Langston Studio to try again."
+            }
+            StudioError::Keychain(_) => {
+                "Allow Langston Studio to access the keychain when asked, or unlock it and try \
+                 again."
+            }
+            StudioError::Config(_) => {
+                "Fix or remove config.json in the app's config folder, then restart Langston \
+                 Studio."
+            }
+            StudioError::MergeConflict { .. } => {
+                "Keep working in the experiment, or discard it to go back to where it started."
+            }
+            StudioError::WorkspaceLocked { .. } => {
+                "Switch to the other window, or quit it and try again. If it has stopped \
+                 responding, open the workspace here anyway."
+            }
+            StudioError::Cancelled(_)
+            | StudioError::InvalidInput(_)
+            | StudioError::NotFound(_)
+            | StudioError::Io { .. }
+            | StudioError::Bucket { .. }
+            | StudioError::Other(_) => return None,
+        };
+        Some(hint)
+    }
+}
+
+impl Serialize for StudioError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("StudioError", 4)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("hint", &self.hint())?;
+        s.serialize_field("retryable", &self.is_retryable())?;
+        s.end()
+    }
+}
+
+impl From<String> for StudioError {
+    fn from(message: String) -> Self {
+        StudioError::Other(message)
+    }
+}
+
+impl From<&str> for StudioError {
+    fn from(message: &str) -> Self {
+        StudioError::Other(message.to_string())
+    }
+}
+
+/// A blocking task that panicked or was cancelled.
+impl From<tauri::Error> for StudioError {
+    fn from(e: tauri::Error) -> Self {
+        StudioError::Other(e.to_string())
+    }
+}
+
+/// For helpers that still report errors as strings.
+impl From<StudioError> for String {
+    fn from(e: StudioError) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,349 @@
+//! Experiments: trying a risky change on a branch of its own.
+//!
+//! Asking the agent for a big restructuring used to mean working straight on
+//! the workspace's history, with only a restore to undo it. `start_experiment`
+//! commits pending changes and switches the workspace to a new branch,
+//! `experiment/<name>`; auto-saves commit to whatever is checked out, so
+//! everything done from then on lands there. `merge_experiment` merges the
+//! branch back into the one it started from, and `discard_experiment` goes
+//! back to that branch and deletes the experiment, snapshotting its last
+//! state first (see `snapshots`) so a discard can still be undone.
+//!
+//! The branch an experiment started from is kept in the repository's config
+//! (`branch.experiment/<name>.studioBase`), which goes away with the branch.
+//! Only one experiment runs at a time. Every step is announced with an
+//! `experiment-status` event, `{ status, experiment, files }`, where status
+//! is "started", "merged", "conflict" or "discarded"; for a conflict `files`
+//! lists the conflicting paths and the workspace stays on the experiment.
+
+use crate::error::StudioError;
+use crate::supervisor::{self, Service};
+use crate::{backup, frontend, git, resolve_workspace_dir, snapshots};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::AppHandle;
+
+const BRANCH_PREFIX: &str = "experiment/";
+const MAX_NAME_LEN: usize = 100;
+
+/// A merge or discard is switching branches.
+static SWITCHING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Experiment {
+    /// The name as the user gave it.
+    pub name: String,
+    /// e.g. "experiment/new-intro-layout".
+    pub branch: String,
+    /// The branch it started from and merges back into.
+    pub base: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+    pub experiment: Experiment,
+    /// The merge commit on the base branch.
+    pub commit: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExperimentStatus<'a> {
+    status: &'a str,
+    experiment: &'a Experiment,
+    files: &'a [String],
+}
+
+fn emit_status(app: &AppHandle, status: &str, experiment: &Experiment, files: &[String]) {
+    frontend::emit(
+        app,
+        "experiment-status",
+        ExperimentStatus {
+            status,
+            experiment,
+            files,
+        },
+    );
+}
+
+/// Whether a merge or discard is changing the workspace's files right now.
+pub fn switching() -> bool {
+    SWITCHING.load(Ordering::SeqCst)
+}
+
+/// Clears `SWITCHING` however a merge or discard ends.
+struct SwitchGuard;
+
+impl SwitchGuard {
+    fn acquire() -> Result<SwitchGuard, StudioError> {
+        if SWITCHING.swap(true, Ordering::SeqCst) {
+            return Err(StudioError::InvalidInput(
+                "The workspace is already switching branches".to_string(),
+            ));
+        }
+        Ok(SwitchGuard)
+    }
+}
+
+impl Drop for SwitchGuard {
+    fn drop(&mut self) {
+        SWITCHING.store(false, Ordering::SeqCst);
+    }
+}
+
+fn current_branch(workspace: &Path) -> Option<String> {
+    git::run_git(workspace, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .ok()
+        .map(|branch| branch.trim().to_string())
+        .filter(|branch| !branch.is_empty())
+}
+
+fn config_key(branch: &str, key: &str) -> String {
+    format!("branch.{}.{}", branch, key)
+}
+
+/// The experiment checked out in `workspace`, if any.
+pub(crate) fn current(workspace: &Path) -> Option<Experiment> {
+    if !workspace.join(".git").exists() {
+        return None;
+    }
+    let branch = current_branch(workspace)?;
+    if !branch.starts_with(BRANCH_PREFIX) {
+        return None;
+    }
+    let read = |key: &str| {
+        git::run_git(workspace, &["config", "--get", &config_key(&branch, key)])
+            .ok()
+            .map(|value| value.trim().to_string())
+    };
+    let base = read("studioBase")?;
+    let name = read("studioName").unwrap_or_else(|| branch[BRANCH_PREFIX.len()..].to_string());
+    Some(Experiment { name, branch, base })
+}
+
+fn require_current(workspace: &Path) -> Result<Experiment, StudioError> {
+    current(workspace)
+        .ok_or_else(|| StudioError::InvalidInput("No experiment is in progress".to_string()))
+}
+
+fn start(workspace: &Path, name: &str) -> Result<Experiment, StudioError> {
+    if !git::has_commits(workspace) {
+        return Err(StudioError::InvalidInput(
+            "The workspace has no history yet".to_string(),
+        ));
+    }
+    if let Some(experiment) = current(workspace) {
+        return Err(StudioError::InvalidInput(format!(
+            "The experiment \"{}\" is still in progress; merge or discard it first",
+            experiment.name
+        )));
+    }
+    let base = current_branch(workspace)
+        .ok_or_else(|| StudioError::InvalidInput("The workspace isn't on a branch".to_string()))?;
+    let branch = format!("{}{}", BRANCH_PREFIX, snapshots::slug(name));
+    let exists = git::run_git(
+        workspace,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{}", branch),
+        ],
+    )
+    .is_ok();
+    if exists {
+        return Err(StudioError::InvalidInput(format!(
+            "There's already an experiment branch called {}",
+            branch
+        )));
+    }
+
+    git::commit_all(workspace, &format!("Auto-save before experiment: {}", name))?;
+    git::run_git(workspace, &["checkout", "-q", "-b", &branch])?;
+    git::run_git(
+        workspace,
+        &["config", &config_key(&branch, "studioBase"), &base],
+    )?;
+    git::run_git(
+        workspace,
+        &["config", &config_key(&branch, "studioName"), name],
+    )?;
+    Ok(Experiment {
+        name: name.to_string(),
+        branch,
+        base,
+    })
+}
+
+/// Paths with unresolved conflicts after a failed merge.
+fn conflicted_files(workspace: &Path) -> Vec<String> {
+    git::run_git(workspace, &["diff", "--name-only", "--diff-filter=U"])
+        .map(|output| output.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Merge `experiment` into its base and delete it. On a conflict the merge
+/// is abandoned and the experiment checked out again.
+fn merge(workspace: &Path, experiment: &Experiment) -> Result<String, StudioError> {
+    git::run_git(workspace, &["checkout", "-q", &experiment.base])?;
+    let merged = git::run_git_as_studio(
+        workspace,
+        &[
+            "merge",
+            "--no-ff",
+            "-m",
+            &format!("Merge experiment: {}", experiment.name),
+            &experiment.branch,
+        ],
+    );
+    if let Err(e) = merged {
+        let files = conflicted_files(workspace);
+        let _ = git::run_git(workspace, &["merge", "--abort"]);
+        git::run_git(workspace, &["checkout", "-q", &experiment.branch])?;
+        if files.is_empty() {
+            return Err(e.into());
+        }
+        return Err(StudioError::MergeConflict { files });
+    }
+    git::run_git(workspace, &["branch", "-d", &experiment.branch])?;
+    Ok(git::run_git(workspace, &["rev-parse", "HEAD"])?
+        .trim()
+        .to_string())
+}
+
+fn discard(workspace: &Path, experiment: &Experiment) -> Result<(), StudioError> {
+    // Anything not committed yet is in the snapshot taken before this.
+    git::run_git(workspace, &["checkout", "-q", "-f", &experiment.base])?;
+    git::run_git(workspace, &["branch", "-D", &experiment.branch])?;
+    Ok(())
+}
+
+/// Run `switch` with Remotion stopped, so its watcher doesn't rebuild on a
+/// half-switched tree. As with a restore, a switch that went through but
+/// whose server was slow to come back is only logged.
+fn with_remotion_stopped<T>(
+    app: &AppHandle,
+    reason: &str,
+    switch: impl FnOnce() -> Result<T, StudioError>,
+) -> Result<T, StudioError> {
+    let mut result = None;
+    let relaunched = supervisor::relaunch(app, &[Service::Remotion], reason, |_| {
+        result = Some(switch());
+        Ok(())
+    });
+    match (result, relaunched) {
+        (Some(result), Ok(())) => result,
+        (Some(result), Err(e)) => {
+            crate::logging::log_line(
+                "WARN",
+                &format!("[experiments] Remotion did not come back: {}", e),
+            );
+            result
+        }
+        (None, Err(e)) => Err(e.into()),
+        (None, Ok(())) => Err(StudioError::Other(format!("{} did not run", reason))),
+    }
+}
+
+/// Commit pending changes and continue on a new branch for the experiment
+/// `name`.
+#[tauri::command]
+pub async fn start_experiment(app: AppHandle, name: String) -> Result<Experiment, StudioError> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.contains('\n') || name.chars().count() > MAX_NAME_LEN {
+        return Err(StudioError::InvalidInput(format!(
+            "An experiment needs a one-line name of up to {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let experiment = start(&resolve_workspace_dir(), &name)?;
+        crate::logging::log_line(
+            "INFO",
+            &format!(
+                "[experiments] Started {} from {}",
+                experiment.branch, experiment.base
+            ),
+        );
+        emit_status(&app, "started", &experiment, &[]);
+        Ok(experiment)
+    })
+    .await?
+}
+
+/// Merge the experiment in progress back into the branch it started from.
+#[tauri::command]
+pub async fn merge_experiment(app: AppHandle) -> Result<MergeResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let workspace = resolve_workspace_dir();
+        let experiment = require_current(&workspace)?;
+        let _guard = SwitchGuard::acquire()?;
+        git::commit_all(
+            &workspace,
+            &format!("Auto-save before merging experiment: {}", experiment.name),
+        )?;
+        let merged = with_remotion_stopped(
+            &app,
+            &format!("Merging experiment {}", experiment.name),
+            || merge(&workspace, &experiment),
+        );
+        match merged {
+            Ok(commit) => {
+                crate::logging::log_line(
+                    "INFO",
+                    &format!(
+                        "[experiments] Merged {} into {} as {}",
+                        experiment.branch, experiment.base, commit
+                    ),
+                );
+                emit_status(&app, "merged", &experiment, &[]);
+                backup::schedule_push(&app);
+                Ok(MergeResult { experiment, commit })
+            }
+            Err(StudioError::MergeConflict { files }) => {
+                crate::logging::log_line(
+                    "WARN",
+                    &format!(
+                        "[experiments] Merging {} conflicts in {}",
+                        experiment.branch,
+                        files.join(", ")
+                    ),
+                );
+                emit_status(&app, "conflict", &experiment, &files);
+                Err(StudioError::MergeConflict { files })
+            }
+            Err(e) => Err(e),
+        }
+    })
+    .await?
+}
+
+/// Drop the experiment in progress and go back to the branch it started
+/// from.
+#[tauri::command]
+pub async fn discard_experiment(app: AppHandle) -> Result<Experiment, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let workspace = resolve_workspace_dir();
+        let experiment = require_current(&workspace)?;
+        let _guard = SwitchGuard::acquire()?;
+        snapshots::take(&workspace, "discard-experiment");
+        with_remotion_stopped(
+            &app,
+            &format!("Discarding experiment {}", experiment.name),
+            || discard(&workspace, &experiment),
+        )?;
+        crate::logging::log_line(
+            "INFO",
+            &format!(
+                "[experiments] Discarded {}, back on {}",
+                experiment.branch, experiment.base
+            ),
+        );
+        emit_status(&app, "discarded", &experiment, &[]);
+        Ok(experiment)
+    })
+    .await?
+}
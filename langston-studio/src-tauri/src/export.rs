@@ -0,0 +1,268 @@
+//! Zip up the open workspace to hand to someone else or attach to a bug
+//! report.
+//!
+//! `export_workspace` leaves out `node_modules` and `.git` unless asked
+//! (the recipient runs `npm install`, and the history is rarely wanted),
+//! writes the archive to Downloads unless given a destination, and sends
+//! `export-progress` events while it works, since a project with renders in
+//! it can take a while. It's listed in `operations` until it's done;
+//! cancelling it there stops after the file being written and removes the
+//! partial archive.
+
+use crate::error::StudioError;
+use crate::operations::Operation;
+use crate::{expand_home, resolve_workspace_dir, workspace_lock};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Minimum time between `export-progress` events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportOptions {
+    /// Folder to write the archive to, or the archive's full path if it
+    /// ends in `.zip`. Defaults to Downloads.
+    pub destination: Option<String>,
+    pub include_node_modules: bool,
+    pub include_git: bool,
+    /// More top-level folders to leave out, e.g. `["out"]` for renders.
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportResult {
+    pub path: String,
+    pub files: usize,
+    /// Uncompressed size of the exported files.
+    pub bytes: u64,
+    /// Size of the archive.
+    pub archive_bytes: u64,
+}
+
+fn emit_progress(
+    app: &AppHandle,
+    stage: &str,
+    files: usize,
+    total_files: usize,
+    bytes: u64,
+    total_bytes: u64,
+) {
+    let progress = (bytes * 100).checked_div(total_bytes).unwrap_or(100);
+    let _ = app.emit(
+        "export-progress",
+        serde_json::json!({
+            "stage": stage,
+            "files": files,
+            "totalFiles": total_files,
+            "bytes": bytes,
+            "totalBytes": total_bytes,
+            "progress": progress,
+        }),
+    );
+}
+
+/// Every file under `dir` with its size, skipping `skip` at the top level.
+/// Symlinks are left out rather than followed out of the workspace.
+fn collect_files(dir: &Path, skip: &[String], top: bool, out: &mut Vec<(PathBuf, u64)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if top && skip.iter().any(|name| entry.file_name() == name.as_str()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            collect_files(&entry.path(), skip, false, out);
+        } else if file_type.is_file() {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            out.push((entry.path(), size));
+        }
+    }
+}
+
+fn archive_path(workspace: &Path, destination: Option<&str>) -> Result<PathBuf, String> {
+    let name = format!(
+        "{}_{}.zip",
+        workspace
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "workspace".to_string()),
+        Local::now().format("%Y-%m-%d_%H-%M-%S")
+    );
+    let path = match destination.map(str::trim).filter(|d| !d.is_empty()) {
+        Some(d) if d.ends_with(".zip") => expand_home(d),
+        Some(d) => expand_home(d).join(name),
+        None => dirs::download_dir()
+            .or_else(dirs::home_dir)
+            .ok_or("No Downloads folder to export to")?
+            .join(name),
+    };
+    if !path.is_absolute() {
+        return Err(format!(
+            "Destination must be an absolute path: {}",
+            path.display()
+        ));
+    }
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+    Ok(path)
+}
+
+/// Write `files` to a zip at `path`, returning the bytes written.
+fn write_archive(
+    app: &AppHandle,
+    operation: &Operation,
+    workspace: &Path,
+    path: &Path,
+    files: &[(PathBuf, u64)],
+    total_bytes: u64,
+) -> Result<u64, String> {
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut bytes = 0;
+    let mut last_progress = Instant::now();
+    for (done, (file, size)) in files.iter().enumerate() {
+        if operation.is_cancelled() {
+            return Err("Export cancelled".to_string());
+        }
+        let Ok(relative) = file.strip_prefix(workspace) else {
+            continue;
+        };
+        // Zip entries always use forward slashes.
+        let name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        // A file can disappear while the project is being worked on; skip it.
+        let Ok(mut source) = File::open(file) else {
+            continue;
+        };
+        zip.start_file(name.as_str(), options.large_file(*size >= u32::MAX as u64))
+            .map_err(|e| format!("Failed to add {}: {}", name, e))?;
+        std::io::copy(&mut source, &mut zip)
+            .map_err(|e| format!("Failed to add {}: {}", name, e))?;
+
+        bytes += size;
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            emit_progress(app, "writing", done + 1, files.len(), bytes, total_bytes);
+            operation.progress(
+                (total_bytes > 0).then(|| bytes as f64 / total_bytes as f64),
+                Some("writing"),
+            );
+        }
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(bytes)
+}
+
+fn export_blocking(
+    app: &AppHandle,
+    operation: &Operation,
+    options: &ExportOptions,
+) -> Result<ExportResult, String> {
+    let workspace = resolve_workspace_dir();
+    if !workspace.join("package.json").exists() {
+        return Err(format!("No project in {}", workspace.display()));
+    }
+    let path = archive_path(&workspace, options.destination.as_deref())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let mut skip = options.exclude.clone();
+    if !options.include_node_modules {
+        skip.push("node_modules".to_string());
+    }
+    if !options.include_git {
+        skip.push(".git".to_string());
+    }
+    skip.push(workspace_lock::LOCK_FILE.to_string());
+
+    emit_progress(app, "scanning", 0, 0, 0, 0);
+    let mut files = Vec::new();
+    collect_files(&workspace, &skip, true, &mut files);
+    // An archive written into the workspace mustn't include itself.
+    files.retain(|(file, _)| *file != path);
+    let total_files = files.len();
+    let total_bytes: u64 = files.iter().map(|(_, size)| size).sum();
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Exporting {} files ({} bytes) to {}",
+            total_files,
+            total_bytes,
+            path.display()
+        ),
+    );
+
+    let written = write_archive(app, operation, &workspace, &path, &files, total_bytes);
+    let bytes = match written {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+    };
+    emit_progress(app, "done", total_files, total_files, bytes, total_bytes);
+
+    let archive_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Exported workspace to {} ({} bytes)",
+            path.display(),
+            archive_bytes
+        ),
+    );
+    Ok(ExportResult {
+        path: path.to_string_lossy().to_string(),
+        files: total_files,
+        bytes,
+        archive_bytes,
+    })
+}
+
+/// Zip the open workspace and return where the archive was written. Sends
+/// `export-progress` events along the way.
+#[tauri::command]
+pub async fn export_workspace(
+    app: AppHandle,
+    options: Option<ExportOptions>,
+) -> Result<ExportResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let operation = Operation::start(&app, "export", "Exporting workspace");
+        let result = export_blocking(&app, &operation, &options.unwrap_or_default());
+        if let Err(e) = &result {
+            crate::logging::log_line("ERROR", &format!("Export failed: {}", e));
+            operation.fail(e);
+            let _ = app.emit(
+                "export-progress",
+                serde_json::json!({ "stage": "failed", "message": e, "progress": 100 }),
+            );
+        }
+        if result.is_err() && operation.is_cancelled() {
+            return Err(StudioError::Cancelled("Export".to_string()));
+        }
+        result.map_err(StudioError::from)
+    })
+    .await?
+}
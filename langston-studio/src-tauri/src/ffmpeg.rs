@@ -0,0 +1,663 @@
+//! ffmpeg for finishing renders before they're delivered.
+//!
+//! A render often needs one more pass: a ProRes master turned into an H.264
+//! file small enough to send, a few seconds trimmed off, the audio brought
+//! to a standard loudness, or the soundtrack on its own. `transcode` and
+//! `extract_audio` do that for any file, and `start_render` can queue the
+//! same as post-steps that run once the render is written (see `render`).
+//!
+//! ffmpeg is found in this order: `ffmpegPath` in config.json, the copy
+//! Remotion installs with its compositor package in the workspace, and the
+//! user's PATH. Progress comes from the `Duration:` and `time=` fields ffmpeg
+//! prints to stderr, and is sent as `ffmpeg-progress` events, `{ id, kind,
+//! input, output, progress }`. Each run is listed in `operations` and can be
+//! cancelled from there.
+//!
+//! Presets:
+//!
+//! - `h264`: H.264/AAC MP4 at high quality, for delivery
+//! - `h264-small`: H.264/AAC MP4 at most 1280 wide, for sharing and review
+//! - `normalize-audio`: the video as it is, with the audio normalized to
+//!   `LOUDNESS_TARGET`
+//!
+//! Each can also be trimmed, with `trimStart`/`trimEnd` in seconds. Outputs
+//! go next to the input, named after it and the preset.
+
+use crate::error::StudioError;
+use crate::operations::Operation;
+use crate::{expand_home, get_path_env, platform, read_config_file, resolve_workspace_dir};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+const PRESETS: &[&str] = &["h264", "h264-small", "normalize-audio"];
+const AUDIO_FORMATS: &[&str] = &["wav", "mp3", "aac"];
+/// EBU R128-style normalization to -16 LUFS, the usual target for online
+/// video.
+const LOUDNESS_TARGET: &str = "loudnorm=I=-16:TP=-1.5:LRA=11";
+/// How often a run checks whether it was cancelled.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Lines of ffmpeg's output kept for error reports.
+const TAIL_LINES: usize = 20;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+/// ffmpeg processes running, so shutdown can stop them.
+static RUNNING: Mutex<Option<HashMap<u64, Child>>> = Mutex::new(None);
+
+/// A post-step queued with a render.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PostStep {
+    #[serde(rename_all = "camelCase")]
+    Transcode {
+        preset: String,
+        #[serde(default)]
+        trim_start: Option<f64>,
+        #[serde(default)]
+        trim_end: Option<f64>,
+    },
+    ExtractAudio {
+        #[serde(default)]
+        format: Option<String>,
+    },
+}
+
+impl PostStep {
+    /// What the render job's stage shows while it runs.
+    pub fn stage(&self) -> &'static str {
+        match self {
+            PostStep::Transcode { .. } => "transcoding",
+            PostStep::ExtractAudio { .. } => "extracting audio",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), StudioError> {
+        match self {
+            PostStep::Transcode {
+                preset,
+                trim_start,
+                trim_end,
+            } => validate_transcode(preset, *trim_start, *trim_end),
+            PostStep::ExtractAudio { format } => validate_audio_format(format.as_deref()),
+        }
+    }
+
+    /// The ffmpeg run for this step on `input`.
+    pub fn plan(&self, input: &Path) -> Result<Plan, StudioError> {
+        match self {
+            PostStep::Transcode {
+                preset,
+                trim_start,
+                trim_end,
+            } => transcode_plan(input, preset, *trim_start, *trim_end),
+            PostStep::ExtractAudio { format } => extract_audio_plan(input, format.as_deref()),
+        }
+    }
+}
+
+/// The ffmpeg binary to run, and where its libraries are when it's
+/// Remotion's.
+pub struct Ffmpeg {
+    path: PathBuf,
+    lib_dir: Option<PathBuf>,
+}
+
+impl Ffmpeg {
+    /// A command running this ffmpeg.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.path);
+        if let Some(dir) = &self.lib_dir {
+            let var = if cfg!(windows) {
+                "PATH"
+            } else if cfg!(target_os = "macos") {
+                "DYLD_LIBRARY_PATH"
+            } else {
+                "LD_LIBRARY_PATH"
+            };
+            let mut paths = vec![dir.clone()];
+            if let Some(existing) = std::env::var_os(var) {
+                paths.extend(std::env::split_paths(&existing));
+            }
+            if let Ok(joined) = std::env::join_paths(paths) {
+                cmd.env(var, joined);
+            }
+        }
+        cmd
+    }
+
+    /// The length of `input` in seconds, from what ffmpeg says about it.
+    pub fn duration(&self, input: &Path) -> Option<f64> {
+        let output = self
+            .command()
+            .args(["-hide_banner", "-nostdin", "-i"])
+            .arg(input)
+            .stdin(Stdio::null())
+            .output()
+            .ok()?;
+        // With no output file ffmpeg describes the input and exits with an
+        // error.
+        String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .find_map(|line| match parse_line(line)? {
+                Output::Duration(duration) => Some(duration),
+                Output::Time(_) => None,
+            })
+    }
+}
+
+/// The ffmpeg in Remotion's compositor package, e.g.
+/// node_modules/@remotion/compositor-darwin-arm64/ffmpeg.
+fn remotion_ffmpeg(workspace: &Path) -> Option<PathBuf> {
+    let binary = if cfg!(windows) {
+        "ffmpeg.exe"
+    } else {
+        "ffmpeg"
+    };
+    std::fs::read_dir(workspace.join("node_modules").join("@remotion"))
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("compositor-")
+        })
+        .map(|entry| entry.path().join(binary))
+        .find(|path| path.is_file())
+}
+
+/// Find ffmpeg; see the module docs for where it's looked for.
+pub fn locate() -> Result<Ffmpeg, StudioError> {
+    let configured = read_config_file()
+        .ffmpeg_path
+        .filter(|path| !path.trim().is_empty());
+    if let Some(path) = configured {
+        let path = expand_home(path.trim());
+        if path.is_file() {
+            return Ok(Ffmpeg {
+                path,
+                lib_dir: None,
+            });
+        }
+        return Err(StudioError::NotFound(format!(
+            "ffmpeg at {} (ffmpegPath)",
+            path.display()
+        )));
+    }
+    if let Some(path) = remotion_ffmpeg(&resolve_workspace_dir()) {
+        let lib_dir = path.parent().map(Path::to_path_buf);
+        return Ok(Ffmpeg { path, lib_dir });
+    }
+    platform::which("ffmpeg", &get_path_env())
+        .map(|path| Ffmpeg {
+            path,
+            lib_dir: None,
+        })
+        .ok_or_else(|| {
+            StudioError::NotFound(
+                "ffmpeg (install the workspace's dependencies, or set ffmpegPath)".to_string(),
+            )
+        })
+}
+
+/// One ffmpeg run: its arguments, where it writes, and which part of the
+/// input it covers, for working out progress.
+pub struct Plan {
+    args: Vec<String>,
+    pub output: PathBuf,
+    start: f64,
+    length: Option<f64>,
+}
+
+fn validate_transcode(
+    preset: &str,
+    trim_start: Option<f64>,
+    trim_end: Option<f64>,
+) -> Result<(), StudioError> {
+    if !PRESETS.contains(&preset) {
+        return Err(StudioError::InvalidInput(format!(
+            "Unknown preset \"{}\"; use one of {}",
+            preset,
+            PRESETS.join(", ")
+        )));
+    }
+    let valid = |t: Option<f64>| t.map_or(true, |t| t.is_finite() && t >= 0.0);
+    if !valid(trim_start) || !valid(trim_end) {
+        return Err(StudioError::InvalidInput(
+            "Trim times must be seconds from the start".to_string(),
+        ));
+    }
+    if let (Some(start), Some(end)) = (trim_start, trim_end) {
+        if end <= start {
+            return Err(StudioError::InvalidInput(
+                "trimEnd must come after trimStart".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_audio_format(format: Option<&str>) -> Result<(), StudioError> {
+    match format {
+        Some(format) if !AUDIO_FORMATS.contains(&format) => {
+            Err(StudioError::InvalidInput(format!(
+                "Unknown audio format \"{}\"; use one of {}",
+                format,
+                AUDIO_FORMATS.join(", ")
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn existing_input(input: &Path) -> Result<(), StudioError> {
+    if input.is_file() {
+        Ok(())
+    } else {
+        Err(StudioError::NotFound(input.display().to_string()))
+    }
+}
+
+/// `<input stem>-<suffix>.<extension>` next to the input, numbered if it's
+/// taken.
+fn output_path(input: &Path, suffix: &str, extension: &str) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    let dir = input.parent().unwrap_or(Path::new("."));
+    let mut path = dir.join(format!("{}-{}.{}", stem, suffix, extension));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}-{}.{}", stem, suffix, n, extension));
+        n += 1;
+    }
+    path
+}
+
+fn to_strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn transcode_plan(
+    input: &Path,
+    preset: &str,
+    trim_start: Option<f64>,
+    trim_end: Option<f64>,
+) -> Result<Plan, StudioError> {
+    validate_transcode(preset, trim_start, trim_end)?;
+    existing_input(input)?;
+    let input_extension = input
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_else(|| "mp4".to_string());
+    let (codec_args, extension) = match preset {
+        "h264" => (
+            to_strings(&[
+                "-c:v",
+                "libx264",
+                "-preset",
+                "medium",
+                "-crf",
+                "18",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "-b:a",
+                "192k",
+                "-movflags",
+                "+faststart",
+            ]),
+            "mp4".to_string(),
+        ),
+        "h264-small" => (
+            to_strings(&[
+                "-vf",
+                "scale='min(1280,iw)':-2",
+                "-c:v",
+                "libx264",
+                "-preset",
+                "slow",
+                "-crf",
+                "26",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "-b:a",
+                "128k",
+                "-movflags",
+                "+faststart",
+            ]),
+            "mp4".to_string(),
+        ),
+        // The container stays, so the audio codec has to suit it.
+        _ => {
+            let audio = if input_extension == "webm" {
+                "libopus"
+            } else {
+                "aac"
+            };
+            (
+                to_strings(&[
+                    "-c:v",
+                    "copy",
+                    "-af",
+                    LOUDNESS_TARGET,
+                    "-c:a",
+                    audio,
+                    "-b:a",
+                    "192k",
+                ]),
+                input_extension,
+            )
+        }
+    };
+
+    let start = trim_start.unwrap_or(0.0);
+    let length = trim_end.map(|end| end - start);
+    let mut args = Vec::new();
+    // Seeking before the input is fast, and exact when re-encoding; a
+    // copied video stream starts at the keyframe before.
+    if start > 0.0 {
+        args.extend(["-ss".to_string(), format!("{:.3}", start)]);
+    }
+    args.extend(["-i".to_string(), input.to_string_lossy().to_string()]);
+    if let Some(length) = length {
+        args.extend(["-t".to_string(), format!("{:.3}", length)]);
+    }
+    args.extend(codec_args);
+    let output = output_path(input, preset, &extension);
+    Ok(Plan {
+        args,
+        output,
+        start,
+        length,
+    })
+}
+
+fn extract_audio_plan(input: &Path, format: Option<&str>) -> Result<Plan, StudioError> {
+    validate_audio_format(format)?;
+    existing_input(input)?;
+    let (codec_args, extension): (&[&str], &str) = match format.unwrap_or("wav") {
+        "mp3" => (&["-c:a", "libmp3lame", "-q:a", "2"], "mp3"),
+        "aac" => (&["-c:a", "aac", "-b:a", "192k"], "m4a"),
+        _ => (&["-c:a", "pcm_s16le"], "wav"),
+    };
+    let mut args = to_strings(&["-i"]);
+    args.push(input.to_string_lossy().to_string());
+    args.push("-vn".to_string());
+    args.extend(to_strings(codec_args));
+    Ok(Plan {
+        args,
+        output: output_path(input, "audio", extension),
+        start: 0.0,
+        length: None,
+    })
+}
+
+/// Seconds in an ffmpeg timestamp, `HH:MM:SS.ss`.
+fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut parts = value.trim().splitn(3, ':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+enum Output {
+    /// The input's length, from the `Duration:` line.
+    Duration(f64),
+    /// How far into the output it is, from a `time=` stats line.
+    Time(f64),
+}
+
+fn parse_line(line: &str) -> Option<Output> {
+    if let Some(rest) = line.trim_start().strip_prefix("Duration:") {
+        return parse_timestamp(rest.split(',').next()?).map(Output::Duration);
+    }
+    let rest = &line[line.find("time=")? + "time=".len()..];
+    parse_timestamp(rest.split_whitespace().next()?).map(Output::Time)
+}
+
+/// Read ffmpeg's stderr, passing on what it says about progress and
+/// keeping the last lines.
+fn read_output(mut pipe: impl Read, events: mpsc::Sender<Output>) -> VecDeque<String> {
+    let mut tail = VecDeque::new();
+    let mut buf = [0u8; 4096];
+    let mut line = Vec::new();
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        for &byte in &buf[..n] {
+            if byte != b'\r' && byte != b'\n' {
+                line.push(byte);
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let text = String::from_utf8_lossy(&line).to_string();
+            line.clear();
+            if let Some(output) = parse_line(&text) {
+                let _ = events.send(output);
+            }
+            tail.push_back(text);
+            if tail.len() > TAIL_LINES {
+                tail.pop_front();
+            }
+        }
+    }
+    tail
+}
+
+fn with_running<T>(f: impl FnOnce(&mut HashMap<u64, Child>) -> T) -> T {
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    f(running.get_or_insert_with(HashMap::new))
+}
+
+/// Run `plan`, calling `on_progress` with 0.0 to 1.0 as it goes. Stops
+/// ffmpeg and fails with `Cancelled` once `cancelled` says so.
+pub fn run(
+    ffmpeg: &Ffmpeg,
+    plan: &Plan,
+    cancelled: impl Fn() -> bool,
+    mut on_progress: impl FnMut(f64),
+) -> Result<(), StudioError> {
+    let mut cmd = ffmpeg.command();
+    cmd.args(["-hide_banner", "-nostdin", "-y"])
+        .args(&plan.args)
+        .arg(&plan.output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| StudioError::io(format!("Failed to start {}", ffmpeg.path.display()), e))?;
+    let (sender, events) = mpsc::channel();
+    let reader = child
+        .stderr
+        .take()
+        .map(|pipe| std::thread::spawn(move || read_output(pipe, sender)));
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    with_running(|running| running.insert(id, child));
+
+    let mut total = plan.length;
+    let mut was_cancelled = false;
+    let status = loop {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(Output::Duration(duration)) => {
+                total = total.or(Some((duration - plan.start).max(0.0)));
+            }
+            Ok(Output::Time(time)) => {
+                if let Some(total) = total.filter(|total| *total > 0.0) {
+                    on_progress((time / total).clamp(0.0, 1.0));
+                }
+            }
+            Err(_) => {}
+        }
+        let cancel_now = !was_cancelled && cancelled();
+        was_cancelled |= cancel_now;
+        let exited = with_running(|running| {
+            // Gone from the list when `kill_all` stopped it.
+            let Some(child) = running.get_mut(&id) else {
+                return Some(None);
+            };
+            if cancel_now {
+                let _ = child.kill();
+            }
+            match child.try_wait() {
+                Ok(Some(status)) => Some(Some(status)),
+                Ok(None) => None,
+                Err(_) => Some(None),
+            }
+        });
+        if let Some(status) = exited {
+            break status;
+        }
+    };
+    with_running(|running| running.remove(&id));
+    let tail = reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+
+    if was_cancelled {
+        let _ = std::fs::remove_file(&plan.output);
+        return Err(StudioError::Cancelled("ffmpeg".to_string()));
+    }
+    match status {
+        Some(status) if status.success() => {
+            on_progress(1.0);
+            Ok(())
+        }
+        Some(status) => Err(StudioError::Other(format!(
+            "ffmpeg exited with code {:?}: {}",
+            status.code(),
+            tail.into_iter().collect::<Vec<_>>().join("\n")
+        ))),
+        None => Err(StudioError::Other("Lost track of ffmpeg".to_string())),
+    }
+}
+
+/// Stop every ffmpeg run, on shutdown.
+pub fn kill_all() {
+    with_running(|running| {
+        for (_, mut child) in running.drain() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FfmpegProgress<'a> {
+    id: u64,
+    kind: &'a str,
+    input: &'a str,
+    output: &'a str,
+    progress: f64,
+}
+
+/// Run `plan` as an operation of its own, reporting progress as it goes,
+/// and return the output's path.
+fn run_standalone(
+    app: &AppHandle,
+    kind: &str,
+    input: &Path,
+    plan: Plan,
+) -> Result<String, StudioError> {
+    let ffmpeg = locate()?;
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    let file_name = input
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let operation = Operation::start(app, "ffmpeg", format!("{} {}", kind, file_name));
+    let (input_str, output) = (
+        input.to_string_lossy().to_string(),
+        plan.output.to_string_lossy().to_string(),
+    );
+    crate::logging::log_line(
+        "INFO",
+        &format!("[ffmpeg] {} {} -> {}", kind, input_str, output),
+    );
+    let mut last_emitted = -1.0;
+    let result = run(
+        &ffmpeg,
+        &plan,
+        || operation.is_cancelled(),
+        |progress| {
+            // Throttle events to whole-percent changes.
+            if (progress - last_emitted).abs() < 0.01 && progress < 1.0 {
+                return;
+            }
+            last_emitted = progress;
+            operation.progress(Some(progress), Some(kind));
+            let _ = app.emit(
+                "ffmpeg-progress",
+                FfmpegProgress {
+                    id,
+                    kind,
+                    input: &input_str,
+                    output: &output,
+                    progress,
+                },
+            );
+        },
+    );
+    match &result {
+        Ok(()) => crate::logging::log_line("INFO", &format!("[ffmpeg] Wrote {}", output)),
+        Err(e) => {
+            crate::logging::log_line(
+                "ERROR",
+                &format!("[ffmpeg] {} {} failed: {}", kind, input_str, e),
+            );
+            operation.fail(e);
+        }
+    }
+    result.map(|()| output)
+}
+
+/// Transcode a video with one of `PRESETS`, optionally trimmed, and return
+/// the new file's path.
+#[tauri::command]
+pub async fn transcode(
+    app: AppHandle,
+    input: String,
+    preset: String,
+    trim_start: Option<f64>,
+    trim_end: Option<f64>,
+) -> Result<String, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let input = expand_home(input.trim());
+        let plan = transcode_plan(&input, &preset, trim_start, trim_end)?;
+        run_standalone(&app, "Transcode", &input, plan)
+    })
+    .await?
+}
+
+/// Write a video's audio to a file of its own, as WAV unless `format` says
+/// "mp3" or "aac", and return its path.
+#[tauri::command]
+pub async fn extract_audio(
+    app: AppHandle,
+    input: String,
+    format: Option<String>,
+) -> Result<String, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let input = expand_home(input.trim());
+        let plan = extract_audio_plan(&input, format.as_deref())?;
+        run_standalone(&app, "Extract audio", &input, plan)
+    })
+    .await?
+}
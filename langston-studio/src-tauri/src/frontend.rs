@@ -0,0 +1,120 @@
+//! Startup handshake with the webview.
+//!
+//! Setup used to start after a fixed 1.5s sleep. On a slow machine the
+//! webview hadn't registered its listeners yet, so the first `setup-status`
+//! events went nowhere; on a fast one the sleep was wasted. The UI now
+//! invokes `frontend_ready` once its listeners are in place, and the setup
+//! thread waits for that in `wait_until_ready`, for at most
+//! `READY_TIMEOUT` in case the page failed to load its script.
+//!
+//! A webview that reloads after that still missed everything sent before.
+//! Events that make up the setup state (`setup-status`, `setup-complete`,
+//! `setup-error` and the like) are therefore sent with `emit`, which also
+//! keeps the last `HISTORY_LEN` of them in `AppState`. The UI replays them
+//! from `get_event_history` when it starts.
+
+use crate::AppState;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How long setup waits for `frontend_ready` before starting anyway.
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+static READY: Mutex<bool> = Mutex::new(false);
+static READY_CHANGED: Condvar = Condvar::new();
+
+/// Events kept for `get_event_history`.
+const HISTORY_LEN: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    /// Increases by one per event, so the UI can tell replayed events apart.
+    pub seq: u64,
+    pub ts: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+/// The most recent events sent with `emit`, oldest first.
+#[derive(Debug, Default)]
+pub struct EventHistory {
+    events: VecDeque<RecordedEvent>,
+    next_seq: u64,
+}
+
+impl EventHistory {
+    fn record(&mut self, event: &str, payload: serde_json::Value) {
+        if self.events.len() == HISTORY_LEN {
+            self.events.pop_front();
+        }
+        self.events.push_back(RecordedEvent {
+            seq: self.next_seq,
+            ts: Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            event: event.to_string(),
+            payload,
+        });
+        self.next_seq += 1;
+    }
+}
+
+/// Send `event` to the UI and keep it for `get_event_history`.
+pub fn emit(app: &AppHandle, event: &str, payload: impl Serialize) {
+    let payload = serde_json::to_value(payload).unwrap_or(serde_json::Value::Null);
+    if let Some(state) = app.try_state::<Mutex<AppState>>() {
+        if let Ok(mut guard) = state.lock() {
+            guard.event_history.record(event, payload.clone());
+        }
+    }
+    let _ = app.emit(event, payload);
+}
+
+/// Whether the UI has called `frontend_ready`.
+pub fn is_ready() -> bool {
+    *READY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Block until the UI has called `frontend_ready`, or `READY_TIMEOUT`
+/// has passed.
+pub fn wait_until_ready() {
+    let started = Instant::now();
+    let ready = READY.lock().unwrap_or_else(|e| e.into_inner());
+    let ready = *READY_CHANGED
+        .wait_timeout_while(ready, READY_TIMEOUT, |ready| !*ready)
+        .unwrap_or_else(|e| e.into_inner())
+        .0;
+    if ready {
+        crate::logging::log_line(
+            "INFO",
+            &format!("Frontend ready after {}ms", started.elapsed().as_millis()),
+        );
+    } else {
+        crate::logging::log_line(
+            "WARN",
+            &format!(
+                "Frontend didn't report ready within {}s, starting setup anyway",
+                READY_TIMEOUT.as_secs()
+            ),
+        );
+    }
+}
+
+/// Called by the UI once its event listeners are registered.
+#[tauri::command]
+pub fn frontend_ready() {
+    *READY.lock().unwrap_or_else(|e| e.into_inner()) = true;
+    READY_CHANGED.notify_all();
+}
+
+/// Setup events sent so far, oldest first, for a UI that just loaded.
+#[tauri::command]
+pub fn get_event_history(state: tauri::State<'_, Mutex<AppState>>) -> Vec<RecordedEvent> {
+    state
+        .lock()
+        .map(|guard| guard.event_history.events.iter().cloned().collect())
+        .unwrap_or_default()
+}
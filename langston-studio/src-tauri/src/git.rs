@@ -0,0 +1,440 @@
+//! Browsing and restoring the workspace's git history.
+//!
+//! The workspace is committed automatically at the start of every session
+//! (see `git_auto_save`). `git_log` lists those commits with the files each
+//! one touched, and `git_diff` returns the full patch for a single commit, so
+//! the frontend can show a timeline of auto-saves.
+//!
+//! `git_restore` rolls the workspace back to one of those commits without
+//! rewriting history: pending changes are committed first, then the old tree
+//! is checked out and committed on top. Restoring is therefore itself undoable
+//! by restoring the commit made just before it, or the snapshot taken first
+//! (see `snapshots`).
+
+use crate::error::StudioError;
+use crate::supervisor::{self, Service};
+use crate::{backup, get_path_env, resolve_workspace_dir, snapshots};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+use tauri::{AppHandle, Emitter};
+
+/// Default and maximum number of commits returned by `git_log`.
+const DEFAULT_LOG_LIMIT: usize = 50;
+const MAX_LOG_LIMIT: usize = 500;
+
+/// Patches larger than this are cut off; the frontend can't usefully show
+/// them anyway.
+const MAX_PATCH_BYTES: usize = 1024 * 1024;
+
+/// Separates commits in `git log` output.
+const RECORD_SEP: char = '\u{1e}';
+/// Separates fields within a commit header.
+const FIELD_SEP: char = '\u{1f}';
+const LOG_FORMAT: &str = "--format=%x1e%H%x1f%h%x1f%aI%x1f%an%x1f%B%x1f";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedFile {
+    pub path: String,
+    /// Set for renames and copies.
+    pub old_path: Option<String>,
+    /// "added", "modified", "deleted", "renamed", "copied" or "typeChanged".
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commit {
+    pub hash: String,
+    pub short_hash: String,
+    /// Author date, ISO 8601.
+    pub timestamp: String,
+    pub author: String,
+    pub message: String,
+    pub files: Vec<ChangedFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitDiff {
+    #[serde(flatten)]
+    pub commit: Commit,
+    pub patch: String,
+    /// Whether `patch` was cut off at `MAX_PATCH_BYTES`.
+    pub truncated: bool,
+}
+
+/// Run git in `workspace` and return its stdout.
+pub(crate) fn run_git(workspace: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["-c", "core.quotePath=false"])
+        .args(args)
+        .current_dir(workspace)
+        .env("PATH", get_path_env())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// `run_git` as Langston Studio, for commands that record an author or
+/// committer (commits, merges, annotated tags); the user may not have set up
+/// a git identity.
+pub(crate) fn run_git_as_studio(workspace: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(workspace)
+        .env("PATH", get_path_env())
+        .env("GIT_AUTHOR_NAME", "Langston Studio")
+        .env("GIT_AUTHOR_EMAIL", "studio@langston.co")
+        .env("GIT_COMMITTER_NAME", "Langston Studio")
+        .env("GIT_COMMITTER_EMAIL", "studio@langston.co")
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Whether the workspace has at least one commit.
+/// Keep `pattern` out of the workspace's history through .git/info/exclude,
+/// for files the app writes into workspaces whose .gitignore predates them.
+pub(crate) fn exclude(workspace: &Path, pattern: &str) {
+    let info = workspace.join(".git").join("info");
+    if !info.parent().is_some_and(Path::is_dir) {
+        return;
+    }
+    let exclude = info.join("exclude");
+    let existing = std::fs::read_to_string(&exclude).unwrap_or_default();
+    if existing.lines().any(|line| line.trim() == pattern) {
+        return;
+    }
+    let mut contents = existing;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(pattern);
+    contents.push('\n');
+    let _ = std::fs::create_dir_all(&info).and_then(|_| std::fs::write(&exclude, contents));
+}
+
+pub(crate) fn has_commits(workspace: &Path) -> bool {
+    workspace.join(".git").exists()
+        && run_git(workspace, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok()
+}
+
+/// Commit hashes end up on a git command line, so only accept hex.
+fn valid_hash(hash: &str) -> bool {
+    (4..=40).contains(&hash.len()) && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn file_status(code: &str) -> &'static str {
+    match code.chars().next() {
+        Some('A') => "added",
+        Some('D') => "deleted",
+        Some('R') => "renamed",
+        Some('C') => "copied",
+        Some('T') => "typeChanged",
+        _ => "modified",
+    }
+}
+
+/// Parse `git log` output produced with `LOG_FORMAT` and `--name-status`.
+fn parse_log(output: &str) -> Vec<Commit> {
+    output
+        .split(RECORD_SEP)
+        .filter_map(|record| {
+            let mut fields = record.splitn(6, FIELD_SEP);
+            let hash = fields.next()?.trim().to_string();
+            if hash.is_empty() {
+                return None;
+            }
+            let short_hash = fields.next()?.to_string();
+            let timestamp = fields.next()?.to_string();
+            let author = fields.next()?.to_string();
+            let message = fields.next()?.trim().to_string();
+            let files = fields
+                .next()
+                .unwrap_or("")
+                .lines()
+                .filter_map(|line| {
+                    let cols: Vec<&str> = line.split('\t').collect();
+                    match cols.as_slice() {
+                        [code, old, new] => Some(ChangedFile {
+                            path: new.to_string(),
+                            old_path: Some(old.to_string()),
+                            status: file_status(code).to_string(),
+                        }),
+                        [code, path] => Some(ChangedFile {
+                            path: path.to_string(),
+                            old_path: None,
+                            status: file_status(code).to_string(),
+                        }),
+                        _ => None,
+                    }
+                })
+                .collect();
+
+            Some(Commit {
+                hash,
+                short_hash,
+                timestamp,
+                author,
+                message,
+                files,
+            })
+        })
+        .collect()
+}
+
+/// List commits in the open workspace, newest first.
+#[tauri::command]
+pub async fn git_log(
+    limit: Option<usize>,
+    skip: Option<usize>,
+) -> Result<Vec<Commit>, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let workspace = resolve_workspace_dir();
+        if !has_commits(&workspace) {
+            return Ok(Vec::new());
+        }
+
+        let limit = limit.unwrap_or(DEFAULT_LOG_LIMIT).clamp(1, MAX_LOG_LIMIT);
+        let max_count = format!("--max-count={}", limit);
+        let skip = format!("--skip={}", skip.unwrap_or(0));
+        let output = run_git(
+            &workspace,
+            &["log", LOG_FORMAT, "--name-status", &max_count, &skip],
+        )?;
+        Ok(parse_log(&output))
+    })
+    .await?
+}
+
+/// Show a single commit with its full patch.
+#[tauri::command]
+pub async fn git_diff(hash: String) -> Result<CommitDiff, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if !valid_hash(&hash) {
+            return Err(StudioError::InvalidInput(format!(
+                "Invalid commit hash: {}",
+                hash
+            )));
+        }
+        let workspace = resolve_workspace_dir();
+        if !has_commits(&workspace) {
+            return Err("Workspace has no history yet".into());
+        }
+
+        let header = run_git(
+            &workspace,
+            &["log", "-1", LOG_FORMAT, "--name-status", &hash],
+        )?;
+        let commit = parse_log(&header)
+            .into_iter()
+            .next()
+            .ok_or_else(|| StudioError::NotFound(format!("Commit {}", hash)))?;
+
+        let mut patch = run_git(
+            &workspace,
+            &["show", "--format=", "--patch", "--no-color", &hash],
+        )?;
+        let truncated = patch.len() > MAX_PATCH_BYTES;
+        if truncated {
+            let mut end = MAX_PATCH_BYTES;
+            while !patch.is_char_boundary(end) {
+                end -= 1;
+            }
+            patch.truncate(end);
+        }
+
+        Ok(CommitDiff {
+            commit,
+            patch,
+            truncated,
+        })
+    })
+    .await?
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreResult {
+    /// The commit that was restored.
+    pub restored: String,
+    /// Auto-save of the changes that were pending before the restore, if any.
+    pub backup: Option<String>,
+    /// The commit recording the restore, or `None` if the workspace already
+    /// matched the restored commit.
+    pub commit: Option<String>,
+}
+
+fn emit_restore_progress(app: &AppHandle, stage: &str, message: &str, progress: u8) {
+    crate::logging::log_line("INFO", &format!("Restore: {}", message));
+    let _ = app.emit(
+        "restore-progress",
+        serde_json::json!({
+            "stage": stage,
+            "message": message,
+            "progress": progress,
+        }),
+    );
+}
+
+/// Stage everything and commit it as Langston Studio. Returns the new commit's
+/// hash, or `None` if there was nothing to commit.
+pub(crate) fn commit_all(workspace: &Path, message: &str) -> Result<Option<String>, String> {
+    if run_git(workspace, &["status", "--porcelain"])?
+        .trim()
+        .is_empty()
+    {
+        return Ok(None);
+    }
+    run_git(workspace, &["add", "-A"])?;
+    run_git_as_studio(workspace, &["commit", "-q", "-m", message])?;
+    run_git(workspace, &["rev-parse", "HEAD"]).map(|h| Some(h.trim().to_string()))
+}
+
+/// Check out the tree of `hash` over the working copy and commit the result.
+/// Files added since `hash` are removed; ignored files (node_modules, out/)
+/// are left alone.
+fn restore_tree(
+    app: &AppHandle,
+    workspace: &Path,
+    target: &Commit,
+) -> Result<RestoreResult, String> {
+    emit_restore_progress(app, "saving", "Saving current changes...", 30);
+    let backup = commit_all(
+        workspace,
+        &format!("Auto-save before restoring {}", target.short_hash),
+    )?;
+
+    emit_restore_progress(
+        app,
+        "restoring",
+        &format!("Restoring {}...", target.short_hash),
+        50,
+    );
+    run_git(
+        workspace,
+        &[
+            "restore",
+            "--source",
+            &target.hash,
+            "--staged",
+            "--worktree",
+            "--",
+            ":/",
+        ],
+    )?;
+
+    let subject = target.message.lines().next().unwrap_or("");
+    let commit = commit_all(
+        workspace,
+        &format!("Restore {} ({})", target.short_hash, subject),
+    )?;
+
+    Ok(RestoreResult {
+        restored: target.hash.clone(),
+        backup,
+        commit,
+    })
+}
+
+/// Roll the workspace back to an earlier commit and restart Remotion so the
+/// preview picks up the restored files. Progress is reported through
+/// `restore-progress` events.
+#[tauri::command]
+pub async fn git_restore(app: AppHandle, hash: String) -> Result<RestoreResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || restore(&app, &hash))
+        .await?
+        .map_err(Into::into)
+}
+
+/// `git_restore`, also used by `snapshots::restore_snapshot`.
+pub(crate) fn restore(app: &AppHandle, hash: &str) -> Result<RestoreResult, String> {
+    let result = git_restore_blocking(app, hash);
+    if let Err(e) = &result {
+        crate::logging::log_line("ERROR", &format!("Restore failed: {}", e));
+        let _ = app.emit(
+            "restore-progress",
+            serde_json::json!({
+                "stage": "failed",
+                "message": e,
+                "progress": 100,
+            }),
+        );
+    }
+    result
+}
+
+fn git_restore_blocking(app: &AppHandle, hash: &str) -> Result<RestoreResult, String> {
+    if !valid_hash(hash) {
+        return Err(format!("Invalid commit hash: {}", hash));
+    }
+    let workspace = resolve_workspace_dir();
+    if !has_commits(&workspace) {
+        return Err("Workspace has no history yet".to_string());
+    }
+    let target = parse_log(&run_git(
+        &workspace,
+        &["log", "-1", LOG_FORMAT, "--no-walk", hash],
+    )?)
+    .into_iter()
+    .next()
+    .ok_or_else(|| format!("Commit not found: {}", hash))?;
+
+    snapshots::take(&workspace, "restore");
+    emit_restore_progress(app, "stopping", "Stopping Remotion...", 10);
+
+    // Remotion is stopped while files change underneath it, so its watcher
+    // doesn't rebuild on a half-restored tree.
+    let mut restored = None;
+    let relaunched = supervisor::relaunch(
+        app,
+        &[Service::Remotion],
+        &format!("Restoring workspace to {}", target.short_hash),
+        |app| {
+            restored = Some(restore_tree(app, &workspace, &target)?);
+            emit_restore_progress(app, "restarting", "Restarting Remotion...", 80);
+            Ok(())
+        },
+    );
+
+    // A restore that went through but whose server was slow to come back is
+    // still a restore; only report the server problem if nothing happened.
+    let restored = match (restored, relaunched) {
+        (Some(restored), Ok(())) => restored,
+        (Some(restored), Err(e)) => {
+            crate::logging::log_line(
+                "WARN",
+                &format!("Restored, but Remotion did not come back: {}", e),
+            );
+            restored
+        }
+        (None, Err(e)) => return Err(e),
+        (None, Ok(())) => return Err("Restore did not run".to_string()),
+    };
+
+    if restored.commit.is_some() || restored.backup.is_some() {
+        backup::schedule_push(app);
+    }
+
+    emit_restore_progress(app, "done", &format!("Restored {}", target.short_hash), 100);
+    Ok(restored)
+}
@@ -0,0 +1,151 @@
+//! Stopping the Remotion dev server while the app sits unused.
+//!
+//! Left minimized all day, the app kept the Remotion dev server running,
+//! rebuilding on every file change and holding its memory. Once the window
+//! has been hidden, minimized or in the background for `idleSuspendMins`
+//! (default `DEFAULT_IDLE_MINS`; 0 turns this off), the dev server is now
+//! stopped. OpenCode is left running so an agent session isn't cut off.
+//! When the window is focused again the server is started back up (see
+//! `supervisor::relaunch`) and the preview reloads.
+//!
+//! Each change is sent as an `idle-state` event, `{ state }`, with state
+//! "suspended", "resuming" or "active". Nothing is stopped outside
+//! `Phase::Ready`.
+
+use crate::phase::{self, Phase};
+use crate::supervisor::{self, Service};
+use crate::{frontend, read_config_file, AppConfig, AppState};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+pub const DEFAULT_IDLE_MINS: u64 = 15;
+/// How often the window's idle time is checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When the window last lost focus, while it hasn't got it back.
+static INACTIVE_SINCE: Mutex<Option<Instant>> = Mutex::new(None);
+/// Whether Remotion was stopped for being idle. Only changed with
+/// `TRANSITION` held, so a suspend and a resume never overlap.
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+static TRANSITION: Mutex<()> = Mutex::new(());
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum IdleState {
+    Suspended,
+    Resuming,
+    Active,
+}
+
+fn emit(app: &AppHandle, state: IdleState) {
+    frontend::emit(app, "idle-state", serde_json::json!({ "state": state }));
+}
+
+/// How long the window may be inactive before Remotion is stopped, or
+/// `None` when it never is.
+pub fn timeout(config: &AppConfig) -> Option<Duration> {
+    match config.idle_suspend_mins.unwrap_or(DEFAULT_IDLE_MINS) {
+        0 => None,
+        mins => Some(Duration::from_secs(mins * 60)),
+    }
+}
+
+/// Called from the window's focus events.
+pub fn on_focus_changed(app: &AppHandle, focused: bool) {
+    let Ok(mut since) = INACTIVE_SINCE.lock() else {
+        return;
+    };
+    if !focused {
+        since.get_or_insert_with(Instant::now);
+        return;
+    }
+    *since = None;
+    drop(since);
+    if SUSPENDED.load(Ordering::SeqCst) {
+        let app = app.clone();
+        std::thread::spawn(move || resume(&app));
+    }
+}
+
+/// Start watching for idleness, once.
+pub fn start(app: &AppHandle) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let shutting_down = app
+            .try_state::<Mutex<AppState>>()
+            .and_then(|state| state.lock().ok().map(|g| g.shutting_down))
+            .unwrap_or(true);
+        if shutting_down {
+            return;
+        }
+        let Some(timeout) = timeout(&read_config_file()) else {
+            continue;
+        };
+        let idle_for = INACTIVE_SINCE
+            .lock()
+            .ok()
+            .and_then(|since| since.map(|s| s.elapsed()));
+        if idle_for.is_some_and(|idle| idle >= timeout)
+            && !SUSPENDED.load(Ordering::SeqCst)
+            && phase::view(&app).phase == Phase::Ready
+        {
+            suspend(&app, timeout);
+        }
+    });
+}
+
+fn suspend(app: &AppHandle, timeout: Duration) {
+    let Ok(_transition) = TRANSITION.lock() else {
+        return;
+    };
+    if SUSPENDED.load(Ordering::SeqCst) {
+        return;
+    }
+    let reason = format!(
+        "[idle] Window inactive for {} min, stopping Remotion",
+        timeout.as_secs() / 60
+    );
+    match supervisor::stop(app, Service::Remotion, &reason) {
+        Ok(()) => {
+            SUSPENDED.store(true, Ordering::SeqCst);
+            emit(app, IdleState::Suspended);
+        }
+        Err(e) => {
+            crate::logging::log_line("WARN", &format!("[idle] Couldn't stop Remotion: {}", e))
+        }
+    }
+}
+
+fn resume(app: &AppHandle) {
+    let Ok(_transition) = TRANSITION.lock() else {
+        return;
+    };
+    if !SUSPENDED.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    // A manual restart may have brought it back already.
+    let running = app
+        .try_state::<Mutex<AppState>>()
+        .and_then(|state| state.lock().ok().map(|g| g.remotion.is_some()))
+        .unwrap_or(false);
+    if !running {
+        emit(app, IdleState::Resuming);
+        if let Err(e) = supervisor::relaunch(
+            app,
+            &[Service::Remotion],
+            "[idle] Window active again, starting Remotion",
+            |_| Ok(()),
+        ) {
+            crate::logging::log_line("ERROR", &format!("[idle] Couldn't restart Remotion: {}", e));
+        }
+    }
+    emit(app, IdleState::Active);
+}
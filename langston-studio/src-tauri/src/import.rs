@@ -0,0 +1,242 @@
+//! Bring in a Remotion project from elsewhere, the counterpart of `export`.
+//!
+//! `import_workspace` takes a zip (such as one from `export_workspace`) or a
+//! folder, checks there's a Remotion project in it, copies it to a new
+//! folder next to the default workspace and registers it as a project.
+//! Opening it then goes through `projects::open_project_blocking`, which
+//! runs `npm install` (node_modules is never copied), initializes git if the
+//! project has no history and respawns the servers against it. Progress is
+//! reported through `import-progress` events.
+
+use crate::error::StudioError;
+use crate::{expand_home, projects, resolve_workspace_dir, snapshots, workspace_lock};
+use std::fs::{self, File};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+use zip::ZipArchive;
+
+fn emit_import_progress(app: &AppHandle, stage: &str, message: &str, progress: u32) {
+    let _ = app.emit(
+        "import-progress",
+        serde_json::json!({
+            "stage": stage,
+            "message": message,
+            "progress": progress,
+        }),
+    );
+}
+
+/// The project's package.json, if `dir` holds a Remotion project.
+fn remotion_package(dir: &Path) -> Option<serde_json::Value> {
+    let contents = fs::read_to_string(dir.join("package.json")).ok()?;
+    let package: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    let has_remotion = ["dependencies", "devDependencies"].iter().any(|field| {
+        package[field].as_object().is_some_and(|deps| {
+            deps.keys()
+                .any(|dep| dep == "remotion" || dep.starts_with("@remotion/"))
+        })
+    });
+    has_remotion.then_some(package)
+}
+
+/// The Remotion project in `dir`: `dir` itself, or its only subfolder, as
+/// archives often wrap everything in one.
+fn find_project_root(dir: &Path) -> Option<(PathBuf, serde_json::Value)> {
+    if let Some(package) = remotion_package(dir) {
+        return Some((dir.to_path_buf(), package));
+    }
+    let subdirs: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| entry.file_name() != "__MACOSX")
+        .map(|entry| entry.path())
+        .collect();
+    match subdirs.as_slice() {
+        [only] => remotion_package(only).map(|package| (only.clone(), package)),
+        _ => None,
+    }
+}
+
+/// Copy a project, leaving out node_modules (reinstalled for this machine),
+/// another copy of the app's lock on it and symlinks.
+fn copy_project(src: &Path, dst: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if entry.file_name() == "node_modules" || entry.file_name() == workspace_lock::LOCK_FILE {
+            continue;
+        }
+        let dst_path = dst.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_project(&entry.path(), &dst_path)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &dst_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpack `zip` into `dst`, leaving out symlinks, like `copy_project`: the
+/// copy and `npm install` that follow would go wherever they point. Entries
+/// whose path would land outside `dst` are left out too.
+pub(crate) fn extract_zip<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    dst: &Path,
+) -> Result<(), String> {
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|e| e.to_string())?;
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        if entry.is_symlink() {
+            continue;
+        }
+        let path = dst.join(relative);
+        if entry.is_dir() {
+            fs::create_dir_all(&path)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut file = File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        std::io::copy(&mut entry, &mut file)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o755));
+        }
+    }
+    Ok(())
+}
+
+/// Unpack `archive` into `dst`; see `extract_zip`.
+fn extract(archive: &Path, dst: &Path) -> Result<(), String> {
+    let file =
+        File::open(archive).map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    let mut zip = ZipArchive::new(file)
+        .map_err(|e| format!("{} is not a valid zip: {}", archive.display(), e))?;
+    extract_zip(&mut zip, dst)
+        .map_err(|e| format!("Failed to extract {}: {}", archive.display(), e))
+}
+
+/// Copy the project at `source` to `dest`. Returns its package.json.
+fn copy_in(app: &AppHandle, source: &Path, dest: &Path) -> Result<serde_json::Value, String> {
+    if source.is_dir() {
+        let (root, package) = find_project_root(source).ok_or_else(|| {
+            format!(
+                "No Remotion project in {} (package.json with remotion dependencies)",
+                source.display()
+            )
+        })?;
+        emit_import_progress(app, "copying", "Copying project files...", 20);
+        copy_project(&root, dest).map_err(|e| format!("Failed to copy project: {}", e))?;
+        return Ok(package);
+    }
+
+    // Unpack next to the destination, then move the project root into place
+    // so a wrapping folder in the archive doesn't end up in the project.
+    let staging = dest.with_file_name(format!(
+        ".{}.importing",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let _ = fs::remove_dir_all(&staging);
+    emit_import_progress(app, "extracting", "Extracting archive...", 20);
+    let result = extract(source, &staging).and_then(|_| {
+        let (root, package) = find_project_root(&staging).ok_or_else(|| {
+            format!(
+                "No Remotion project in {} (package.json with remotion dependencies)",
+                source.display()
+            )
+        })?;
+        fs::rename(&root, dest).map_err(|e| format!("Failed to move project: {}", e))?;
+        let _ = fs::remove_dir_all(dest.join("node_modules"));
+        Ok(package)
+    });
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn import_blocking(app: &AppHandle, path: &str, name: Option<String>) -> Result<String, String> {
+    let source = expand_home(path.trim());
+    if !source.is_absolute() {
+        return Err(format!("Import path must be absolute: {}", path));
+    }
+    if !source.exists() {
+        return Err(format!("{} not found", path));
+    }
+    emit_import_progress(app, "validating", "Checking project...", 5);
+
+    let stem = source
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Imported project".to_string());
+    let dest = projects::new_project_dir(name.as_deref().unwrap_or(&stem));
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let package = copy_in(app, &source, &dest).inspect_err(|_| {
+        let _ = fs::remove_dir_all(&dest);
+    })?;
+    let name = name
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .or_else(|| package["name"].as_str().map(str::to_string))
+        .unwrap_or(stem);
+
+    let id = projects::register(&name, &dest).inspect_err(|_| {
+        let _ = fs::remove_dir_all(&dest);
+    })?;
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Imported {} as project {:?} at {}",
+            path,
+            name,
+            dest.display()
+        ),
+    );
+
+    // Opening the import replaces the open project's servers and files
+    // on screen; keep a way back to where it was.
+    snapshots::take(&resolve_workspace_dir(), "import");
+    emit_import_progress(app, "installing", "Installing dependencies...", 40);
+    if let Err(e) = projects::open_project_blocking(app, &id) {
+        // Leave nothing half-imported behind; the previous project is open
+        // again.
+        let _ = projects::unregister(&id);
+        let _ = fs::remove_dir_all(&dest);
+        return Err(e);
+    }
+    emit_import_progress(app, "done", "Project imported", 100);
+    Ok(id)
+}
+
+/// Import a Remotion project from a zip or folder as a new project and open
+/// it. Returns the new project's id.
+#[tauri::command]
+pub async fn import_workspace(
+    app: AppHandle,
+    path: String,
+    name: Option<String>,
+) -> Result<String, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = import_blocking(&app, &path, name);
+        if let Err(e) = &result {
+            crate::logging::log_line("ERROR", &format!("Import failed: {}", e));
+            emit_import_progress(&app, "failed", e, 100);
+        }
+        result
+    })
+    .await?
+    .map_err(Into::into)
+}
@@ -0,0 +1,83 @@
+//! What happens when the app is launched while it's already running.
+//!
+//! A second launch used to start a whole second app: another window, and
+//! setup again, with its own OpenCode and Remotion (see `workspace_lock` for
+//! what that did to the workspace). The single-instance plugin now hands the
+//! second launch's arguments to the running app and exits it. The running
+//! app brings its window to the front, and when the launch was asking for a
+//! project, sends an `open-project-request` event, `{ projectId, path }`,
+//! for the UI to offer to open it:
+//!
+//! - `--project <id>` (or `--project=<id>`) names a registered project;
+//! - a folder is matched against the registered projects by path, and
+//!   passed on as `path` alone when it isn't one, to be imported.
+
+use crate::{deep_link, projects, tray};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenProjectRequest {
+    /// The registered project asked for, if it is one.
+    pub project_id: Option<String>,
+    /// The folder the launch pointed at, if it did.
+    pub path: Option<String>,
+}
+
+/// The project `args` ask for; `cwd` is where the launch was run from.
+fn open_request(args: &[String], cwd: &Path) -> Option<OpenProjectRequest> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(id) = arg.strip_prefix("--project=") {
+            return Some(OpenProjectRequest {
+                project_id: Some(id.to_string()),
+                path: None,
+            });
+        }
+        if arg == "--project" {
+            return args.next().map(|id| OpenProjectRequest {
+                project_id: Some(id.clone()),
+                path: None,
+            });
+        }
+        // Flags from the OS or the shell, such as macOS's -psn_. Links are
+        // passed to `deep_link` by the deep-link plugin.
+        if arg.starts_with('-') || arg.starts_with(&format!("{}:", deep_link::SCHEME)) {
+            continue;
+        }
+        let path = PathBuf::from(arg);
+        let path = if path.is_absolute() {
+            path
+        } else {
+            cwd.join(path)
+        };
+        if !path.is_dir() {
+            continue;
+        }
+        return Some(OpenProjectRequest {
+            project_id: projects::id_for_path(&path),
+            path: Some(path.to_string_lossy().to_string()),
+        });
+    }
+    None
+}
+
+/// Called in the running app with the arguments of a second launch, which
+/// exits once this returns.
+pub fn on_second_launch(app: &AppHandle, argv: Vec<String>, cwd: String) {
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "[instance] Launched again with {:?}, focusing this window",
+            argv
+        ),
+    );
+    tray::show_main_window(app);
+    // The first argument is the executable.
+    let args = argv.get(1..).unwrap_or_default();
+    if let Some(request) = open_request(args, Path::new(&cwd)) {
+        let _ = app.emit("open-project-request", request);
+    }
+}
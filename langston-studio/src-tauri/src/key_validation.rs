@@ -0,0 +1,167 @@
+//! Startup validation of the configured API keys.
+//!
+//! An invalid or expired key otherwise only shows up much later, as an opaque
+//! failure inside OpenCode once the user has already started working. Each
+//! configured key is checked with a cheap authenticated request (listing
+//! models); a 401/403 emits `config-invalid` so the frontend can prompt for a
+//! new key right away. A provider that can't be reached or answers with a
+//! rate limit or server error is tried again (see `retry`), then only
+//! logged, since being offline says nothing about the key.
+
+use crate::{providers, retry, AppConfig};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Timeout for each validation request.
+const VALIDATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of checking a single key.
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyStatus {
+    Valid,
+    /// The provider rejected the key (HTTP status attached).
+    Invalid(u16),
+    /// The provider couldn't be reached, rate limited us or is having an
+    /// outage; worth trying again.
+    Unreachable(String),
+    /// Couldn't tell, and trying again won't change that.
+    Unknown(String),
+}
+
+fn provider_label(provider: &str) -> &str {
+    providers::find(provider).map_or(provider, |p| p.name)
+}
+
+fn provider_request(
+    client: &reqwest::Client,
+    provider: &str,
+    key: &str,
+) -> Option<reqwest::RequestBuilder> {
+    match provider {
+        "anthropic" => Some(
+            client
+                .get("https://api.anthropic.com/v1/models?limit=1")
+                .header("x-api-key", key)
+                .header("anthropic-version", "2023-06-01"),
+        ),
+        "openai" => Some(
+            client
+                .get("https://api.openai.com/v1/models")
+                .bearer_auth(key),
+        ),
+        "google" => Some(
+            client
+                .get("https://generativelanguage.googleapis.com/v1beta/models?pageSize=1")
+                .header("x-goog-api-key", key),
+        ),
+        "groq" => Some(
+            client
+                .get("https://api.groq.com/openai/v1/models")
+                .bearer_auth(key),
+        ),
+        // The models list is public; this one needs a valid key.
+        "openrouter" => Some(
+            client
+                .get("https://openrouter.ai/api/v1/key")
+                .bearer_auth(key),
+        ),
+        _ => None,
+    }
+}
+
+pub async fn check_key(client: &reqwest::Client, provider: &str, key: &str) -> KeyStatus {
+    let Some(request) = provider_request(client, provider, key) else {
+        return KeyStatus::Unknown(format!("no validation endpoint for {}", provider));
+    };
+
+    match request.send().await {
+        Ok(resp) => match resp.status().as_u16() {
+            200..=299 => KeyStatus::Valid,
+            status @ (401 | 403) => KeyStatus::Invalid(status),
+            status @ (429 | 500..=599) => KeyStatus::Unreachable(format!("HTTP {}", status)),
+            status => KeyStatus::Unknown(format!("unexpected HTTP {}", status)),
+        },
+        Err(e) => KeyStatus::Unreachable(e.to_string()),
+    }
+}
+
+/// Check every configured key in the background and report the results.
+pub fn validate_in_background(app: &AppHandle, config: &AppConfig) {
+    // A key for a custom base URL (a gateway or proxy) can't be checked
+    // against the provider's own API.
+    let keys: Vec<(&'static str, String)> = providers::PROVIDERS
+        .iter()
+        .filter_map(|provider| {
+            let entry = config.provider(provider.id)?;
+            if entry.base_url.is_some() {
+                return None;
+            }
+            Some((provider.id, entry.api_key.clone()?))
+        })
+        .collect();
+
+    if keys.is_empty() {
+        return;
+    }
+
+    let policy = retry::Policy::from_config(config);
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let client = match reqwest::Client::builder()
+            .timeout(VALIDATION_TIMEOUT)
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                crate::logging::log_line("WARN", &format!("Key validation client error: {}", e));
+                return;
+            }
+        };
+
+        let client = &client;
+        for (provider, key) in keys {
+            let key = key.as_str();
+            let status = retry::run(
+                &format!("Validating the {} API key", provider),
+                policy,
+                |_: &String| true,
+                || async move {
+                    match check_key(client, provider, key).await {
+                        KeyStatus::Unreachable(reason) => Err(reason),
+                        status => Ok(status),
+                    }
+                },
+            )
+            .await
+            .unwrap_or_else(KeyStatus::Unreachable);
+            match status {
+                KeyStatus::Valid => {
+                    crate::logging::log_line("INFO", &format!("{} API key is valid", provider));
+                }
+                KeyStatus::Invalid(status) => {
+                    crate::logging::log_line(
+                        "ERROR",
+                        &format!("{} API key was rejected (HTTP {})", provider, status),
+                    );
+                    let _ = app.emit(
+                        "config-invalid",
+                        serde_json::json!({
+                            "provider": provider,
+                            "status": status,
+                            "message": format!(
+                                "Your {} API key was rejected. It may be invalid or expired.",
+                                provider_label(provider)
+                            ),
+                        }),
+                    );
+                }
+                KeyStatus::Unreachable(reason) | KeyStatus::Unknown(reason) => {
+                    crate::logging::log_line(
+                        "WARN",
+                        &format!("Could not validate {} API key: {}", provider, reason),
+                    );
+                }
+            }
+        }
+    });
+}
@@ -1,39 +1,352 @@
+mod assets;
+mod autosave;
+mod backup;
+mod capture;
+mod checkpoint;
+mod copy;
+mod crash;
+mod deep_link;
+mod diagnostics;
+mod disk;
+mod doctor;
+mod editor;
+mod error;
+mod experiments;
+mod export;
+mod ffmpeg;
+mod frontend;
+mod git;
+mod idle;
+mod import;
+mod instance;
+mod key_validation;
+mod local_models;
+mod logging;
+mod media;
+mod notifications;
+mod npm;
+mod onboarding;
+mod operations;
+mod phase;
+mod platform;
+mod ports;
+mod prewarm;
+mod projects;
+mod providers;
 mod proxy;
+mod proxy_cache;
+mod proxy_inject;
+mod proxy_limit;
+mod proxy_scripts;
+mod proxy_stats;
+mod readiness;
+mod render;
+mod repair;
+mod resources;
+mod retry;
+mod runtime;
+mod scaffold;
+mod secrets;
+mod sessions;
+mod settings;
+mod setup;
+mod snapshots;
+mod supervisor;
+mod telemetry;
+mod template;
+mod templates;
+mod transcripts;
+mod tray;
+mod updater;
+mod upload;
+mod usage;
+mod watcher;
+mod workspace_lock;
 
 use chrono::Local;
-use sentry::IntoDsn;
+use error::StudioError;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{self, File, OpenOptions};
-use std::io::Write;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::Mutex;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
 
-const SENTRY_DSN: &str = "https://3a30fa628bbd0e5f55d9d25f394076c0@o4506593499873280.ingest.us.sentry.io/4510817219444736";
-
-/// Configuration loaded from ~/Library/Application Support/Langston Studio/config.json
+/// Configuration loaded from config.json in the app's config directory
+/// (~/Library/Application Support/Langston Studio on macOS).
 #[derive(Debug, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
+    /// API keys are filled in from the keychain by `load_config`. Values in
+    /// config.json are legacy and get moved to the keychain on startup.
     #[serde(default)]
     pub anthropic_api_key: Option<String>,
     #[serde(default)]
     pub openai_api_key: Option<String>,
+    /// How long to wait for OpenCode and Remotion to accept connections
+    /// before giving up with a `setup-timeout` event.
+    #[serde(default)]
+    pub startup_timeout_secs: Option<u64>,
+    /// Where the Remotion project lives. Defaults to
+    /// ~/Documents/code/langston-videos when unset.
+    #[serde(default)]
+    pub workspace_dir: Option<String>,
+    /// Starter template the default workspace is created from; see
+    /// `templates`. Defaults to the blank one.
+    #[serde(default)]
+    pub workspace_template: Option<String>,
+    /// Where to fetch the index of more starter templates from.
+    #[serde(default)]
+    pub template_registry_url: Option<String>,
+    /// Where `export_still` writes PNGs. Defaults to `out/stills` inside the
+    /// open workspace when unset.
+    #[serde(default)]
+    pub stills_dir: Option<String>,
+    /// The ffmpeg to transcode with. Found in the workspace's Remotion
+    /// install or on PATH when unset; see `ffmpeg`.
+    #[serde(default)]
+    pub ffmpeg_path: Option<String>,
+    /// Git remote URL auto-save commits are pushed to. No backup when unset.
+    #[serde(default)]
+    pub backup_remote: Option<String>,
+    /// Bucket finished renders are uploaded to for sharing; see `upload`.
+    /// The secret key is in the keychain.
+    #[serde(default)]
+    pub upload: Option<upload::UploadConfig>,
+    /// URLs notified when renders finish or setup fails; see
+    /// `notifications`.
+    #[serde(default)]
+    pub webhooks: Option<Vec<notifications::Webhook>>,
+    /// Minimum level written to the logs: "debug", "info", "warn" or
+    /// "error". Defaults to "info".
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Whether to keep writing the plain-text log next to the JSON one.
+    /// Defaults to true.
+    #[serde(default)]
+    pub human_readable_log: Option<bool>,
+    /// Log files older than this are deleted on startup. Defaults to 14.
+    #[serde(default)]
+    pub log_retention_days: Option<u32>,
+    /// How many sessions' worth of logs to keep. Defaults to 30.
+    #[serde(default)]
+    pub log_keep_sessions: Option<usize>,
+    /// How long the proxy waits to connect to OpenCode or Remotion.
+    /// Defaults to 30.
+    #[serde(default)]
+    pub proxy_connect_timeout_secs: Option<u64>,
+    /// How long the proxy waits for more of a streamed response (messages,
+    /// event streams, pages) before giving up. Defaults to 600; raise it for
+    /// slow local models.
+    #[serde(default)]
+    pub proxy_read_timeout_secs: Option<u64>,
+    /// How long a static asset (JS, CSS, fonts) may take in total.
+    /// Defaults to 30.
+    #[serde(default)]
+    pub proxy_asset_timeout_secs: Option<u64>,
+    /// How long a non-streaming API request may take in total. Defaults to
+    /// 120.
+    #[serde(default)]
+    pub proxy_api_timeout_secs: Option<u64>,
+    /// Idle upstream connections the proxy keeps open. Defaults to 10.
+    #[serde(default)]
+    pub proxy_pool_max_idle: Option<usize>,
+    /// Largest request body the proxy forwards. Defaults to 100 MB.
+    #[serde(default)]
+    pub proxy_max_body_bytes: Option<u64>,
+    /// Whether the proxy rejects requests without its access token.
+    /// Defaults to true.
+    #[serde(default)]
+    pub proxy_require_token: Option<bool>,
+    /// Whether the proxy speaks HTTP/2 to OpenCode and Remotion (prior
+    /// knowledge, no upgrade), so streams and API calls share a connection.
+    /// Defaults to false; the upstream has to accept cleartext HTTP/2.
+    #[serde(default)]
+    pub proxy_http2: Option<bool>,
+    /// Prompts the proxy sends to OpenCode per minute, queueing the rest.
+    /// Unset or 0 for no limit; see `proxy_limit`.
+    #[serde(default)]
+    pub proxy_rate_limit_per_min: Option<u64>,
+    /// Prompts that may go through back to back before the limit applies.
+    /// Defaults to 3.
+    #[serde(default)]
+    pub proxy_rate_limit_burst: Option<u64>,
+    /// Folder to load the proxy's injected scripts from instead of the
+    /// bundled ones, for working on them; see `proxy_scripts`.
+    #[serde(default)]
+    pub inject_scripts_dir: Option<String>,
+    /// Onboarding steps finished so far; see `onboarding`.
+    #[serde(default)]
+    pub onboarding_completed_steps: Option<Vec<String>>,
+    /// Whether errors are reported to Sentry. Defaults to true.
+    #[serde(default)]
+    pub telemetry_enabled: Option<bool>,
+    /// Whether long operations that finish while the window is in the
+    /// background show a system notification. Defaults to true.
+    #[serde(default)]
+    pub desktop_notifications: Option<bool>,
+    /// Minutes the window can be in the background before the Remotion dev
+    /// server is stopped; 0 never stops it. See `idle`.
+    #[serde(default)]
+    pub idle_suspend_mins: Option<u64>,
+    /// CPU use (percent of one core) of a server's process tree that gets a
+    /// `resource-warning` when sustained; see `resources`.
+    #[serde(default)]
+    pub resource_cpu_warn_percent: Option<u64>,
+    /// Same for memory, in MB.
+    #[serde(default)]
+    pub resource_memory_warn_mb: Option<u64>,
+    /// Soft daily and monthly budgets for estimated AI cost; see `usage`.
+    #[serde(default)]
+    pub usage_budget: Option<usage::Budget>,
+    /// The version that last ran; see `updater::after_launch`.
+    #[serde(default)]
+    pub last_run_version: Option<String>,
+    /// Editor for `open_in_editor`: "code" or "cursor". Whichever is
+    /// installed is used when unset.
+    #[serde(default)]
+    pub editor: Option<String>,
+    /// Extra environment variables for OpenCode and Remotion, e.g. HTTP
+    /// proxies or REMOTION_* flags.
+    #[serde(default)]
+    pub env: Option<ServiceEnv>,
+    /// Keys and base URLs for LLM providers, by provider id; see
+    /// `providers`. After `load_config` this also has the keys from the
+    /// keychain, including the Anthropic and OpenAI ones.
+    #[serde(default)]
+    pub providers: Option<BTreeMap<String, providers::ProviderConfig>>,
+    /// Attempts for operations that can fail transiently, like npm
+    /// install; see `retry`. Defaults to 3.
+    #[serde(default)]
+    pub retry_attempts: Option<u32>,
+    /// Wait before the first retry, doubling after each. Defaults to 2000.
+    #[serde(default)]
+    pub retry_delay_ms: Option<u64>,
+    /// Whether setup fills an empty webpack cache with `remotion bundle`
+    /// before starting Remotion; see `prewarm`. Defaults to false.
+    #[serde(default)]
+    pub remotion_prewarm: Option<bool>,
+    /// Minutes between background commits of the workspace; see
+    /// `autosave`. Defaults to 10, and 0 turns it off.
+    #[serde(default)]
+    pub auto_save_interval_mins: Option<u64>,
+}
+
+impl AppConfig {
+    pub fn provider(&self, id: &str) -> Option<&providers::ProviderConfig> {
+        self.providers.as_ref()?.get(id)
+    }
+}
+
+/// `env` in config.json: variables added to each child's environment on
+/// top of what the app sets itself. PATH is managed by the app and can't be
+/// overridden.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServiceEnv {
+    #[serde(default)]
+    pub opencode: BTreeMap<String, String>,
+    #[serde(default)]
+    pub remotion: BTreeMap<String, String>,
+}
+
+/// Shown instead of the value of a secret variable.
+pub const REDACTED_ENV_VALUE: &str = "<redacted>";
+
+impl ServiceEnv {
+    /// A copy with the values of secret variables replaced, for logs and
+    /// the settings screen.
+    pub fn redacted(&self) -> ServiceEnv {
+        let redact = |vars: &BTreeMap<String, String>| {
+            vars.iter()
+                .map(|(name, value)| {
+                    let value = if diagnostics::is_secret_field(name) {
+                        REDACTED_ENV_VALUE.to_string()
+                    } else {
+                        value.clone()
+                    };
+                    (name.clone(), value)
+                })
+                .collect()
+        };
+        ServiceEnv {
+            opencode: redact(&self.opencode),
+            remotion: redact(&self.remotion),
+        }
+    }
+}
+
+/// Add the configured extra variables to a child's environment.
+fn apply_extra_env(cmd: &mut Command, service: &str, vars: &BTreeMap<String, String>) {
+    if vars.is_empty() {
+        return;
+    }
+    let mut shown = Vec::new();
+    for (name, value) in vars {
+        if name.eq_ignore_ascii_case("PATH") {
+            logging::log_line(
+                "WARN",
+                &format!("Ignoring PATH in the {} environment settings", service),
+            );
+            continue;
+        }
+        cmd.env(name, value);
+        shown.push(if diagnostics::is_secret_field(name) {
+            format!("{}={}", name, REDACTED_ENV_VALUE)
+        } else {
+            format!("{}={}", name, value)
+        });
+    }
+    logging::log_line(
+        "INFO",
+        &format!("Extra environment for {}: {}", service, shown.join(", ")),
+    );
 }
 
 fn get_config_dir() -> PathBuf {
-    let home = dirs::home_dir().expect("Could not find home directory");
-    home.join("Library/Application Support/Langston Studio")
+    platform::config_dir()
 }
 
 fn get_config_path() -> PathBuf {
     get_config_dir().join("config.json")
 }
 
+/// Full config, with API keys filled in from the keychain.
 fn load_config() -> AppConfig {
+    let mut config = read_config_file();
+    if config.anthropic_api_key.is_none() {
+        config.anthropic_api_key = secrets::get_api_key("anthropic");
+    }
+    if config.openai_api_key.is_none() {
+        config.openai_api_key = secrets::get_api_key("openai");
+    }
+    for provider in providers::PROVIDERS.iter().filter(|p| p.key_env.is_some()) {
+        let key = match provider.id {
+            "anthropic" => config.anthropic_api_key.clone(),
+            "openai" => config.openai_api_key.clone(),
+            id => config
+                .provider(id)
+                .and_then(|entry| entry.api_key.clone())
+                .or_else(|| secrets::get_api_key(id)),
+        };
+        if key.is_some() {
+            config
+                .providers
+                .get_or_insert_with(BTreeMap::new)
+                .entry(provider.id.to_string())
+                .or_default()
+                .api_key = key;
+        }
+    }
+    config
+}
+
+/// Config as stored in config.json, without touching the keychain. Use this
+/// for settings that don't involve API keys.
+fn read_config_file() -> AppConfig {
     let config_path = get_config_path();
 
     if !config_path.exists() {
@@ -41,10 +354,7 @@ fn load_config() -> AppConfig {
     }
 
     match fs::read_to_string(&config_path) {
-        Ok(contents) => match serde_json::from_str(&contents) {
-            Ok(config) => config,
-            Err(_) => AppConfig::default(),
-        },
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
         Err(_) => AppConfig::default(),
     }
 }
@@ -53,6 +363,27 @@ struct AppState {
     opencode: Option<Child>,
     remotion: Option<Child>,
     log_file_path: PathBuf,
+    /// Set when the window is closing so the supervisor doesn't treat the
+    /// children we kill as crashes.
+    shutting_down: bool,
+    /// Set while `restart_services` is replacing the children.
+    restarting: bool,
+    /// Bumped on every manual restart so the supervisor resets its backoff.
+    services_generation: u64,
+    /// Ports the servers and the proxy are using right now.
+    ports: ports::ServicePorts,
+    /// Servers we started that may outlive their parent process; see `ports`.
+    owned_pids: HashSet<u32>,
+    /// The running reverse proxies.
+    proxies: Vec<proxy::ProxyHandle>,
+    /// Setup events, replayed to a UI that attaches late.
+    event_history: frontend::EventHistory,
+    /// Where the app is in its lifecycle; see `phase`.
+    phase: phase::Phase,
+    /// The most recent setup or supervisor failure.
+    last_error: Option<String>,
+    /// Long-running work that can be cancelled; see `operations`.
+    operations: operations::Registry,
 }
 
 impl Drop for AppState {
@@ -67,8 +398,7 @@ impl Drop for AppState {
 }
 
 fn get_logs_dir() -> PathBuf {
-    let home = dirs::home_dir().expect("Could not find home directory");
-    home.join("Library/Logs/Langston Studio")
+    platform::logs_dir()
 }
 
 fn get_username() -> String {
@@ -79,21 +409,20 @@ fn get_username() -> String {
 
 fn get_path_env() -> String {
     let home = dirs::home_dir().unwrap_or_default();
-    let home_str = home.to_string_lossy();
-
-    let paths = vec![
-        format!("{}/.opencode/bin", home_str),
-        format!("{}/.local/bin", home_str),
-        format!("{}/.bun/bin", home_str),
-        "/opt/homebrew/bin".to_string(),
-        "/usr/local/bin".to_string(),
-        "/usr/bin".to_string(),
-        "/bin".to_string(),
-        "/usr/sbin".to_string(),
-        "/sbin".to_string(),
-    ];
+    let mut paths = platform::search_path(&home);
+    // The managed runtime first, then the newest node a version manager
+    // installed, then the usual locations.
+    if let Some(node_bin) = runtime::discovered_node_bin() {
+        paths.retain(|p| p != &node_bin);
+        paths.insert(0, node_bin);
+    }
+    if let Some(node_bin) = runtime::node_bin_dir() {
+        paths.insert(0, node_bin);
+    }
 
-    paths.join(":")
+    std::env::join_paths(paths)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default()
 }
 
 fn has_nvm() -> bool {
@@ -103,11 +432,7 @@ fn has_nvm() -> bool {
 
 /// nvm is a shell function (not a binary), so we source nvm.sh and run through bash.
 /// `nvm install` reads .nvmrc, installs if missing, and activates the version.
-fn run_nvm_command(
-    cmd: &str,
-    work_dir: &PathBuf,
-    path_env: &str,
-) -> Result<std::process::Output, std::io::Error> {
+fn nvm_command(cmd: &str, work_dir: &Path, path_env: &str) -> Command {
     let home = dirs::home_dir().unwrap_or_default();
     let nvm_sh = home.join(".nvm/nvm.sh");
 
@@ -116,168 +441,135 @@ fn run_nvm_command(
         nvm_sh, cmd
     );
 
-    Command::new("bash")
+    let mut command = Command::new("bash");
+    command
         .args(["-c", &script])
         .current_dir(work_dir)
         .env("PATH", path_env)
-        .env("NVM_DIR", home.join(".nvm"))
-        .output()
+        .env("NVM_DIR", home.join(".nvm"));
+    command
 }
 
 fn find_opencode(path_env: &str) -> Option<PathBuf> {
-    let output = Command::new("bash")
-        .args(["-c", "which opencode"])
-        .env("PATH", path_env)
-        .output()
-        .ok()?;
-
-    if output.status.success() {
-        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if !path.is_empty() {
-            return Some(PathBuf::from(path));
-        }
-    }
-    None
+    platform::which("opencode", path_env)
 }
 
-fn install_opencode(state: &Mutex<AppState>, path_env: &str) -> Result<(), String> {
-    write_log(state, "INFO", "opencode CLI not found, installing...");
+fn install_opencode(path_env: &str) -> Result<(), String> {
+    logging::log_line("INFO", "opencode CLI not found, installing...");
 
-    let output = Command::new("bash")
-        .args(["-c", "curl -fsSL https://opencode.ai/install | bash"])
+    // The install script is POSIX-only; on Windows install the npm package.
+    let mut installer = if cfg!(windows) {
+        platform::login_shell_command("npm install -g opencode-ai")
+    } else {
+        let mut cmd = Command::new("bash");
+        cmd.args(["-c", "curl -fsSL https://opencode.ai/install | bash"]);
+        cmd
+    };
+    let output = installer
         .env("PATH", path_env)
         .output()
         .map_err(|e| format!("Failed to run opencode installer: {}", e))?;
 
     if output.status.success() {
-        write_log(state, "INFO", "opencode CLI installed successfully");
+        logging::log_line("INFO", "opencode CLI installed successfully");
         Ok(())
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
         let err = format!("opencode install failed: {}", stderr);
-        write_log(state, "ERROR", &err);
+        logging::log_line("ERROR", &err);
         Err(err)
     }
 }
 
-fn create_log_file() -> (PathBuf, File) {
-    let logs_dir = get_logs_dir();
-    fs::create_dir_all(&logs_dir).expect("Failed to create logs directory");
-
+fn create_log_files() -> logging::LogFiles {
     let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S");
     let username = get_username();
-    let filename = format!("langston-studio_{}_{}.log", timestamp, username);
-    let log_path = logs_dir.join(&filename);
-
-    let file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .append(true)
-        .open(&log_path)
-        .expect("Failed to create log file");
+    let stem = format!("langston-studio_{}_{}", timestamp, username);
+
+    let config = read_config_file();
+    let level = config
+        .log_level
+        .as_deref()
+        .and_then(logging::Level::parse)
+        .unwrap_or(logging::Level::Info);
+    let files = logging::init(
+        &get_logs_dir(),
+        &stem,
+        level,
+        config.human_readable_log.unwrap_or(true),
+    );
 
-    (log_path, file)
+    // Clear out logs from old sessions without holding up startup.
+    std::thread::spawn(|| {
+        logging::purge_old_logs(None);
+    });
+    files
 }
 
-fn write_log(state: &Mutex<AppState>, level: &str, message: &str) {
-    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-    let line = format!("[{}] [{}] {}\n", timestamp, level, message);
-
-    if let Ok(guard) = state.lock() {
-        if let Ok(mut file) = OpenOptions::new().append(true).open(&guard.log_file_path) {
-            let _ = file.write_all(line.as_bytes());
-        }
-    }
+/// Read config.json as raw JSON, apply `update`, and write it back. Working on
+/// the raw object keeps fields this version of the app doesn't know about.
+fn update_config_file(
+    update: impl FnOnce(&mut serde_json::Map<String, serde_json::Value>),
+) -> Result<(), StudioError> {
+    let config_path = get_config_path();
 
-    match level {
-        "ERROR" => log::error!("{}", message),
-        "WARN" => log::warn!("{}", message),
-        _ => log::info!("{}", message),
-    }
+    let mut root = match fs::read_to_string(&config_path) {
+        Ok(contents) => serde_json::from_str::<serde_json::Value>(&contents)
+            .map_err(|e| StudioError::Config(format!("config.json is not valid JSON: {}", e)))?,
+        Err(_) => serde_json::json!({}),
+    };
+    let obj = root.as_object_mut().ok_or_else(|| {
+        StudioError::Config("config.json must contain a JSON object".to_string())
+    })?;
+    update(obj);
+
+    fs::create_dir_all(get_config_dir())
+        .map_err(|e| StudioError::io("Failed to create config directory", e))?;
+    let contents = serde_json::to_string_pretty(&root).map_err(|e| e.to_string())?;
+
+    // Write to a temp file and rename it over config.json so a crash mid-write
+    // can never leave a truncated config behind.
+    let tmp_path = config_path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents).map_err(|e| StudioError::io("Failed to write config.json", e))?;
+    fs::rename(&tmp_path, &config_path)
+        .map_err(|e| StudioError::io("Failed to write config.json", e))
 }
 
-fn get_workspace_dir() -> PathBuf {
+fn default_workspace_dir() -> PathBuf {
     let home = dirs::home_dir().expect("Could not find home directory");
     home.join("Documents/code/langston-videos")
 }
 
-const OPENCODE_PORT: u16 = 7501;
-/// Port the reverse proxy listens on — the iframe connects here instead of
-/// directly to OpenCode. The proxy forwards to OPENCODE_PORT with long
-/// read timeouts to prevent WKWebView from killing idle streaming connections.
-const OPENCODE_PROXY_PORT: u16 = 7502;
-const REMOTION_PORT: u16 = 7500;
-
-fn check_port_available(port: u16) -> bool {
-    let output = Command::new("lsof")
-        .args(["-i", &format!(":{}", port)])
-        .output();
-
-    match output {
-        Ok(out) => out.stdout.is_empty(),
-        Err(_) => true,
+/// Expand a leading `~` so config.json can use home-relative paths.
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None if path == "~" => dirs::home_dir().unwrap_or_default(),
+        None => PathBuf::from(path),
     }
 }
 
-fn kill_port(port: u16) {
-    let _ = Command::new("sh")
-        .args([
-            "-c",
-            &format!("lsof -ti:{} 2>/dev/null | xargs kill -9 2>/dev/null", port),
-        ])
-        .status();
-}
-
-fn git_auto_save(app: &AppHandle, workspace: &PathBuf, path_env: &str, message: &str) {
-    let status_output = Command::new("git")
-        .args(["status", "--porcelain"])
-        .current_dir(workspace)
-        .env("PATH", path_env)
-        .output();
-
-    let has_changes = match status_output {
-        Ok(output) => !output.stdout.is_empty(),
-        Err(_) => false,
-    };
-
-    if !has_changes {
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(&state, "INFO", "No changes to auto-save");
-        }
-        return;
-    }
-
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        write_log(&state, "INFO", &format!("Auto-saving changes: {}", message));
+/// The workspace directory from config.json, or the default location.
+fn base_workspace_dir() -> PathBuf {
+    match read_config_file().workspace_dir {
+        Some(dir) if !dir.trim().is_empty() => expand_home(dir.trim()),
+        _ => default_workspace_dir(),
     }
+}
 
-    let _ = Command::new("git")
-        .args(["add", "-A"])
-        .current_dir(workspace)
-        .env("PATH", path_env)
-        .status();
-
-    let _ = Command::new("git")
-        .args(["commit", "-m", message])
-        .current_dir(workspace)
-        .env("PATH", path_env)
-        .env("GIT_AUTHOR_NAME", "Langston Studio")
-        .env("GIT_AUTHOR_EMAIL", "studio@langston.co")
-        .env("GIT_COMMITTER_NAME", "Langston Studio")
-        .env("GIT_COMMITTER_EMAIL", "studio@langston.co")
-        .status();
+/// The workspace of the open project, falling back to the base workspace.
+fn resolve_workspace_dir() -> PathBuf {
+    projects::active_project_dir().unwrap_or_else(base_workspace_dir)
 }
 
+/// How long children get to exit after SIGTERM before they're force-killed.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
 fn emit_status(app: &AppHandle, status: &str, progress: u8) {
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        write_log(
-            &state,
-            "INFO",
-            &format!("Status: {} ({}%)", status, progress),
-        );
-    }
+    logging::log_line("INFO", &format!("Status: {} ({}%)", status, progress));
 
-    let _ = app.emit(
+    frontend::emit(
+        app,
         "setup-status",
         serde_json::json!({
             "status": status,
@@ -286,10 +578,10 @@ fn emit_status(app: &AppHandle, status: &str, progress: u8) {
     );
 }
 
-fn log_environment(state: &Mutex<AppState>, path_env: &str) {
+fn log_environment(path_env: &str) {
     let nvm_available = has_nvm();
-    write_log(state, "INFO", &format!("Using PATH: {}", path_env));
-    write_log(state, "INFO", &format!("nvm available: {}", nvm_available));
+    logging::log_line("INFO", &format!("Using PATH: {}", path_env));
+    logging::log_line("INFO", &format!("nvm available: {}", nvm_available));
 
     if nvm_available {
         let home = dirs::home_dir().unwrap_or_default();
@@ -299,478 +591,481 @@ fn log_environment(state: &Mutex<AppState>, path_env: &str) {
                 .flatten()
                 .filter_map(|e| e.file_name().into_string().ok())
                 .collect();
-            write_log(
-                state,
-                "INFO",
-                &format!("nvm installed versions: {:?}", versions),
-            );
+            logging::log_line("INFO", &format!("nvm installed versions: {:?}", versions));
         }
     }
 
-    let node_check = Command::new("bash")
-        .args(["-c", "which node && node --version"])
-        .env("PATH", path_env)
-        .output();
-    match node_check {
-        Ok(out) => {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            if stdout.is_empty() {
-                write_log(
-                    state,
-                    "WARN",
-                    "node not found on system PATH (will use nvm if available)",
+    match platform::which("node", path_env) {
+        Some(node) => match Command::new(&node).arg("--version").output() {
+            Ok(out) => {
+                let version = String::from_utf8_lossy(&out.stdout);
+                logging::log_line(
+                    "INFO",
+                    &format!("System node: {:?} {}", node, version.trim()),
                 );
-            } else {
-                write_log(state, "INFO", &format!("System node: {}", stdout.trim()));
             }
-        }
-        Err(e) => write_log(state, "WARN", &format!("Failed to check node: {}", e)),
+            Err(e) => logging::log_line("WARN", &format!("Failed to check node: {}", e)),
+        },
+        None => logging::log_line(
+            "WARN",
+            "node not found on system PATH (will use nvm if available)",
+        ),
     }
 }
 
-fn setup_workspace(app: &AppHandle) -> Result<(), String> {
-    let workspace = get_workspace_dir();
+/// The workspace template bundled with the app.
+fn template_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .resource_dir()
+        .map(|dir| dir.join("workspace-template"))
+        .map_err(|e| format!("Failed to get resource dir: {}", e))
+}
+
+/// Create the workspace from the template, or bring an existing one up to
+/// date, and install its dependencies. `template_current` skips the
+/// template migration for an existing workspace that's known to be up to
+/// date. An existing workspace is checked for damage first (see `repair`).
+/// Returns whether it was created, including when an earlier, unfinished
+/// creation was resumed from its checkpoint. Old processes, version control
+/// and the file watcher are handled by `setup`.
+fn setup_workspace(app: &AppHandle, template_current: bool) -> Result<bool, StudioError> {
+    let workspace = resolve_workspace_dir();
     let path_env = get_path_env();
 
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        write_log(
-            &state,
-            "INFO",
-            &format!("Checking workspace at {:?}", workspace),
-        );
-        log_environment(&state, &path_env);
-    }
+    logging::log_line("INFO", &format!("Checking workspace at {:?}", workspace));
+    log_environment(&path_env);
 
-    let resource_path = app
-        .path()
-        .resource_dir()
-        .map_err(|e| format!("Failed to get resource dir: {}", e))?
-        .join("workspace-template");
+    let resource_path = template_dir(app)?;
 
-    if workspace.join("package.json").exists() {
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(&state, "INFO", "Workspace already exists");
-        }
-
-        emit_status(app, "Cleaning up old processes...", 20);
-        kill_port(OPENCODE_PORT);
-        kill_port(OPENCODE_PROXY_PORT);
-        kill_port(REMOTION_PORT);
-
-        emit_status(app, "Saving progress...", 40);
-        git_auto_save(app, &workspace, &path_env, "Auto-save on session start");
-
-        emit_status(app, "Updating config...", 60);
-        let config_src = resource_path.join("opencode.jsonc");
-        let config_dst = workspace.join("opencode.jsonc");
-        if config_src.exists() {
-            fs::copy(&config_src, &config_dst)
-                .map_err(|e| format!("Failed to update opencode.jsonc: {}", e))?;
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(&state, "INFO", "Updated opencode.jsonc from template");
-            }
-        }
+    let resumed = checkpoint::Checkpoint::load(&workspace);
+    let repaired = match resumed {
+        Some(_) => repair::RepairOutcome::default(),
+        None => repair::run(app, &workspace, &resource_path)?,
+    };
+    if resumed.is_none() && workspace.join("package.json").exists() {
+        logging::log_line("INFO", "Workspace already exists");
 
-        let remotion_config_src = resource_path.join("remotion.config.ts");
-        let remotion_config_dst = workspace.join("remotion.config.ts");
-        if remotion_config_src.exists() {
-            fs::copy(&remotion_config_src, &remotion_config_dst)
-                .map_err(|e| format!("Failed to update remotion.config.ts: {}", e))?;
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(&state, "INFO", "Updated remotion.config.ts from template");
-            }
+        let outcome = if template_current {
+            template::MigrationOutcome::default()
+        } else {
+            emit_status(app, "Updating workspace template...", 60);
+            template::migrate(app, &resource_path, &workspace)?
+        };
+        if !outcome.applied.is_empty() {
+            logging::log_line(
+                "INFO",
+                &format!("Applied template migrations: {:?}", outcome.applied),
+            );
         }
-
-        // Keep AGENTS.md in sync with the bundled template so the AI
-        // always has correct port numbers and workflow instructions.
-        let agents_src = resource_path.join("AGENTS.md");
-        let agents_dst = workspace.join("AGENTS.md");
-        if agents_src.exists() {
-            fs::copy(&agents_src, &agents_dst)
-                .map_err(|e| format!("Failed to update AGENTS.md: {}", e))?;
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(&state, "INFO", "Updated AGENTS.md from template");
-            }
+        // node_modules is missing after a cancelled or failed first install.
+        if outcome.dependencies_changed
+            || repaired.dependencies_changed
+            || !workspace.join("node_modules").exists()
+        {
+            emit_status(app, "Updating dependencies...", 70);
+            install_dependencies(app, &workspace, &path_env)?;
         }
-
-        git_auto_save(app, &workspace, &path_env, "Update app config");
-
-        emit_status(app, "Workspace ready", 100);
-        return Ok(());
+        return Ok(false);
     }
 
     emit_status(app, "Setting up workspace...", 10);
 
     if !resource_path.exists() {
-        let err = format!("Workspace template not found at {:?}", resource_path);
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(&state, "ERROR", &err);
-        }
+        let err = StudioError::NotFound(format!("Workspace template at {:?}", resource_path));
+        logging::log_line("ERROR", &err.to_string());
         return Err(err);
     }
 
-    emit_status(app, "Creating workspace directory...", 20);
-
-    if let Some(parent) = workspace.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-    }
-
-    emit_status(app, "Copying workspace template...", 30);
-
-    copy_dir_recursive(&resource_path, &workspace)
-        .map_err(|e| format!("Failed to copy workspace: {}", e))?;
-
-    emit_status(
-        app,
-        "Installing dependencies (this may take a minute)...",
-        50,
-    );
-
-    let use_nvm = has_nvm();
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        write_log(
-            &state,
-            "INFO",
-            &format!("Running npm install (nvm: {})...", use_nvm),
-        );
-    }
-
-    let npm_output = if use_nvm {
-        run_nvm_command("npm install --no-progress", &workspace, &path_env)
-            .map_err(|e| format!("Failed to run npm install via nvm: {}", e))?
-    } else {
-        // Use the user's login shell to inherit their full PATH (Homebrew,
-        // fnm, volta, etc.) — prevents ENOENT when npm isn't on system PATH.
-        let user_shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
-        Command::new(&user_shell)
-            .args(["-ilc", "npm install --no-progress"])
-            .current_dir(&workspace)
-            .env("npm_config_progress", "false")
-            .output()
-            .map_err(|e| format!("Failed to run npm install: {}", e))?
-    };
-
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        if !npm_output.stdout.is_empty() {
-            write_log(
-                &state,
+    let mut progress = match resumed {
+        Some(progress) => {
+            logging::log_line(
                 "INFO",
                 &format!(
-                    "npm stdout: {}",
-                    String::from_utf8_lossy(&npm_output.stdout)
+                    "Resuming workspace setup at {:?}, finished: {:?}",
+                    progress.next(),
+                    progress.completed
                 ),
             );
+            progress
         }
-        if !npm_output.stderr.is_empty() {
-            write_log(
-                &state,
-                "WARN",
-                &format!(
-                    "npm stderr: {}",
-                    String::from_utf8_lossy(&npm_output.stderr)
-                ),
-            );
+        None => {
+            emit_status(app, "Creating workspace directory...", 20);
+            fs::create_dir_all(&workspace)
+                .map_err(|e| StudioError::io_at("Failed to create", &workspace, e))?;
+            let progress = checkpoint::Checkpoint::new(template::version(&resource_path));
+            progress.save(&workspace)?;
+            progress
         }
-    }
+    };
 
-    if !npm_output.status.success() {
-        let err = "npm install failed".to_string();
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(&state, "ERROR", &err);
+    if !progress.is_done(checkpoint::Step::Template) {
+        emit_status(app, "Copying workspace template...", 30);
+        copy_template(app, &resource_path, &workspace)?;
+        if let Some(id) = projects::template_for(&workspace) {
+            emit_status(app, "Applying starter template...", 45);
+            templates::apply(app, &id, &workspace)?;
         }
-        return Err(err);
+        progress.template_version = template::version(&resource_path);
+        progress.complete(&workspace, checkpoint::Step::Template)?;
+    } else if progress.template_version != template::version(&resource_path) {
+        // The app was updated since the template was copied.
+        emit_status(app, "Updating workspace template...", 40);
+        template::migrate(app, &resource_path, &workspace)?;
+        progress.template_version = template::version(&resource_path);
+        progress.save(&workspace)?;
     }
 
-    emit_status(app, "Initializing version control...", 90);
-
-    let _ = Command::new("git")
-        .args(["init"])
-        .current_dir(&workspace)
-        .env("PATH", &path_env)
-        .status();
-
-    let _ = Command::new("git")
-        .args(["add", "-A"])
-        .current_dir(&workspace)
-        .env("PATH", &path_env)
-        .status();
-
-    let _ = Command::new("git")
-        .args(["commit", "-m", "Initial workspace setup"])
-        .current_dir(&workspace)
-        .env("PATH", &path_env)
-        .env("GIT_AUTHOR_NAME", "Langston Studio")
-        .env("GIT_AUTHOR_EMAIL", "studio@langston.co")
-        .env("GIT_COMMITTER_NAME", "Langston Studio")
-        .env("GIT_COMMITTER_EMAIL", "studio@langston.co")
-        .status();
+    if !progress.is_done(checkpoint::Step::Dependencies) {
+        emit_status(
+            app,
+            "Installing dependencies (this may take a minute)...",
+            50,
+        );
+        install_dependencies(app, &workspace, &path_env)?;
+        progress.complete(&workspace, checkpoint::Step::Dependencies)?;
+    }
 
-    emit_status(app, "Setup complete!", 100);
+    checkpoint::clear(&workspace)?;
+    Ok(true)
+}
 
+/// Copy the template into a new workspace, reporting progress through
+/// `setup-status` (from 30 to 45) and `copy-progress` events.
+fn copy_template(app: &AppHandle, template: &Path, workspace: &Path) -> Result<(), StudioError> {
+    let mut last_percent = None;
+    let mode = copy::Mode::LinkDependencies;
+    let copied = copy::copy_dir(template, workspace, mode, |progress| {
+        let percent = progress.percent();
+        if last_percent == Some(percent) {
+            return;
+        }
+        last_percent = Some(percent);
+        emit_status(
+            app,
+            &format!(
+                "Copying workspace template ({} of {} files)...",
+                progress.files_copied, progress.files_total
+            ),
+            30 + (percent * 15 / 100) as u8,
+        );
+        let _ = app.emit("copy-progress", progress);
+    })
+    .map_err(|e| StudioError::io("Failed to copy workspace", e))?;
+    logging::log_line(
+        "INFO",
+        &format!(
+            "Copied {} files ({} bytes) from the template",
+            copied.files_copied, copied.bytes_copied
+        ),
+    );
     Ok(())
 }
 
-fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> std::io::Result<()> {
-    fs::create_dir_all(dst)?;
+/// `npm::install`, retried when it fails for a reason that may pass, such
+/// as a dropped connection. Stops once setup is cancelled.
+fn install_dependencies(
+    app: &AppHandle,
+    workspace: &Path,
+    path_env: &str,
+) -> Result<(), StudioError> {
+    retry::blocking(
+        "npm install",
+        retry::Policy::current(),
+        StudioError::is_retryable,
+        || {
+            if setup::cancel_requested() {
+                return Err(StudioError::Cancelled("npm install".to_string()));
+            }
+            npm::install(app, workspace, path_env)
+        },
+    )
+}
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let file_type = entry.file_type()?;
-        let src_path = entry.path();
-        let dst_path = dst.join(entry.file_name());
+fn copy_dir_recursive(src: &Path, dst: &Path) -> std::io::Result<()> {
+    copy::copy_dir(src, dst, copy::Mode::Copy, |_| {}).map(|_| ())
+}
 
-        if file_type.is_dir() {
-            copy_dir_recursive(&src_path, &dst_path)?;
-        } else {
-            fs::copy(&src_path, &dst_path)?;
-        }
+/// Forward a child's stdout/stderr into the app log, line by line, and emit
+/// each line as a `process-log` event for the live log panel. Without this
+/// the piped output is never read and server errors are lost.
+fn pipe_child_output(app: &AppHandle, child: &mut Child, name: &'static str) {
+    crash::clear_output(name);
+    if let Some(stdout) = child.stdout.take() {
+        spawn_log_reader(app.clone(), stdout, name, "stdout", "INFO");
+    }
+    if let Some(stderr) = child.stderr.take() {
+        spawn_log_reader(app.clone(), stderr, name, "stderr", "WARN");
     }
+}
 
-    Ok(())
+fn spawn_log_reader<R: Read + Send + 'static>(
+    app: AppHandle,
+    pipe: R,
+    name: &'static str,
+    stream: &'static str,
+    level: &'static str,
+) {
+    std::thread::spawn(move || {
+        let reader = BufReader::new(pipe);
+        for line in reader.split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            logging::log_line(level, &format!("[{}] {}", name, line));
+            crash::record_output(name, stream, line);
+            if name == "remotion" {
+                prewarm::observe_line(&app, line);
+            }
+            let _ = app.emit(
+                "process-log",
+                serde_json::json!({
+                    "service": name,
+                    "stream": stream,
+                    "line": line,
+                }),
+            );
+        }
+    });
 }
 
 fn spawn_opencode(
     app: &AppHandle,
     workspace: &PathBuf,
     config: &AppConfig,
-) -> Result<Child, String> {
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        write_log(
-            &state,
-            "INFO",
-            &format!(
-                "Starting OpenCode server at {:?} on port {}",
-                workspace, OPENCODE_PORT
-            ),
-        );
-
-        let has_anthropic = config.anthropic_api_key.is_some();
-        let has_openai = config.openai_api_key.is_some();
-        write_log(
-            &state,
-            "INFO",
-            &format!(
-                "API keys configured - Anthropic: {}, OpenAI: {}",
-                has_anthropic, has_openai
-            ),
-        );
-    }
+) -> Result<Child, StudioError> {
+    let port = ports::claim(app, ports::PortKind::OpenCode)?;
+
+    logging::log_line(
+        "INFO",
+        &format!(
+            "Starting OpenCode server at {:?} on port {}",
+            workspace, port
+        ),
+    );
 
-    if !check_port_available(OPENCODE_PORT) {
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(
-                &state,
-                "INFO",
-                &format!("Port {} in use, cleaning up...", OPENCODE_PORT),
-            );
-        }
-        kill_port(OPENCODE_PORT);
-        std::thread::sleep(std::time::Duration::from_millis(500));
-    }
+    let configured: Vec<&str> = providers::PROVIDERS
+        .iter()
+        .filter(|provider| provider.is_configured(config))
+        .map(|provider| provider.name)
+        .collect();
+    logging::log_line(
+        "INFO",
+        &format!("Providers configured: {}", configured.join(", ")),
+    );
 
     let path_env = get_path_env();
 
     if find_opencode(&path_env).is_none() {
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            install_opencode(&state, &path_env)?;
-        }
-        if find_opencode(&path_env).is_none() {
-            return Err("opencode CLI not found after install attempt".to_string());
-        }
+        install_opencode(&path_env).map_err(StudioError::OpencodeMissing)?;
     }
+    let opencode_bin = find_opencode(&path_env).ok_or_else(|| {
+        StudioError::OpencodeMissing("opencode CLI not found after install attempt".to_string())
+    })?;
 
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        if let Some(path) = find_opencode(&path_env) {
-            write_log(&state, "INFO", &format!("opencode binary: {:?}", path));
-        }
-    }
+    logging::log_line("INFO", &format!("opencode binary: {:?}", opencode_bin));
 
-    let mut cmd = Command::new("opencode");
-    cmd.args(["serve", "--port", &OPENCODE_PORT.to_string()])
+    let mut cmd = Command::new(&opencode_bin);
+    cmd.args(["serve", "--port", &port.to_string()])
         .current_dir(workspace)
         .env("PATH", &path_env)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
+    platform::new_process_group(&mut cmd);
 
-    if let Some(ref key) = config.anthropic_api_key {
-        cmd.env("ANTHROPIC_API_KEY", key);
+    for (name, value) in providers::env_vars(config) {
+        cmd.env(name, value);
     }
-    if let Some(ref key) = config.openai_api_key {
-        cmd.env("OPENAI_API_KEY", key);
+    if let Some(server) = local_models::use_local(config) {
+        logging::log_line(
+            "INFO",
+            &format!("No API keys, using {} at {}", server.name, server.base_url),
+        );
+        cmd.env(
+            "OPENCODE_CONFIG_CONTENT",
+            local_models::opencode_config(&server).to_string(),
+        );
     }
+    let env = config.env.clone().unwrap_or_default();
+    apply_extra_env(&mut cmd, "OpenCode", &env.opencode);
 
     match cmd.spawn() {
-        Ok(child) => {
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(
-                    &state,
-                    "INFO",
-                    &format!("OpenCode started with PID: {}", child.id()),
-                );
-            }
+        Ok(mut child) => {
+            logging::log_line(
+                "INFO",
+                &format!("OpenCode started with PID: {}", child.id()),
+            );
+            pipe_child_output(app, &mut child, "opencode");
             Ok(child)
         }
         Err(e) => {
-            let err = format!("Failed to start OpenCode: {}", e);
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(&state, "ERROR", &err);
-            }
+            let err = StudioError::io("Failed to start OpenCode", e);
+            logging::log_line("ERROR", &err.to_string());
             Err(err)
         }
     }
 }
 
-fn spawn_remotion(app: &AppHandle, workspace: &PathBuf) -> Result<Child, String> {
-    if let Some(state) = app.try_state::<Mutex<AppState>>() {
-        write_log(
-            &state,
-            "INFO",
-            &format!(
-                "Starting Remotion dev server at {:?} on port {}",
-                workspace, REMOTION_PORT
-            ),
-        );
-    }
-
-    if !check_port_available(REMOTION_PORT) {
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(
-                &state,
-                "INFO",
-                &format!("Port {} in use, cleaning up...", REMOTION_PORT),
-            );
-        }
-        kill_port(REMOTION_PORT);
-        std::thread::sleep(std::time::Duration::from_millis(500));
-    }
+/// Run an npm/npx `script` through the user's login shell. If nvm is
+/// installed it is sourced first so the project's .nvmrc node version is
+/// active; otherwise whatever npm is on the login shell PATH is used.
+fn node_shell_command(script: &str) -> Command {
+    let script = if let Some(script) = runtime::with_node_path(script) {
+        script
+    } else if has_nvm() {
+        let nvm_sh = dirs::home_dir().unwrap_or_default().join(".nvm/nvm.sh");
+        format!(
+            "source {:?} && nvm use --silent 2>/dev/null; {}",
+            nvm_sh, script
+        )
+    } else {
+        script.to_string()
+    };
+    platform::login_shell_command(&script)
+}
 
-    let use_nvm = has_nvm();
+fn spawn_remotion(app: &AppHandle, workspace: &PathBuf) -> Result<Child, StudioError> {
+    // Compiling next to a pre-warm would only do the same work twice.
+    prewarm::wait();
+    let port = ports::claim(app, ports::PortKind::Remotion)?;
+
+    logging::log_line(
+        "INFO",
+        &format!(
+            "Starting Remotion dev server at {:?} on port {}",
+            workspace, port
+        ),
+    );
 
     // Spawn Remotion through the user's login shell so we inherit their full
     // PATH (nvm, fnm, volta, Homebrew, etc.). This prevents ENOENT errors
     // when npm isn't on the hardcoded system PATH.
     let spawn_result = {
-        let home = dirs::home_dir().unwrap_or_default();
-        let user_shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
-
-        // Build a script that:
-        // 1. Sources nvm if available (activates the project's .nvmrc node version)
-        // 2. Falls back to whatever npm is on the user's login shell PATH
-        let script = if use_nvm {
-            let nvm_sh = home.join(".nvm/nvm.sh");
-            format!(
-                "source {:?} && nvm use --silent 2>/dev/null; BROWSER=none exec npm run dev",
-                nvm_sh
-            )
+        // --port overrides the studio port set in remotion.config.ts.
+        let script = if cfg!(windows) {
+            format!("set BROWSER=none&& npm run dev -- --port={}", port)
         } else {
-            "BROWSER=none exec npm run dev".to_string()
+            format!("BROWSER=none exec npm run dev -- --port={}", port)
         };
 
-        if let Some(state) = app.try_state::<Mutex<AppState>>() {
-            write_log(
-                &state,
-                "INFO",
-                &format!("Spawning Remotion via login shell: {} -ilc '...'", user_shell),
-            );
-        }
+        let mut cmd = node_shell_command(&script);
 
-        Command::new(&user_shell)
-            .args(["-ilc", &script])
-            .current_dir(workspace)
+        logging::log_line(
+            "INFO",
+            &format!(
+                "Spawning Remotion via login shell: {:?} '...'",
+                cmd.get_program()
+            ),
+        );
+
+        platform::new_process_group(&mut cmd);
+        cmd.envs(prewarm::env(workspace));
+        let env = read_config_file().env.unwrap_or_default();
+        apply_extra_env(&mut cmd, "Remotion", &env.remotion);
+        cmd.current_dir(workspace)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
     };
 
     match spawn_result {
-        Ok(child) => {
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(
-                    &state,
-                    "INFO",
-                    &format!("Remotion started with PID: {}", child.id()),
-                );
-            }
+        Ok(mut child) => {
+            logging::log_line(
+                "INFO",
+                &format!("Remotion started with PID: {}", child.id()),
+            );
+            prewarm::compiling(app);
+            pipe_child_output(app, &mut child, "remotion");
             Ok(child)
         }
         Err(e) => {
-            let err = format!("Failed to start Remotion: {}", e);
-            if let Some(state) = app.try_state::<Mutex<AppState>>() {
-                write_log(&state, "ERROR", &err);
-            }
+            let err = StudioError::io("Failed to start Remotion", e);
+            logging::log_line("ERROR", &err.to_string());
             Err(err)
         }
     }
 }
 
-/// Response from the Rust-side HTTP fetch, serialized back to the webview.
-#[derive(Serialize)]
-struct ProxyFetchResponse {
-    status: u16,
-    headers: HashMap<String, String>,
-    body: String,
-}
-
-/// Execute an HTTP request through Rust's reqwest, bypassing WKWebView's
-/// networking stack (and its ~60s idle timeout on POST requests).
-/// Called from the parent webview via postMessage relay from the iframe.
-#[tauri::command]
-async fn proxy_fetch(
-    method: String,
-    url: String,
-    body: Option<String>,
-    headers: HashMap<String, String>,
-) -> Result<ProxyFetchResponse, String> {
-    let client = reqwest::Client::builder()
-        .read_timeout(Duration::from_secs(600))
-        .connect_timeout(Duration::from_secs(30))
-        .pool_idle_timeout(Duration::from_secs(600))
-        .no_proxy()
-        .build()
-        .map_err(|e| format!("proxy_fetch client build error: {}", e))?;
-
-    let rw_method = match method.to_uppercase().as_str() {
-        "POST" => reqwest::Method::POST,
-        "PUT" => reqwest::Method::PUT,
-        "PATCH" => reqwest::Method::PATCH,
-        "DELETE" => reqwest::Method::DELETE,
-        "HEAD" => reqwest::Method::HEAD,
-        "OPTIONS" => reqwest::Method::OPTIONS,
-        _ => reqwest::Method::GET,
+/// Claim a port for the reverse proxy in front of `upstream` and run it on
+/// its own tokio runtime.
+fn start_proxy(app: &AppHandle, upstream: proxy::Upstream) {
+    let (kind, name) = match upstream {
+        proxy::Upstream::OpenCode => (ports::PortKind::Proxy, "OpenCode"),
+        proxy::Upstream::Remotion => (ports::PortKind::RemotionProxy, "Remotion"),
     };
+    let proxy_port = match ports::claim(app, kind) {
+        Ok(port) => port,
+        Err(e) => {
+            logging::log_line(
+                "ERROR",
+                &format!("Reverse proxy for {} not started: {}", name, e),
+            );
+            return;
+        }
+    };
+    let current = ports::current(app);
+    let upstream_port = match upstream {
+        proxy::Upstream::OpenCode => current.opencode,
+        proxy::Upstream::Remotion => current.remotion,
+    };
+    logging::log_line(
+        "INFO",
+        &format!(
+            "Starting reverse proxy on port {} -> {} ({})",
+            proxy_port, upstream_port, name
+        ),
+    );
 
-    let mut req = client.request(rw_method, &url);
-
-    for (k, v) in &headers {
-        req = req.header(k.as_str(), v.as_str());
+    if let Ok(resource_dir) = app.path().resource_dir() {
+        proxy_scripts::init(&resource_dir);
+    }
+    let handle = proxy::spawn(upstream, proxy_port, upstream_port);
+    if let Some(state) = app.try_state::<Mutex<AppState>>() {
+        if let Ok(mut guard) = state.lock() {
+            guard.proxies.push(handle);
+        }
     }
+}
 
-    if let Some(b) = body {
-        req = req.body(b);
+/// Stop both proxies, letting in-flight requests finish first.
+fn stop_proxies(app: &AppHandle) {
+    // Take the handles out first; stopping waits for the proxy threads.
+    let proxies = match app.try_state::<Mutex<AppState>>() {
+        Some(state) => match state.lock() {
+            Ok(mut guard) => std::mem::take(&mut guard.proxies),
+            Err(_) => return,
+        },
+        None => return,
+    };
+    for handle in proxies {
+        logging::log_line(
+            "INFO",
+            &format!(
+                "Stopping reverse proxy on port {} ({})",
+                handle.port,
+                handle.upstream.name()
+            ),
+        );
+        handle.stop();
     }
+}
 
-    let resp = req
-        .send()
-        .await
-        .map_err(|e| format!("proxy_fetch send error: {}", e))?;
+/// Stop both proxies and start them again, e.g. after a port change.
+pub(crate) fn restart_proxies(app: &AppHandle) {
+    stop_proxies(app);
+    start_proxy(app, proxy::Upstream::OpenCode);
+    start_proxy(app, proxy::Upstream::Remotion);
+}
 
-    let status = resp.status().as_u16();
-    let resp_headers: HashMap<String, String> = resp
-        .headers()
-        .iter()
-        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-        .collect();
-    let resp_body = resp
-        .text()
-        .await
-        .map_err(|e| format!("proxy_fetch body error: {}", e))?;
-
-    Ok(ProxyFetchResponse {
-        status,
-        headers: resp_headers,
-        body: resp_body,
-    })
+/// Kill and respawn OpenCode and Remotion without restarting the app.
+/// Resolves once both servers accept connections again.
+#[tauri::command]
+async fn restart_services(app: AppHandle) -> Result<(), StudioError> {
+    tauri::async_runtime::spawn_blocking(move || supervisor::restart_all(&app))
+        .await?
+        .map_err(Into::into)
 }
 
 #[tauri::command]
@@ -779,25 +1074,21 @@ fn get_version(app: AppHandle) -> String {
 }
 
 #[tauri::command]
-fn get_logs(state: tauri::State<'_, Mutex<AppState>>) -> Result<String, String> {
-    let guard = state.lock().map_err(|e| e.to_string())?;
-    fs::read_to_string(&guard.log_file_path).map_err(|e| e.to_string())
+fn get_logs(state: tauri::State<'_, Mutex<AppState>>) -> Result<String, StudioError> {
+    let path = state.lock().map_err(|e| e.to_string())?.log_file_path.clone();
+    logging::read_for_display(&path).map_err(|e| StudioError::io_at("Failed to read", &path, e))
 }
 
 #[tauri::command]
-fn get_log_file_path(state: tauri::State<'_, Mutex<AppState>>) -> Result<String, String> {
+fn get_log_file_path(state: tauri::State<'_, Mutex<AppState>>) -> Result<String, StudioError> {
     let guard = state.lock().map_err(|e| e.to_string())?;
     Ok(guard.log_file_path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-fn open_logs_folder() -> Result<(), String> {
+fn open_logs_folder() -> Result<(), StudioError> {
     let logs_dir = get_logs_dir();
-    Command::new("open")
-        .arg(&logs_dir)
-        .spawn()
-        .map_err(|e| e.to_string())?;
-    Ok(())
+    platform::open_path(&logs_dir).map_err(|e| StudioError::io_at("Failed to open", &logs_dir, e))
 }
 
 #[tauri::command]
@@ -810,51 +1101,243 @@ fn get_config_status() -> serde_json::Value {
         "configExists": config_path.exists(),
         "hasAnthropicKey": config.anthropic_api_key.is_some(),
         "hasOpenaiKey": config.openai_api_key.is_some(),
+        "providers": providers::statuses(&config),
     })
 }
 
+#[tauri::command]
+fn get_workspace_dir() -> serde_json::Value {
+    let workspace = resolve_workspace_dir();
+    serde_json::json!({
+        "path": workspace.to_string_lossy(),
+        "isDefault": workspace == default_workspace_dir(),
+        "exists": workspace.join("package.json").exists(),
+    })
+}
+
+/// Point the app at a different workspace directory. The directory is set up
+/// from the template on the next launch if it doesn't contain a project yet.
+#[tauri::command]
+fn set_workspace_dir(path: Option<String>) -> Result<String, StudioError> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+
+    if let Some(ref p) = path {
+        let dir = expand_home(p);
+        if !dir.is_absolute() {
+            return Err(StudioError::InvalidInput(format!(
+                "Workspace path must be absolute: {}",
+                p
+            )));
+        }
+        if dir.exists() && !dir.is_dir() {
+            return Err(StudioError::InvalidInput(format!(
+                "Workspace path is not a directory: {}",
+                p
+            )));
+        }
+    }
+
+    update_config_file(|obj| match path {
+        Some(ref p) => {
+            obj.insert("workspaceDir".to_string(), serde_json::json!(p));
+        }
+        None => {
+            obj.remove("workspaceDir");
+        }
+    })?;
+
+    let workspace = base_workspace_dir();
+    logging::log_line(
+        "INFO",
+        &format!(
+            "Workspace directory set to {:?} (applies on next launch)",
+            workspace
+        ),
+    );
+    Ok(workspace.to_string_lossy().to_string())
+}
+
+/// Stop the children, renders and proxies and free the ports, before the
+/// app exits. Only the first call does anything.
+fn shutdown(app: &AppHandle, reason: &str) {
+    let Some(state) = app.try_state::<Mutex<AppState>>() else {
+        return;
+    };
+    if let Ok(mut guard) = state.lock() {
+        if guard.shutting_down {
+            return;
+        }
+        guard.shutting_down = true;
+    }
+    logging::log_line("INFO", &format!("{}, cleaning up processes...", reason));
+
+    // Note which servers are ours while they can still be traced back to the
+    // children.
+    ports::remember_listeners(app);
+    let service_ports = ports::current(app);
+    sessions::save_active(app);
+    transcripts::sync_now(app);
+
+    // Take the children out of the state before stopping them.
+    let (opencode, remotion) = match state.lock() {
+        Ok(mut guard) => (guard.opencode.take(), guard.remotion.take()),
+        Err(_) => (None, None),
+    };
+
+    let mut children = Vec::new();
+    for (name, child) in [("OpenCode", opencode), ("Remotion", remotion)] {
+        if let Some(child) = child {
+            logging::log_line("INFO", &format!("Stopping {} (PID: {})", name, child.id()));
+            children.push(child);
+        }
+    }
+
+    // Give OpenCode a chance to flush its session state before anything is
+    // force-killed.
+    let forced = platform::stop_process_trees(&mut children, SHUTDOWN_GRACE);
+    if !forced.is_empty() {
+        logging::log_line(
+            "WARN",
+            &format!("Force-killed processes that ignored SIGTERM: {:?}", forced),
+        );
+    }
+
+    logging::log_line(
+        "INFO",
+        &format!(
+            "Cleaning up ports {}, {}...",
+            service_ports.remotion, service_ports.opencode
+        ),
+    );
+
+    render::kill_running(app);
+    ffmpeg::kill_all();
+    stop_proxies(app);
+
+    // The shell wrappers are gone now, but the servers they started may
+    // still hold the ports.
+    ports::release(app, service_ports.opencode);
+    ports::release(app, service_ports.remotion);
+    workspace_lock::release();
+    logging::flush();
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let version = env!("CARGO_PKG_VERSION");
     let username = get_username();
 
-    let _sentry_guard = sentry::init((
-        SENTRY_DSN.into_dsn().expect("Invalid Sentry DSN"),
-        sentry::ClientOptions {
-            release: Some(format!("langston-studio@{}", version).into()),
-            environment: Some("production".into()),
-            ..Default::default()
-        },
-    ));
-
-    sentry::configure_scope(|scope| {
-        scope.set_user(Some(sentry::User {
-            username: Some(username.clone()),
-            ..Default::default()
-        }));
-        scope.set_tag("platform", "macos");
-    });
+    let _sentry_guard = telemetry::init(version, &username);
 
-    let (log_file_path, mut log_file) = create_log_file();
+    let log_files = create_log_files();
+    let log_file_path = log_files.display_path().to_path_buf();
 
-    let startup_msg = format!(
-        "=== Langston Studio Started ===\nTime: {}\nUser: {}\nVersion: {}\nLog file: {:?}\n",
-        Local::now().format("%Y-%m-%d %H:%M:%S"),
-        username,
-        version,
-        log_file_path
+    logging::log_line(
+        "INFO",
+        &format!(
+            "=== Langston Studio {} started (user: {}, log: {:?}) ===",
+            version, username, log_files.json
+        ),
     );
-    let _ = log_file.write_all(startup_msg.as_bytes());
 
     tauri::Builder::default()
+        // First, so a second launch exits before setting anything up.
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            instance::on_second_launch(app, argv, cwd)
+        }))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(
+            tauri_plugin_updater::Builder::new()
+                .pubkey(updater::PUBKEY.unwrap_or_default())
+                .build(),
+        )
         .invoke_handler(tauri::generate_handler![
-            proxy_fetch,
+            restart_services,
             get_version,
             get_logs,
             get_log_file_path,
             open_logs_folder,
-            get_config_status
+            get_config_status,
+            get_workspace_dir,
+            set_workspace_dir,
+            projects::list_projects,
+            projects::create_project,
+            projects::open_project,
+            projects::delete_project,
+            secrets::set_api_key,
+            secrets::delete_api_key,
+            settings::get_config,
+            settings::set_config,
+            render::start_render,
+            render::cancel_render,
+            render::list_renders,
+            upload::upload_render,
+            notifications::test_webhook,
+            resources::get_resource_usage,
+            sessions::list_sessions,
+            sessions::get_resume_session,
+            transcripts::search_transcripts,
+            usage::get_usage_stats,
+            templates::list_templates,
+            scaffold::list_blueprints,
+            scaffold::scaffold_composition,
+            render::export_still,
+            render::capture_preview_frame,
+            ffmpeg::transcode,
+            ffmpeg::extract_audio,
+            media::analyze_media,
+            git::git_log,
+            git::git_diff,
+            git::git_restore,
+            snapshots::list_snapshots,
+            snapshots::restore_snapshot,
+            snapshots::create_checkpoint,
+            snapshots::list_checkpoints,
+            experiments::start_experiment,
+            experiments::merge_experiment,
+            experiments::discard_experiment,
+            backup::push_backup,
+            ports::get_service_ports,
+            ports::get_service_endpoints,
+            logging::purge_old_logs,
+            logging::get_logs_since,
+            logging::tail_logs,
+            logging::stop_tail_logs,
+            diagnostics::create_diagnostics_bundle,
+            doctor::run_doctor,
+            npm::cancel_npm_install,
+            operations::list_operations,
+            operations::cancel_operation,
+            assets::import_assets,
+            assets::list_assets,
+            capture::enable_capture,
+            capture::disable_capture,
+            capture::get_capture_path,
+            proxy::reload_proxy_config,
+            proxy_cache::clear_proxy_cache,
+            proxy::relay_fetch,
+            proxy::get_proxy_token,
+            proxy_stats::get_proxy_stats,
+            onboarding::get_onboarding_state,
+            onboarding::complete_onboarding_step,
+            frontend::frontend_ready,
+            frontend::get_event_history,
+            phase::get_app_state,
+            setup::cancel_setup,
+            setup::retry_setup,
+            workspace_lock::take_over_workspace,
+            telemetry::set_telemetry_enabled,
+            updater::check_for_update,
+            updater::install_update,
+            editor::open_in_finder,
+            editor::open_in_editor,
+            export::export_workspace,
+            import::import_workspace,
+            disk::get_disk_usage,
+            disk::clean_workspace,
+            local_models::list_local_models
         ])
         .setup(move |app| {
             app.handle().plugin(
@@ -867,151 +1350,46 @@ pub fn run() {
                 opencode: None,
                 remotion: None,
                 log_file_path: log_file_path.clone(),
+                shutting_down: false,
+                restarting: false,
+                services_generation: 0,
+                ports: ports::ServicePorts::default(),
+                owned_pids: ports::load_owned_pids(),
+                proxies: Vec::new(),
+                event_history: frontend::EventHistory::default(),
+                phase: phase::Phase::default(),
+                last_error: None,
+                operations: operations::Registry::default(),
             }));
+            app.manage(render::RenderQueue::default());
+            tray::init(app.handle())?;
+
+            // Installed apps have the scheme registered by their bundle;
+            // Linux and dev builds on Windows register it at runtime.
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                logging::log_line("WARN", &format!("[deep-link] Couldn't register: {}", e));
+            }
+            let handle = app.handle().clone();
+            app.deep_link()
+                .on_open_url(move |event| deep_link::handle(&handle, event.urls()));
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                deep_link::handle(app.handle(), urls);
+            }
 
-            let app_handle = app.handle().clone();
-
-            std::thread::spawn(move || {
-                std::thread::sleep(std::time::Duration::from_millis(1500));
-
-                if let Some(state) = app_handle.try_state::<Mutex<AppState>>() {
-                    write_log(&state, "INFO", "Starting workspace setup...");
-                }
-
-                let config = load_config();
-                let config_path = get_config_path();
-
-                if let Some(state) = app_handle.try_state::<Mutex<AppState>>() {
-                    write_log(&state, "INFO", &format!("Config path: {:?}", config_path));
-                    write_log(
-                        &state,
-                        "INFO",
-                        &format!("Config exists: {}", config_path.exists()),
-                    );
-                    write_log(
-                        &state,
-                        "INFO",
-                        &format!(
-                            "Anthropic key configured: {}",
-                            config.anthropic_api_key.is_some()
-                        ),
-                    );
-                    write_log(
-                        &state,
-                        "INFO",
-                        &format!("OpenAI key configured: {}", config.openai_api_key.is_some()),
-                    );
-                }
-
-                match setup_workspace(&app_handle) {
-                    Ok(_) => {
-                        if let Some(state) = app_handle.try_state::<Mutex<AppState>>() {
-                            write_log(&state, "INFO", "Workspace setup complete");
-                        }
-
-                        let workspace = get_workspace_dir();
-
-                        let opencode_result = spawn_opencode(&app_handle, &workspace, &config);
-                        let remotion_result = spawn_remotion(&app_handle, &workspace);
-
-                        match (&opencode_result, &remotion_result) {
-                            (Ok(_), Ok(_)) => {}
-                            (Err(e), _) | (_, Err(e)) => {
-                                sentry::capture_message(e, sentry::Level::Error);
-                                let _ = app_handle.emit("setup-error", e.clone());
-                                return;
-                            }
-                        }
-
-                        // Start the reverse proxy that sits between the webview
-                        // and OpenCode, preventing WKWebView timeout kills on
-                        // long-running streaming responses.
-                        if let Some(state) = app_handle.try_state::<Mutex<AppState>>() {
-                            write_log(
-                                &state,
-                                "INFO",
-                                &format!(
-                                    "Starting reverse proxy on port {} -> {}",
-                                    OPENCODE_PROXY_PORT, OPENCODE_PORT
-                                ),
-                            );
-                        }
-
-                        // Clean up proxy port before binding
-                        kill_port(OPENCODE_PROXY_PORT);
-                        std::thread::sleep(std::time::Duration::from_millis(200));
-
-                        // Get the log file path so the proxy can write to the same file
-                        let proxy_log_path = app_handle
-                            .try_state::<Mutex<AppState>>()
-                            .and_then(|state| {
-                                state.lock().ok().map(|g| g.log_file_path.clone())
-                            })
-                            .unwrap_or_else(|| get_logs_dir().join("proxy.log"));
-
-                        let proxy_handle = app_handle.clone();
-                        std::thread::spawn(move || {
-                            let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime for proxy");
-                            rt.block_on(async {
-                                if let Err(e) = proxy::run_proxy(OPENCODE_PROXY_PORT, OPENCODE_PORT, proxy_log_path).await {
-                                    log::error!("Proxy exited with error: {}", e);
-                                    if let Some(state) = proxy_handle.try_state::<Mutex<AppState>>() {
-                                        write_log(
-                                            &state,
-                                            "ERROR",
-                                            &format!("Reverse proxy failed: {}", e),
-                                        );
-                                    }
-                                }
-                            });
-                        });
-
-                        let _ = app_handle.emit("setup-complete", ());
-
-                        if let Some(state) = app_handle.try_state::<Mutex<AppState>>() {
-                            let mut guard = state.lock().unwrap();
-                            guard.opencode = opencode_result.ok();
-                            guard.remotion = remotion_result.ok();
-                        }
-                    }
-                    Err(e) => {
-                        if let Some(state) = app_handle.try_state::<Mutex<AppState>>() {
-                            write_log(&state, "ERROR", &format!("Workspace setup failed: {}", e));
-                        }
-                        sentry::capture_message(
-                            &format!("Workspace setup failed: {}", e),
-                            sentry::Level::Error,
-                        );
-                        let _ = app_handle.emit("setup-error", e);
-                    }
-                }
-            });
+            tauri::async_runtime::spawn(setup::run(app.handle().clone()));
+            updater::start(app.handle().clone());
 
             Ok(())
         })
-        .on_window_event(|window, event| {
-            if let tauri::WindowEvent::CloseRequested { .. } = event {
-                if let Some(state) = window.app_handle().try_state::<Mutex<AppState>>() {
-                    write_log(&state, "INFO", "Window closing, cleaning up processes...");
-                    let mut guard = state.lock().unwrap();
-
-                    if let Some(ref mut child) = guard.opencode {
-                        write_log(&state, "INFO", &format!("Killing OpenCode (PID: {})", child.id()));
-                        let _ = child.kill();
-                    }
-                    if let Some(ref mut child) = guard.remotion {
-                        write_log(&state, "INFO", &format!("Killing Remotion (PID: {})", child.id()));
-                        let _ = child.kill();
-                    }
-                    
-                    write_log(&state, "INFO", &format!("Cleaning up ports {}, {}, {}...", REMOTION_PORT, OPENCODE_PORT, OPENCODE_PROXY_PORT));
-                    
-                    // Spawn cleanup without blocking - use spawn() not status()
-                    let _ = Command::new("sh")
-                        .args(["-c", &format!("sleep 0.5 && lsof -ti:{},{},{} 2>/dev/null | xargs kill -9 2>/dev/null", OPENCODE_PORT, OPENCODE_PROXY_PORT, REMOTION_PORT)])
-                        .spawn();
-                }
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::CloseRequested { .. } => {
+                shutdown(window.app_handle(), "Window closing");
+            }
+            tauri::WindowEvent::Focused(focused) => {
+                idle::on_focus_changed(window.app_handle(), *focused);
             }
+            _ => {}
         })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,173 @@
+//! Local model servers, so the app works without any cloud API key.
+//!
+//! At startup, when no provider has a key, `detect` probes Ollama (its
+//! configured base URL or the default port) and LM Studio's OpenAI-compatible
+//! server. The first one that answers is remembered; onboarding then accepts
+//! it in place of a key, and `spawn_opencode` hands OpenCode a provider for
+//! it through `OPENCODE_CONFIG_CONTENT`, with its first model as the default.
+//! `list_local_models` probes every server again and lists what's running.
+
+use crate::error::StudioError;
+use crate::{load_config, providers};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Local servers answer fast; anything slower isn't worth waiting for.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const OLLAMA_DEFAULT_URL: &str = "http://127.0.0.1:11434";
+
+/// The server found by the last `detect`.
+static DETECTED: Mutex<Option<LocalServer>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalServer {
+    /// Provider id used in OpenCode: "ollama" or "lmstudio".
+    pub id: &'static str,
+    pub name: &'static str,
+    /// OpenAI-compatible endpoint, ending in `/v1`.
+    pub base_url: String,
+    pub models: Vec<String>,
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Models listed by the Ollama at `root`, if one is running there.
+async fn probe_ollama(client: &reqwest::Client, root: &str) -> Option<LocalServer> {
+    let root = root.trim_end_matches('/');
+    let body: serde_json::Value = client
+        .get(format!("{}/api/tags", root))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+    let models = body["models"]
+        .as_array()?
+        .iter()
+        .filter_map(|model| model["name"].as_str().map(str::to_string))
+        .collect();
+    Some(LocalServer {
+        id: "ollama",
+        name: "Ollama",
+        base_url: format!("{}/v1", root),
+        models,
+    })
+}
+
+/// Models served by LM Studio, if it's running.
+async fn probe_lm_studio(client: &reqwest::Client) -> Option<LocalServer> {
+    let base_url = "http://127.0.0.1:1234/v1";
+    let body: serde_json::Value = client
+        .get(format!("{}/models", base_url))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+    let models = body["data"]
+        .as_array()?
+        .iter()
+        .filter_map(|model| model["id"].as_str().map(str::to_string))
+        .collect();
+    Some(LocalServer {
+        id: "lmstudio",
+        name: "LM Studio",
+        base_url: base_url.to_string(),
+        models,
+    })
+}
+
+/// Every local server that answers right now.
+async fn probe_all() -> Result<Vec<LocalServer>, String> {
+    let client = client()?;
+    let ollama_url = load_config()
+        .provider("ollama")
+        .and_then(|entry| entry.base_url.clone())
+        .unwrap_or_else(|| OLLAMA_DEFAULT_URL.to_string());
+    let (ollama, lm_studio) =
+        tokio::join!(probe_ollama(&client, &ollama_url), probe_lm_studio(&client));
+    Ok(ollama.into_iter().chain(lm_studio).collect())
+}
+
+/// Look for a local server with at least one model and remember it for
+/// `detected`.
+pub async fn detect() -> Option<LocalServer> {
+    let servers = match probe_all().await {
+        Ok(servers) => servers,
+        Err(e) => {
+            crate::logging::log_line("WARN", &format!("Local model detection failed: {}", e));
+            Vec::new()
+        }
+    };
+    let found = servers.into_iter().find(|server| !server.models.is_empty());
+    match &found {
+        Some(server) => crate::logging::log_line(
+            "INFO",
+            &format!(
+                "Found {} at {} with {} models",
+                server.name,
+                server.base_url,
+                server.models.len()
+            ),
+        ),
+        None => crate::logging::log_line("INFO", "No local model server found"),
+    }
+    *DETECTED.lock().unwrap_or_else(|e| e.into_inner()) = found.clone();
+    found
+}
+
+pub fn detected() -> Option<LocalServer> {
+    DETECTED.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// OpenCode config adding `server` as a provider and making its first model
+/// the default.
+pub fn opencode_config(server: &LocalServer) -> serde_json::Value {
+    let models: serde_json::Map<String, serde_json::Value> = server
+        .models
+        .iter()
+        .map(|model| (model.clone(), serde_json::json!({})))
+        .collect();
+    let mut config = serde_json::json!({
+        "provider": {
+            server.id: {
+                "npm": "@ai-sdk/openai-compatible",
+                "name": server.name,
+                "options": { "baseURL": server.base_url },
+                "models": models,
+            }
+        }
+    });
+    if let Some(model) = server.models.first() {
+        config["model"] = serde_json::json!(format!("{}/{}", server.id, model));
+    }
+    config
+}
+
+/// Whether OpenCode should run against the local server: there's one, and
+/// no provider has a key.
+pub fn use_local(config: &crate::AppConfig) -> Option<LocalServer> {
+    if providers::any_key(config) {
+        return None;
+    }
+    detected()
+}
+
+/// Local model servers running now and their models.
+#[tauri::command]
+pub async fn list_local_models() -> Result<Vec<LocalServer>, StudioError> {
+    Ok(probe_all().await?)
+}
@@ -0,0 +1,611 @@
+//! Application log sinks.
+//!
+//! Every line goes to a JSON Lines file (one object per line with `ts`,
+//! `level`, `target`, an optional `requestId` and `msg`) that can be filtered
+//! with jq or loaded into support tooling. The human-readable `.log` file
+//! the Logs panel shows is kept as a secondary sink and can be turned off
+//! with `humanReadableLog: false` in config.json. Lines below `logLevel`
+//! (default "info") are dropped from both.
+//!
+//! Messages carry their subsystem as a `[target]` prefix (`[proxy]`,
+//! `[opencode]`, `[remotion]`, ...) and proxy requests as `#id`; those become
+//! the `target` and `requestId` fields. Untagged lines use the `app` target.
+//!
+//! A file that grows past `MAX_FILE_BYTES` is rotated to `<stem>.1.log`,
+//! `<stem>.2.log`, ... (the newest rotation has the lowest number) and only
+//! `MAX_ROTATIONS` of them are kept. Old sessions are purged on startup:
+//! anything older than `logRetentionDays` (default 14) or beyond the newest
+//! `logKeepSessions` (default 30) sessions is deleted. `purge_old_logs` runs
+//! the same cleanup on demand.
+//!
+//! Lines are written by one writer thread fed through a channel, so logging
+//! never waits on disk I/O and lines keep the order they were logged in.
+//! The writer keeps both files open behind buffers and flushes them every
+//! `FLUSH_INTERVAL`, right after an ERROR line, and when `flush` is called
+//! (before the logs are read and when the app quits).
+//!
+//! The Logs panel loads the log with `get_logs_since(0)` and then calls
+//! `tail_logs` with the returned offset; a watcher polls the file and emits
+//! each new line as a `log-line` event until `stop_tail_logs`. If the events
+//! can't be used, polling `get_logs_since` with the last offset gives the
+//! same result without re-reading the whole file.
+
+use crate::error::StudioError;
+use crate::AppState;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Log files are rotated once they grow past this size.
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated files kept per log file.
+const MAX_ROTATIONS: u32 = 3;
+pub const DEFAULT_RETENTION_DAYS: u32 = 14;
+pub const DEFAULT_KEEP_SESSIONS: usize = 30;
+/// Every log file we write starts with this.
+const FILE_PREFIX: &str = "langston-studio_";
+/// How often the `tail_logs` watcher checks for new lines.
+const TAIL_INTERVAL: Duration = Duration::from_millis(500);
+/// Longest a logged line stays in the writer's buffers.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(200);
+/// How long `flush` waits for the writer.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug = 0,
+    Info = 1,
+    Warn = 2,
+    Error = 3,
+}
+
+impl Level {
+    /// Parse a level name, case-insensitively. "WARNING" is accepted for
+    /// "WARN".
+    pub fn parse(name: &str) -> Option<Level> {
+        match name.to_ascii_lowercase().as_str() {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" | "warning" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    fn from_u8(value: u8) -> Level {
+        match value {
+            0 => Level::Debug,
+            1 => Level::Info,
+            2 => Level::Warn,
+            _ => Level::Error,
+        }
+    }
+}
+
+/// Files this session logs to.
+#[derive(Debug, Clone)]
+pub struct LogFiles {
+    pub json: PathBuf,
+    /// The human-readable log, unless it's turned off.
+    pub text: Option<PathBuf>,
+}
+
+impl LogFiles {
+    /// The file the Logs panel shows.
+    pub fn display_path(&self) -> &Path {
+        self.text.as_deref().unwrap_or(&self.json)
+    }
+}
+
+static FILES: OnceLock<LogFiles> = OnceLock::new();
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+/// Feeds the writer thread started by `init`.
+static WRITER: OnceLock<mpsc::Sender<Message>> = OnceLock::new();
+/// Bumped to start a new tail watcher or stop the current one.
+static TAIL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Create this session's log files in `logs_dir`, named `<stem>.jsonl` and
+/// `<stem>.log`. Only the first call has any effect.
+pub fn init(logs_dir: &Path, stem: &str, level: Level, human_readable: bool) -> LogFiles {
+    set_level(level);
+    FILES
+        .get_or_init(|| {
+            let _ = std::fs::create_dir_all(logs_dir);
+            let files = LogFiles {
+                json: logs_dir.join(format!("{}.jsonl", stem)),
+                text: human_readable.then(|| logs_dir.join(format!("{}.log", stem))),
+            };
+            for path in [Some(&files.json), files.text.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                let _ = OpenOptions::new().create(true).append(true).open(path);
+            }
+            start_writer(&files);
+            files
+        })
+        .clone()
+}
+
+/// Another file belonging to this session, `<stem>.<suffix>`, so it's
+/// bundled and purged along with the session's logs.
+pub fn session_file(suffix: &str) -> Option<PathBuf> {
+    FILES.get().map(|files| files.json.with_extension(suffix))
+}
+
+pub fn set_level(level: Level) {
+    MIN_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    Level::from_u8(MIN_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Split a `[target] #id rest` message into its parts.
+fn parse_tags(message: &str) -> (&str, Option<u64>) {
+    let Some(rest) = message.strip_prefix('[') else {
+        return ("app", None);
+    };
+    let Some((target, rest)) = rest.split_once(']') else {
+        return ("app", None);
+    };
+    if target.is_empty()
+        || !target
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return ("app", None);
+    }
+    let request_id = rest
+        .trim_start()
+        .strip_prefix('#')
+        .and_then(|r| r.split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|id| id.parse().ok());
+    (target, request_id)
+}
+
+/// `dir/stem.ext` -> `dir/stem.N.ext`.
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.{}", stem, n, ext))
+}
+
+/// Shift `path` into the rotation: .2 -> .3, .1 -> .2, current -> .1.
+fn rotate(path: &Path) {
+    let _ = std::fs::remove_file(rotated_path(path, MAX_ROTATIONS));
+    for n in (1..MAX_ROTATIONS).rev() {
+        let _ = std::fs::rename(rotated_path(path, n), rotated_path(path, n + 1));
+    }
+    let _ = std::fs::rename(path, rotated_path(path, 1));
+}
+
+enum Message {
+    Write {
+        json: String,
+        text: Option<String>,
+        /// Flush right away instead of within `FLUSH_INTERVAL`.
+        urgent: bool,
+    },
+    /// Flush, then signal the sender.
+    Flush(mpsc::Sender<()>),
+}
+
+/// One log file as held open by the writer.
+struct Sink {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+    len: u64,
+}
+
+impl Sink {
+    fn new(path: &Path) -> Sink {
+        let mut sink = Sink {
+            path: path.to_path_buf(),
+            file: None,
+            len: 0,
+        };
+        sink.open();
+        sink
+    }
+
+    fn open(&mut self) {
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .ok()
+            .map(BufWriter::new);
+        self.len = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+    }
+
+    fn write(&mut self, line: &str) {
+        if self.len >= MAX_FILE_BYTES {
+            self.flush();
+            self.file = None;
+            rotate(&self.path);
+            self.open();
+        } else if self.file.is_none() {
+            self.open();
+        }
+        if let Some(file) = &mut self.file {
+            if file.write_all(line.as_bytes()).is_ok() {
+                self.len += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(file) = &mut self.file {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn flush_all(json: &mut Sink, text: &mut Option<Sink>) {
+    json.flush();
+    if let Some(text) = text {
+        text.flush();
+    }
+}
+
+fn start_writer(files: &LogFiles) {
+    let (sender, receiver) = mpsc::channel::<Message>();
+    let mut json = Sink::new(&files.json);
+    let mut text = files.text.as_deref().map(Sink::new);
+    let spawned = std::thread::Builder::new()
+        .name("log-writer".to_string())
+        .spawn(move || {
+            let mut last_flush = Instant::now();
+            let mut dirty = false;
+            let mut urgent_pending = false;
+            loop {
+                match receiver.recv_timeout(FLUSH_INTERVAL) {
+                    Ok(Message::Write {
+                        json: json_line,
+                        text: text_line,
+                        urgent,
+                    }) => {
+                        json.write(&json_line);
+                        if let (Some(sink), Some(line)) = (&mut text, text_line) {
+                            sink.write(&line);
+                        }
+                        dirty = true;
+                        urgent_pending |= urgent;
+                    }
+                    Ok(Message::Flush(done)) => {
+                        flush_all(&mut json, &mut text);
+                        dirty = false;
+                        urgent_pending = false;
+                        last_flush = Instant::now();
+                        let _ = done.send(());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => {
+                        flush_all(&mut json, &mut text);
+                        break;
+                    }
+                }
+                if dirty && (urgent_pending || last_flush.elapsed() >= FLUSH_INTERVAL) {
+                    flush_all(&mut json, &mut text);
+                    dirty = false;
+                    urgent_pending = false;
+                    last_flush = Instant::now();
+                }
+            }
+        });
+    if spawned.is_ok() {
+        let _ = WRITER.set(sender);
+    }
+}
+
+/// Write out everything logged so far. Returns once the writer has flushed
+/// its buffers, or after `FLUSH_TIMEOUT`.
+pub fn flush() {
+    let Some(writer) = WRITER.get() else {
+        return;
+    };
+    let (done, flushed) = mpsc::channel();
+    if writer.send(Message::Flush(done)).is_ok() {
+        let _ = flushed.recv_timeout(FLUSH_TIMEOUT);
+    }
+}
+
+/// Log `message` with the target and request id taken from its tags.
+pub fn log_line(level: &str, message: &str) {
+    let (target, request_id) = parse_tags(message);
+    log(target, level, request_id, message);
+}
+
+/// Write one line to every sink.
+pub fn log(target: &str, level: &str, request_id: Option<u64>, message: &str) {
+    let level = Level::parse(level).unwrap_or(Level::Info);
+    if level < self::level() {
+        return;
+    }
+
+    crate::telemetry::remember_log_line(level.as_str(), message);
+
+    match level {
+        Level::Error => log::error!("{}", message),
+        Level::Warn => log::warn!("{}", message),
+        Level::Info => log::info!("{}", message),
+        Level::Debug => log::debug!("{}", message),
+    }
+
+    let (Some(files), Some(writer)) = (FILES.get(), WRITER.get()) else {
+        return;
+    };
+    let now = Local::now();
+
+    let mut entry = serde_json::json!({
+        "ts": now.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+        "level": level.as_str(),
+        "target": target,
+        "msg": message,
+    });
+    if let Some(id) = request_id {
+        entry["requestId"] = serde_json::json!(id);
+    }
+    let text = files.text.as_ref().map(|_| {
+        format!(
+            "[{}] [{}] {}\n",
+            now.format("%Y-%m-%d %H:%M:%S%.3f"),
+            level.as_str(),
+            message
+        )
+    });
+    let _ = writer.send(Message::Write {
+        json: format!("{}\n", entry),
+        text,
+        urgent: level == Level::Error,
+    });
+}
+
+/// Render one line of a log file for reading: JSON Lines entries in the same
+/// layout as the text log, anything else as-is.
+fn display_line(line: &str, json: bool) -> String {
+    if !json {
+        return line.to_string();
+    }
+    let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+        return line.to_string();
+    };
+    let field = |name: &str| entry.get(name).and_then(|v| v.as_str()).unwrap_or("");
+    format!("[{}] [{}] {}", field("ts"), field("level"), field("msg"))
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("jsonl")
+}
+
+/// Render a log file for reading: text logs as-is, JSON Lines logs in the
+/// same layout as the text log.
+pub fn read_for_display(path: &Path) -> std::io::Result<String> {
+    flush();
+    let contents = std::fs::read_to_string(path)?;
+    if !is_json(path) {
+        return Ok(contents);
+    }
+
+    let mut out = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        out.push_str(&display_line(line, true));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Lines appended to a log file after a byte offset.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogChunk {
+    pub lines: Vec<String>,
+    /// Pass this back to continue where this chunk ended.
+    pub offset: u64,
+    /// The file was rotated or truncated since `offset`, so reading started
+    /// over from the beginning.
+    pub reset: bool,
+}
+
+/// The complete lines of `path` after byte `offset`, rendered for display. A
+/// partially written last line is left for the next read.
+pub fn read_since(path: &Path, offset: u64) -> std::io::Result<LogChunk> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let reset = offset > len;
+    let start = if reset { 0 } else { offset };
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.take(len - start).read_to_end(&mut bytes)?;
+    let complete = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+
+    let json = is_json(path);
+    let lines = String::from_utf8_lossy(&bytes[..complete])
+        .lines()
+        .map(|line| display_line(line, json))
+        .collect();
+    Ok(LogChunk {
+        lines,
+        offset: start + complete as u64,
+        reset,
+    })
+}
+
+fn session_log_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let state = app.state::<Mutex<AppState>>();
+    let guard = state.lock().map_err(|e| e.to_string())?;
+    Ok(guard.log_file_path.clone())
+}
+
+/// Log lines written after `offset` (0 for the whole log).
+#[tauri::command]
+pub fn get_logs_since(app: AppHandle, offset: u64) -> Result<LogChunk, StudioError> {
+    let path = session_log_path(&app)?;
+    flush();
+    read_since(&path, offset).map_err(|e| StudioError::io_at("Failed to read", &path, e))
+}
+
+/// Start emitting each line written to the session log after `offset` as a
+/// `log-line` event. Without an offset, only lines written from now on are
+/// sent. Replaces any watcher started earlier. Returns the starting offset.
+#[tauri::command]
+pub fn tail_logs(app: AppHandle, offset: Option<u64>) -> Result<u64, StudioError> {
+    let path = session_log_path(&app)?;
+    let start = match offset {
+        Some(offset) => offset,
+        None => std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+    };
+    let generation = TAIL_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    std::thread::spawn(move || {
+        let mut offset = start;
+        while TAIL_GENERATION.load(Ordering::SeqCst) == generation {
+            if let Ok(chunk) = read_since(&path, offset) {
+                if chunk.reset {
+                    let _ = app.emit("log-reset", ());
+                }
+                offset = chunk.offset;
+                for line in chunk.lines {
+                    let _ = app.emit(
+                        "log-line",
+                        serde_json::json!({ "line": line, "offset": offset }),
+                    );
+                }
+            }
+            std::thread::sleep(TAIL_INTERVAL);
+        }
+    });
+    Ok(start)
+}
+
+/// Stop the `tail_logs` watcher.
+#[tauri::command]
+pub fn stop_tail_logs() {
+    TAIL_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeResult {
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+}
+
+/// The session a log file belongs to: its name up to the first dot.
+fn session_of(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+/// A session's log files with their sizes.
+type SessionFiles = Vec<(PathBuf, u64)>;
+
+/// Delete log files from sessions older than `max_age` or beyond the newest
+/// `keep_sessions`. The current session is never touched.
+pub fn purge(logs_dir: &Path, max_age: Duration, keep_sessions: usize) -> PurgeResult {
+    let current = FILES.get().and_then(|f| {
+        f.json
+            .file_name()
+            .map(|n| session_of(&n.to_string_lossy()).to_string())
+    });
+
+    let Ok(entries) = std::fs::read_dir(logs_dir) else {
+        return PurgeResult::default();
+    };
+
+    // session -> (newest modification time, files)
+    let mut sessions: HashMap<String, (SystemTime, SessionFiles)> = HashMap::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(FILE_PREFIX) {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let session = sessions
+            .entry(session_of(&name).to_string())
+            .or_insert((modified, Vec::new()));
+        session.0 = session.0.max(modified);
+        session.1.push((entry.path(), meta.len()));
+    }
+
+    let mut ordered: Vec<(String, SystemTime, SessionFiles)> = sessions
+        .into_iter()
+        .map(|(name, (modified, files))| (name, modified, files))
+        .collect();
+    ordered.sort_by_key(|session| std::cmp::Reverse(session.1));
+
+    let cutoff = SystemTime::now()
+        .checked_sub(max_age)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut result = PurgeResult::default();
+    for (index, (name, modified, files)) in ordered.into_iter().enumerate() {
+        if Some(&name) == current.as_ref() {
+            continue;
+        }
+        if index < keep_sessions && modified >= cutoff {
+            continue;
+        }
+        for (path, size) in files {
+            if std::fs::remove_file(&path).is_ok() {
+                result.files_removed += 1;
+                result.bytes_freed += size;
+            }
+        }
+    }
+    result
+}
+
+/// Delete old log files. `older_than_days` overrides `logRetentionDays`;
+/// pass 0 to delete everything but the current session.
+#[tauri::command]
+pub fn purge_old_logs(older_than_days: Option<u32>) -> PurgeResult {
+    let config = crate::read_config_file();
+    let days = older_than_days
+        .or(config.log_retention_days)
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    let keep = if older_than_days == Some(0) {
+        0
+    } else {
+        config.log_keep_sessions.unwrap_or(DEFAULT_KEEP_SESSIONS)
+    };
+
+    let result = purge(
+        &crate::get_logs_dir(),
+        Duration::from_secs(u64::from(days) * 24 * 60 * 60),
+        keep,
+    );
+    if result.files_removed > 0 {
+        log_line(
+            "INFO",
+            &format!(
+                "Purged {} old log files ({} bytes)",
+                result.files_removed, result.bytes_freed
+            ),
+        );
+    }
+    result
+}
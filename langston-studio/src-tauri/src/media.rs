@@ -0,0 +1,345 @@
+//! Previews of media assets for the timeline: waveforms and thumbnails.
+//!
+//! `analyze_media` takes an asset as `list_assets` names it (relative to
+//! `public/`, e.g. "assets/interview.mp4") and returns, using ffmpeg (see
+//! `ffmpeg`):
+//!
+//! - for audio, and video with a soundtrack, a waveform: the loudest sample
+//!   in every `1 / PEAKS_PER_SECOND` s, from 0.0 to 1.0;
+//! - for video, a sprite sheet of up to `MAX_THUMBNAILS` frames spread over
+//!   its length, `THUMB_WIDTH` x `THUMB_HEIGHT` each (letterboxed), in rows
+//!   of `SHEET_COLUMNS`.
+//!
+//! Results are cached in a `.langston-media` folder next to the asset, as
+//! `<name>.json` and `<name>.thumbs.jpg`, and reused while the asset's size
+//! and modification time are unchanged. The folder is hidden from
+//! `list_assets` and kept out of the workspace's history.
+
+use crate::assets::{self, ASSETS_DIR};
+use crate::error::StudioError;
+use crate::ffmpeg::{self, Ffmpeg};
+use crate::{git, resolve_workspace_dir};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::UNIX_EPOCH;
+
+const CACHE_DIR: &str = ".langston-media";
+/// Bumped when the cached format changes, so old caches are redone.
+const CACHE_VERSION: u32 = 1;
+/// Audio is decoded to mono at this rate for the waveform.
+const SAMPLE_RATE: u32 = 8000;
+const PEAKS_PER_SECOND: u32 = 50;
+const MAX_THUMBNAILS: u32 = 100;
+const SHEET_COLUMNS: u32 = 10;
+const THUMB_WIDTH: u32 = 160;
+const THUMB_HEIGHT: u32 = 90;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Waveform {
+    pub peaks_per_second: u32,
+    /// Rounded to three decimals.
+    pub peaks: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpriteSheet {
+    /// The sheet, relative to `public/` like the asset.
+    pub file: String,
+    pub thumb_width: u32,
+    pub thumb_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub count: u32,
+    /// Seconds between thumbnails; thumbnail `i` shows `i * interval`.
+    pub interval: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaAnalysis {
+    pub file: String,
+    pub kind: String,
+    pub duration: Option<f64>,
+    pub waveform: Option<Waveform>,
+    pub thumbnails: Option<SpriteSheet>,
+}
+
+/// What's cached: the analysis and the asset it was made from.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Cached {
+    version: u32,
+    size: u64,
+    modified: u64,
+    analysis: MediaAnalysis,
+}
+
+/// The asset `file` names, which has to be under `public/assets`.
+fn asset_path(workspace: &Path, file: &str) -> Result<PathBuf, StudioError> {
+    let public = workspace.join("public");
+    let path = public.join(file.trim_start_matches('/'));
+    let assets_dir = fs::canonicalize(workspace.join(ASSETS_DIR))
+        .map_err(|_| StudioError::NotFound("The assets folder".to_string()))?;
+    let canonical = fs::canonicalize(&path).map_err(|_| StudioError::NotFound(file.to_string()))?;
+    if !canonical.starts_with(&assets_dir) || !canonical.is_file() {
+        return Err(StudioError::InvalidInput(format!(
+            "Not a file in {}: {}",
+            ASSETS_DIR, file
+        )));
+    }
+    Ok(path)
+}
+
+/// Size and modification time, which tell whether a cache is current.
+fn stamp(path: &Path) -> (u64, u64) {
+    let meta = fs::metadata(path).ok();
+    let modified = meta
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    (meta.map_or(0, |m| m.len()), modified)
+}
+
+fn cache_dir(asset: &Path) -> PathBuf {
+    asset.parent().unwrap_or(Path::new(".")).join(CACHE_DIR)
+}
+
+fn cache_file(asset: &Path, suffix: &str) -> PathBuf {
+    let name = asset
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    cache_dir(asset).join(format!("{}{}", name, suffix))
+}
+
+fn read_cache(asset: &Path) -> Option<MediaAnalysis> {
+    let contents = fs::read_to_string(cache_file(asset, ".json")).ok()?;
+    let cached: Cached = serde_json::from_str(&contents).ok()?;
+    let (size, modified) = stamp(asset);
+    let current = cached.version == CACHE_VERSION
+        && cached.size == size
+        && cached.modified == modified
+        && cached
+            .analysis
+            .thumbnails
+            .as_ref()
+            .map_or(true, |_| cache_file(asset, ".thumbs.jpg").is_file());
+    current.then_some(cached.analysis)
+}
+
+/// Write `contents` to `path` through a temporary file, so a concurrent
+/// reader never sees half of it.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), StudioError> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).map_err(|e| StudioError::io_at("Failed to write", &tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| StudioError::io_at("Failed to write", path, e))
+}
+
+fn write_cache(asset: &Path, analysis: &MediaAnalysis) -> Result<(), StudioError> {
+    let (size, modified) = stamp(asset);
+    let cached = Cached {
+        version: CACHE_VERSION,
+        size,
+        modified,
+        analysis: analysis.clone(),
+    };
+    let contents = serde_json::to_vec(&cached).map_err(|e| e.to_string())?;
+    write_atomically(&cache_file(asset, ".json"), &contents)
+}
+
+/// Peaks of `asset`'s audio, or `None` when it has none.
+fn waveform(ffmpeg: &Ffmpeg, asset: &Path) -> Result<Option<Waveform>, StudioError> {
+    let mut child = ffmpeg
+        .command()
+        .args(["-hide_banner", "-nostdin", "-v", "error", "-i"])
+        .arg(asset)
+        .args(["-vn", "-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+        .args(["-f", "s16le", "-acodec", "pcm_s16le", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| StudioError::io("Failed to run ffmpeg", e))?;
+
+    let window = (SAMPLE_RATE / PEAKS_PER_SECOND) as usize;
+    let mut peaks = Vec::new();
+    let (mut peak, mut in_window) = (0u16, 0usize);
+    let mut buf = [0u8; 64 * 1024];
+    // A sample can be split across reads.
+    let mut carry: Option<u8> = None;
+    if let Some(mut stdout) = child.stdout.take() {
+        loop {
+            let n = match stdout.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let mut bytes = buf[..n].iter().copied();
+            loop {
+                let low = match carry.take() {
+                    Some(low) => low,
+                    None => match bytes.next() {
+                        Some(low) => low,
+                        None => break,
+                    },
+                };
+                let Some(high) = bytes.next() else {
+                    carry = Some(low);
+                    break;
+                };
+                let sample = i16::from_le_bytes([low, high]);
+                peak = peak.max(sample.unsigned_abs());
+                in_window += 1;
+                if in_window == window {
+                    peaks.push(peak);
+                    (peak, in_window) = (0, 0);
+                }
+            }
+        }
+    }
+    if in_window > 0 {
+        peaks.push(peak);
+    }
+    let status = child
+        .wait()
+        .map_err(|e| StudioError::io("Failed to run ffmpeg", e))?;
+    // ffmpeg fails when there's no audio stream to decode.
+    if !status.success() || peaks.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Waveform {
+        peaks_per_second: PEAKS_PER_SECOND,
+        peaks: peaks
+            .into_iter()
+            .map(|p| (p as f32 / i16::MAX as f32 * 1000.0).round().min(1000.0) / 1000.0)
+            .collect(),
+    }))
+}
+
+/// A sprite sheet of `asset`'s frames, written into the cache folder.
+fn thumbnails(
+    ffmpeg: &Ffmpeg,
+    asset: &Path,
+    file: &str,
+    duration: f64,
+) -> Result<SpriteSheet, StudioError> {
+    let count = (duration.ceil() as u32).clamp(1, MAX_THUMBNAILS);
+    let interval = duration / count as f64;
+    let columns = count.min(SHEET_COLUMNS);
+    let rows = count.div_ceil(SHEET_COLUMNS);
+    let filter = format!(
+        "fps={count}/{duration:.3},scale={w}:{h}:force_original_aspect_ratio=decrease,\
+         pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,tile={columns}x{rows}",
+        count = count,
+        duration = duration.max(0.001),
+        w = THUMB_WIDTH,
+        h = THUMB_HEIGHT,
+        columns = columns,
+        rows = rows,
+    );
+    let sheet = cache_file(asset, ".thumbs.jpg");
+    let tmp = cache_file(asset, ".thumbs.tmp.jpg");
+    // Only keyframes are decoded, which is much faster on long videos and
+    // close enough for a preview.
+    let output = ffmpeg
+        .command()
+        .args(["-hide_banner", "-nostdin", "-v", "error", "-y"])
+        .args(["-skip_frame", "nokey", "-i"])
+        .arg(asset)
+        .args(["-an", "-vf", &filter, "-frames:v", "1", "-q:v", "5"])
+        .arg(&tmp)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| StudioError::io("Failed to run ffmpeg", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&tmp);
+        return Err(StudioError::Other(format!(
+            "ffmpeg couldn't make thumbnails: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    fs::rename(&tmp, &sheet).map_err(|e| StudioError::io_at("Failed to write", &sheet, e))?;
+
+    let name = sheet
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+    Ok(SpriteSheet {
+        file: dir
+            .join(CACHE_DIR)
+            .join(name)
+            .to_string_lossy()
+            .replace('\\', "/"),
+        thumb_width: THUMB_WIDTH,
+        thumb_height: THUMB_HEIGHT,
+        columns,
+        rows,
+        count,
+        interval,
+    })
+}
+
+fn analyze(file: &str, refresh: bool) -> Result<MediaAnalysis, StudioError> {
+    let workspace = resolve_workspace_dir();
+    let asset = asset_path(&workspace, file)?;
+    let kind = assets::kind_of(&asset);
+    if kind != "audio" && kind != "video" {
+        return Err(StudioError::InvalidInput(format!(
+            "Only audio and video can be analyzed: {}",
+            file
+        )));
+    }
+    if !refresh {
+        if let Some(analysis) = read_cache(&asset) {
+            return Ok(analysis);
+        }
+    }
+
+    let ffmpeg = ffmpeg::locate()?;
+    let dir = cache_dir(&asset);
+    fs::create_dir_all(&dir).map_err(|e| StudioError::io_at("Failed to create", &dir, e))?;
+    // Workspaces created from the current template ignore it already.
+    git::exclude(&workspace, &format!("{}/", CACHE_DIR));
+
+    let duration = ffmpeg.duration(&asset);
+    let waveform = waveform(&ffmpeg, &asset)?;
+    let thumbnails = match (kind, duration) {
+        ("video", Some(duration)) => Some(thumbnails(&ffmpeg, &asset, file, duration)?),
+        _ => None,
+    };
+    let analysis = MediaAnalysis {
+        file: file.to_string(),
+        kind: kind.to_string(),
+        duration,
+        waveform,
+        thumbnails,
+    };
+    write_cache(&asset, &analysis)?;
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "[media] Analyzed {} (waveform: {}, thumbnails: {})",
+            file,
+            analysis.waveform.is_some(),
+            analysis.thumbnails.as_ref().map_or(0, |t| t.count)
+        ),
+    );
+    Ok(analysis)
+}
+
+/// The waveform and thumbnails of an asset, from the cache when it's
+/// current. `refresh` makes them again regardless.
+#[tauri::command]
+pub async fn analyze_media(
+    file: String,
+    refresh: Option<bool>,
+) -> Result<MediaAnalysis, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || analyze(file.trim(), refresh.unwrap_or(false)))
+        .await?
+}
@@ -0,0 +1,272 @@
+//! Notifications for things that finish while nobody's watching.
+//!
+//! Every operation (see `operations`) that ran for at least
+//! `MIN_NOTIFY_DURATION` and wasn't cancelled shows a system notification
+//! when it ends while the window is in the background: npm installs,
+//! renders, exports, transcodes and uploads. `desktopNotifications: false`
+//! in config.json turns them off.
+//!
+//! On a shared workstation a long render can finish, or setup fail, with no
+//! one at the screen. Each of `webhooks` in config.json gets a POST when one
+//! of the events it lists happens (all of `EVENTS` when it lists none):
+//!
+//! ```json
+//! "webhooks": [
+//!   { "url": "https://hooks.slack.com/services/...", "events": ["render-failed"] },
+//!   { "url": "https://example.com/hooks/studio" }
+//! ]
+//! ```
+//!
+//! Slack and Discord webhooks get a message in the shape they expect, going
+//! by the URL. Anything else gets the whole notification as JSON: `event`,
+//! `message`, `host`, `timestamp` and `details`. Sending happens in the
+//! background and is retried on network errors and 5xx responses (see
+//! `retry`); a webhook that still fails is only logged. `test_webhook`
+//! sends a test notification to a URL and reports how it went.
+//!
+//! Webhook URLs carry their credentials, so only their host is logged.
+
+use crate::error::StudioError;
+use crate::{read_config_file, retry};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+pub const EVENTS: &[&str] = &["render-complete", "render-failed", "setup-error"];
+const TIMEOUT: Duration = Duration::from_secs(15);
+/// Shorter operations finish before anyone's looked away.
+const MIN_NOTIFY_DURATION: Duration = Duration::from_secs(10);
+/// Errors are cut to this many characters in a notification.
+const MAX_ERROR_LEN: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub url: String,
+    /// Events from `EVENTS` to send; all of them when unset or empty.
+    #[serde(default)]
+    pub events: Option<Vec<String>>,
+}
+
+impl Webhook {
+    fn wants(&self, event: &str) -> bool {
+        match &self.events {
+            Some(events) if !events.is_empty() => events.iter().any(|e| e == event),
+            _ => true,
+        }
+    }
+
+    /// What's wrong with it, for `set_config`.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !valid_url(&self.url) {
+            problems.push(format!(
+                "webhook URL must be http(s): {}",
+                host_of(&self.url)
+            ));
+        }
+        for event in self.events.iter().flatten() {
+            if !EVENTS.contains(&event.as_str()) {
+                problems.push(format!(
+                    "Unknown webhook event \"{}\"; expected one of {}",
+                    event,
+                    EVENTS.join(", ")
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    event: String,
+    message: String,
+    /// The machine it happened on.
+    host: String,
+    timestamp: String,
+    details: serde_json::Value,
+}
+
+fn valid_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some())
+}
+
+/// The URL's host, safe to log.
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "(invalid URL)".to_string())
+}
+
+/// The body to POST to `url`.
+fn payload(url: &str, notification: &Notification) -> serde_json::Value {
+    let text = format!("{} ({})", notification.message, notification.host);
+    let host = host_of(url);
+    if host == "hooks.slack.com" {
+        serde_json::json!({ "text": text })
+    } else if (host == "discord.com" || host.ends_with(".discord.com") || host == "discordapp.com")
+        && url.contains("/api/webhooks/")
+    {
+        serde_json::json!({ "content": text, "username": "Langston Studio" })
+    } else {
+        serde_json::to_value(notification).unwrap_or_default()
+    }
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<(), StudioError> {
+    let response = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| StudioError::Network(e.without_url().to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let detail = response.text().await.unwrap_or_default();
+    let detail: String = detail.trim().chars().take(200).collect();
+    let message = format!("{} answered HTTP {}: {}", host_of(url), status, detail);
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        Err(StudioError::Network(message))
+    } else {
+        Err(StudioError::Other(message))
+    }
+}
+
+async fn send(url: &str, notification: &Notification) -> Result<(), StudioError> {
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| StudioError::Network(e.to_string()))?;
+    let body = payload(url, notification);
+    retry::run(
+        &format!("Webhook to {}", host_of(url)),
+        retry::Policy::current(),
+        StudioError::is_retryable,
+        || post(&client, url, &body),
+    )
+    .await
+}
+
+fn notification(event: &str, message: String, details: serde_json::Value) -> Notification {
+    Notification {
+        event: event.to_string(),
+        message,
+        host: sysinfo::System::host_name().unwrap_or_else(|| "unknown host".to_string()),
+        timestamp: Local::now().to_rfc3339(),
+        details,
+    }
+}
+
+/// Send `event` to the webhooks that want it, in the background.
+pub fn notify(event: &'static str, message: String, details: serde_json::Value) {
+    let webhooks: Vec<Webhook> = read_config_file()
+        .webhooks
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|w| w.wants(event) && valid_url(&w.url))
+        .collect();
+    if webhooks.is_empty() {
+        return;
+    }
+    let notification = notification(event, message, details);
+    tauri::async_runtime::spawn(async move {
+        for webhook in webhooks {
+            let host = host_of(&webhook.url);
+            match send(&webhook.url, &notification).await {
+                Ok(()) => crate::logging::log_line(
+                    "INFO",
+                    &format!("[webhook] Sent {} to {}", event, host),
+                ),
+                Err(e) => crate::logging::log_line(
+                    "WARN",
+                    &format!("[webhook] Couldn't send {} to {}: {}", event, host, e),
+                ),
+            }
+        }
+    });
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
+
+/// Called by `operations` when an operation that wasn't cancelled ends.
+pub fn operation_finished(app: &AppHandle, label: &str, elapsed: Duration, failure: Option<&str>) {
+    if elapsed < MIN_NOTIFY_DURATION || !read_config_file().desktop_notifications.unwrap_or(true) {
+        return;
+    }
+    let focused = app
+        .get_webview_window("main")
+        .is_some_and(|w| w.is_focused().unwrap_or(false));
+    if focused {
+        return;
+    }
+    let (title, body) = match failure {
+        Some(error) => (
+            format!("{} failed", label),
+            error
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .take(MAX_ERROR_LEN)
+                .collect(),
+        ),
+        None => (
+            format!("{} finished", label),
+            format!("Took {}", format_elapsed(elapsed)),
+        ),
+    };
+    if let Err(e) = app.notification().builder().title(&title).body(body).show() {
+        crate::logging::log_line(
+            "WARN",
+            &format!("[notifications] Couldn't show \"{}\": {}", title, e),
+        );
+    }
+}
+
+/// Send a test notification to `url` and wait for the answer.
+#[tauri::command]
+pub async fn test_webhook(url: String) -> Result<(), StudioError> {
+    let url = url.trim();
+    if !valid_url(url) {
+        return Err(StudioError::InvalidInput(
+            "Webhook URL must be an http(s) URL".to_string(),
+        ));
+    }
+    let notification = notification(
+        "test",
+        "Test notification from Langston Studio".to_string(),
+        serde_json::json!({}),
+    );
+    let result = send(url, &notification).await;
+    crate::logging::log_line(
+        if result.is_ok() { "INFO" } else { "WARN" },
+        &format!(
+            "[webhook] Test to {}: {}",
+            host_of(url),
+            match &result {
+                Ok(()) => "sent".to_string(),
+                Err(e) => e.to_string(),
+            }
+        ),
+    );
+    result
+}
@@ -0,0 +1,330 @@
+//! `npm install` with live progress and cancellation.
+//!
+//! A first install takes minutes, and a single "Installing dependencies"
+//! status made users think setup had hung. npm's output is now streamed line
+//! by line into the log and the `process-log` event (service "npm"). Running
+//! it at `--loglevel=http` makes npm print a line per package fetched; those
+//! are counted against the number of packages in package-lock.json to move
+//! the setup progress bar from 50% towards 90%.
+//!
+//! `cancel_npm_install`, or cancelling its entry in `operations`, kills the
+//! install's process tree. The half-written
+//! node_modules is removed so the next launch installs from scratch instead
+//! of starting servers against a broken tree.
+//!
+//! Release builds ship an npm cache of the template's dependencies as the
+//! `npm-cache` resource (see scripts/build-npm-cache.sh). When the registry
+//! can't be reached, or an online install fails, the dependencies are
+//! installed from it with `npm ci --offline`. The cache is copied to the app
+//! data dir first because npm writes to its cache and the app bundle must
+//! stay untouched.
+
+use crate::error::StudioError;
+use crate::operations::Operation;
+use crate::{
+    copy, emit_status, get_config_dir, has_nvm, node_shell_command, nvm_command, platform, runtime,
+};
+use std::io::{BufRead, BufReader, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+const INSTALL_SCRIPT: &str = "npm install --no-progress --no-audit --no-fund --loglevel=http";
+const OFFLINE_INSTALL_SCRIPT: &str =
+    "npm ci --offline --no-progress --no-audit --no-fund --loglevel=http";
+const REGISTRY_HOST: &str = "registry.npmjs.org:443";
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Records which app version the copied npm cache came from.
+const CACHE_VERSION_FILE: &str = ".bundled-version";
+/// Setup progress while installing runs from `PROGRESS_START` to `PROGRESS_END`.
+const PROGRESS_START: u8 = 50;
+const PROGRESS_END: u8 = 90;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The install in progress, so it can be cancelled.
+static RUNNING: Mutex<Option<Child>> = Mutex::new(None);
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+const CANCELLED_ERROR: &str = "npm install was cancelled";
+
+/// Number of packages package-lock.json lists, if there is one.
+fn locked_package_count(workspace: &Path) -> Option<usize> {
+    let contents = std::fs::read_to_string(workspace.join("package-lock.json")).ok()?;
+    let lock: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    let packages = lock.get("packages")?.as_object()?;
+    // The "" entry is the workspace itself.
+    Some(packages.keys().filter(|k| !k.is_empty()).count())
+}
+
+/// Whether an npm output line reports a package download.
+fn is_fetch_line(line: &str) -> bool {
+    line.contains("http fetch GET 200")
+}
+
+struct Progress {
+    app: AppHandle,
+    operation: Operation,
+    total: Option<usize>,
+    fetched: AtomicUsize,
+    last_percent: AtomicUsize,
+}
+
+impl Progress {
+    fn fetched_one(&self) {
+        let fetched = self.fetched.fetch_add(1, Ordering::Relaxed) + 1;
+        let Some(total) = self.total.filter(|&t| t > 0) else {
+            return;
+        };
+        let fetched = fetched.min(total);
+        let span = usize::from(PROGRESS_END - PROGRESS_START);
+        let percent = usize::from(PROGRESS_START) + fetched * span / total;
+        if self.last_percent.swap(percent, Ordering::Relaxed) != percent {
+            self.operation.progress(
+                Some(fetched as f64 / total as f64),
+                Some("downloading packages"),
+            );
+            emit_status(
+                &self.app,
+                &format!(
+                    "Installing dependencies ({}/{} packages)...",
+                    fetched, total
+                ),
+                percent as u8,
+            );
+        }
+    }
+}
+
+fn read_output<R: Read + Send + 'static>(
+    pipe: R,
+    stream: &'static str,
+    progress: Arc<Progress>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            if is_fetch_line(line) {
+                progress.fetched_one();
+                // One line per package is too much for the log at info.
+                crate::logging::log_line("DEBUG", &format!("[npm] {}", line));
+            } else {
+                crate::logging::log_line("INFO", &format!("[npm] {}", line));
+            }
+            let _ = progress.app.emit(
+                "process-log",
+                serde_json::json!({ "service": "npm", "stream": stream, "line": line }),
+            );
+        }
+    })
+}
+
+/// Whether the npm registry accepts connections.
+fn registry_reachable() -> bool {
+    let Ok(addrs) = REGISTRY_HOST.to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, REGISTRY_TIMEOUT).is_ok())
+}
+
+fn quote(path: &Path) -> String {
+    format!("\"{}\"", path.display())
+}
+
+/// A writable copy of the bundled npm cache, if the app ships one.
+fn offline_cache(app: &AppHandle) -> Option<PathBuf> {
+    let bundled = app.path().resource_dir().ok()?.join("npm-cache");
+    if !bundled.join("_cacache").is_dir() {
+        return None;
+    }
+    let cache = get_config_dir().join("npm-cache");
+    let version = app.package_info().version.to_string();
+    let copied = std::fs::read_to_string(cache.join(CACHE_VERSION_FILE)).ok();
+    if copied.as_deref() != Some(version.as_str()) {
+        crate::logging::log_line("INFO", "Copying bundled npm cache for offline install");
+        let _ = std::fs::remove_dir_all(&cache);
+        if let Err(e) = copy::copy_dir(&bundled, &cache, copy::Mode::Copy, |_| {}) {
+            crate::logging::log_line("WARN", &format!("Failed to copy bundled npm cache: {}", e));
+            return None;
+        }
+        let _ = std::fs::write(cache.join(CACHE_VERSION_FILE), &version);
+    }
+    Some(cache)
+}
+
+fn install_error(e: String) -> StudioError {
+    if e == CANCELLED_ERROR {
+        StudioError::Cancelled("npm install".to_string())
+    } else {
+        StudioError::NpmInstall(e)
+    }
+}
+
+/// Install the workspace's dependencies: online when the registry is
+/// reachable, otherwise (or if that fails) from the bundled cache.
+pub fn install(app: &AppHandle, workspace: &Path, path_env: &str) -> Result<(), StudioError> {
+    let online = registry_reachable();
+    if online {
+        match run(app, workspace, path_env, INSTALL_SCRIPT) {
+            Err(e) if e != CANCELLED_ERROR => {
+                crate::logging::log_line("WARN", "Online install failed, trying the bundled cache");
+                if let Some(cache) = offline_cache(app) {
+                    return install_offline(app, workspace, path_env, &cache)
+                        .map_err(|_| install_error(e));
+                }
+                Err(install_error(e))
+            }
+            result => result.map_err(install_error),
+        }
+    } else {
+        crate::logging::log_line("WARN", "npm registry unreachable, installing offline");
+        let Some(cache) = offline_cache(app) else {
+            return Err(StudioError::Network(
+                "Can't reach the npm registry and this build has no bundled dependencies."
+                    .to_string(),
+            ));
+        };
+        install_offline(app, workspace, path_env, &cache).map_err(install_error)
+    }
+}
+
+fn install_offline(
+    app: &AppHandle,
+    workspace: &Path,
+    path_env: &str,
+    cache: &Path,
+) -> Result<(), String> {
+    emit_status(app, "Installing dependencies offline...", PROGRESS_START);
+    let script = format!("{} --cache {}", OFFLINE_INSTALL_SCRIPT, quote(cache));
+    run(app, workspace, path_env, &script)
+}
+
+/// Run an npm install `script` in `workspace`, streaming its output and
+/// progress.
+fn run(app: &AppHandle, workspace: &Path, path_env: &str, script: &str) -> Result<(), String> {
+    // The managed runtime, once installed, takes precedence over nvm.
+    let managed_node = runtime::node_bin_dir().is_some();
+    let use_nvm = has_nvm() && !managed_node;
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Running {} (nvm: {}, managed node: {})...",
+            script, use_nvm, managed_node
+        ),
+    );
+
+    // Use the user's login shell to inherit their full PATH (Homebrew,
+    // fnm, volta, etc.) — prevents ENOENT when npm isn't on system PATH.
+    let mut cmd = if use_nvm {
+        nvm_command(script, workspace, path_env)
+    } else {
+        node_shell_command(script)
+    };
+    platform::new_process_group(&mut cmd);
+    let mut child = cmd
+        .current_dir(workspace)
+        .env("npm_config_progress", "false")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run npm install: {}", e))?;
+
+    let operation =
+        Operation::start(app, "npm-install", "Installing dependencies").on_cancel(|| {
+            cancel_npm_install();
+        });
+    let progress = Arc::new(Progress {
+        app: app.clone(),
+        operation,
+        total: locked_package_count(workspace),
+        fetched: AtomicUsize::new(0),
+        last_percent: AtomicUsize::new(usize::from(PROGRESS_START)),
+    });
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(read_output(stdout, "stdout", progress.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        // npm writes its http and notice lines to stderr.
+        readers.push(read_output(stderr, "stderr", progress.clone()));
+    }
+
+    CANCELLED.store(false, Ordering::SeqCst);
+    *RUNNING.lock().map_err(|e| e.to_string())? = Some(child);
+    // Cancelled before there was a child to kill.
+    if progress.operation.is_cancelled() {
+        cancel_npm_install();
+    }
+
+    let status = loop {
+        let mut running = RUNNING.lock().map_err(|e| e.to_string())?;
+        let Some(child) = running.as_mut() else {
+            break None;
+        };
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                running.take();
+                break Some(status);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                running.take();
+                return Err(format!("Failed to wait for npm install: {}", e));
+            }
+        }
+        drop(running);
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    if CANCELLED.swap(false, Ordering::SeqCst) {
+        progress.operation.cancel();
+        let _ = std::fs::remove_dir_all(workspace.join("node_modules"));
+        crate::logging::log_line("WARN", CANCELLED_ERROR);
+        return Err(CANCELLED_ERROR.to_string());
+    }
+    match status {
+        Some(status) if status.success() => {
+            crate::logging::log_line(
+                "INFO",
+                &format!(
+                    "npm install finished ({} packages downloaded)",
+                    progress.fetched.load(Ordering::Relaxed)
+                ),
+            );
+            Ok(())
+        }
+        _ => {
+            let err = "npm install failed".to_string();
+            crate::logging::log_line("ERROR", &err);
+            progress.operation.fail(&err);
+            Err(err)
+        }
+    }
+}
+
+/// Abort the running `npm install`, if any. Returns whether one was running.
+#[tauri::command]
+pub fn cancel_npm_install() -> bool {
+    let Ok(mut running) = RUNNING.lock() else {
+        return false;
+    };
+    let Some(mut child) = running.take() else {
+        return false;
+    };
+    CANCELLED.store(true, Ordering::SeqCst);
+    crate::logging::log_line("INFO", "Cancelling npm install");
+    platform::kill_process_tree(&mut child);
+    let _ = child.wait();
+    true
+}
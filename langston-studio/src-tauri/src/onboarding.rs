@@ -0,0 +1,188 @@
+//! First-run onboarding.
+//!
+//! A new install used to go straight into setup, which then failed on a
+//! missing API key or Node.js with an error the user had no way to act on
+//! from the app. Setup now waits in `wait_until_complete` until onboarding
+//! is done. The UI is told with an `onboarding-required` event, reads what's
+//! missing with `get_onboarding_state`, and finishes each of `STEPS` with
+//! `complete_onboarding_step`:
+//!
+//! - "keys": at least one model provider is set up (see `providers`), or
+//!   a local model server is running (see `local_models`).
+//! - "dependencies": node, npm and git pass the doctor's checks; a missing
+//!   Node.js is provisioned first (see `runtime`).
+//! - "workspace": the user has confirmed where the workspace goes.
+//!
+//! Completed steps are saved in config.json. Installs that already have a
+//! key and a workspace from before onboarding existed skip it.
+
+use crate::error::StudioError;
+use crate::{
+    doctor, frontend, load_config, local_models, providers, read_config_file,
+    resolve_workspace_dir, runtime, update_config_file, AppConfig,
+};
+use serde::Serialize;
+use std::sync::{Condvar, Mutex};
+use tauri::AppHandle;
+
+/// Onboarding steps, in the order the UI shows them.
+pub const STEPS: &[&str] = &["keys", "dependencies", "workspace"];
+
+/// Whether onboarding has finished this run; setup waits on it.
+static DONE: Mutex<bool> = Mutex::new(false);
+static DONE_CHANGED: Condvar = Condvar::new();
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardingState {
+    pub complete: bool,
+    pub completed_steps: Vec<String>,
+    pub anthropic_key: bool,
+    pub openai_key: bool,
+    pub keys_configured: bool,
+    pub providers: Vec<providers::ProviderStatus>,
+    pub node_found: bool,
+    pub workspace_dir: String,
+    pub workspace_exists: bool,
+}
+
+/// Whether OpenCode has a model to use.
+fn has_model(config: &AppConfig) -> bool {
+    providers::any_configured(config) || local_models::detected().is_some()
+}
+
+fn completed_steps() -> Vec<String> {
+    read_config_file()
+        .onboarding_completed_steps
+        .unwrap_or_default()
+}
+
+/// Whether onboarding is done, either this run, in an earlier one, or
+/// because the install predates it.
+fn is_complete() -> bool {
+    if *DONE.lock().unwrap_or_else(|e| e.into_inner()) {
+        return true;
+    }
+    let config = read_config_file();
+    match config.onboarding_completed_steps {
+        Some(steps) => STEPS.iter().all(|step| steps.iter().any(|s| s == step)),
+        None => {
+            let config = load_config();
+            has_model(&config) && resolve_workspace_dir().join("package.json").exists()
+        }
+    }
+}
+
+pub fn state() -> OnboardingState {
+    let config = load_config();
+    let workspace = resolve_workspace_dir();
+    let node_found = doctor::run()
+        .checks
+        .iter()
+        .any(|c| c.name == "node" && !c.blocking);
+    OnboardingState {
+        complete: is_complete(),
+        completed_steps: completed_steps(),
+        anthropic_key: config.anthropic_api_key.is_some(),
+        openai_key: config.openai_api_key.is_some(),
+        keys_configured: has_model(&config),
+        providers: providers::statuses(&config),
+        node_found,
+        workspace_dir: workspace.to_string_lossy().to_string(),
+        workspace_exists: workspace.join("package.json").exists(),
+    }
+}
+
+/// Block until onboarding is complete. Returns right away when it already
+/// is; otherwise sends `onboarding-required` first.
+pub fn wait_until_complete(app: &AppHandle) {
+    if is_complete() {
+        return;
+    }
+    crate::logging::log_line("INFO", "[onboarding] Waiting for onboarding to finish");
+    frontend::emit(app, "onboarding-required", state());
+    let mut done = DONE.lock().unwrap_or_else(|e| e.into_inner());
+    while !*done {
+        done = DONE_CHANGED.wait(done).unwrap_or_else(|e| e.into_inner());
+    }
+    crate::logging::log_line("INFO", "[onboarding] Onboarding complete, continuing setup");
+}
+
+/// Check that `step` can be marked done, doing any work it needs.
+fn run_step(app: &AppHandle, step: &str) -> Result<(), StudioError> {
+    match step {
+        "keys" => {
+            let config = load_config();
+            if !has_model(&config) {
+                return Err(StudioError::InvalidInput(
+                    "Set up a model provider or start a local model server first".to_string(),
+                ));
+            }
+            Ok(())
+        }
+        "dependencies" => {
+            let mut report = doctor::preflight(app);
+            if !report.ok && report.needs_node() {
+                runtime::provision(app)?;
+                report = doctor::preflight(app);
+            }
+            if report.ok {
+                Ok(())
+            } else {
+                Err(report.error())
+            }
+        }
+        "workspace" => {
+            let workspace = resolve_workspace_dir();
+            if workspace.exists() && !workspace.is_dir() {
+                return Err(StudioError::InvalidInput(format!(
+                    "{} is a file, not a folder",
+                    workspace.display()
+                )));
+            }
+            Ok(())
+        }
+        other => Err(StudioError::InvalidInput(format!(
+            "Unknown onboarding step: {} (expected one of {})",
+            other,
+            STEPS.join(", ")
+        ))),
+    }
+}
+
+fn complete_step(app: &AppHandle, step: &str) -> Result<OnboardingState, StudioError> {
+    run_step(app, step)?;
+
+    let mut steps = completed_steps();
+    if !steps.iter().any(|s| s == step) {
+        steps.push(step.to_string());
+    }
+    let value = serde_json::to_value(&steps).map_err(|e| e.to_string())?;
+    update_config_file(|obj| {
+        obj.insert("onboardingCompletedSteps".to_string(), value);
+    })?;
+    crate::logging::log_line("INFO", &format!("[onboarding] Completed step: {}", step));
+
+    if STEPS.iter().all(|step| steps.iter().any(|s| s == step)) {
+        *DONE.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        DONE_CHANGED.notify_all();
+        frontend::emit(app, "onboarding-complete", ());
+    }
+    Ok(state())
+}
+
+/// What onboarding still needs: keys, Node.js and the workspace.
+#[tauri::command]
+pub async fn get_onboarding_state() -> Result<OnboardingState, StudioError> {
+    Ok(tauri::async_runtime::spawn_blocking(state).await?)
+}
+
+/// Finish one of `STEPS`. Fails with what's still missing; once every step
+/// is done, setup continues.
+#[tauri::command]
+pub async fn complete_onboarding_step(
+    app: AppHandle,
+    step: String,
+) -> Result<OnboardingState, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || complete_step(&app, &step)).await?
+}
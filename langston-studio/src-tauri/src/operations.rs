@@ -0,0 +1,230 @@
+//! Long-running work the user can see and stop.
+//!
+//! npm installs, renders and exports each had their own way of being
+//! cancelled, or none. They now register an `Operation` in `AppState` while
+//! they run: an id, what it is, its progress and a `CancellationToken`.
+//! `list_operations` reports everything in flight and `cancel_operation`
+//! stops any of it by id: the token is cancelled for work that checks it
+//! between steps, and the operation's `on_cancel` hook runs for work that
+//! has to be interrupted, such as killing a child process tree.
+//!
+//! An operation is removed when its guard is dropped, however the work
+//! ends. The list is sent as an `operations-changed` event whenever an
+//! operation starts, is cancelled or finishes; progress is only polled.
+//! Work that fails says so with `fail` first, and work cancelled other than
+//! through `cancel_operation` with `cancel`, so that the system notification
+//! for a long operation that wasn't cancelled (see `notifications`) can say
+//! how it went.
+
+use crate::error::StudioError;
+use crate::{notifications, AppState};
+use chrono::Local;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+type CancelHook = Arc<dyn Fn() + Send + Sync>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationView {
+    pub id: u64,
+    /// "npm-install", "render" or "export".
+    pub kind: &'static str,
+    pub label: String,
+    pub started_at: String,
+    /// From 0.0 to 1.0, when it's known.
+    pub progress: Option<f64>,
+    pub stage: Option<String>,
+    /// Cancelled but not finished yet.
+    pub cancelling: bool,
+}
+
+struct Entry {
+    view: OperationView,
+    token: CancellationToken,
+    on_cancel: Option<CancelHook>,
+    started: Instant,
+    /// Why it failed, if it did.
+    failure: Option<String>,
+}
+
+/// The operations in flight, kept in `AppState`.
+#[derive(Default)]
+pub struct Registry {
+    next_id: u64,
+    running: Vec<Entry>,
+}
+
+impl Registry {
+    fn views(&self) -> Vec<OperationView> {
+        self.running
+            .iter()
+            .map(|entry| entry.view.clone())
+            .collect()
+    }
+}
+
+fn with_registry<T>(app: &AppHandle, f: impl FnOnce(&mut Registry) -> T) -> Option<T> {
+    let state = app.try_state::<Mutex<AppState>>()?;
+    let mut guard = state.lock().ok()?;
+    Some(f(&mut guard.operations))
+}
+
+fn emit_changed(app: &AppHandle, operations: Vec<OperationView>) {
+    let _ = app.emit("operations-changed", operations);
+}
+
+/// A registered operation. Dropping it removes the operation.
+pub struct Operation {
+    app: AppHandle,
+    id: u64,
+    token: CancellationToken,
+}
+
+impl Operation {
+    pub fn start(app: &AppHandle, kind: &'static str, label: impl Into<String>) -> Operation {
+        let token = CancellationToken::new();
+        let label = label.into();
+        let registered = with_registry(app, |registry| {
+            registry.next_id += 1;
+            registry.running.push(Entry {
+                view: OperationView {
+                    id: registry.next_id,
+                    kind,
+                    label,
+                    started_at: Local::now().to_rfc3339(),
+                    progress: None,
+                    stage: None,
+                    cancelling: false,
+                },
+                token: token.clone(),
+                on_cancel: None,
+                started: Instant::now(),
+                failure: None,
+            });
+            (registry.next_id, registry.views())
+        });
+        let id = match registered {
+            Some((id, views)) => {
+                emit_changed(app, views);
+                id
+            }
+            None => 0,
+        };
+        Operation {
+            app: app.clone(),
+            id,
+            token,
+        }
+    }
+
+    /// Run `hook` when the operation is cancelled, e.g. to kill its process.
+    pub fn on_cancel(self, hook: impl Fn() + Send + Sync + 'static) -> Operation {
+        let hook: CancelHook = Arc::new(hook);
+        with_registry(&self.app, |registry| {
+            if let Some(entry) = registry.running.iter_mut().find(|e| e.view.id == self.id) {
+                entry.on_cancel = Some(hook);
+            }
+        });
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Mark it cancelled, for work stopped through its own command rather
+    /// than `cancel_operation`.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Record that the work failed, and why.
+    pub fn fail(&self, error: impl std::fmt::Display) {
+        with_registry(&self.app, |registry| {
+            if let Some(entry) = registry.running.iter_mut().find(|e| e.view.id == self.id) {
+                entry.failure = Some(error.to_string());
+            }
+        });
+    }
+
+    /// Record how far along it is, for `list_operations`.
+    pub fn progress(&self, progress: Option<f64>, stage: Option<&str>) {
+        with_registry(&self.app, |registry| {
+            if let Some(entry) = registry.running.iter_mut().find(|e| e.view.id == self.id) {
+                entry.view.progress = progress.map(|p| p.clamp(0.0, 1.0));
+                entry.view.stage = stage.map(str::to_string);
+            }
+        });
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        let finished = with_registry(&self.app, |registry| {
+            let entry = registry
+                .running
+                .iter()
+                .position(|entry| entry.view.id == self.id)
+                .map(|index| registry.running.remove(index));
+            (entry, registry.views())
+        });
+        let Some((entry, views)) = finished else {
+            return;
+        };
+        emit_changed(&self.app, views);
+        if let Some(entry) = entry.filter(|_| !self.token.is_cancelled()) {
+            notifications::operation_finished(
+                &self.app,
+                &entry.view.label,
+                entry.started.elapsed(),
+                entry.failure.as_deref(),
+            );
+        }
+    }
+}
+
+/// Operations in flight, oldest first.
+#[tauri::command]
+pub fn list_operations(app: AppHandle) -> Vec<OperationView> {
+    with_registry(&app, |registry| registry.views()).unwrap_or_default()
+}
+
+/// Stop an operation. It's reported as cancelling until the work has wound
+/// down.
+#[tauri::command]
+pub fn cancel_operation(app: AppHandle, id: u64) -> Result<(), StudioError> {
+    let cancelled = with_registry(&app, |registry| {
+        let entry = registry.running.iter_mut().find(|e| e.view.id == id)?;
+        if entry.view.cancelling {
+            return Some((entry.view.clone(), None, None));
+        }
+        entry.view.cancelling = true;
+        entry.token.cancel();
+        Some((
+            entry.view.clone(),
+            entry.on_cancel.clone(),
+            Some(registry.views()),
+        ))
+    })
+    .flatten();
+    let Some((view, hook, views)) = cancelled else {
+        return Err(StudioError::NotFound(format!("Operation {}", id)));
+    };
+    let Some(views) = views else {
+        return Ok(());
+    };
+    crate::logging::log_line(
+        "INFO",
+        &format!("[operations] Cancelling {} ({})", view.label, view.kind),
+    );
+    emit_changed(&app, views);
+    // Outside the lock: hooks may finish the operation themselves.
+    if let Some(hook) = hook {
+        hook();
+    }
+    Ok(())
+}
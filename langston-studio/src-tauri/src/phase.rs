@@ -0,0 +1,97 @@
+//! Where the app is in its lifecycle.
+//!
+//! The UI used to piece this together from whichever of `setup-status`,
+//! `setup-complete`, `process-crashed` and friends it happened to see.
+//! The phase now lives in `AppState` and moves
+//! Initializing → InstallingDeps → StartingServers → Ready, with Error when
+//! setup fails and Recovering while servers are restarted. Every change is
+//! sent as a `state-changed` event, and `get_app_state` returns the phase
+//! together with the child PIDs, ports, workspace and last error.
+
+use crate::{frontend, ports, resolve_workspace_dir, telemetry, tray, AppState};
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Phase {
+    #[default]
+    Initializing,
+    InstallingDeps,
+    StartingServers,
+    Ready,
+    Error,
+    Recovering,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStateView {
+    pub phase: Phase,
+    pub opencode_pid: Option<u32>,
+    pub remotion_pid: Option<u32>,
+    pub ports: ports::ServicePorts,
+    pub workspace_dir: String,
+    pub last_error: Option<String>,
+}
+
+pub fn view(app: &AppHandle) -> AppStateView {
+    let mut view = AppStateView {
+        phase: Phase::default(),
+        opencode_pid: None,
+        remotion_pid: None,
+        ports: ports::ServicePorts::default(),
+        workspace_dir: resolve_workspace_dir().to_string_lossy().to_string(),
+        last_error: None,
+    };
+    if let Some(state) = app.try_state::<Mutex<AppState>>() {
+        if let Ok(guard) = state.lock() {
+            view.phase = guard.phase;
+            view.opencode_pid = guard.opencode.as_ref().map(|child| child.id());
+            view.remotion_pid = guard.remotion.as_ref().map(|child| child.id());
+            view.ports = guard.ports;
+            view.last_error = guard.last_error.clone();
+        }
+    }
+    view
+}
+
+fn transition(app: &AppHandle, phase: Phase, error: Option<&str>) {
+    let Some(state) = app.try_state::<Mutex<AppState>>() else {
+        return;
+    };
+    let previous = {
+        let Ok(mut guard) = state.lock() else {
+            return;
+        };
+        if let Some(error) = error {
+            guard.last_error = Some(error.to_string());
+        }
+        std::mem::replace(&mut guard.phase, phase)
+    };
+    if previous == phase && error.is_none() {
+        return;
+    }
+    let msg = format!("{:?} -> {:?}", previous, phase);
+    telemetry::breadcrumb("state", &msg);
+    crate::logging::log_line("INFO", &format!("[state] {}", msg));
+    frontend::emit(app, "state-changed", view(app));
+    tray::refresh(app);
+}
+
+/// Move to `phase`. Does nothing if the app is already there.
+pub fn set(app: &AppHandle, phase: Phase) {
+    transition(app, phase, None);
+}
+
+/// Move to `Phase::Error`, recording `error` as the last error.
+pub fn fail(app: &AppHandle, error: &str) {
+    transition(app, Phase::Error, Some(error));
+}
+
+/// The current phase, child PIDs, ports, workspace and last error.
+#[tauri::command]
+pub fn get_app_state(app: AppHandle) -> AppStateView {
+    view(&app)
+}
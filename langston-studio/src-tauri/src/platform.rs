@@ -0,0 +1,352 @@
+//! Platform-specific paths and process helpers.
+//!
+//! The app started out macOS-only. Everything that differs between macOS,
+//! Windows and Linux (where config and logs live, how to find what's holding
+//! a port, how to open a folder, which shell to run scripts through) is kept
+//! here so the rest of the code doesn't need `cfg` attributes.
+
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+const APP_DIR_NAME: &str = "Langston Studio";
+
+/// Where config.json and other app data live.
+/// macOS: ~/Library/Application Support/Langston Studio
+/// Windows: %APPDATA%\Langston Studio
+/// Linux: ~/.config/Langston Studio
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .expect("Could not find config directory")
+        .join(APP_DIR_NAME)
+}
+
+/// Where log files are written.
+/// macOS: ~/Library/Logs/Langston Studio
+/// Windows: %LOCALAPPDATA%\Langston Studio\Logs
+/// Linux: ~/.local/share/Langston Studio/logs
+pub fn logs_dir() -> PathBuf {
+    if cfg!(target_os = "macos") {
+        let home = dirs::home_dir().expect("Could not find home directory");
+        home.join("Library/Logs").join(APP_DIR_NAME)
+    } else if cfg!(windows) {
+        dirs::data_local_dir()
+            .expect("Could not find local data directory")
+            .join(APP_DIR_NAME)
+            .join("Logs")
+    } else {
+        dirs::data_local_dir()
+            .expect("Could not find local data directory")
+            .join(APP_DIR_NAME)
+            .join("logs")
+    }
+}
+
+/// Well-known binary locations to search in addition to the inherited PATH.
+/// GUI apps on macOS get a minimal PATH, so there we only use this list.
+pub fn search_path(home: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![
+        home.join(".opencode/bin"),
+        home.join(".local/bin"),
+        home.join(".bun/bin"),
+    ];
+
+    if cfg!(windows) {
+        if let Some(appdata) = dirs::data_dir() {
+            paths.push(appdata.join("npm"));
+        }
+    } else {
+        paths.extend(
+            [
+                "/opt/homebrew/bin",
+                "/usr/local/bin",
+                "/usr/bin",
+                "/bin",
+                "/usr/sbin",
+                "/sbin",
+            ]
+            .iter()
+            .map(PathBuf::from),
+        );
+    }
+
+    if !cfg!(target_os = "macos") {
+        if let Some(inherited) = std::env::var_os("PATH") {
+            for p in std::env::split_paths(&inherited) {
+                if !paths.contains(&p) {
+                    paths.push(p);
+                }
+            }
+        }
+    }
+
+    paths
+}
+
+/// Look up `binary` on `path_env` without shelling out to `which`.
+pub fn which(binary: &str, path_env: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &["exe", "cmd", "bat"]
+    } else {
+        &[""]
+    };
+
+    std::env::split_paths(path_env).find_map(|dir| {
+        extensions.iter().find_map(|ext| {
+            let candidate = if ext.is_empty() {
+                dir.join(binary)
+            } else {
+                dir.join(format!("{}.{}", binary, ext))
+            };
+            candidate.is_file().then_some(candidate)
+        })
+    })
+}
+
+/// A command that runs `script` through the user's login shell, so it
+/// inherits their full environment (nvm, fnm, volta, Homebrew, ...).
+/// On Windows there is no login shell; `cmd /C` uses the user's PATH as-is.
+pub fn login_shell_command(script: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", script]);
+        cmd
+    } else {
+        let user_shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+        let mut cmd = Command::new(user_shell);
+        cmd.args(["-ilc", script]);
+        cmd
+    }
+}
+
+/// Whether something is already listening on `port` on localhost.
+pub fn port_in_use(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_err()
+}
+
+/// PIDs of processes listening on `port`, excluding our own process. They
+/// come from the OS's socket table rather than lsof or netstat, which aren't
+/// on every system.
+pub fn pids_on_port(port: u16) -> Vec<u32> {
+    use netstat2::{AddressFamilyFlags, ProtocolFlags, ProtocolSocketInfo, TcpState};
+
+    let own_pid = std::process::id();
+    let Ok(sockets) = netstat2::get_sockets_info(
+        AddressFamilyFlags::IPV4 | AddressFamilyFlags::IPV6,
+        ProtocolFlags::TCP,
+    ) else {
+        return Vec::new();
+    };
+    let mut pids: Vec<u32> = sockets
+        .into_iter()
+        .filter(|socket| {
+            matches!(
+                &socket.protocol_socket_info,
+                ProtocolSocketInfo::Tcp(tcp)
+                    if tcp.local_port == port && tcp.state == TcpState::Listen
+            )
+        })
+        .flat_map(|socket| socket.associated_pids)
+        .filter(|&pid| pid != own_pid && pid != 0)
+        .collect();
+    pids.sort_unstable();
+    pids.dedup();
+    pids
+}
+
+/// Forcefully kill a process by PID, and everything it spawned.
+pub fn kill_pid(pid: u32) {
+    let extra = descendants(pid);
+    signal_pid(pid, true);
+    for descendant in extra {
+        signal_pid(descendant, true);
+    }
+}
+
+/// Start the command in a new process group so the whole tree it spawns
+/// (npm -> node -> webpack workers) can be signalled at once.
+pub fn new_process_group(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// Every process descended from `pid`, found by walking the process table.
+/// Process groups don't exist on Windows, and on Unix a descendant can leave
+/// the group (setsid), so this backs up the group signals.
+fn descendants(pid: u32) -> Vec<u32> {
+    let mut system = sysinfo::System::new();
+    system.refresh_processes();
+    descendants_in(&system, pid)
+}
+
+/// `descendants` in an already refreshed process table.
+pub(crate) fn descendants_in(system: &sysinfo::System, pid: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (child, process) in system.processes() {
+        if let Some(parent) = process.parent() {
+            children
+                .entry(parent.as_u32())
+                .or_default()
+                .push(child.as_u32());
+        }
+    }
+
+    let mut found = Vec::new();
+    let mut stack = vec![pid];
+    while let Some(next) = stack.pop() {
+        for &child in children.get(&next).into_iter().flatten() {
+            if !found.contains(&child) && child != pid {
+                found.push(child);
+                stack.push(child);
+            }
+        }
+    }
+    found
+}
+
+pub(crate) fn pid_alive(pid: u32) -> bool {
+    let mut system = sysinfo::System::new();
+    system.refresh_process(sysinfo::Pid::from_u32(pid))
+}
+
+/// Ask `pid` to exit, or kill it when `force`. Windows has no SIGTERM, so
+/// there it's killed either way.
+fn signal_pid(pid: u32, force: bool) {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut system = sysinfo::System::new();
+    if !system.refresh_process(pid) {
+        return;
+    }
+    let Some(process) = system.process(pid) else {
+        return;
+    };
+    let signal = if force {
+        sysinfo::Signal::Kill
+    } else {
+        sysinfo::Signal::Term
+    };
+    if process.kill_with(signal).is_none() {
+        process.kill();
+    }
+}
+
+/// Signal the whole tree rooted at `pid`: its process group on Unix, plus
+/// `extra` descendants found beforehand (all of the tree on Windows).
+fn signal_tree(pid: u32, extra: &[u32], force: bool) {
+    #[cfg(unix)]
+    {
+        let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+        // SAFETY: killpg only sends a signal; a group that's gone is ESRCH.
+        unsafe { libc::killpg(pid as libc::pid_t, signal) };
+    }
+    #[cfg(not(unix))]
+    signal_pid(pid, force);
+    for &descendant in extra {
+        if pid_alive(descendant) {
+            signal_pid(descendant, force);
+        }
+    }
+}
+
+/// Whether anything in the tree rooted at `pid` is still running. On Unix the
+/// process group outlives its leader as long as any member is alive.
+fn tree_alive(pid: u32, extra: &[u32]) -> bool {
+    #[cfg(unix)]
+    // SAFETY: signal 0 only checks that the group exists.
+    let group_alive = unsafe { libc::killpg(pid as libc::pid_t, 0) } == 0;
+    #[cfg(not(unix))]
+    let group_alive = pid_alive(pid);
+    group_alive || extra.iter().any(|&p| pid_alive(p))
+}
+
+/// Stop children started with `new_process_group` and everything they
+/// spawned: ask them to exit, wait up to `grace`, then force-kill whatever
+/// is left. Returns the PIDs that had to be force-killed.
+pub fn stop_process_trees(children: &mut [Child], grace: Duration) -> Vec<u32> {
+    // Find the descendants while the roots are still alive; once a root
+    // exits its children are reparented and can't be traced to it.
+    let trees: Vec<Vec<u32>> = children.iter().map(|c| descendants(c.id())).collect();
+    for (child, extra) in children.iter().zip(&trees) {
+        signal_tree(child.id(), extra, false);
+    }
+
+    let deadline = Instant::now() + grace;
+    let mut forced = Vec::new();
+    for (child, extra) in children.iter_mut().zip(&trees) {
+        let pid = child.id();
+        loop {
+            let exited = child.try_wait().map(|s| s.is_some()).unwrap_or(true);
+            if exited && !tree_alive(pid, extra) {
+                break;
+            }
+            if Instant::now() >= deadline {
+                signal_tree(pid, extra, true);
+                let _ = child.kill();
+                forced.push(pid);
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let _ = child.wait();
+    }
+    forced
+}
+
+/// Force-kill `child` and everything it spawned without waiting for it.
+pub fn kill_process_tree(child: &mut Child) {
+    let extra = descendants(child.id());
+    signal_tree(child.id(), &extra, true);
+    let _ = child.kill();
+}
+
+/// Parent of `pid`, if it is still running.
+pub fn parent_pid(pid: u32) -> Option<u32> {
+    let pid = sysinfo::Pid::from_u32(pid);
+    let mut system = sysinfo::System::new();
+    system.refresh_process(pid);
+    system
+        .process(pid)?
+        .parent()
+        .map(|parent| parent.as_u32())
+        .filter(|&ppid| ppid != 0)
+}
+
+/// Reveal a folder in Finder / Explorer / the default file manager.
+pub fn open_path(path: &Path) -> std::io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener).arg(path).spawn().map(|_| ())
+}
+
+/// Show `path` selected in Finder / Explorer. Other file managers can't
+/// select a file, so they open the folder it's in.
+pub fn reveal_path(path: &Path) -> std::io::Result<()> {
+    if cfg!(target_os = "macos") {
+        Command::new("open").arg("-R").arg(path).spawn().map(|_| ())
+    } else if cfg!(windows) {
+        Command::new("explorer")
+            .arg(format!("/select,{}", path.display()))
+            .spawn()
+            .map(|_| ())
+    } else if path.is_dir() {
+        open_path(path)
+    } else {
+        open_path(path.parent().unwrap_or(path))
+    }
+}
@@ -0,0 +1,365 @@
+//! Port ownership for the OpenCode and Remotion servers and the proxy.
+//!
+//! Ports used to be freed by killing whatever was listening on them, which
+//! also took down unrelated dev servers that happened to use the same port.
+//! Now a process on one of our ports is only killed if we own it: it is one
+//! of our children, a descendant of one (npm starts node, which is what
+//! actually listens), or a server we recorded on a previous run that didn't
+//! shut down cleanly. If anything else holds a port we leave it alone and
+//! move to the next free port, then tell the proxy and the frontend.
+//!
+//! The defaults (7500-7503) are only preferences. The frontend learns the
+//! ports actually in use from the `service-ports` event, or by calling
+//! `get_service_ports` if it missed it.
+//!
+//! `get_service_endpoints` goes a step further and returns the URLs the
+//! iframes should load (the proxies) along with whether each server answers
+//! right now; `endpoints-changed` carries the same whenever a port moves,
+//! so the frontend never has to build URLs from port numbers itself.
+
+use crate::error::StudioError;
+use crate::{get_config_dir, platform, proxy, readiness, AppState};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+pub const DEFAULT_REMOTION_PORT: u16 = 7500;
+pub const DEFAULT_OPENCODE_PORT: u16 = 7501;
+/// Port the reverse proxy listens on — the iframe connects here instead of
+/// directly to OpenCode. The proxy forwards to OpenCode with long read
+/// timeouts to prevent WKWebView from killing idle streaming connections.
+pub const DEFAULT_PROXY_PORT: u16 = 7502;
+/// Port the Remotion Studio iframe connects to; proxied to the Remotion dev
+/// server, including its hot-reload WebSocket.
+pub const DEFAULT_REMOTION_PROXY_PORT: u16 = 7503;
+
+/// How far past the preferred port to look for a free one.
+const SEARCH_RANGE: u16 = 100;
+/// How long to wait for a killed server to release its port.
+const RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Ancestor levels walked when checking whether we own a process.
+const MAX_ANCESTRY_DEPTH: usize = 16;
+
+/// The ports each server is currently using.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServicePorts {
+    pub remotion: u16,
+    pub opencode: u16,
+    pub proxy: u16,
+    pub remotion_proxy: u16,
+}
+
+impl Default for ServicePorts {
+    fn default() -> Self {
+        ServicePorts {
+            remotion: DEFAULT_REMOTION_PORT,
+            opencode: DEFAULT_OPENCODE_PORT,
+            proxy: DEFAULT_PROXY_PORT,
+            remotion_proxy: DEFAULT_REMOTION_PROXY_PORT,
+        }
+    }
+}
+
+/// Which port to claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortKind {
+    Remotion,
+    OpenCode,
+    Proxy,
+    RemotionProxy,
+}
+
+impl PortKind {
+    fn preferred(self) -> u16 {
+        match self {
+            PortKind::Remotion => DEFAULT_REMOTION_PORT,
+            PortKind::OpenCode => DEFAULT_OPENCODE_PORT,
+            PortKind::Proxy => DEFAULT_PROXY_PORT,
+            PortKind::RemotionProxy => DEFAULT_REMOTION_PROXY_PORT,
+        }
+    }
+
+    fn slot(self, ports: &mut ServicePorts) -> &mut u16 {
+        match self {
+            PortKind::Remotion => &mut ports.remotion,
+            PortKind::OpenCode => &mut ports.opencode,
+            PortKind::Proxy => &mut ports.proxy,
+            PortKind::RemotionProxy => &mut ports.remotion_proxy,
+        }
+    }
+}
+
+fn pids_file() -> PathBuf {
+    get_config_dir().join("server-pids.json")
+}
+
+/// Server PIDs recorded by a previous run, so leftovers from a crash can
+/// still be cleaned up.
+pub fn load_owned_pids() -> HashSet<u32> {
+    std::fs::read_to_string(pids_file())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_owned_pids(pids: &HashSet<u32>) {
+    if let Ok(json) = serde_json::to_string(pids) {
+        let _ = std::fs::create_dir_all(get_config_dir());
+        let _ = std::fs::write(pids_file(), json);
+    }
+}
+
+/// The ports currently in use.
+pub fn current(app: &AppHandle) -> ServicePorts {
+    app.try_state::<Mutex<AppState>>()
+        .and_then(|state| state.lock().ok().map(|g| g.ports))
+        .unwrap_or_default()
+}
+
+/// Ports the webview should connect to.
+#[tauri::command]
+pub fn get_service_ports(app: AppHandle) -> ServicePorts {
+    current(&app)
+}
+
+/// Where the webview reaches a server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoint {
+    /// The proxy URL the iframe loads.
+    pub url: String,
+    /// The port the server itself listens on, behind the proxy.
+    pub upstream_port: u16,
+    /// Whether the server answered just now.
+    pub healthy: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceEndpoints {
+    pub opencode: Endpoint,
+    pub remotion: Endpoint,
+}
+
+async fn endpoints(ports: ServicePorts) -> ServiceEndpoints {
+    let (opencode, remotion) = tokio::join!(
+        readiness::probe(ports.opencode),
+        readiness::probe(ports.remotion)
+    );
+    ServiceEndpoints {
+        opencode: Endpoint {
+            url: format!("http://localhost:{}", ports.proxy),
+            upstream_port: ports.opencode,
+            healthy: opencode,
+        },
+        remotion: Endpoint {
+            url: format!("http://localhost:{}", ports.remotion_proxy),
+            upstream_port: ports.remotion,
+            healthy: remotion,
+        },
+    }
+}
+
+/// The URLs the iframes should load, and whether each server is up.
+#[tauri::command]
+pub async fn get_service_endpoints(app: AppHandle) -> ServiceEndpoints {
+    endpoints(current(&app)).await
+}
+
+/// Send `endpoints-changed` once the servers have been probed.
+fn announce_endpoints(app: &AppHandle, ports: ServicePorts) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        crate::frontend::emit(&app, "endpoints-changed", endpoints(ports).await);
+    });
+}
+
+/// What we own: our live children (and anything they started) and the
+/// servers recorded earlier, which only count on an exact PID match.
+struct Owned {
+    children: HashSet<u32>,
+    recorded: HashSet<u32>,
+}
+
+impl Owned {
+    fn snapshot(state: &Mutex<AppState>) -> Owned {
+        let Ok(guard) = state.lock() else {
+            return Owned {
+                children: HashSet::new(),
+                recorded: HashSet::new(),
+            };
+        };
+        let children = [guard.opencode.as_ref(), guard.remotion.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|child| child.id())
+            .collect();
+        Owned {
+            children,
+            recorded: guard.owned_pids.clone(),
+        }
+    }
+
+    fn contains(&self, pid: u32) -> bool {
+        if self.recorded.contains(&pid) {
+            return true;
+        }
+        let mut current = pid;
+        for _ in 0..MAX_ANCESTRY_DEPTH {
+            if self.children.contains(&current) {
+                return true;
+            }
+            match platform::parent_pid(current) {
+                Some(parent) if parent != current => current = parent,
+                _ => return false,
+            }
+        }
+        false
+    }
+}
+
+/// Record which processes are listening on our server ports. Call while the
+/// children are still alive: once npm is killed its node child is reparented
+/// and can no longer be traced back to us.
+pub fn remember_listeners(app: &AppHandle) {
+    let Some(state) = app.try_state::<Mutex<AppState>>() else {
+        return;
+    };
+    let owned = Owned::snapshot(&state);
+    let ports = current(app);
+
+    let listeners: HashSet<u32> = [ports.opencode, ports.remotion]
+        .iter()
+        .flat_map(|&port| platform::pids_on_port(port))
+        .filter(|&pid| owned.contains(pid))
+        .collect();
+
+    if let Ok(mut guard) = state.lock() {
+        guard.owned_pids.extend(listeners);
+        save_owned_pids(&guard.owned_pids);
+    };
+}
+
+/// Kill the processes we own that are listening on `port`. Anything else on
+/// the port is left running. Returns whether anything was killed.
+pub fn release(app: &AppHandle, port: u16) -> bool {
+    let Some(state) = app.try_state::<Mutex<AppState>>() else {
+        return false;
+    };
+    let ours = Owned::snapshot(&state);
+    let (owned, foreign): (Vec<u32>, Vec<u32>) = platform::pids_on_port(port)
+        .into_iter()
+        .partition(|&pid| ours.contains(pid));
+
+    for &pid in &owned {
+        crate::logging::log_line(
+            "INFO",
+            &format!("Stopping our server on port {} (PID: {})", port, pid),
+        );
+        platform::kill_pid(pid);
+    }
+    if !foreign.is_empty() {
+        crate::logging::log_line(
+            "INFO",
+            &format!(
+                "Leaving port {} alone: held by another program (PID: {:?})",
+                port, foreign
+            ),
+        );
+    }
+
+    if owned.is_empty() {
+        return false;
+    }
+    if let Ok(mut guard) = state.lock() {
+        for pid in &owned {
+            guard.owned_pids.remove(pid);
+        }
+        save_owned_pids(&guard.owned_pids);
+    }
+    true
+}
+
+fn wait_until_free(port: u16) -> bool {
+    let started = Instant::now();
+    while platform::port_in_use(port) {
+        if started.elapsed() > RELEASE_TIMEOUT {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    true
+}
+
+/// A free port at or after `preferred`, skipping `taken`. Falls back to one
+/// picked by the OS.
+fn find_free_port(preferred: u16, taken: &[u16]) -> Option<u16> {
+    (preferred..=preferred.saturating_add(SEARCH_RANGE))
+        .find(|p| !taken.contains(p) && !platform::port_in_use(*p))
+        .or_else(|| {
+            std::net::TcpListener::bind(("127.0.0.1", 0))
+                .and_then(|l| l.local_addr())
+                .map(|addr| addr.port())
+                .ok()
+        })
+}
+
+/// Pick the port a server should listen on: the preferred port if it's free
+/// or held by a leftover server of ours, otherwise the next free one. The
+/// choice is stored in `AppState`, passed on to the proxy, and announced
+/// with a `service-ports` event if it changed.
+pub fn claim(app: &AppHandle, kind: PortKind) -> Result<u16, StudioError> {
+    let preferred = kind.preferred();
+    let before = current(app);
+    // Ports the other servers are using; don't hand those out twice.
+    let taken: Vec<u16> = {
+        let mut others = before;
+        *kind.slot(&mut others) = 0;
+        vec![
+            others.remotion,
+            others.opencode,
+            others.proxy,
+            others.remotion_proxy,
+        ]
+    };
+
+    let use_preferred = !taken.contains(&preferred)
+        && (!platform::port_in_use(preferred)
+            || (release(app, preferred) && wait_until_free(preferred)));
+
+    let port = if use_preferred {
+        preferred
+    } else {
+        let port = find_free_port(preferred.saturating_add(1), &taken)
+            .ok_or(StudioError::PortInUse { port: preferred })?;
+        crate::logging::log_line(
+            "WARN",
+            &format!("Port {} is unavailable, using {} instead", preferred, port),
+        );
+        port
+    };
+
+    let after = {
+        let Some(state) = app.try_state::<Mutex<AppState>>() else {
+            return Ok(port);
+        };
+        let mut guard = state.lock().map_err(|e| e.to_string())?;
+        *kind.slot(&mut guard.ports) = port;
+        guard.ports
+    };
+
+    match kind {
+        PortKind::OpenCode => proxy::set_upstream_port(proxy::Upstream::OpenCode, port),
+        PortKind::Remotion => proxy::set_upstream_port(proxy::Upstream::Remotion, port),
+        PortKind::Proxy | PortKind::RemotionProxy => {}
+    }
+    if after != before {
+        crate::frontend::emit(app, "service-ports", after);
+        announce_endpoints(app, after);
+    }
+    Ok(port)
+}
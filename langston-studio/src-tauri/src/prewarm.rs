@@ -0,0 +1,166 @@
+//! Getting the Remotion preview up faster.
+//!
+//! Remotion's dev server compiles the whole bundle on every launch, and
+//! its webpack cache lived in `node_modules/.cache`, which goes away with
+//! every reinstall. The cache now lives in the app's config folder, one
+//! per workspace (`cache_dir`); the workspace's remotion.config.ts points
+//! webpack at it through `LANGSTON_WEBPACK_CACHE_DIR`.
+//!
+//! With `remotionPrewarm` set, setup also runs `remotion bundle` in the
+//! background while the cache is empty, so it fills while OpenCode starts.
+//! Remotion's dev server is started once that finishes (or after
+//! `PREWARM_WAIT`) rather than compiling the same files next to it. It's
+//! off by default: it only helps when OpenCode takes a while to start.
+//!
+//! The UI is told `bundle-compiling` when the dev server starts and
+//! `bundle-ready` with how long it took once it logs its first build.
+
+use crate::{frontend, get_config_dir, node_shell_command, platform, read_config_file};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Longest the dev server is held back for a running pre-warm.
+const PREWARM_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// Whether a pre-warm bundle is running.
+static PREWARMING: Mutex<bool> = Mutex::new(false);
+static PREWARM_DONE: Condvar = Condvar::new();
+
+/// When the dev server was started, until its first build is logged.
+static COMPILE_STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Where webpack caches `workspace`'s bundle.
+pub fn cache_dir(workspace: &Path) -> PathBuf {
+    let digest = Sha256::digest(workspace.to_string_lossy().as_bytes());
+    let id: String = format!("{:x}", digest).chars().take(16).collect();
+    get_config_dir().join("webpack-cache").join(id)
+}
+
+fn is_warm(workspace: &Path) -> bool {
+    std::fs::read_dir(cache_dir(workspace)).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// Environment variables Remotion needs to use the shared cache.
+pub fn env(workspace: &Path) -> [(&'static str, String); 1] {
+    [(
+        "LANGSTON_WEBPACK_CACHE_DIR",
+        cache_dir(workspace).to_string_lossy().to_string(),
+    )]
+}
+
+fn set_prewarming(running: bool) {
+    *PREWARMING.lock().unwrap_or_else(|e| e.into_inner()) = running;
+    PREWARM_DONE.notify_all();
+}
+
+/// Start `remotion bundle` in the background if `remotionPrewarm` is set
+/// and the cache is empty.
+pub fn start(workspace: &Path) {
+    if read_config_file().remotion_prewarm != Some(true) || is_warm(workspace) {
+        return;
+    }
+    let out_dir = std::env::temp_dir().join("langston-prewarm-bundle");
+    let script = format!("npx remotion bundle --out-dir={:?}", out_dir);
+    let mut cmd = node_shell_command(&script);
+    platform::new_process_group(&mut cmd);
+    cmd.current_dir(workspace)
+        .envs(env(workspace))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            crate::logging::log_line(
+                "WARN",
+                &format!("[prewarm] Couldn't start remotion bundle: {}", e),
+            );
+            return;
+        }
+    };
+
+    crate::logging::log_line("INFO", "[prewarm] Bundling to fill the webpack cache");
+    set_prewarming(true);
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        let result = child.wait_with_output();
+        let _ = std::fs::remove_dir_all(&out_dir);
+        match result {
+            Ok(output) if output.status.success() => crate::logging::log_line(
+                "INFO",
+                &format!(
+                    "[prewarm] Bundled in {:.1}s",
+                    started.elapsed().as_secs_f64()
+                ),
+            ),
+            Ok(output) => crate::logging::log_line(
+                "WARN",
+                &format!(
+                    "[prewarm] remotion bundle failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ),
+            Err(e) => crate::logging::log_line(
+                "WARN",
+                &format!("[prewarm] remotion bundle failed: {}", e),
+            ),
+        }
+        set_prewarming(false);
+    });
+}
+
+/// Wait for a running pre-warm to finish, for at most `PREWARM_WAIT`.
+pub fn wait() {
+    let running = PREWARMING.lock().unwrap_or_else(|e| e.into_inner());
+    if !*running {
+        return;
+    }
+    crate::logging::log_line(
+        "INFO",
+        "[prewarm] Waiting for the bundle before starting Remotion",
+    );
+    let (_running, timeout) = PREWARM_DONE
+        .wait_timeout_while(running, PREWARM_WAIT, |running| *running)
+        .unwrap_or_else(|e| e.into_inner());
+    if timeout.timed_out() {
+        crate::logging::log_line("WARN", "[prewarm] Still bundling; starting Remotion anyway");
+    }
+}
+
+/// The dev server was just started; send `bundle-compiling`.
+pub fn compiling(app: &AppHandle) {
+    *COMPILE_STARTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    frontend::emit(app, "bundle-compiling", ());
+}
+
+/// Look for the dev server's first build in its output and send
+/// `bundle-ready`.
+pub fn observe_line(app: &AppHandle, line: &str) {
+    let mut started = COMPILE_STARTED.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(since) = *started else {
+        return;
+    };
+    let lower = line.to_ascii_lowercase();
+    if !lower.contains("built in") && !lower.contains("compiled successfully") {
+        return;
+    }
+    *started = None;
+    drop(started);
+    let elapsed = since.elapsed();
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "[prewarm] Preview compiled in {:.1}s",
+            elapsed.as_secs_f64()
+        ),
+    );
+    frontend::emit(
+        app,
+        "bundle-ready",
+        serde_json::json!({ "durationMs": elapsed.as_millis() as u64 }),
+    );
+}
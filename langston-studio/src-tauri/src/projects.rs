@@ -0,0 +1,449 @@
+//! Registry of separate video projects.
+//!
+//! Each project is its own Remotion workspace. The default workspace (from
+//! `workspaceDir` in config.json, or ~/Documents/code/langston-videos) is
+//! always listed under the id `default`; additional projects are recorded in
+//! projects.json in the config dir, together with which one is active.
+//! `resolve_workspace_dir` follows the active project, so opening a project
+//! is just "mark it active, set it up, and respawn the servers".
+//! A project can be created from one of the starter `templates`.
+
+use crate::error::StudioError;
+use crate::{
+    base_workspace_dir, expand_home, get_config_dir, read_config_file, setup, supervisor, templates,
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+/// Id under which the default workspace is listed.
+pub const DEFAULT_PROJECT_ID: &str = "default";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Project {
+    id: String,
+    name: String,
+    path: String,
+    created_at: String,
+    #[serde(default)]
+    last_opened_at: Option<String>,
+    /// Starter template it's created from; blank when unset.
+    #[serde(default)]
+    template: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Registry {
+    #[serde(default)]
+    projects: Vec<Project>,
+    /// Id of the open project; `None` means the default workspace.
+    #[serde(default)]
+    active: Option<String>,
+}
+
+/// Project as returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInfo {
+    id: String,
+    name: String,
+    path: String,
+    created_at: Option<String>,
+    last_opened_at: Option<String>,
+    template: Option<String>,
+    active: bool,
+    exists: bool,
+}
+
+fn registry_path() -> PathBuf {
+    get_config_dir().join("projects.json")
+}
+
+fn load_registry() -> Registry {
+    fs::read_to_string(registry_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_registry(registry: &Registry) -> Result<(), String> {
+    fs::create_dir_all(get_config_dir())
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    let contents = serde_json::to_string_pretty(registry).map_err(|e| e.to_string())?;
+    fs::write(registry_path(), contents)
+        .map_err(|e| format!("Failed to write projects.json: {}", e))
+}
+
+/// Directory of the active project, if one other than the default is open.
+pub fn active_project_dir() -> Option<PathBuf> {
+    let registry = load_registry();
+    let id = registry.active?;
+    registry
+        .projects
+        .into_iter()
+        .find(|p| p.id == id)
+        .map(|p| PathBuf::from(p.path))
+}
+
+/// The id of the project at `dir`, the default workspace included.
+pub fn id_for_path(dir: &Path) -> Option<String> {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = canonical(dir);
+    if canonical(&base_workspace_dir()) == dir {
+        return Some(DEFAULT_PROJECT_ID.to_string());
+    }
+    load_registry()
+        .projects
+        .into_iter()
+        .find(|p| canonical(Path::new(&p.path)) == dir)
+        .map(|p| p.id)
+}
+
+/// The starter template the workspace at `dir` is to be created from, if
+/// it isn't the blank one.
+pub fn template_for(dir: &Path) -> Option<String> {
+    let template = match id_for_path(dir)?.as_str() {
+        DEFAULT_PROJECT_ID => read_config_file().workspace_template,
+        id => {
+            load_registry()
+                .projects
+                .into_iter()
+                .find(|p| p.id == id)?
+                .template
+        }
+    };
+    template.filter(|t| t != templates::BLANK)
+}
+
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+/// An id for a project called `name` that isn't taken yet.
+fn unused_id(registry: &Registry, name: &str) -> String {
+    let base_slug = slugify(name);
+    let base_slug = if base_slug.is_empty() {
+        "project".to_string()
+    } else {
+        base_slug
+    };
+
+    let mut id = base_slug.clone();
+    let mut n = 2;
+    while id == DEFAULT_PROJECT_ID || registry.projects.iter().any(|p| p.id == id) {
+        id = format!("{}-{}", base_slug, n);
+        n += 1;
+    }
+    id
+}
+
+/// Where new projects go by default: next to the default workspace.
+fn projects_parent_dir() -> PathBuf {
+    base_workspace_dir()
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default())
+}
+
+fn default_project_info(registry: &Registry) -> ProjectInfo {
+    let path = base_workspace_dir();
+    ProjectInfo {
+        id: DEFAULT_PROJECT_ID.to_string(),
+        name: "Langston Videos".to_string(),
+        exists: path.join("package.json").exists(),
+        path: path.to_string_lossy().to_string(),
+        created_at: None,
+        last_opened_at: None,
+        template: read_config_file().workspace_template,
+        active: registry.active.is_none(),
+    }
+}
+
+fn project_info(project: &Project, registry: &Registry) -> ProjectInfo {
+    ProjectInfo {
+        id: project.id.clone(),
+        name: project.name.clone(),
+        path: project.path.clone(),
+        created_at: Some(project.created_at.clone()),
+        last_opened_at: project.last_opened_at.clone(),
+        template: project.template.clone(),
+        active: registry.active.as_deref() == Some(project.id.as_str()),
+        exists: PathBuf::from(&project.path).join("package.json").exists(),
+    }
+}
+
+#[tauri::command]
+pub fn list_projects() -> Vec<ProjectInfo> {
+    let registry = load_registry();
+    let mut projects = vec![default_project_info(&registry)];
+    projects.extend(registry.projects.iter().map(|p| project_info(p, &registry)));
+    projects
+}
+
+/// Register a new project. The directory is created from the workspace
+/// template, with the starter `template` (see `list_templates`) over it,
+/// when the project is first opened; pointing `path` at an existing
+/// Remotion project adopts it as-is.
+#[tauri::command]
+pub fn create_project(
+    app: AppHandle,
+    name: String,
+    path: Option<String>,
+    template: Option<String>,
+) -> Result<ProjectInfo, StudioError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(StudioError::InvalidInput(
+            "Project name cannot be empty".to_string(),
+        ));
+    }
+    let template = template
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty() && t != templates::BLANK);
+    if let Some(t) = &template {
+        if !templates::valid_id(t) || !templates::exists(&app, t) {
+            return Err(StudioError::NotFound(format!("Template {}", t)));
+        }
+    }
+
+    let mut registry = load_registry();
+    let id = unused_id(&registry, &name);
+
+    let dir = match path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty()) {
+        Some(p) => {
+            let dir = expand_home(&p);
+            if !dir.is_absolute() {
+                return Err(StudioError::InvalidInput(format!(
+                    "Project path must be absolute: {}",
+                    p
+                )));
+            }
+            if dir.exists() && !dir.is_dir() {
+                return Err(StudioError::InvalidInput(format!(
+                    "Project path is not a directory: {}",
+                    p
+                )));
+            }
+            dir
+        }
+        None => projects_parent_dir().join(&id),
+    };
+
+    let project = add_project(&mut registry, id, name, &dir, template)?;
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Created project {:?} at {} from template {}",
+            project.name,
+            project.path,
+            project.template.as_deref().unwrap_or(templates::BLANK)
+        ),
+    );
+    Ok(project_info(&project, &registry))
+}
+
+fn add_project(
+    registry: &mut Registry,
+    id: String,
+    name: String,
+    dir: &Path,
+    template: Option<String>,
+) -> Result<Project, String> {
+    let path = dir.to_string_lossy().to_string();
+    if dir == base_workspace_dir() || registry.projects.iter().any(|p| p.path == path) {
+        return Err(format!("A project already exists at {}", path));
+    }
+
+    let project = Project {
+        id,
+        name,
+        path,
+        created_at: Local::now().to_rfc3339(),
+        last_opened_at: None,
+        template,
+    };
+    registry.projects.push(project.clone());
+    save_registry(registry)?;
+    Ok(project)
+}
+
+/// A folder for a new project called `name`, next to the default workspace,
+/// that doesn't exist yet.
+pub fn new_project_dir(name: &str) -> PathBuf {
+    let registry = load_registry();
+    let parent = projects_parent_dir();
+    let mut id = unused_id(&registry, name);
+    let mut n = 2;
+    while parent.join(&id).exists() {
+        id = format!("{}-{}", unused_id(&registry, name), n);
+        n += 1;
+    }
+    parent.join(id)
+}
+
+/// Add an existing project folder to the registry and return its id; see
+/// `import`.
+pub fn register(name: &str, dir: &Path) -> Result<String, String> {
+    let mut registry = load_registry();
+    let id = unused_id(&registry, name);
+    Ok(add_project(&mut registry, id, name.to_string(), dir, None)?.id)
+}
+
+/// Remove a project from the registry, leaving its files alone.
+pub fn unregister(id: &str) -> Result<(), String> {
+    let mut registry = load_registry();
+    registry.projects.retain(|p| p.id != id);
+    save_registry(&registry)
+}
+
+/// Switch to another project: stop the servers, set the project up from the
+/// template if needed, and respawn against it. If setup fails the previously
+/// active project is restored so the app isn't left without a workspace.
+#[tauri::command]
+pub async fn open_project(app: AppHandle, id: String) -> Result<(), StudioError> {
+    tauri::async_runtime::spawn_blocking(move || open_project_blocking(&app, &id))
+        .await?
+        .map_err(Into::into)
+}
+
+pub fn open_project_blocking(app: &AppHandle, id: &str) -> Result<(), String> {
+    let registry = load_registry();
+    let previous = registry.active.clone();
+    let target = if id == DEFAULT_PROJECT_ID {
+        None
+    } else if registry.projects.iter().any(|p| p.id == id) {
+        Some(id.to_string())
+    } else {
+        return Err(format!("Unknown project: {}", id));
+    };
+
+    let result = supervisor::relaunch(
+        app,
+        &supervisor::Service::ALL,
+        &format!("Opening project {}", id),
+        |app| {
+            set_active(target.clone())?;
+            setup::prepare_workspace_blocking(app).map_err(|e| {
+                let _ = set_active(previous.clone());
+                format!("Failed to set up project: {}", e)
+            })
+        },
+    );
+
+    if result.is_ok() {
+        let registry = load_registry();
+        let info = match registry
+            .projects
+            .iter()
+            .find(|p| Some(&p.id) == target.as_ref())
+        {
+            Some(project) => project_info(project, &registry),
+            None => default_project_info(&registry),
+        };
+        let _ = app.emit("project-opened", info);
+    }
+    result
+}
+
+fn set_active(id: Option<String>) -> Result<(), String> {
+    let mut registry = load_registry();
+    if let Some(ref id) = id {
+        if let Some(project) = registry.projects.iter_mut().find(|p| &p.id == id) {
+            project.last_opened_at = Some(Local::now().to_rfc3339());
+        }
+    }
+    registry.active = id;
+    save_registry(&registry)
+}
+
+/// Why the files of the project at `dir` can't be deleted, if they can't.
+/// A project can be registered at any existing folder, so only one that
+/// looks like a project, and isn't home, the config dir, the default
+/// workspace or a folder holding any of them, is deleted.
+fn refuse_deleting(dir: &Path) -> Option<String> {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = canonical(dir);
+    if !dir.join("package.json").is_file() {
+        return Some(format!(
+            "{} has no package.json, so it doesn't look like a project",
+            dir.display()
+        ));
+    }
+    let protected = [
+        dirs::home_dir(),
+        Some(get_config_dir()),
+        Some(base_workspace_dir()),
+    ];
+    protected
+        .into_iter()
+        .flatten()
+        .map(|path| canonical(&path))
+        .find(|path| path.starts_with(&dir))
+        .map(|path| format!("{} holds {}", dir.display(), path.display()))
+}
+
+/// Remove a project from the registry, optionally moving its files to the
+/// trash. The default and the currently open project can't be deleted, nor
+/// files that don't look like a project (see `refuse_deleting`).
+#[tauri::command]
+pub fn delete_project(id: String, delete_files: Option<bool>) -> Result<(), StudioError> {
+    if id == DEFAULT_PROJECT_ID {
+        return Err(StudioError::InvalidInput(
+            "The default project can't be deleted".to_string(),
+        ));
+    }
+
+    let mut registry = load_registry();
+    if registry.active.as_deref() == Some(id.as_str()) {
+        return Err(StudioError::InvalidInput(
+            "Open another project before deleting this one".to_string(),
+        ));
+    }
+    let index = registry
+        .projects
+        .iter()
+        .position(|p| p.id == id)
+        .ok_or_else(|| StudioError::NotFound(format!("Project {}", id)))?;
+    let dir = PathBuf::from(&registry.projects[index].path);
+    let delete_files = delete_files.unwrap_or(false) && dir.exists();
+    if delete_files {
+        if let Some(reason) = refuse_deleting(&dir) {
+            return Err(StudioError::InvalidInput(format!(
+                "Won't delete the project's files: {}",
+                reason
+            )));
+        }
+    }
+    let project = registry.projects.remove(index);
+    save_registry(&registry)?;
+
+    crate::logging::log_line(
+        "INFO",
+        &format!("Removed project {:?} ({})", project.name, project.path),
+    );
+
+    if delete_files {
+        trash::delete(&dir).map_err(|e| {
+            StudioError::Other(format!(
+                "Failed to move {} to the trash: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+        crate::logging::log_line("INFO", &format!("Moved {} to the trash", project.path));
+    }
+
+    Ok(())
+}
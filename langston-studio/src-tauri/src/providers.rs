@@ -0,0 +1,160 @@
+//! LLM providers OpenCode can be given credentials for.
+//!
+//! Only Anthropic and OpenAI keys used to be supported. `providers` in
+//! config.json now configures any provider in `PROVIDERS`:
+//!
+//! ```json
+//! "providers": {
+//!   "openrouter": {},
+//!   "ollama": { "baseUrl": "http://localhost:11434" }
+//! }
+//! ```
+//!
+//! API keys go to the keychain like the Anthropic and OpenAI ones (see
+//! `secrets`); `load_config` fills them in. `spawn_opencode` passes each
+//! configured provider's key and base URL in the environment variables
+//! OpenCode reads for it. Ollama needs no key; it counts as configured once
+//! it has a base URL. When no provider has a key, `local_models` points
+//! OpenCode at the local server.
+
+use crate::AppConfig;
+use serde::{Deserialize, Serialize};
+
+pub struct Provider {
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Variable the API key is passed in; `None` for providers without keys.
+    pub key_env: Option<&'static str>,
+    /// Variable a custom base URL is passed in, if the provider takes one.
+    pub base_url_env: Option<&'static str>,
+}
+
+pub static PROVIDERS: [Provider; 6] = [
+    Provider {
+        id: "anthropic",
+        name: "Anthropic",
+        key_env: Some("ANTHROPIC_API_KEY"),
+        base_url_env: Some("ANTHROPIC_BASE_URL"),
+    },
+    Provider {
+        id: "openai",
+        name: "OpenAI",
+        key_env: Some("OPENAI_API_KEY"),
+        base_url_env: Some("OPENAI_BASE_URL"),
+    },
+    Provider {
+        id: "google",
+        name: "Google",
+        key_env: Some("GOOGLE_GENERATIVE_AI_API_KEY"),
+        base_url_env: None,
+    },
+    Provider {
+        id: "groq",
+        name: "Groq",
+        key_env: Some("GROQ_API_KEY"),
+        base_url_env: None,
+    },
+    Provider {
+        id: "openrouter",
+        name: "OpenRouter",
+        key_env: Some("OPENROUTER_API_KEY"),
+        base_url_env: None,
+    },
+    Provider {
+        id: "ollama",
+        name: "Ollama",
+        key_env: None,
+        base_url_env: Some("OLLAMA_HOST"),
+    },
+];
+
+pub fn find(id: &str) -> Option<&'static Provider> {
+    PROVIDERS.iter().find(|provider| provider.id == id)
+}
+
+/// An entry in `providers` in config.json. `apiKey` is only there until
+/// it's moved to the keychain on startup.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+}
+
+/// A provider as shown to the frontend; never includes the key.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderStatus {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub needs_key: bool,
+    pub has_key: bool,
+    pub base_url: Option<String>,
+    pub configured: bool,
+}
+
+impl Provider {
+    pub fn is_configured(&self, config: &AppConfig) -> bool {
+        let entry = config.provider(self.id);
+        match self.key_env {
+            Some(_) => entry.is_some_and(|entry| entry.api_key.is_some()),
+            None => entry.is_some_and(|entry| entry.base_url.is_some()),
+        }
+    }
+}
+
+/// Every known provider and whether it's set up. `config` should come from
+/// `load_config`, so keys in the keychain count.
+pub fn statuses(config: &AppConfig) -> Vec<ProviderStatus> {
+    PROVIDERS
+        .iter()
+        .map(|provider| {
+            let entry = config.provider(provider.id);
+            ProviderStatus {
+                id: provider.id,
+                name: provider.name,
+                needs_key: provider.key_env.is_some(),
+                has_key: entry.is_some_and(|entry| entry.api_key.is_some()),
+                base_url: entry.and_then(|entry| entry.base_url.clone()),
+                configured: provider.is_configured(config),
+            }
+        })
+        .collect()
+}
+
+/// Whether any provider is set up, so OpenCode has a model to use.
+pub fn any_configured(config: &AppConfig) -> bool {
+    PROVIDERS
+        .iter()
+        .any(|provider| provider.is_configured(config))
+}
+
+/// Whether any provider has an API key, i.e. a cloud model is available.
+pub fn any_key(config: &AppConfig) -> bool {
+    PROVIDERS.iter().any(|provider| {
+        provider.key_env.is_some()
+            && config
+                .provider(provider.id)
+                .is_some_and(|entry| entry.api_key.is_some())
+    })
+}
+
+/// Environment variables that pass the configured keys and base URLs to
+/// OpenCode.
+pub fn env_vars(config: &AppConfig) -> Vec<(&'static str, String)> {
+    let mut vars = Vec::new();
+    for provider in &PROVIDERS {
+        let Some(entry) = config.provider(provider.id) else {
+            continue;
+        };
+        if let (Some(name), Some(key)) = (provider.key_env, &entry.api_key) {
+            vars.push((name, key.clone()));
+        }
+        if let (Some(name), Some(url)) = (provider.base_url_env, &entry.base_url) {
+            vars.push((name, url.clone()));
+        }
+    }
+    vars
+}
@@ -1,4 +1,4 @@
-//! Reverse proxy for OpenCode's web UI.
+//! Reverse proxy for OpenCode's web UI and Remotion Studio.
 //!
 //! WKWebView (Tauri's macOS webview engine) enforces aggressive HTTP connection
 //! timeouts (~60-120s) on idle streaming connections. When OpenCode streams a
@@ -7,201 +7,1380 @@
 //! connection and appear frozen.
 //!
 //! This proxy sits between the webview and the OpenCode server, forwarding
-//! requests with explicitly long timeouts (10 minutes) so the Rust-side
-//! connection never times out. The webview sees fast, local responses from the
+//! requests with explicitly long timeouts (10 minutes by default, see
+//! `ProxyConfig`) so the Rust-side connection never times out. The webview sees fast, local responses from the
 //! proxy and the proxy holds the long-lived upstream connection open.
+//!
+//! A second instance fronts the Remotion dev server, whose iframe hits the
+//! same WKWebView quirks. Requests asking for an HTTP Upgrade (Remotion's
+//! hot-reload WebSocket) are tunnelled: the handshake is replayed to the
+//! upstream and, once it answers `101 Switching Protocols`, bytes are copied
+//! both ways until either side closes.
+//!
+//! Binding to 127.0.0.1 keeps other machines out but not other local
+//! processes, so every request must carry the access token generated at
+//! startup (see `get_proxy_token`): in the `x-langston-proxy-token` header,
+//! or, for the iframes, as a `langston_token` query parameter on the first
+//! load. The proxy answers that with a cookie, and same-origin requests from
+//! such a page also carry the token in their `Referer`. The parameter is
+//! removed before the request goes upstream. Setting `proxyRequireToken` to
+//! false in config.json turns the check off.
+//!
+//! Headers are forwarded as a `HeaderMap` both ways, so repeated headers
+//! (several `Set-Cookie`s) and values that aren't UTF-8 come through intact.
+//! Hop-by-hop headers stay behind, and upstream gets `X-Forwarded-For`,
+//! `X-Forwarded-Proto` and `X-Forwarded-Host` describing the real client.
 
+use crate::error::StudioError;
+use crate::{
+    proxy_cache, proxy_inject, proxy_limit, proxy_scripts, proxy_stats, sessions, transcripts,
+};
+use base64::Engine;
 use bytes::Bytes;
-use chrono::Local;
 use futures_util::StreamExt;
 use http_body_util::{BodyExt, Full, StreamBody};
 use hyper::body::Frame;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::convert::Infallible;
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::net::SocketAddr;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::{Duration, Instant};
-use tokio::net::TcpListener;
+use tauri::Emitter;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
 
 /// Maximum time to wait for upstream response headers.
-const UPSTREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
 /// Maximum time to wait between body chunks from upstream (10 min).
-const UPSTREAM_READ_TIMEOUT: Duration = Duration::from_secs(600);
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 600;
+/// Total time a static asset may take.
+const DEFAULT_ASSET_TIMEOUT_SECS: u64 = 30;
+/// Total time a non-streaming API request may take.
+const DEFAULT_API_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_POOL_MAX_IDLE: usize = 10;
+/// Largest request body forwarded upstream.
+const DEFAULT_MAX_BODY_BYTES: u64 = 100 * 1024 * 1024;
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+/// HTTP/2 pings keep a shared connection from being dropped by the
+/// upstream while it's idle between streams.
+const HTTP2_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+const HTTP2_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest header block accepted from upstream over HTTP/2.
+const HTTP2_MAX_HEADER_LIST_BYTES: u32 = 256 * 1024;
+
+/// Upstream silence on an SSE stream after which a keepalive comment is
+/// sent to the webview, which otherwise drops streams idle for minutes.
+const SSE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(20);
+/// An SSE comment line; EventSource clients ignore it.
+const SSE_HEARTBEAT: &[u8] = b": keepalive\n\n";
+
+/// Times a broken SSE stream is re-requested before the client is told.
+const SSE_MAX_RETRIES: u32 = 3;
+/// Delay before the first re-request; later ones wait proportionally longer.
+const SSE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How long in-flight requests get to finish when a proxy stops.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest upgrade response head accepted from upstream.
+const MAX_UPGRADE_HEAD: usize = 16 * 1024;
+
+/// How often progress is logged while a request body is uploaded.
+const BODY_PROGRESS_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Proxy timeouts and limits, from config.json.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfig {
+    pub connect_timeout_secs: u64,
+    pub read_timeout_secs: u64,
+    pub asset_timeout_secs: u64,
+    pub api_timeout_secs: u64,
+    pub pool_max_idle: usize,
+    pub max_body_bytes: u64,
+    pub require_token: bool,
+    pub http2: bool,
+    /// Prompts let through per minute; 0 for no limit. See `proxy_limit`.
+    pub rate_limit_per_min: u64,
+    pub rate_limit_burst: u64,
+}
+
+impl ProxyConfig {
+    pub fn from_app_config(config: &crate::AppConfig) -> ProxyConfig {
+        ProxyConfig {
+            connect_timeout_secs: config
+                .proxy_connect_timeout_secs
+                .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+            read_timeout_secs: config
+                .proxy_read_timeout_secs
+                .unwrap_or(DEFAULT_READ_TIMEOUT_SECS),
+            asset_timeout_secs: config
+                .proxy_asset_timeout_secs
+                .unwrap_or(DEFAULT_ASSET_TIMEOUT_SECS),
+            api_timeout_secs: config
+                .proxy_api_timeout_secs
+                .unwrap_or(DEFAULT_API_TIMEOUT_SECS),
+            pool_max_idle: config.proxy_pool_max_idle.unwrap_or(DEFAULT_POOL_MAX_IDLE),
+            max_body_bytes: config
+                .proxy_max_body_bytes
+                .unwrap_or(DEFAULT_MAX_BODY_BYTES),
+            require_token: config.proxy_require_token.unwrap_or(true),
+            http2: config.proxy_http2.unwrap_or(false),
+            rate_limit_per_min: config.proxy_rate_limit_per_min.unwrap_or(0),
+            rate_limit_burst: config
+                .proxy_rate_limit_burst
+                .unwrap_or(proxy_limit::DEFAULT_BURST),
+        }
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout_secs)
+    }
+
+    /// The timeout for a class of request, in seconds: in total for assets
+    /// and API calls, between chunks for streams.
+    fn timeout_secs(&self, class: TimeoutClass) -> u64 {
+        match class {
+            TimeoutClass::Asset => self.asset_timeout_secs,
+            TimeoutClass::Api => self.api_timeout_secs,
+            TimeoutClass::Streaming => self.read_timeout_secs,
+        }
+    }
+
+    fn build_client(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout())
+            .read_timeout(self.read_timeout())
+            .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+            .pool_max_idle_per_host(self.pool_max_idle)
+            .no_proxy();
+        if self.http2 {
+            // Streams per connection are capped by the upstream's
+            // SETTINGS_MAX_CONCURRENT_STREAMS, which hyper honours; the
+            // adaptive window keeps one busy stream from starving the rest.
+            builder = builder
+                .http2_prior_knowledge()
+                .http2_adaptive_window(true)
+                .http2_max_header_list_size(HTTP2_MAX_HEADER_LIST_BYTES)
+                .http2_keep_alive_interval(HTTP2_KEEP_ALIVE_INTERVAL)
+                .http2_keep_alive_timeout(HTTP2_KEEP_ALIVE_TIMEOUT)
+                .http2_keep_alive_while_idle(true);
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build proxy client: {}", e))
+    }
+}
+
+/// The settings in effect and the upstream client built from them, shared
+/// by both proxy instances. Replaced by `reload`.
+static SETTINGS: RwLock<Option<(ProxyConfig, reqwest::Client)>> = RwLock::new(None);
+
+/// Build the settings and client from config.json and make them current.
+fn load() -> Result<(ProxyConfig, reqwest::Client), String> {
+    let config = ProxyConfig::from_app_config(&crate::read_config_file());
+    let client = config.build_client()?;
+    let mut settings = SETTINGS.write().map_err(|e| e.to_string())?;
+    *settings = Some((config, client.clone()));
+    Ok((config, client))
+}
+
+/// The current settings and client, loaded on first use.
+fn current() -> Result<(ProxyConfig, reqwest::Client), String> {
+    match SETTINGS.read().ok().and_then(|s| s.clone()) {
+        Some(settings) => Ok(settings),
+        None => load(),
+    }
+}
+
+/// The settings in effect.
+pub fn config() -> ProxyConfig {
+    current()
+        .map(|(config, _)| config)
+        .unwrap_or_else(|_| ProxyConfig::from_app_config(&crate::AppConfig::default()))
+}
+
+/// Re-read the settings from config.json and rebuild the upstream client.
+/// Requests already in flight keep the old one.
+pub fn reload() -> Result<ProxyConfig, String> {
+    proxy_scripts::reload();
+    load().map(|(config, _)| config)
+}
+
+/// Apply changed proxy settings from config.json without restarting.
+#[tauri::command]
+pub fn reload_proxy_config() -> Result<ProxyConfig, StudioError> {
+    let config = reload()?;
+    plog(
+        "INFO",
+        &format!(
+            "[proxy] Settings reloaded: connect timeout {}s, read timeout {}s (assets {}s, API {}s), {} idle connections, {} byte body limit, HTTP/{}, {}",
+            config.connect_timeout_secs,
+            config.read_timeout_secs,
+            config.asset_timeout_secs,
+            config.api_timeout_secs,
+            config.pool_max_idle,
+            config.max_body_bytes,
+            if config.http2 { "2" } else { "1.1" },
+            if config.rate_limit_per_min == 0 {
+                "no prompt rate limit".to_string()
+            } else {
+                format!(
+                    "{} prompts/min (burst {})",
+                    config.rate_limit_per_min, config.rate_limit_burst
+                )
+            },
+        ),
+    );
+    Ok(config)
+}
+
+/// Response from a relayed fetch, serialized back to the webview.
+#[derive(Serialize)]
+pub struct RelayResponse {
+    status: u16,
+    /// Name/value pairs, repeated headers included.
+    headers: Vec<(String, String)>,
+    /// The whole body, unless it's sent as `tauri-fetch-chunk` events.
+    body: Option<String>,
+    streamed: bool,
+}
+
+/// A piece of a streamed relay response, sent as a `tauri-fetch-chunk`
+/// event. The last one for a request has `done` set.
+#[derive(Clone, Serialize)]
+struct RelayChunk {
+    id: String,
+    /// Base64, since a chunk can end partway through a UTF-8 character.
+    data: Option<String>,
+    done: bool,
+    error: Option<String>,
+}
+
+/// Send the body of `resp` to the webview as `tauri-fetch-chunk` events for
+/// `stream_id` as it arrives, so streamed replies aren't held back until the
+/// end.
+fn relay_stream(
+    app: tauri::AppHandle,
+    stream_id: String,
+    req_id: u64,
+    resp: reqwest::Response,
+    started: Instant,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut body = resp.bytes_stream();
+        let mut total = 0usize;
+        let error = loop {
+            match body.next().await {
+                Some(Ok(chunk)) => {
+                    total += chunk.len();
+                    let _ = app.emit(
+                        "tauri-fetch-chunk",
+                        RelayChunk {
+                            id: stream_id.clone(),
+                            data: Some(base64::engine::general_purpose::STANDARD.encode(&chunk)),
+                            done: false,
+                            error: None,
+                        },
+                    );
+                }
+                Some(Err(e)) => break Some(format!("relay_fetch body error: {}", e)),
+                None => break None,
+            }
+        };
+        match &error {
+            Some(e) => plog(
+                "ERROR",
+                &format!("[proxy] #{} relay stream failed: {}", req_id, e),
+            ),
+            None => plog(
+                "INFO",
+                &format!(
+                    "[proxy] #{} relay <- streamed {} bytes ({:.1}s)",
+                    req_id,
+                    total,
+                    started.elapsed().as_secs_f64()
+                ),
+            ),
+        }
+        let _ = app.emit(
+            "tauri-fetch-chunk",
+            RelayChunk {
+                id: stream_id,
+                data: None,
+                done: true,
+                error,
+            },
+        );
+    });
+}
+
+/// Whether `url` points at one of our own servers or proxies. The relay
+/// must not become a way for page content to reach arbitrary hosts.
+fn relay_allowed(app: &tauri::AppHandle, url: &reqwest::Url) -> bool {
+    let local = matches!(
+        url.host_str(),
+        Some("localhost") | Some("127.0.0.1") | Some("[::1]")
+    );
+    let ports = crate::ports::current(app);
+    let ours = [
+        ports.opencode,
+        ports.remotion,
+        ports.proxy,
+        ports.remotion_proxy,
+    ];
+    url.scheme() == "http"
+        && local
+        && url
+            .port_or_known_default()
+            .is_some_and(|port| ours.contains(&port))
+}
+
+/// Execute an HTTP request through Rust's reqwest, bypassing WKWebView's
+/// networking stack (and its ~60s idle timeout on POST requests). This is
+/// the other half of the fetch-override script (see `proxy_scripts`): the
+/// iframe posts `tauri-fetch` messages to the parent webview, which calls
+/// this. Uses the proxy's client and timeouts, and only reaches our own
+/// servers.
+///
+/// With a `stream_id` it returns as soon as the response headers are in and
+/// relays the body through `relay_stream`; without one the whole body comes
+/// back in the response.
+#[tauri::command]
+pub async fn relay_fetch(
+    app: tauri::AppHandle,
+    method: String,
+    url: String,
+    body: Option<String>,
+    headers: HashMap<String, String>,
+    stream_id: Option<String>,
+) -> Result<RelayResponse, StudioError> {
+    let req_id = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let parsed = reqwest::Url::parse(&url)
+        .map_err(|e| StudioError::InvalidInput(format!("Invalid URL {}: {}", url, e)))?;
+    if !relay_allowed(&app, &parsed) {
+        plog(
+            "WARN",
+            &format!("[proxy] #{} Refused to relay {} {}", req_id, method, url),
+        );
+        return Err(StudioError::InvalidInput(format!(
+            "relay_fetch only reaches Langston Studio's servers: {}",
+            url
+        )));
+    }
+    let (_, client) = current()?;
+
+    let rw_method = reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+        .map_err(|e| StudioError::InvalidInput(format!("Invalid method {}: {}", method, e)))?;
+    plog(
+        "INFO",
+        &format!("[proxy] #{} relay {} {}", req_id, rw_method, url),
+    );
+
+    let mut req = client
+        .request(rw_method, parsed)
+        .header(TOKEN_HEADER, token());
+    for (k, v) in &headers {
+        req = req.header(k.as_str(), v.as_str());
+    }
+    if let Some(b) = body {
+        req = req.body(b);
+    }
+
+    let started = Instant::now();
+    let resp = req.send().await.map_err(|e| {
+        plog("ERROR", &format!("[proxy] #{} relay failed: {}", req_id, e));
+        StudioError::Network(format!("relay_fetch send error: {}", e))
+    })?;
+
+    let status = resp.status().as_u16();
+    let resp_headers: Vec<(String, String)> = resp
+        .headers()
+        .iter()
+        .map(|(k, v)| {
+            (
+                k.to_string(),
+                String::from_utf8_lossy(v.as_bytes()).to_string(),
+            )
+        })
+        .collect();
+    if let Some(stream_id) = stream_id {
+        plog(
+            "INFO",
+            &format!(
+                "[proxy] #{} relay <- {} (streaming, {:.1}s)",
+                req_id,
+                status,
+                started.elapsed().as_secs_f64()
+            ),
+        );
+        relay_stream(app, stream_id, req_id, resp, started);
+        return Ok(RelayResponse {
+            status,
+            headers: resp_headers,
+            body: None,
+            streamed: true,
+        });
+    }
+    let resp_body = resp
+        .text()
+        .await
+        .map_err(|e| StudioError::Network(format!("relay_fetch body error: {}", e)))?;
+    plog(
+        "INFO",
+        &format!(
+            "[proxy] #{} relay <- {} ({} bytes, {:.1}s)",
+            req_id,
+            status,
+            resp_body.len(),
+            started.elapsed().as_secs_f64()
+        ),
+    );
+
+    Ok(RelayResponse {
+        status,
+        headers: resp_headers,
+        body: Some(resp_body),
+        streamed: false,
+    })
+}
+
+const TOKEN_HEADER: &str = "x-langston-proxy-token";
+const TOKEN_PARAM: &str = "langston_token";
+const TOKEN_COOKIE: &str = "langston_proxy_token";
+
+/// The access token for this run.
+static TOKEN: OnceLock<String> = OnceLock::new();
+
+/// The token clients must present, generated on first use.
+pub fn token() -> &'static str {
+    TOKEN.get_or_init(|| {
+        let mut bytes = [0u8; 32];
+        getrandom::getrandom(&mut bytes).expect("Failed to generate proxy token");
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    })
+}
+
+/// The token the webview needs to load the proxied servers.
+#[tauri::command]
+pub fn get_proxy_token() -> String {
+    token().to_string()
+}
+
+/// Compare without leaking where the first difference is.
+fn token_matches(candidate: &str) -> bool {
+    let expected = token().as_bytes();
+    let candidate = candidate.as_bytes();
+    candidate.len() == expected.len()
+        && candidate
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// The value of `name` in a query string.
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// How a request proved it may use the proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    /// Header, cookie or referer: nothing to do.
+    Granted,
+    /// Query parameter: strip it and set the cookie.
+    GrantedByQuery,
+    Denied,
+}
+
+fn check_access<B>(req: &Request<B>) -> Access {
+    if !config().require_token {
+        return Access::Granted;
+    }
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|v: &hyper::header::HeaderValue| v.to_str().ok())
+    };
+    if header(TOKEN_HEADER).is_some_and(token_matches) {
+        return Access::Granted;
+    }
+    if req
+        .uri()
+        .query()
+        .and_then(|q| query_param(q, TOKEN_PARAM))
+        .is_some_and(token_matches)
+    {
+        return Access::GrantedByQuery;
+    }
+    let cookie_ok = req
+        .headers()
+        .get_all(hyper::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .any(|(name, value)| name == TOKEN_COOKIE && token_matches(value));
+    if cookie_ok {
+        return Access::Granted;
+    }
+    let referer_ok = header("referer")
+        .and_then(|r| reqwest::Url::parse(r).ok())
+        .and_then(|url| {
+            url.query()
+                .and_then(|q| query_param(q, TOKEN_PARAM))
+                .map(token_matches)
+        })
+        .unwrap_or(false);
+    if referer_ok {
+        Access::Granted
+    } else {
+        Access::Denied
+    }
+}
+
+/// `uri` without the token parameter.
+fn strip_token(uri: &hyper::Uri) -> hyper::Uri {
+    let Some(query) = uri.query() else {
+        return uri.clone();
+    };
+    let rest: Vec<&str> = query
+        .split('&')
+        .filter(|pair| pair.split('=').next() != Some(TOKEN_PARAM))
+        .collect();
+    let path_and_query = if rest.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), rest.join("&"))
+    };
+    path_and_query.parse().unwrap_or_else(|_| uri.clone())
+}
+
+/// Remove the token's header and cookie from `headers`. They've been checked
+/// by then, and neither upstream needs them.
+fn strip_token_headers(headers: &mut HeaderMap) {
+    headers.remove(TOKEN_HEADER);
+    let has_token_cookie = headers
+        .get_all(hyper::header::COOKIE)
+        .iter()
+        .any(|v| v.to_str().is_ok_and(|v| v.contains(TOKEN_COOKIE)));
+    if !has_token_cookie {
+        return;
+    }
+    let cookies: Vec<String> = headers
+        .get_all(hyper::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .map(str::trim)
+        .filter(|c| !c.is_empty() && c.split('=').next().map(str::trim) != Some(TOKEN_COOKIE))
+        .map(str::to_string)
+        .collect();
+    headers.remove(hyper::header::COOKIE);
+    if cookies.is_empty() {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&cookies.join("; ")) {
+        headers.insert(hyper::header::COOKIE, value);
+    }
+}
 
 /// Monotonic request counter for correlating log lines.
 static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(1);
 
-/// JavaScript injected into every HTML response from upstream.
-/// Overrides `window.fetch` for mutating HTTP methods (POST, PUT, PATCH, DELETE)
-/// so those requests are relayed via `postMessage` to the parent Tauri webview.
-/// The parent executes them through Rust's reqwest with a 10-minute timeout,
-/// completely bypassing WKWebView's ~60s idle connection kill.
-///
-/// GET/HEAD/OPTIONS requests continue through native fetch (they're fast and
-/// don't trigger the timeout issue).
-const FETCH_OVERRIDE_SCRIPT: &str = r#"
-(function() {
-  var _origFetch = window.fetch;
-  var _pending = {};
-
-  window.addEventListener('message', function(e) {
-    if (!e.data || !e.data.id) return;
-    if (e.data.type === 'tauri-fetch-response' && _pending[e.data.id]) {
-      _pending[e.data.id].resolve(e.data);
-      delete _pending[e.data.id];
-    }
-    if (e.data.type === 'tauri-fetch-error' && _pending[e.data.id]) {
-      _pending[e.data.id].reject(new Error(e.data.error));
-      delete _pending[e.data.id];
-    }
-  });
-
-  window.fetch = function(input, init) {
-    var method = (init && init.method) ? init.method.toUpperCase() : 'GET';
-    // Only intercept mutating methods — these are the ones that can block
-    // for minutes while the LLM processes. GETs are fast or use SSE (streaming).
-    if (method === 'GET' || method === 'HEAD' || method === 'OPTIONS') {
-      return _origFetch.call(window, input, init);
-    }
-
-    // If we're not in an iframe (no parent), fall back to native fetch
-    if (window === window.parent) {
-      return _origFetch.call(window, input, init);
-    }
-
-    var url = typeof input === 'string' ? input : (input && input.url ? input.url : String(input));
-    // Make relative URLs absolute
-    if (url.startsWith('/')) {
-      url = window.location.origin + url;
-    }
-
-    var id = Math.random().toString(36).slice(2) + Date.now().toString(36);
-    var headers = {};
-    if (init && init.headers) {
-      try {
-        var h = new Headers(init.headers);
-        h.forEach(function(v, k) { headers[k] = v; });
-      } catch(ex) {
-        // If headers aren't iterable, skip
-      }
-    }
-
-    var body = (init && init.body) ? init.body : null;
-    // Convert body to string if it's not already
-    if (body && typeof body !== 'string') {
-      try { body = JSON.stringify(body); } catch(ex) { body = String(body); }
-    }
-
-    console.log('[tauri-fetch] Relaying ' + method + ' ' + url + ' via postMessage (id: ' + id + ')');
-
-    return new Promise(function(resolve, reject) {
-      _pending[id] = { resolve: resolve, reject: reject };
-
-      window.parent.postMessage({
-        type: 'tauri-fetch',
-        id: id,
-        method: method,
-        url: url,
-        body: body,
-        headers: headers
-      }, '*');
-
-      // Safety timeout: 10 minutes (matches Rust-side timeout)
-      setTimeout(function() {
-        if (_pending[id]) {
-          console.error('[tauri-fetch] Timeout for ' + method + ' ' + url + ' (id: ' + id + ')');
-          delete _pending[id];
-          reject(new Error('tauri-fetch timeout after 600s'));
-        }
-      }, 600000);
-    }).then(function(data) {
-      console.log('[tauri-fetch] Got response for ' + method + ' ' + url + ': ' + data.status);
-      return new Response(data.body, {
-        status: data.status,
-        headers: data.headers
-      });
-    });
-  };
+/// Ports the servers are listening on. A server may move to another port
+/// when it is restarted, so these are read on every request rather than
+/// captured once.
+static OPENCODE_PORT: AtomicU16 = AtomicU16::new(0);
+static REMOTION_PORT: AtomicU16 = AtomicU16::new(0);
 
-  console.log('[tauri-fetch] Fetch override active: POST/PUT/PATCH/DELETE -> Tauri relay');
-})();
-"#;
+/// The server a proxy instance forwards to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Upstream {
+    OpenCode,
+    Remotion,
+}
 
-/// Write a log line to the shared app log file.
-/// This ensures proxy logs appear in the same file the Logs viewer reads.
-fn plog(log_file: &PathBuf, level: &str, msg: &str) {
-    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-    let line = format!("[{}] [{}] {}\n", timestamp, level, msg);
+impl Upstream {
+    fn port(self) -> &'static AtomicU16 {
+        match self {
+            Upstream::OpenCode => &OPENCODE_PORT,
+            Upstream::Remotion => &REMOTION_PORT,
+        }
+    }
 
-    if let Ok(mut file) = OpenOptions::new().append(true).open(log_file) {
-        let _ = file.write_all(line.as_bytes());
+    pub fn name(self) -> &'static str {
+        match self {
+            Upstream::OpenCode => "OpenCode",
+            Upstream::Remotion => "Remotion",
+        }
     }
+}
+
+/// Point the proxy for `upstream` at the server's new port.
+pub fn set_upstream_port(upstream: Upstream, port: u16) {
+    upstream.port().store(port, Ordering::SeqCst);
+}
+
+/// Write a log line to the shared app logs, so proxy logs appear in the same
+/// file the Logs viewer reads.
+fn plog(level: &str, msg: &str) {
+    crate::logging::log_line(level, msg);
+}
+
+/// A running proxy. Dropping it stops the proxy without waiting; `stop`
+/// waits for it to drain.
+pub struct ProxyHandle {
+    pub upstream: Upstream,
+    pub port: u16,
+    shutdown: watch::Sender<bool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
 
-    // Also emit via the log crate for stdout/Tauri console
-    match level {
-        "ERROR" => log::error!("{}", msg),
-        "WARN" => log::warn!("{}", msg),
-        _ => log::info!("{}", msg),
+impl ProxyHandle {
+    /// Stop accepting connections, give in-flight requests up to
+    /// `DRAIN_TIMEOUT` to finish, and wait for the proxy to exit.
+    pub fn stop(mut self) {
+        let _ = self.shutdown.send(true);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Run a proxy for `upstream` on its own thread and tokio runtime.
+pub fn spawn(upstream: Upstream, proxy_port: u16, upstream_port: u16) -> ProxyHandle {
+    let (shutdown, receiver) = watch::channel(false);
+    let thread = std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime for proxy");
+        rt.block_on(async {
+            if let Err(e) = run_proxy(upstream, proxy_port, upstream_port, receiver).await {
+                plog(
+                    "ERROR",
+                    &format!(
+                        "[proxy] Reverse proxy for {} failed: {}",
+                        upstream.name(),
+                        e
+                    ),
+                );
+            }
+        });
+    });
+    ProxyHandle {
+        upstream,
+        port: proxy_port,
+        shutdown,
+        thread: Some(thread),
     }
 }
 
 /// Start the reverse proxy on `proxy_port`, forwarding all traffic to
-/// `upstream_port` on localhost. This function runs forever and should be
-/// spawned on a tokio runtime.
+/// `upstream_port` on localhost (or wherever `set_upstream_port` points it
+/// later). Runs until `shutdown` changes or its sender is dropped, then
+/// drains the open connections.
 pub async fn run_proxy(
+    upstream: Upstream,
     proxy_port: u16,
     upstream_port: u16,
-    log_file: PathBuf,
+    mut shutdown: watch::Receiver<bool>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    set_upstream_port(upstream, upstream_port);
     let addr = SocketAddr::from(([127, 0, 0, 1], proxy_port));
     let listener = TcpListener::bind(addr).await?;
 
     plog(
-        &log_file,
         "INFO",
-        &format!("[proxy] Listening on {} -> localhost:{}", addr, upstream_port),
+        &format!(
+            "[proxy] Listening on {} -> {} on localhost:{}",
+            addr,
+            upstream.name(),
+            upstream_port
+        ),
     );
 
-    let client = reqwest::Client::builder()
-        .connect_timeout(UPSTREAM_CONNECT_TIMEOUT)
-        .read_timeout(UPSTREAM_READ_TIMEOUT)
-        .pool_idle_timeout(Duration::from_secs(600))
-        .pool_max_idle_per_host(10)
-        .no_proxy()
-        .build()?;
+    // Fail early on settings that can't produce a client.
+    current()?;
 
+    let mut connections = JoinSet::new();
     loop {
-        let (stream, peer) = listener.accept().await?;
-        let io = TokioIo::new(stream);
-        let client = client.clone();
-        let upstream = upstream_port;
-        let lf = log_file.clone();
-
-        tokio::spawn(async move {
-            let service = service_fn(move |req: Request<hyper::body::Incoming>| {
-                let client = client.clone();
-                let lf = lf.clone();
-                async move { handle_request(req, client, upstream, lf).await }
-            });
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = accepted?;
+                let io = TokioIo::new(stream);
+                let conn_shutdown = shutdown.clone();
+                connections.spawn(serve_connection(upstream, io, peer, conn_shutdown));
+            }
+            // Reap finished connections so the set doesn't grow.
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = shutdown.changed() => break,
+        }
+    }
 
-            if let Err(e) = http1::Builder::new()
-                .keep_alive(true)
-                .serve_connection(io, service)
-                .await
-            {
-                let msg = e.to_string();
-                if !msg.contains("connection reset") && !msg.contains("broken pipe") {
-                    // Can't easily pass log_file here, use log crate only
-                    log::warn!("[proxy] Connection error ({}): {}", peer, msg);
+    drop(listener);
+    plog(
+        "INFO",
+        &format!(
+            "[proxy] Stopping {} proxy on {}, draining {} connections",
+            upstream.name(),
+            addr,
+            connections.len()
+        ),
+    );
+    let drain = async { while connections.join_next().await.is_some() {} };
+    if tokio::time::timeout(DRAIN_TIMEOUT, drain).await.is_err() {
+        plog(
+            "WARN",
+            &format!(
+                "[proxy] Closing {} connections that didn't finish in {}s",
+                connections.len(),
+                DRAIN_TIMEOUT.as_secs()
+            ),
+        );
+        connections.abort_all();
+    }
+    Ok(())
+}
+
+/// Serve one client connection. On shutdown the request in progress is
+/// allowed to finish before the connection closes.
+async fn serve_connection(
+    upstream: Upstream,
+    io: TokioIo<TcpStream>,
+    peer: SocketAddr,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let service = service_fn(move |req: Request<hyper::body::Incoming>| async move {
+        handle_request(upstream, peer, req).await
+    });
+    let conn = http1::Builder::new()
+        .keep_alive(true)
+        .serve_connection(io, service)
+        .with_upgrades();
+    tokio::pin!(conn);
+
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = shutdown.changed() => {
+            conn.as_mut().graceful_shutdown();
+            conn.as_mut().await
+        }
+    };
+    if let Err(e) = result {
+        let msg = e.to_string();
+        if !msg.contains("connection reset") && !msg.contains("broken pipe") {
+            plog(
+                "WARN",
+                &format!("[proxy] Connection error ({}): {}", peer, msg),
+            );
+        }
+    }
+}
+
+/// A plain-text error response from the proxy itself.
+fn error_response<S>(
+    status: StatusCode,
+    message: &str,
+) -> Response<http_body_util::Either<Full<Bytes>, S>> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "text/plain")
+        .body(http_body_util::Either::Left(Full::new(Bytes::from(
+            format!("Proxy error: {}", message),
+        ))))
+        .unwrap()
+}
+
+/// Send `SSE_HEARTBEAT` down `stream` whenever nothing has come from
+/// upstream for `interval`. Heartbeats only go out between events (after a
+/// blank line), so a slow event is never split. With no `interval` the stream
+/// is passed through untouched.
+fn with_heartbeat<S>(
+    stream: S,
+    interval: Option<Duration>,
+    req_id: u64,
+) -> impl futures_util::Stream<Item = Result<Frame<Bytes>, std::io::Error>>
+where
+    S: futures_util::Stream<Item = Result<Frame<Bytes>, std::io::Error>> + Send + 'static,
+{
+    let state = (Box::pin(stream), true, 0u64);
+    futures_util::stream::unfold(
+        state,
+        move |(mut stream, at_boundary, mut heartbeats)| async move {
+            let next = match interval {
+                Some(interval) if at_boundary => {
+                    match tokio::time::timeout(interval, stream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            heartbeats += 1;
+                            if heartbeats == 1 || heartbeats % 10 == 0 {
+                                plog(
+                                    "INFO",
+                                    &format!(
+                                        "[proxy] #{} upstream idle, sent keepalive #{}",
+                                        req_id, heartbeats
+                                    ),
+                                );
+                            }
+                            let frame = Frame::data(Bytes::from_static(SSE_HEARTBEAT));
+                            return Some((Ok(frame), (stream, true, heartbeats)));
+                        }
+                    }
+                }
+                _ => stream.next().await,
+            };
+            let item = next?;
+            let at_boundary = match item.as_ref().ok().and_then(|frame| frame.data_ref()) {
+                Some(data) if !data.is_empty() => {
+                    data.ends_with(b"\n\n") || data.ends_with(b"\r\n\r\n")
                 }
+                _ => at_boundary,
+            };
+            Some((item, (stream, at_boundary, heartbeats)))
+        },
+    )
+}
+
+type ByteStream = Pin<Box<dyn futures_util::Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
+/// What's needed to request an SSE stream again.
+struct SseRequest {
+    client: reqwest::Client,
+    upstream: Upstream,
+    path: String,
+    headers: HeaderMap,
+    req_id: u64,
+}
+
+impl SseRequest {
+    /// Re-request the stream, resuming after `last_event_id` if we have one.
+    /// The upstream's port is looked up again, in case it restarted elsewhere.
+    async fn reconnect(&self, last_event_id: Option<&str>) -> Result<ByteStream, String> {
+        let port = self.upstream.port().load(Ordering::SeqCst);
+        let url = format!("http://127.0.0.1:{}{}", port, self.path);
+        let mut headers = self.headers.clone();
+        headers.remove("last-event-id");
+        let mut req = self.client.get(&url).headers(headers);
+        if let Some(id) = last_event_id {
+            req = req.header("last-event-id", id);
+        }
+        let resp = req.send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("upstream returned {}", resp.status()));
+        }
+        Ok(Box::pin(resp.bytes_stream()))
+    }
+}
+
+/// State of a resumable SSE stream; see `resumable_sse`.
+struct SseState {
+    request: SseRequest,
+    stream: Option<ByteStream>,
+    /// Bytes of an event that hasn't been completed yet.
+    partial: Vec<u8>,
+    last_event_id: Option<String>,
+    retries: u32,
+    /// Items to hand out before reading more.
+    queued: VecDeque<reqwest::Result<Bytes>>,
+}
+
+impl SseState {
+    /// Add `chunk` and return whatever complete events are now buffered,
+    /// noting the last event ID among them and any provider rate limits
+    /// OpenCode reports in them.
+    fn complete_events(&mut self, chunk: &[u8]) -> Option<Bytes> {
+        self.partial.extend_from_slice(chunk);
+        let end = [&b"\n\n"[..], &b"\r\n\r\n"[..]]
+            .iter()
+            .filter_map(|sep| {
+                self.partial
+                    .windows(sep.len())
+                    .rposition(|w| w == *sep)
+                    .map(|pos| pos + sep.len())
+            })
+            .max()?;
+        let rest = self.partial.split_off(end);
+        let complete = std::mem::replace(&mut self.partial, rest);
+        // Data is looked at once its event is complete, as the id may follow it.
+        let mut event_id = None;
+        let mut data_lines = Vec::new();
+        for line in String::from_utf8_lossy(&complete).lines() {
+            if let Some(id) = line.strip_prefix("id:") {
+                let id = id.strip_prefix(' ').unwrap_or(id);
+                self.last_event_id = Some(id.to_string());
+                event_id = Some(id.to_string());
+            } else if let Some(data) = line.strip_prefix("data:") {
+                data_lines.push(data.to_string());
+            } else if line.is_empty() {
+                if self.request.upstream == Upstream::OpenCode {
+                    for data in &data_lines {
+                        observe_rate_limit(self.request.req_id, event_id.as_deref(), data);
+                    }
+                }
+                event_id = None;
+                data_lines.clear();
             }
-        });
+        }
+        Some(Bytes::from(complete))
+    }
+
+    /// The next item to hand to the client, reconnecting if the upstream
+    /// breaks.
+    async fn next_item(&mut self) -> Option<reqwest::Result<Bytes>> {
+        loop {
+            if let Some(item) = self.queued.pop_front() {
+                return Some(item);
+            }
+            let stream = self.stream.as_mut()?;
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    self.retries = 0;
+                    if let Some(events) = self.complete_events(&chunk) {
+                        return Some(Ok(events));
+                    }
+                }
+                None => {
+                    self.stream = None;
+                    if !self.partial.is_empty() {
+                        let rest = std::mem::take(&mut self.partial);
+                        return Some(Ok(Bytes::from(rest)));
+                    }
+                    return None;
+                }
+                Some(Err(e)) => {
+                    let req_id = self.request.req_id;
+                    self.stream = None;
+                    // The upstream resends from the last complete event.
+                    self.partial.clear();
+                    while self.retries < SSE_MAX_RETRIES {
+                        self.retries += 1;
+                        plog(
+                            "WARN",
+                            &format!(
+                                "[proxy] #{} SSE stream broke ({}), reconnect {}/{} after event {}",
+                                req_id,
+                                e,
+                                self.retries,
+                                SSE_MAX_RETRIES,
+                                self.last_event_id.as_deref().unwrap_or("none"),
+                            ),
+                        );
+                        tokio::time::sleep(SSE_RETRY_DELAY * self.retries).await;
+                        match self.request.reconnect(self.last_event_id.as_deref()).await {
+                            Ok(stream) => {
+                                plog("INFO", &format!("[proxy] #{} SSE stream resumed", req_id));
+                                self.stream = Some(stream);
+                                break;
+                            }
+                            Err(reconnect_err) => plog(
+                                "WARN",
+                                &format!(
+                                    "[proxy] #{} SSE reconnect failed: {}",
+                                    req_id, reconnect_err
+                                ),
+                            ),
+                        }
+                    }
+                    if self.stream.is_none() {
+                        let event =
+                            sse_error_event(&e, self.retries, self.last_event_id.as_deref());
+                        self.queued.push_back(Ok(event));
+                        self.queued.push_back(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Hold prompts back if `data`, from OpenCode's event stream, says the
+/// provider rate limited a session and that event hasn't been seen yet (see
+/// `proxy_limit`).
+fn observe_rate_limit(req_id: u64, event_id: Option<&str>, data: &str) {
+    let Some(event) = proxy_limit::rate_limit_event(data) else {
+        return;
+    };
+    if !proxy_limit::first_sighting(&event, event_id.unwrap_or(data)) {
+        return;
+    }
+    if let Some(backoff) = proxy_limit::back_off(&config(), event.retry_after) {
+        plog(
+            "WARN",
+            &format!(
+                "[proxy] #{} Provider rate limited session {}; holding prompts back for {}s",
+                req_id,
+                event.session,
+                backoff.as_secs()
+            ),
+        );
+    }
+}
+
+/// The `proxy-error` event sent before an SSE stream is cut off by an
+/// upstream error.
+fn sse_error_event(error: &reqwest::Error, retries: u32, last_event_id: Option<&str>) -> Bytes {
+    let data = serde_json::json!({
+        "error": error.to_string(),
+        "retries": retries,
+        "lastEventId": last_event_id,
+    });
+    Bytes::from(format!("event: proxy-error\ndata: {}\n\n", data))
+}
+
+/// Pass an SSE response that can't be re-requested (anything but a GET)
+/// through, sending a `proxy-error` event before the error if it breaks.
+/// An event cut off halfway is closed first so the error event parses.
+fn sse_with_error_event(stream: ByteStream) -> ByteStream {
+    Box::pin(futures_util::stream::unfold(
+        (stream, true),
+        |(mut stream, at_boundary)| async move {
+            match stream.next().await? {
+                Ok(chunk) => {
+                    let at_boundary = if chunk.is_empty() {
+                        at_boundary
+                    } else {
+                        chunk.ends_with(b"\n\n") || chunk.ends_with(b"\r\n\r\n")
+                    };
+                    Some((Ok(chunk), (stream, at_boundary)))
+                }
+                Err(e) => {
+                    let mut event = if at_boundary {
+                        Vec::new()
+                    } else {
+                        b"\n\n".to_vec()
+                    };
+                    event.extend_from_slice(&sse_error_event(&e, 0, None));
+                    let rest: ByteStream = Box::pin(futures_util::stream::iter([Err(e)]));
+                    Some((Ok(Bytes::from(event)), (rest, true)))
+                }
+            }
+        },
+    ))
+}
+
+/// Pass an SSE response through, re-requesting it (with `Last-Event-ID`,
+/// when the upstream sends IDs) if it breaks. Events are only forwarded once
+/// complete, so a resumed stream never follows half an event. Once the
+/// retries are used up the client gets a `proxy-error` event before the
+/// error itself.
+fn resumable_sse(first: ByteStream, request: SseRequest) -> ByteStream {
+    let state = SseState {
+        request,
+        stream: Some(first),
+        partial: Vec::new(),
+        last_event_id: None,
+        retries: 0,
+        queued: VecDeque::new(),
+    };
+    Box::pin(futures_util::stream::unfold(
+        state,
+        |mut state| async move {
+            let item = state.next_item().await?;
+            Some((item, state))
+        },
+    ))
+}
+
+/// A response served from the asset cache.
+fn cached_response<S>(
+    cached: proxy_cache::CachedAsset,
+) -> Response<http_body_util::Either<Full<Bytes>, S>> {
+    let status = StatusCode::from_u16(cached.status).unwrap_or(StatusCode::OK);
+    let mut response = Response::builder()
+        .status(status)
+        .body(http_body_util::Either::Left(Full::new(cached.body)))
+        .unwrap();
+    *response.headers_mut() = cached.headers;
+    response
+}
+
+/// Headers that describe one connection rather than the message: the
+/// standard ones and whatever `connection` lists.
+fn is_hop_by_hop(name: &HeaderName, headers: &HeaderMap) -> bool {
+    const HOP_BY_HOP: &[&str] = &[
+        "connection",
+        "keep-alive",
+        "proxy-connection",
+        "proxy-authenticate",
+        "proxy-authorization",
+        "te",
+        "trailer",
+        "transfer-encoding",
+        "upgrade",
+    ];
+    HOP_BY_HOP.contains(&name.as_str())
+        || headers
+            .get_all(hyper::header::CONNECTION)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|listed| name.as_str().eq_ignore_ascii_case(listed.trim()))
+}
+
+/// Add `X-Forwarded-For` (appending `peer` to any the client sent),
+/// `X-Forwarded-Proto` and `X-Forwarded-Host` (the `Host` the client
+/// asked for, since upstream sees its own address in `Host`).
+fn add_forwarding_headers(headers: &mut HeaderMap, original: &HeaderMap, peer: SocketAddr) {
+    let mut chain: Vec<String> = original
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    chain.push(peer.ip().to_string());
+    if let Ok(value) = HeaderValue::from_str(&chain.join(", ")) {
+        headers.insert("x-forwarded-for", value);
+    }
+    headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+    if let Some(host) = original.get(hyper::header::HOST) {
+        headers.insert("x-forwarded-host", host.clone());
+    }
+}
+
+/// The headers to send upstream for a client request: all of them,
+/// repeats and non-UTF-8 values included, except `host` (set to upstream's
+/// address by reqwest) and the hop-by-hop ones, plus the forwarding headers.
+fn upstream_request_headers(original: &HeaderMap, peer: SocketAddr) -> HeaderMap {
+    let mut headers = HeaderMap::with_capacity(original.len() + 3);
+    for (name, value) in original {
+        if name != hyper::header::HOST && !is_hop_by_hop(name, original) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+    strip_token_headers(&mut headers);
+    add_forwarding_headers(&mut headers, original, peer);
+    headers
+}
+
+/// What `request_body` needs to enforce the size limit and report on an
+/// upload.
+struct RequestBodyLog {
+    req_id: u64,
+    limit: u64,
+    /// Set when the body passed `limit`, so the caller can answer 413.
+    too_large: Arc<AtomicBool>,
+    exchange: Option<Arc<crate::capture::Exchange>>,
+    /// Whether to log the body's size once it's all sent.
+    log_size: bool,
+}
+
+/// A client's request body as a stream for reqwest. It fails once more than
+/// `limit` bytes have come through, and progress is logged every
+/// `BODY_PROGRESS_BYTES` so a large upload can be followed.
+fn request_body(body: hyper::body::Incoming, log: RequestBodyLog) -> reqwest::Body {
+    let log = Arc::new(log);
+    let stream = futures_util::stream::unfold(
+        (body.into_data_stream(), 0u64, false),
+        move |(mut body, sent, failed)| {
+            let log = log.clone();
+            async move {
+                if failed {
+                    return None;
+                }
+                let chunk = match body.next().await {
+                    Some(Ok(chunk)) => chunk,
+                    Some(Err(e)) => {
+                        plog(
+                            "ERROR",
+                            &format!("[proxy] #{} Failed to read request body: {}", log.req_id, e),
+                        );
+                        return Some((Err(std::io::Error::other(e)), (body, sent, true)));
+                    }
+                    None => {
+                        if log.log_size && sent > 0 {
+                            plog(
+                                "INFO",
+                                &format!("[proxy] #{} Request body: {} bytes", log.req_id, sent),
+                            );
+                        }
+                        return None;
+                    }
+                };
+                let total = sent + chunk.len() as u64;
+                if total > log.limit {
+                    plog(
+                        "WARN",
+                        &format!(
+                            "[proxy] #{} Request body over the {} byte limit",
+                            log.req_id, log.limit
+                        ),
+                    );
+                    log.too_large.store(true, Ordering::Relaxed);
+                    let error = std::io::Error::other("request body too large");
+                    return Some((Err(error), (body, total, true)));
+                }
+                if total / BODY_PROGRESS_BYTES > sent / BODY_PROGRESS_BYTES {
+                    plog(
+                        "INFO",
+                        &format!(
+                            "[proxy] #{} Uploading request body: {} MB so far",
+                            log.req_id,
+                            total / (1024 * 1024)
+                        ),
+                    );
+                }
+                if let Some(exchange) = &log.exchange {
+                    exchange.request_chunk(&chunk);
+                }
+                Some((Ok(chunk), (body, total, false)))
+            }
+        },
+    );
+    reqwest::Body::wrap_stream(stream)
+}
+
+/// Upstream's CSP header `value`, changed to let the injected scripts run.
+/// Every change is logged, as it means upstream has started restricting
+/// scripts; a policy that can't be read as text is passed on as it is.
+fn allow_script_nonce(
+    req_id: u64,
+    name: &HeaderName,
+    value: &HeaderValue,
+    nonce: &str,
+) -> HeaderValue {
+    let Ok(policy) = value.to_str() else {
+        plog(
+            "WARN",
+            &format!(
+                "[proxy] #{} Can't read {}, injected scripts may be blocked",
+                req_id, name
+            ),
+        );
+        return value.clone();
+    };
+    let Some(rewritten) = proxy_inject::allow_nonce(policy, nonce) else {
+        return value.clone();
+    };
+    match HeaderValue::from_str(&rewritten) {
+        Ok(rewritten) => {
+            plog(
+                "INFO",
+                &format!(
+                    "[proxy] #{} Rewrote {} to allow the injected scripts (was: {})",
+                    req_id, name, policy
+                ),
+            );
+            rewritten
+        }
+        Err(_) => value.clone(),
+    }
+}
+
+/// Decode HTML from `stream` and inject `scripts` on the way through, such
+/// as the fetch-override script that relays POST/PUT/PATCH/DELETE through
+/// the parent Tauri webview (bypassing WKWebView timeouts). Only what comes
+/// before the end of `<head>` is held back; a body that turns out not to
+/// decode is cut short.
+fn inject_html(
+    stream: ByteStream,
+    decoder: proxy_inject::Decoder,
+    scripts: &[String],
+    nonce: &str,
+    req_id: u64,
+) -> ByteStream {
+    let injector = proxy_inject::HeadInjector::new(scripts, nonce);
+    let state = Some((stream, decoder, injector, 0usize));
+    Box::pin(futures_util::stream::unfold(
+        state,
+        move |state| async move {
+            let (mut stream, mut decoder, mut injector, mut received) = state?;
+            loop {
+                let was_injected = injector.injected();
+                let (html, ended) = match stream.next().await {
+                    Some(Ok(chunk)) => {
+                        received += chunk.len();
+                        (decoder.push(&chunk).map(|html| injector.push(&html)), false)
+                    }
+                    Some(Err(e)) => return Some((Err(e), None)),
+                    None => {
+                        let html = decoder.finish().map(|html| {
+                            let mut html = injector.push(&html);
+                            html.extend(injector.finish());
+                            html
+                        });
+                        (html, true)
+                    }
+                };
+                let html = match html {
+                    Ok(html) => html,
+                    Err(e) => {
+                        plog(
+                            "WARN",
+                            &format!(
+                                "[proxy] #{} Can't decode HTML ({}), cutting it short",
+                                req_id, e
+                            ),
+                        );
+                        return None;
+                    }
+                };
+                if !was_injected && injector.injected() {
+                    plog(
+                    "INFO",
+                    &format!(
+                        "[proxy] #{} Injected fetch-override script into HTML after {} bytes from upstream",
+                        req_id, received,
+                    ),
+                );
+                }
+                if ended {
+                    return (!html.is_empty()).then(|| (Ok(Bytes::from(html)), None));
+                }
+                if !html.is_empty() {
+                    let state = Some((stream, decoder, injector, received));
+                    return Some((Ok(Bytes::from(html)), state));
+                }
+            }
+        },
+    ))
+}
+
+/// Which timeout a request gets. Only streaming requests get the long read
+/// timeout; a hung asset or API call fails after its own, shorter, total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeoutClass {
+    Asset,
+    Api,
+    Streaming,
+}
+
+impl TimeoutClass {
+    /// From `classify_request`'s `kind`, and whether the client asked for a
+    /// streamed response.
+    fn of(kind: &str, wants_stream: bool) -> TimeoutClass {
+        match kind {
+            _ if wants_stream => TimeoutClass::Streaming,
+            "static asset" => TimeoutClass::Asset,
+            "session API" | "API" => TimeoutClass::Api,
+            _ => TimeoutClass::Streaming,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            TimeoutClass::Asset => "asset",
+            TimeoutClass::Api => "API",
+            TimeoutClass::Streaming => "streaming",
+        }
     }
 }
 
@@ -222,17 +1401,208 @@ fn classify_request(path: &str) -> &'static str {
     }
 }
 
+/// Whether `req` asks to switch protocols, e.g. to a WebSocket.
+fn is_upgrade_request<B>(req: &Request<B>) -> bool {
+    req.headers().contains_key(hyper::header::UPGRADE)
+        && req
+            .headers()
+            .get(hyper::header::CONNECTION)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_ascii_lowercase().contains("upgrade"))
+            .unwrap_or(false)
+}
+
+/// Read an HTTP response head from `stream`. Returns the head and any bytes
+/// read past it, which already belong to the upgraded connection.
+async fn read_response_head(stream: &mut TcpStream) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return Ok((buf, rest));
+        }
+        if buf.len() > MAX_UPGRADE_HEAD {
+            return Err("upstream response head too large".to_string());
+        }
+        let n = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("upstream closed the connection during the handshake".to_string());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Tunnel an upgrade request (a WebSocket, in practice) to the upstream.
+/// The handshake is forwarded without the access token; if the upstream
+/// agrees to switch protocols, its response is returned to the client and
+/// bytes are copied between the two connections until one side closes. If
+/// it refuses, the client gets a 502: the refusal's body isn't read, so its
+/// framing headers can't be passed on.
+async fn handle_upgrade(
+    upstream: Upstream,
+    peer: SocketAddr,
+    req_id: u64,
+    mut req: Request<hyper::body::Incoming>,
+) -> Result<Response<Full<Bytes>>, String> {
+    let upstream_port = upstream.port().load(Ordering::SeqCst);
+    let mut upstream_stream = tokio::time::timeout(
+        config().connect_timeout(),
+        TcpStream::connect(("127.0.0.1", upstream_port)),
+    )
+    .await
+    .map_err(|_| "timed out connecting to upstream".to_string())?
+    .map_err(|e| e.to_string())?;
+
+    let uri = strip_token(req.uri());
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    // The handshake needs `connection` and `upgrade`, so only `host` is
+    // replaced here. Values are written as raw bytes.
+    let mut headers = req.headers().clone();
+    headers.remove(hyper::header::HOST);
+    strip_token_headers(&mut headers);
+    add_forwarding_headers(&mut headers, req.headers(), peer);
+    let mut head = format!("{} {} HTTP/1.1\r\n", req.method(), path).into_bytes();
+    for (name, value) in &headers {
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(format!("host: 127.0.0.1:{}\r\n\r\n", upstream_port).as_bytes());
+    upstream_stream
+        .write_all(&head)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (resp_head, leftover) = read_response_head(&mut upstream_stream).await?;
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Response::new(&mut headers);
+    parsed
+        .parse(&resp_head)
+        .map_err(|e| format!("invalid upstream response: {}", e))?;
+    let status = parsed
+        .code
+        .and_then(|c| StatusCode::from_u16(c).ok())
+        .unwrap_or(StatusCode::BAD_GATEWAY);
+
+    if status != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(format!("upstream refused the upgrade: {}", status.as_u16()));
+    }
+    let mut builder = Response::builder().status(status);
+    for header in parsed.headers.iter() {
+        builder = builder.header(header.name, header.value);
+    }
+
+    plog(
+        "INFO",
+        &format!(
+            "[proxy] #{} Upgraded, tunnelling to {}",
+            req_id,
+            upstream.name()
+        ),
+    );
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let upgraded = match on_upgrade.await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                plog(
+                    "WARN",
+                    &format!("[proxy] #{} Client upgrade failed: {}", req_id, e),
+                );
+                return;
+            }
+        };
+        let mut client_io = TokioIo::new(upgraded);
+        if !leftover.is_empty() && client_io.write_all(&leftover).await.is_err() {
+            return;
+        }
+        match tokio::io::copy_bidirectional(&mut client_io, &mut upstream_stream).await {
+            Ok((sent, received)) => plog(
+                "INFO",
+                &format!(
+                    "[proxy] #{} Tunnel closed after {:.1}s ({} bytes sent, {} received)",
+                    req_id,
+                    started.elapsed().as_secs_f64(),
+                    sent,
+                    received,
+                ),
+            ),
+            Err(e) => plog("WARN", &format!("[proxy] #{} Tunnel error: {}", req_id, e)),
+        }
+    });
+
+    builder
+        .body(Full::new(Bytes::new()))
+        .map_err(|e| e.to_string())
+}
+
+/// Check the request's token, then forward it.
 async fn handle_request(
+    upstream: Upstream,
+    peer: SocketAddr,
+    mut req: Request<hyper::body::Incoming>,
+) -> Result<
+    Response<
+        http_body_util::Either<
+            Full<Bytes>,
+            StreamBody<impl futures_util::Stream<Item = Result<Frame<Bytes>, std::io::Error>>>,
+        >,
+    >,
+    Infallible,
+> {
+    let access = check_access(&req);
+    if access == Access::Denied {
+        proxy_stats::record_error("rejected");
+        plog(
+            "WARN",
+            &format!(
+                "[proxy] Rejected {} {} from {}: missing or invalid access token",
+                req.method(),
+                req.uri().path(),
+                peer
+            ),
+        );
+        return Ok(error_response(
+            StatusCode::UNAUTHORIZED,
+            "access token required",
+        ));
+    }
+    if access == Access::GrantedByQuery {
+        *req.uri_mut() = strip_token(req.uri());
+    }
+    if req.uri().path() == proxy_stats::STATS_PATH {
+        let json = serde_json::to_string(&proxy_stats::snapshot()).unwrap_or_default();
+        return Ok(Response::builder()
+            .header("content-type", "application/json")
+            .header("cache-control", "no-store")
+            .body(http_body_util::Either::Left(Full::new(Bytes::from(json))))
+            .unwrap());
+    }
+    let mut response = forward_request(upstream, peer, req).await?;
+    if access == Access::GrantedByQuery {
+        let cookie = format!("{}={}; Path=/; HttpOnly", TOKEN_COOKIE, token());
+        if let Ok(value) = hyper::header::HeaderValue::from_str(&cookie) {
+            response
+                .headers_mut()
+                .append(hyper::header::SET_COOKIE, value);
+        }
+    }
+    Ok(response)
+}
+
+async fn forward_request(
+    upstream: Upstream,
+    peer: SocketAddr,
     req: Request<hyper::body::Incoming>,
-    client: reqwest::Client,
-    upstream_port: u16,
-    log_file: PathBuf,
 ) -> Result<
     Response<
         http_body_util::Either<
             Full<Bytes>,
             StreamBody<
-                impl futures_util::Stream<Item = Result<Frame<Bytes>, Infallible>>,
+                impl futures_util::Stream<Item = Result<Frame<Bytes>, std::io::Error>>,
             >,
         >,
     >,
@@ -242,14 +1612,53 @@ async fn handle_request(
     let started = Instant::now();
     let method = req.method().clone();
     let uri = req.uri().to_string();
+    let path = req.uri().path().to_string();
     let kind = classify_request(&uri);
+    let rate_limited = proxy_limit::applies(upstream, &method, &path);
+    if upstream == Upstream::OpenCode {
+        sessions::observe_path(&path);
+        transcripts::observe_path(&path);
+    }
+
+    if is_upgrade_request(&req) {
+        proxy_stats::record_request("upgrade");
+        plog(
+            "INFO",
+            &format!("[proxy] #{} {} {} -> upgrade", req_id, method, uri),
+        );
+        let response = handle_upgrade(upstream, peer, req_id, req)
+            .await
+            .unwrap_or_else(|e| {
+                plog(
+                    "ERROR",
+                    &format!("[proxy] #{} Upgrade failed: {}", req_id, e),
+                );
+                proxy_stats::record_error("upgrade");
+                Response::builder()
+                    .status(StatusCode::BAD_GATEWAY)
+                    .header("content-type", "text/plain")
+                    .body(Full::new(Bytes::from(format!("Proxy error: {}", e))))
+                    .unwrap()
+            });
+        return Ok(response.map(http_body_util::Either::Left));
+    }
+
+    proxy_stats::record_request(kind);
 
+    let (config, client) = match current() {
+        Ok(settings) => settings,
+        Err(e) => {
+            plog("ERROR", &format!("[proxy] #{} {}", req_id, e));
+            return Ok(error_response(StatusCode::BAD_GATEWAY, &e));
+        }
+    };
+
+    let upstream_port = upstream.port().load(Ordering::SeqCst);
     let upstream_url = format!("http://127.0.0.1:{}{}", upstream_port, req.uri());
 
     // Log all non-static requests
     if kind != "static asset" {
         plog(
-            &log_file,
             "INFO",
             &format!("[proxy] #{} {} {} -> upstream ({})", req_id, method, uri, kind),
         );
@@ -269,27 +1678,39 @@ async fn handle_request(
 
     let mut upstream_req = client.request(rw_method, &upstream_url);
 
-    // Forward headers (skip host, it'll be set by reqwest)
-    let mut has_accept_stream = false;
-    for (name, value) in req.headers() {
-        if name == "host" {
-            continue;
-        }
-        if name == "accept" {
-            if let Ok(v) = value.to_str() {
-                if v.contains("text/event-stream") || v.contains("text/x-component") {
-                    has_accept_stream = true;
-                }
-            }
-        }
-        if let Ok(v) = value.to_str() {
-            upstream_req = upstream_req.header(name.as_str(), v);
-        }
+    // Forward headers (host is set by reqwest)
+    let has_accept_stream = req
+        .headers()
+        .get_all(hyper::header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains("text/event-stream") || v.contains("text/x-component"));
+    let forwarded_headers = upstream_request_headers(req.headers(), peer);
+    upstream_req = upstream_req.headers(forwarded_headers.clone());
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "/".to_string());
+
+    // Static assets are revalidated against our cached copy; see
+    // `proxy_cache`. Conditional and range requests from the client pass
+    // through untouched.
+    let cacheable = kind == "static asset"
+        && method == hyper::Method::GET
+        && !forwarded_headers.contains_key(hyper::header::IF_NONE_MATCH)
+        && !forwarded_headers.contains_key(hyper::header::RANGE);
+    let cached = if cacheable {
+        proxy_cache::get(upstream, &path)
+    } else {
+        None
+    };
+    if let Some(cached) = &cached {
+        upstream_req = upstream_req.header("if-none-match", cached.etag.as_str());
     }
 
     if has_accept_stream {
         plog(
-            &log_file,
             "INFO",
             &format!(
                 "[proxy] #{} Client requested streaming response (Accept: event-stream/x-component)",
@@ -298,39 +1719,111 @@ async fn handle_request(
         );
     }
 
-    // Forward body
-    let body_bytes = match req.into_body().collect().await {
-        Ok(collected) => collected.to_bytes(),
-        Err(e) => {
+    // Headers are needed for the capture after the request is consumed.
+    let captured_headers = (crate::capture::enabled() && kind != "static asset")
+        .then(|| crate::capture::headers(req.headers()));
+
+    // Refuse a body over the configured limit up front when the client says
+    // how big it is; one without a length is cut off once it passes it.
+    let declared_length = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared_length.is_some_and(|len| len > config.max_body_bytes) {
+        plog(
+            "WARN",
+            &format!(
+                "[proxy] #{} Request body over the {} byte limit",
+                req_id, config.max_body_bytes
+            ),
+        );
+        return Ok(error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body too large",
+        ));
+    }
+    let exchange = captured_headers
+        .and_then(|headers| {
+            crate::capture::Exchange::start(req_id, upstream.name(), method.as_str(), &uri, headers)
+        })
+        .map(Arc::new);
+
+    // Stream the body upstream as it arrives rather than holding it all.
+    let body_too_large = Arc::new(AtomicBool::new(false));
+    if !hyper::body::Body::is_end_stream(req.body()) {
+        upstream_req = upstream_req.body(request_body(
+            req.into_body(),
+            RequestBodyLog {
+                req_id,
+                limit: config.max_body_bytes,
+                too_large: body_too_large.clone(),
+                exchange: exchange.clone(),
+                log_size: kind != "static asset",
+            },
+        ));
+    }
+
+    if rate_limited {
+        let waited = proxy_limit::acquire(&config).await;
+        if waited >= Duration::from_millis(100) {
             plog(
-                &log_file,
-                "ERROR",
-                &format!("[proxy] #{} Failed to read request body: {}", req_id, e),
+                "INFO",
+                &format!(
+                    "[proxy] #{} Held back {:.1}s by the prompt rate limit",
+                    req_id,
+                    waited.as_secs_f64()
+                ),
             );
-            Bytes::new()
         }
-    };
-    if !body_bytes.is_empty() && kind != "static asset" {
-        plog(
-            &log_file,
-            "INFO",
-            &format!("[proxy] #{} Request body: {} bytes", req_id, body_bytes.len()),
-        );
-        upstream_req = upstream_req.body(body_bytes);
-    } else if !body_bytes.is_empty() {
-        upstream_req = upstream_req.body(body_bytes);
+    }
+
+    let in_flight = proxy_stats::UpstreamRequest::start(config.pool_max_idle, config.http2);
+    if in_flight.pool_exhausted {
+        let count = proxy_stats::UpstreamRequest::exhausted_count();
+        if count == 1 || count % 50 == 0 {
+            plog(
+                "WARN",
+                &format!(
+                    "[proxy] #{} All {} pooled upstream connections busy; waiting on a new one ({} times so far)",
+                    req_id, config.pool_max_idle, count
+                ),
+            );
+        }
+    }
+
+    let timeout_class = TimeoutClass::of(kind, has_accept_stream);
+    if timeout_class != TimeoutClass::Streaming {
+        upstream_req =
+            upstream_req.timeout(Duration::from_secs(config.timeout_secs(timeout_class)));
     }
 
     // Send upstream request
     let upstream_resp = match upstream_req.send().await {
         Ok(resp) => resp,
         Err(e) => {
+            if body_too_large.load(Ordering::Relaxed) {
+                if let Some(exchange) = &exchange {
+                    exchange.error("request body too large");
+                    exchange.finish();
+                }
+                return Ok(error_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Request body too large",
+                ));
+            }
             let elapsed = started.elapsed();
             let is_timeout = e.is_timeout();
             let is_connect = e.is_connect();
+            proxy_stats::record_error(if is_timeout {
+                "timeout"
+            } else if is_connect {
+                "connect"
+            } else {
+                "upstream"
+            });
 
             plog(
-                &log_file,
                 "ERROR",
                 &format!(
                     "[proxy] #{} UPSTREAM ERROR after {:.1}s: {} (timeout={}, connect_err={})",
@@ -344,16 +1837,22 @@ async fn handle_request(
 
             if is_timeout {
                 plog(
-                    &log_file,
                     "ERROR",
                     &format!(
-                        "[proxy] #{} Upstream timed out — OpenCode took longer than {}s to respond",
+                        "[proxy] #{} Upstream timed out — {} took longer than {}s to respond ({} timeout)",
                         req_id,
-                        UPSTREAM_READ_TIMEOUT.as_secs(),
+                        upstream.name(),
+                        config.timeout_secs(timeout_class),
+                        timeout_class.name(),
                     ),
                 );
             }
 
+            if let Some(exchange) = &exchange {
+                exchange.error(&e.to_string());
+                exchange.finish();
+            }
+
             let body = Full::new(Bytes::from(format!("Proxy error: {}", e)));
             return Ok(Response::builder()
                 .status(StatusCode::BAD_GATEWAY)
@@ -363,7 +1862,39 @@ async fn handle_request(
         }
     };
 
+    if rate_limited {
+        let backoff = proxy_limit::on_response(
+            &config,
+            upstream_resp.status().as_u16(),
+            upstream_resp.headers().get(reqwest::header::RETRY_AFTER),
+        );
+        if let Some(backoff) = backoff {
+            plog(
+                "WARN",
+                &format!(
+                    "[proxy] #{} {} answered 429; holding prompts back for {}s",
+                    req_id,
+                    upstream.name(),
+                    backoff.as_secs()
+                ),
+            );
+        }
+    }
+
     let ttfb = started.elapsed();
+    proxy_stats::record_ttfb(ttfb);
+    if let Some(exchange) = &exchange {
+        exchange.response(
+            upstream_resp.status().as_u16(),
+            crate::capture::headers(upstream_resp.headers()),
+        );
+    }
+
+    if upstream_resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cached {
+            return Ok(cached_response(cached));
+        }
+    }
 
     // Build response with same status and headers
     let status = StatusCode::from_u16(upstream_resp.status().as_u16())
@@ -391,7 +1922,6 @@ async fn handle_request(
 
     if kind != "static asset" {
         plog(
-            &log_file,
             "INFO",
             &format!(
                 "[proxy] #{} <- {} {} (TTFB: {:.0}ms, type: {}, length: {}, chunked: {})",
@@ -407,8 +1937,8 @@ async fn handle_request(
     }
 
     if status.is_server_error() {
+        proxy_stats::record_error("server error");
         plog(
-            &log_file,
             "ERROR",
             &format!(
                 "[proxy] #{} Upstream returned server error {} for {} {}",
@@ -422,74 +1952,104 @@ async fn handle_request(
 
     let mut response_builder = Response::builder().status(status);
 
-    // Copy headers but skip content-length for HTML (we'll modify the body)
-    let is_html = content_type.contains("text/html");
-    for (name, value) in upstream_resp.headers() {
-        if let Ok(v) = value.to_str() {
-            // Skip content-length for HTML since we'll inject a script
-            if is_html && name == "content-length" {
+    // HTML gets the scripts configured for its route (see `proxy_scripts`)
+    // injected as it streams through (see `inject_html`). Scripts can only go
+    // into plain text, so HTML is decoded on the way and sent uncompressed;
+    // an encoding that can't be decoded is passed on untouched.
+    let scripts = if content_type.contains("text/html") {
+        proxy_scripts::for_route(upstream, &path)
+    } else {
+        Vec::new()
+    };
+    let mut html_decoder = None;
+    if !scripts.is_empty() {
+        let encoding = upstream_resp
+            .headers()
+            .get("content-encoding")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        match proxy_inject::Decoder::new(encoding) {
+            Ok(decoder) => html_decoder = Some(decoder),
+            Err(e) => plog(
+                "WARN",
+                &format!(
+                    "[proxy] #{} Can't decode HTML ({}), not injecting scripts",
+                    req_id, e
+                ),
+            ),
+        }
+    }
+    let is_html = html_decoder.is_some();
+    // The injected tags carry a nonce that upstream's CSP is changed to
+    // allow (see `proxy_inject::allow_nonce`).
+    let nonce = if is_html {
+        proxy_inject::nonce()
+    } else {
+        String::new()
+    };
+    if let Some(headers) = response_builder.headers_mut() {
+        for (name, value) in upstream_resp.headers() {
+            if is_hop_by_hop(name, upstream_resp.headers()) {
                 continue;
             }
-            response_builder = response_builder.header(name.as_str(), v);
+            if is_html && !proxy_inject::keeps_header(name) {
+                continue;
+            }
+            if is_html
+                && (name == hyper::header::CONTENT_SECURITY_POLICY
+                    || name == hyper::header::CONTENT_SECURITY_POLICY_REPORT_ONLY)
+            {
+                headers.append(
+                    name.clone(),
+                    allow_script_nonce(req_id, name, value, &nonce),
+                );
+                continue;
+            }
+            headers.append(name.clone(), value.clone());
         }
     }
 
-    // For HTML responses, buffer the body and inject the fetch-override script.
-    // This script overrides window.fetch for POST/PUT/PATCH/DELETE so those
-    // requests are relayed via postMessage to the parent Tauri webview, which
-    // executes them through Rust's reqwest (bypassing WKWebView timeouts).
-    if is_html {
-        let html_bytes = match upstream_resp.bytes().await {
-            Ok(b) => b,
-            Err(e) => {
-                plog(
-                    &log_file,
-                    "ERROR",
-                    &format!("[proxy] #{} Failed to read HTML body: {}", req_id, e),
-                );
-                Bytes::from("Proxy error reading HTML")
+    // Keep small static assets that upstream can revalidate by ETag.
+    if cacheable && status == StatusCode::OK {
+        let etag = upstream_resp
+            .headers()
+            .get("etag")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let fits = content_length
+            .as_deref()
+            .and_then(|len| len.parse::<u64>().ok())
+            .is_some_and(|len| len <= proxy_cache::MAX_ENTRY_BYTES);
+        match etag {
+            Some(etag) if fits => {
+                let headers = upstream_resp.headers().clone();
+                return match upstream_resp.bytes().await {
+                    Ok(body) => {
+                        proxy_cache::put(
+                            upstream,
+                            &path,
+                            proxy_cache::CachedAsset {
+                                etag,
+                                status: status.as_u16(),
+                                headers,
+                                body: body.clone(),
+                            },
+                        );
+                        Ok(response_builder
+                            .body(http_body_util::Either::Left(Full::new(body)))
+                            .unwrap())
+                    }
+                    Err(e) => {
+                        plog(
+                            "ERROR",
+                            &format!("[proxy] #{} Failed to read asset body: {}", req_id, e),
+                        );
+                        Ok(error_response(StatusCode::BAD_GATEWAY, &e.to_string()))
+                    }
+                };
             }
-        };
-
-        let html = String::from_utf8_lossy(&html_bytes);
-        let inject_script = FETCH_OVERRIDE_SCRIPT;
-
-        // Inject after <head> tag (or at the very beginning if no <head>)
-        let modified = if let Some(pos) = html.find("<head>") {
-            let insert_at = pos + "<head>".len();
-            format!(
-                "{}<script>{}</script>{}",
-                &html[..insert_at],
-                inject_script,
-                &html[insert_at..]
-            )
-        } else if let Some(pos) = html.find("<HEAD>") {
-            let insert_at = pos + "<HEAD>".len();
-            format!(
-                "{}<script>{}</script>{}",
-                &html[..insert_at],
-                inject_script,
-                &html[insert_at..]
-            )
-        } else {
-            format!("<script>{}</script>{}", inject_script, html)
-        };
-
-        plog(
-            &log_file,
-            "INFO",
-            &format!(
-                "[proxy] #{} Injected fetch-override script into HTML ({} -> {} bytes)",
-                req_id,
-                html_bytes.len(),
-                modified.len(),
-            ),
-        );
-
-        let body = Full::new(Bytes::from(modified));
-        return Ok(response_builder
-            .body(http_body_util::Either::Left(body))
-            .unwrap());
+            _ => proxy_cache::invalidate(upstream, &path),
+        }
     }
 
     // Stream the response body through without buffering.
@@ -501,21 +2061,58 @@ async fn handle_request(
 
     let tb = total_bytes.clone();
     let cc = chunk_count.clone();
-    let lf = log_file.clone();
     let log_req_id = req_id;
     let log_is_streaming = is_streaming;
+    let chunk_exchange = exchange.clone();
+    let active = is_streaming.then(proxy_stats::ActiveStream::start);
+
+    // Only a GET can be safely re-requested when an SSE stream breaks. Other
+    // event streams just get told why they end.
+    let is_sse = content_type.contains("event-stream");
+    let body_stream: ByteStream = if is_sse && method == hyper::Method::GET {
+        let request = SseRequest {
+            client,
+            upstream,
+            path,
+            headers: forwarded_headers,
+            req_id,
+        };
+        resumable_sse(Box::pin(upstream_resp.bytes_stream()), request)
+    } else if is_sse {
+        sse_with_error_event(Box::pin(upstream_resp.bytes_stream()))
+    } else {
+        Box::pin(upstream_resp.bytes_stream())
+    };
+    let body_stream = match html_decoder {
+        Some(decoder) => {
+            let scripts: Vec<String> = scripts
+                .iter()
+                .map(|script| {
+                    script.replace(
+                        "__READ_TIMEOUT_SECS__",
+                        &config.read_timeout_secs.to_string(),
+                    )
+                })
+                .collect();
+            inject_html(body_stream, decoder, &scripts, &nonce, req_id)
+        }
+        None => body_stream,
+    };
 
-    let byte_stream = upstream_resp.bytes_stream().map(move |result| {
+    let byte_stream = body_stream.map(move |result| {
         match result {
             Ok(chunk) => {
                 let size = chunk.len() as u64;
+                proxy_stats::record_bytes(size);
                 let prev_total = tb.fetch_add(size, Ordering::Relaxed);
                 let n = cc.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(exchange) = &chunk_exchange {
+                    exchange.response_chunk(&chunk);
+                }
 
                 // For streaming responses, log periodic progress
                 if log_is_streaming && (n == 1 || n % 50 == 0) {
                     plog(
-                        &lf,
                         "INFO",
                         &format!(
                             "[proxy] #{} streaming: chunk #{}, +{} bytes, total {} bytes, elapsed {:.1}s",
@@ -535,18 +2132,30 @@ async fn handle_request(
                 let total = tb.load(Ordering::Relaxed);
                 let n = cc.load(Ordering::Relaxed);
                 plog(
-                    &lf,
                     "ERROR",
                     &format!(
-                        "[proxy] #{} STREAM ERROR after {:.1}s ({} chunks, {} bytes): {}",
+                        "[proxy] #{} STREAM ERROR after {:.1}s ({} chunks, {} bytes): {}{}",
                         log_req_id,
                         elapsed.as_secs_f64(),
                         n,
                         total,
                         e,
+                        if e.is_timeout() {
+                            format!(" ({} timeout)", timeout_class.name())
+                        } else {
+                            String::new()
+                        },
                     ),
                 );
-                Ok(Frame::data(Bytes::new()))
+                if let Some(exchange) = &chunk_exchange {
+                    exchange.error(&e.to_string());
+                    exchange.finish();
+                }
+                proxy_stats::record_error("stream");
+                // Failing the body makes hyper abort the response instead of
+                // ending it cleanly, so the client sees that it was cut short
+                // rather than taking a partial reply as the whole one.
+                Err(std::io::Error::other(e))
             }
         }
     });
@@ -554,7 +2163,6 @@ async fn handle_request(
     // Log when the stream ends
     let tb_final = total_bytes.clone();
     let cc_final = chunk_count.clone();
-    let lf_final = log_file.clone();
     let final_started = Instant::now();
     let log_kind = kind;
 
@@ -562,9 +2170,13 @@ async fn handle_request(
         let elapsed = final_started.elapsed();
         let total = tb_final.load(Ordering::Relaxed);
         let n = cc_final.load(Ordering::Relaxed);
+        if let Some(exchange) = &exchange {
+            exchange.finish();
+        }
+        drop(active);
+        drop(in_flight);
         if log_kind != "static asset" || elapsed.as_secs() > 5 {
             plog(
-                &lf_final,
                 "INFO",
                 &format!(
                     "[proxy] #{} COMPLETE: {} chunks, {} bytes, {:.1}s total",
@@ -575,6 +2187,12 @@ async fn handle_request(
         Ok(Frame::data(Bytes::new()))
     }));
 
+    let byte_stream = with_heartbeat(
+        byte_stream,
+        is_sse.then_some(SSE_HEARTBEAT_INTERVAL),
+        req_id,
+    );
+
     let stream_body = StreamBody::new(byte_stream);
 
     Ok(response_builder
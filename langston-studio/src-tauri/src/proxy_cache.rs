@@ -0,0 +1,137 @@
+//! In-memory cache of static assets served through the proxy.
+//!
+//! OpenCode's and Remotion's JS, CSS and fonts don't change within a
+//! session, but every UI load fetched them all from upstream again. GET
+//! responses the proxy classifies as static assets are now kept when they
+//! carry an `ETag`. The next request for the same path is sent upstream
+//! with `If-None-Match`; a `304` is answered from the cache, anything else
+//! replaces the entry.
+//!
+//! Entries are evicted least recently used first once the cache holds more
+//! than `MAX_TOTAL_BYTES`. `clear_proxy_cache` empties it.
+
+use crate::proxy::Upstream;
+use bytes::Bytes;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Largest response that's cached.
+pub const MAX_ENTRY_BYTES: u64 = 5 * 1024 * 1024;
+const MAX_TOTAL_BYTES: usize = 64 * 1024 * 1024;
+
+/// A cached response.
+#[derive(Debug, Clone)]
+pub struct CachedAsset {
+    pub etag: String,
+    pub status: u16,
+    pub headers: reqwest::header::HeaderMap,
+    pub body: Bytes,
+}
+
+struct Entry {
+    asset: CachedAsset,
+    /// When the entry was last used, in `Cache::clock` ticks.
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<(Upstream, String), Entry>,
+    bytes: usize,
+    clock: u64,
+}
+
+impl Cache {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn remove(&mut self, key: &(Upstream, String)) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.asset.body.len();
+        }
+    }
+
+    fn evict_to(&mut self, budget: usize) {
+        while self.bytes > budget {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+fn with_cache<T>(f: impl FnOnce(&mut Cache) -> T) -> Option<T> {
+    let mut guard = CACHE.lock().ok()?;
+    Some(f(guard.get_or_insert_with(Cache::default)))
+}
+
+/// The cached response for `path`, marking it as recently used.
+pub fn get(upstream: Upstream, path: &str) -> Option<CachedAsset> {
+    with_cache(|cache| {
+        let now = cache.tick();
+        let entry = cache.entries.get_mut(&(upstream, path.to_string()))?;
+        entry.last_used = now;
+        Some(entry.asset.clone())
+    })
+    .flatten()
+}
+
+/// Cache a response for `path`, replacing any earlier one.
+pub fn put(upstream: Upstream, path: &str, asset: CachedAsset) {
+    if asset.body.len() as u64 > MAX_ENTRY_BYTES {
+        return;
+    }
+    with_cache(|cache| {
+        let key = (upstream, path.to_string());
+        cache.remove(&key);
+        cache.evict_to(MAX_TOTAL_BYTES.saturating_sub(asset.body.len()));
+        let last_used = cache.tick();
+        cache.bytes += asset.body.len();
+        cache.entries.insert(key, Entry { asset, last_used });
+    });
+}
+
+/// Drop the entry for `path`, e.g. when upstream stopped sending an ETag.
+pub fn invalidate(upstream: Upstream, path: &str) {
+    with_cache(|cache| cache.remove(&(upstream, path.to_string())));
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearedCache {
+    pub entries_removed: usize,
+    pub bytes_freed: usize,
+}
+
+/// Empty the proxy's asset cache.
+#[tauri::command]
+pub fn clear_proxy_cache() -> ClearedCache {
+    let cleared = with_cache(|cache| {
+        let cleared = ClearedCache {
+            entries_removed: cache.entries.len(),
+            bytes_freed: cache.bytes,
+        };
+        *cache = Cache::default();
+        cleared
+    })
+    .unwrap_or_default();
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "[proxy] Asset cache cleared ({} entries, {} bytes)",
+            cleared.entries_removed, cleared.bytes_freed
+        ),
+    );
+    cleared
+}
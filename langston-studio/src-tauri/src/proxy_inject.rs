@@ -0,0 +1,530 @@
+//! Injecting scripts into HTML as it streams through the proxy.
+//!
+//! The proxy used to read an HTML response in full, decode it, insert the
+//! script after `<head>` and only then send anything, which held back first
+//! paint and kept very large documents in memory. HTML now flows through
+//! chunk by chunk: `Decoder` undoes the `content-encoding` incrementally and
+//! `HeadInjector` holds back only what arrives before the end of the
+//! `<head>` tag, however the tag is split across chunks. Without a `<head>`
+//! (by `<body>`, `MAX_HEAD_SEARCH` or the end of the document) the scripts
+//! go first. Which scripts is up to `proxy_scripts`.
+//!
+//! A `Content-Security-Policy` header from upstream would block the inline
+//! tags without a word, so each response gets a random `nonce` on its tags
+//! and `allow_nonce` adds it to the policy's script directives. A policy
+//! that already allows inline scripts is left alone: adding a nonce would
+//! switch `'unsafe-inline'` off for the page's own scripts. A policy in a
+//! `<meta>` tag doesn't need it, since it only covers what comes after it
+//! and the tags go right after `<head>`.
+
+use base64::Engine;
+use hyper::header::{self, HeaderName};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// How much of a document is held back looking for `<head>`.
+const MAX_HEAD_SEARCH: usize = 64 * 1024;
+
+/// Where a decoder writes its output, drained after every chunk.
+#[derive(Clone, Default)]
+struct Sink(Arc<Mutex<Vec<u8>>>);
+
+impl Sink {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Stage {
+    Gzip(flate2::write::MultiGzDecoder<Sink>),
+    Zlib(flate2::write::ZlibDecoder<Sink>),
+    Deflate(flate2::write::DeflateDecoder<Sink>),
+    /// "deflate" should be zlib-wrapped, but some servers send it raw; the
+    /// first two bytes tell.
+    DeflateUnknown(Vec<u8>),
+    Brotli(Box<brotli::DecompressorWriter<Sink>>),
+}
+
+impl Stage {
+    fn write(&mut self, data: &[u8], out: &Sink) -> io::Result<()> {
+        match self {
+            Stage::Gzip(d) => d.write_all(data),
+            Stage::Zlib(d) => d.write_all(data),
+            Stage::Deflate(d) => d.write_all(data),
+            Stage::Brotli(d) => d.write_all(data),
+            Stage::DeflateUnknown(head) => {
+                head.extend_from_slice(data);
+                if head.len() < 2 {
+                    return Ok(());
+                }
+                let (cmf, flg) = (head[0], head[1]);
+                let zlib = cmf & 0x0f == 8 && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0;
+                let head = std::mem::take(head);
+                *self = if zlib {
+                    Stage::Zlib(flate2::write::ZlibDecoder::new(out.clone()))
+                } else {
+                    Stage::Deflate(flate2::write::DeflateDecoder::new(out.clone()))
+                };
+                self.write(&head, out)
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Stage::Gzip(d) => d.try_finish(),
+            Stage::Zlib(d) => d.try_finish(),
+            Stage::Deflate(d) => d.try_finish(),
+            Stage::Brotli(d) => d.flush(),
+            Stage::DeflateUnknown(head) if head.is_empty() => Ok(()),
+            Stage::DeflateUnknown(_) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated deflate data",
+            )),
+        }
+    }
+}
+
+/// Undoes a `content-encoding`, which lists codings in the order they were
+/// applied (e.g. "gzip" or "deflate, br"), a chunk at a time.
+pub struct Decoder {
+    stages: Vec<(Stage, Sink)>,
+}
+
+impl Decoder {
+    pub fn new(encoding: &str) -> Result<Decoder, String> {
+        let mut stages = Vec::new();
+        for coding in encoding
+            .split(',')
+            .map(|c| c.trim().to_ascii_lowercase())
+            .rev()
+        {
+            let out = Sink::default();
+            let stage = match coding.as_str() {
+                "" | "identity" => continue,
+                "gzip" | "x-gzip" => Stage::Gzip(flate2::write::MultiGzDecoder::new(out.clone())),
+                "deflate" => Stage::DeflateUnknown(Vec::new()),
+                "br" => Stage::Brotli(Box::new(brotli::DecompressorWriter::new(out.clone(), 4096))),
+                other => return Err(format!("unsupported content-encoding \"{}\"", other)),
+            };
+            stages.push((stage, out));
+        }
+        Ok(Decoder { stages })
+    }
+
+    /// Decode the next chunk. May return nothing while a decoder waits for
+    /// more input.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>, String> {
+        let mut data = chunk.to_vec();
+        for (stage, out) in &mut self.stages {
+            stage
+                .write(&data, out)
+                .map_err(|e| format!("invalid compressed data: {}", e))?;
+            data = out.take();
+        }
+        Ok(data)
+    }
+
+    /// Whatever the decoders still hold once the body has ended.
+    pub fn finish(&mut self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        for (stage, out) in &mut self.stages {
+            if !data.is_empty() {
+                stage
+                    .write(&data, out)
+                    .map_err(|e| format!("invalid compressed data: {}", e))?;
+            }
+            stage
+                .finish()
+                .map_err(|e| format!("invalid compressed data: {}", e))?;
+            data = out.take();
+        }
+        Ok(data)
+    }
+}
+
+/// Case-insensitive search for `needle` (lowercase) in `haystack`.
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Where the `<head ...>` start tag ends in `html`, if it's all there.
+fn head_tag_end(html: &[u8]) -> Option<usize> {
+    let mut from = 0;
+    while let Some(pos) = find_ignore_case(&html[from..], b"<head") {
+        let after = from + pos + "<head".len();
+        match html.get(after) {
+            // Not `<header>` and friends.
+            Some(b'>') => return Some(after + 1),
+            Some(c) if c.is_ascii_whitespace() || *c == b'/' => {
+                return html[after..]
+                    .iter()
+                    .position(|&c| c == b'>')
+                    .map(|end| after + end + 1);
+            }
+            None => return None,
+            Some(_) => from = after,
+        }
+    }
+    None
+}
+
+/// Inserts `<script>` tags right after `<head>` in an HTML stream.
+pub struct HeadInjector {
+    /// `None` once it's been injected.
+    script: Option<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl HeadInjector {
+    /// One tag per script, so an error in one doesn't stop the others, each
+    /// carrying `nonce` (see `allow_nonce`).
+    pub fn new(scripts: &[String], nonce: &str) -> HeadInjector {
+        let tags: String = scripts
+            .iter()
+            .map(|script| format!("<script nonce=\"{}\">{}</script>", nonce, script))
+            .collect();
+        HeadInjector {
+            script: Some(tags.into_bytes()),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Whether the script has gone out yet.
+    pub fn injected(&self) -> bool {
+        self.script.is_none()
+    }
+
+    fn inject_at(&mut self, at: usize) -> Vec<u8> {
+        let script = self.script.take().unwrap_or_default();
+        let mut pending = std::mem::take(&mut self.pending);
+        let rest = pending.split_off(at);
+        pending.extend_from_slice(&script);
+        pending.extend_from_slice(&rest);
+        pending
+    }
+
+    /// The next piece of decoded HTML. Returns what can be sent on now.
+    pub fn push(&mut self, html: &[u8]) -> Vec<u8> {
+        if self.injected() {
+            return html.to_vec();
+        }
+        self.pending.extend_from_slice(html);
+        if let Some(at) = head_tag_end(&self.pending) {
+            return self.inject_at(at);
+        }
+        if self.pending.len() > MAX_HEAD_SEARCH
+            || find_ignore_case(&self.pending, b"<body").is_some()
+        {
+            return self.inject_at(0);
+        }
+        Vec::new()
+    }
+
+    /// The end of the document: send what was held back, with the script
+    /// first if there was no `<head>`.
+    pub fn finish(&mut self) -> Vec<u8> {
+        if self.injected() {
+            return Vec::new();
+        }
+        self.inject_at(0)
+    }
+}
+
+/// Whether an upstream header is passed on with HTML that gets scripts
+/// injected. The body is sent decoded and its length changes, so
+/// `content-encoding` and `content-length` no longer hold.
+pub fn keeps_header(name: &HeaderName) -> bool {
+    name != header::CONTENT_LENGTH && name != header::CONTENT_ENCODING
+}
+
+/// A fresh nonce for one response's script tags.
+pub fn nonce() -> String {
+    let mut bytes = [0u8; 18];
+    getrandom::getrandom(&mut bytes).expect("Failed to generate script nonce");
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Whether `sources` already lets inline scripts run. `'unsafe-inline'` is
+/// ignored once a nonce, hash or `'strict-dynamic'` is present.
+fn allows_inline(sources: &[&str]) -> bool {
+    let lowered: Vec<String> = sources.iter().map(|s| s.to_ascii_lowercase()).collect();
+    lowered.iter().any(|s| s == "'unsafe-inline'")
+        && !lowered
+            .iter()
+            .any(|s| s.starts_with("'nonce-") || s.starts_with("'sha") || s == "'strict-dynamic'")
+}
+
+/// Add `nonce` to the directive that governs inline scripts in one policy.
+fn allow_nonce_in_policy(policy: &str, nonce: &str) -> Option<String> {
+    let source = format!("'nonce-{}'", nonce);
+    let mut directives: Vec<Vec<&str>> = policy
+        .split(';')
+        .map(|directive| directive.split_ascii_whitespace().collect::<Vec<_>>())
+        .filter(|directive| !directive.is_empty())
+        .collect();
+    // Both script directives apply to a `<script>` when present, and
+    // `default-src` only when neither is.
+    let mut targets: Vec<usize> = ["script-src-elem", "script-src"]
+        .iter()
+        .filter_map(|name| {
+            directives
+                .iter()
+                .position(|d| d[0].eq_ignore_ascii_case(name))
+        })
+        .collect();
+    if targets.is_empty() {
+        targets.extend(
+            directives
+                .iter()
+                .position(|d| d[0].eq_ignore_ascii_case("default-src")),
+        );
+    }
+    let mut changed = false;
+    for index in targets {
+        let directive = &mut directives[index];
+        if allows_inline(&directive[1..]) {
+            continue;
+        }
+        directive.retain(|s| !s.eq_ignore_ascii_case("'none'"));
+        directive.push(&source);
+        changed = true;
+    }
+    changed.then(|| {
+        directives
+            .iter()
+            .map(|directive| directive.join(" "))
+            .collect::<Vec<_>>()
+            .join("; ")
+    })
+}
+
+/// `header`, a `Content-Security-Policy` value, changed so that script tags
+/// carrying `nonce` run. `None` when it lets them run already.
+pub fn allow_nonce(header: &str, nonce: &str) -> Option<String> {
+    // Several policies can be folded into one header, separated by commas,
+    // and each of them has to allow the script.
+    let mut changed = false;
+    let policies: Vec<String> = header
+        .split(',')
+        .map(|policy| match allow_nonce_in_policy(policy, nonce) {
+            Some(rewritten) => {
+                changed = true;
+                rewritten
+            }
+            None => policy.trim().to_string(),
+        })
+        .collect();
+    changed.then(|| policies.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    const PAGE: &str = "<!doctype html><html><head lang=\"en\"><title>OpenCode</title></head>\
+                        <body><div id=\"root\"></div></body></html>";
+    const SCRIPT: &str = "window.injected = true;";
+    const NONCE: &str = "abc123";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn raw_deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn brotli(data: &[u8]) -> Vec<u8> {
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data).unwrap();
+        encoder.into_inner()
+    }
+
+    /// Send `body` through a decoder and injector in chunks of `chunk_size`,
+    /// as `inject_html` does.
+    fn run(encoding: &str, body: &[u8], chunk_size: usize) -> Result<String, String> {
+        let mut decoder = Decoder::new(encoding)?;
+        let mut injector = HeadInjector::new(&[SCRIPT.to_string()], NONCE);
+        let mut out = Vec::new();
+        for chunk in body.chunks(chunk_size) {
+            out.extend(injector.push(&decoder.push(chunk)?));
+        }
+        out.extend(injector.push(&decoder.finish()?));
+        out.extend(injector.finish());
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn injected_page() -> String {
+        PAGE.replacen(
+            "<head lang=\"en\">",
+            &format!(
+                "<head lang=\"en\"><script nonce=\"{}\">{}</script>",
+                NONCE, SCRIPT
+            ),
+            1,
+        )
+    }
+
+    #[test]
+    fn identity_is_passed_through_with_the_script_after_head() {
+        assert_eq!(run("", PAGE.as_bytes(), 4096).unwrap(), injected_page());
+        assert_eq!(
+            run("identity", PAGE.as_bytes(), 7).unwrap(),
+            injected_page()
+        );
+    }
+
+    #[test]
+    fn gzip_is_decoded_whole_and_split_across_chunks() {
+        let body = gzip(PAGE.as_bytes());
+        for chunk_size in [body.len(), 64, 5, 1] {
+            assert_eq!(run("gzip", &body, chunk_size).unwrap(), injected_page());
+        }
+    }
+
+    #[test]
+    fn deflate_is_decoded_zlib_wrapped_or_raw() {
+        for body in [zlib(PAGE.as_bytes()), raw_deflate(PAGE.as_bytes())] {
+            for chunk_size in [body.len(), 3, 1] {
+                assert_eq!(run("deflate", &body, chunk_size).unwrap(), injected_page());
+            }
+        }
+    }
+
+    #[test]
+    fn brotli_is_decoded_split_across_chunks() {
+        let body = brotli(PAGE.as_bytes());
+        for chunk_size in [body.len(), 16, 1] {
+            assert_eq!(run("br", &body, chunk_size).unwrap(), injected_page());
+        }
+    }
+
+    #[test]
+    fn stacked_encodings_are_undone_in_reverse() {
+        let body = brotli(&gzip(PAGE.as_bytes()));
+        assert_eq!(run("gzip, br", &body, 9).unwrap(), injected_page());
+    }
+
+    #[test]
+    fn head_tag_split_across_chunks_is_found() {
+        let mut injector = HeadInjector::new(&[SCRIPT.to_string()], NONCE);
+        let mut out = Vec::new();
+        for piece in ["<html><he", "ad lang=\"en", "\"><title>x</title></head>"] {
+            out.extend(injector.push(piece.as_bytes()));
+        }
+        out.extend(injector.finish());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "<html><head lang=\"en\"><script nonce=\"{}\">{}</script><title>x</title></head>",
+                NONCE, SCRIPT
+            )
+        );
+    }
+
+    #[test]
+    fn header_element_is_not_head() {
+        let html = "<body><header>Hi</header></body>";
+        let out = run("", html.as_bytes(), 4096).unwrap();
+        assert!(out.starts_with("<script nonce="));
+        assert!(out.ends_with(html));
+    }
+
+    #[test]
+    fn without_head_the_script_goes_first() {
+        let html = "<p>fragment</p>";
+        assert_eq!(
+            run("gzip", &gzip(html.as_bytes()), 4).unwrap(),
+            format!("<script nonce=\"{}\">{}</script>{}", NONCE, SCRIPT, html)
+        );
+    }
+
+    #[test]
+    fn nothing_is_sent_before_head_is_complete() {
+        let mut injector = HeadInjector::new(&[SCRIPT.to_string()], NONCE);
+        assert!(injector.push(b"<html><head").is_empty());
+        assert!(!injector.injected());
+        assert!(!injector.push(b">").is_empty());
+        assert!(injector.injected());
+        assert_eq!(injector.push(b"rest"), b"rest");
+    }
+
+    #[test]
+    fn corrupt_and_truncated_bodies_are_errors() {
+        assert!(run("gzip", b"definitely not gzip", 4).is_err());
+        let body = gzip(PAGE.as_bytes());
+        assert!(run("gzip", &body[..body.len() / 2], 8).is_err());
+        assert!(run("deflate", &[0x78], 1).is_err());
+    }
+
+    #[test]
+    fn unsupported_encodings_are_rejected() {
+        assert!(Decoder::new("zstd").is_err());
+        assert!(Decoder::new("gzip, compress").is_err());
+    }
+
+    #[test]
+    fn length_and_encoding_headers_are_dropped() {
+        let mut upstream = hyper::HeaderMap::new();
+        upstream.insert(header::CONTENT_LENGTH, HeaderValue::from_static("123"));
+        upstream.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        upstream.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        upstream.insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+        let kept: Vec<&str> = upstream
+            .keys()
+            .filter(|name| keeps_header(name))
+            .map(|name| name.as_str())
+            .collect();
+        assert_eq!(kept, ["content-type", "etag"]);
+    }
+
+    #[test]
+    fn csp_gets_the_nonce_unless_inline_scripts_run_already() {
+        assert_eq!(
+            allow_nonce("default-src 'self'; script-src 'self'", NONCE).as_deref(),
+            Some("default-src 'self'; script-src 'self' 'nonce-abc123'")
+        );
+        assert_eq!(
+            allow_nonce("default-src 'none'", NONCE).as_deref(),
+            Some("default-src 'nonce-abc123'")
+        );
+        assert_eq!(
+            allow_nonce("script-src 'self' 'unsafe-inline'", NONCE),
+            None
+        );
+        assert_eq!(allow_nonce("img-src *", NONCE), None);
+    }
+}
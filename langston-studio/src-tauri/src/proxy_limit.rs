@@ -0,0 +1,378 @@
+//! Pacing of the prompts sent to OpenCode.
+//!
+//! Sending messages in quick succession got the provider answering 429, and
+//! each of those surfaced as its own confusing failure. With
+//! `proxyRateLimitPerMin` set in config.json, requests that send a prompt
+//! (`POST /session/:id/message`, `prompt_async`, `command` and `shell`)
+//! take a token from a bucket that refills at that rate and holds up to
+//! `proxyRateLimitBurst` (default `DEFAULT_BURST`). When it's empty they
+//! wait, in the order they came, instead of being rejected.
+//!
+//! Whether or not the limit is on, an upstream 429 on one of those requests
+//! holds the next ones back for its `Retry-After`, or for a backoff that
+//! starts at `INITIAL_BACKOFF` and doubles up to `MAX_BACKOFF` while 429s
+//! keep coming. The first answer that isn't a 429 resets it.
+//!
+//! OpenCode accepts a prompt before asking the provider, so the provider's
+//! own 429 doesn't come back on the request: it's a `session.error` event on
+//! the event stream, with the provider's status code and response headers.
+//! The proxy looks for those in the streams it passes through
+//! (`rate_limit_event`) and backs off for them the same way (`back_off`).
+//! Every open stream carries the same event, and a stream resumed with
+//! Last-Event-ID can replay it, so each is acted on once (`first_sighting`).
+//!
+//! `stats` is part of `get_proxy_stats`.
+
+use crate::proxy::{ProxyConfig, Upstream};
+use crate::sessions;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BURST: u64 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How long a rate limit event is remembered after it's first seen.
+const SEEN_FOR: Duration = Duration::from_secs(60);
+/// Prompt requests end in one of these after the session id.
+const PROMPT_ROUTES: &[&str] = &["message", "prompt_async", "command", "shell"];
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+    /// Set after a 429; nothing is let through before it.
+    blocked_until: Option<Instant>,
+    backoff: Duration,
+}
+
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+/// Held while waiting for a token, so waiting requests go in order.
+static QUEUE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+static LIMITED: AtomicU64 = AtomicU64::new(0);
+static QUEUED_NOW: AtomicU64 = AtomicU64::new(0);
+static QUEUED_TOTAL: AtomicU64 = AtomicU64::new(0);
+static WAIT_TOTAL_MS: AtomicU64 = AtomicU64::new(0);
+static WAIT_MAX_MS: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_429: AtomicU64 = AtomicU64::new(0);
+/// Rate limit events seen in the last `SEEN_FOR`, by session and event id.
+static SEEN: Mutex<Vec<(String, Instant)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimiterStats {
+    /// 0 when only 429 backoff applies.
+    pub rate_per_min: u64,
+    pub burst: u64,
+    /// Prompt requests seen.
+    pub limited_requests: u64,
+    /// Waiting right now.
+    pub queued: u64,
+    /// Had to wait at all.
+    pub queued_total: u64,
+    pub wait_max_ms: u64,
+    pub wait_avg_ms: Option<u64>,
+    pub upstream_rate_limited: u64,
+    /// Until the current 429 backoff ends.
+    pub backoff_remaining_ms: u64,
+}
+
+/// Whether a request sends a prompt and so goes through the limiter.
+pub fn applies(upstream: Upstream, method: &hyper::Method, path: &str) -> bool {
+    upstream == Upstream::OpenCode
+        && method == hyper::Method::POST
+        && sessions::session_in_path(path).is_some()
+        && path
+            .rsplit('/')
+            .next()
+            .is_some_and(|last| PROMPT_ROUTES.contains(&last))
+}
+
+fn with_bucket<T>(config: &ProxyConfig, f: impl FnOnce(&mut Bucket) -> T) -> Option<T> {
+    let mut guard = BUCKET.lock().ok()?;
+    let bucket = guard.get_or_insert_with(|| Bucket {
+        tokens: config.rate_limit_burst as f64,
+        refilled: Instant::now(),
+        blocked_until: None,
+        backoff: INITIAL_BACKOFF,
+    });
+    Some(f(bucket))
+}
+
+/// How long to wait before a token can be taken, taking it if that's now.
+fn try_take(config: &ProxyConfig) -> Duration {
+    with_bucket(config, |bucket| {
+        let now = Instant::now();
+        if let Some(until) = bucket.blocked_until {
+            if until > now {
+                return until - now;
+            }
+            bucket.blocked_until = None;
+        }
+        if config.rate_limit_per_min == 0 {
+            return Duration::ZERO;
+        }
+        let per_sec = config.rate_limit_per_min as f64 / 60.0;
+        let burst = config.rate_limit_burst.max(1) as f64;
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.refilled).as_secs_f64() * per_sec)
+            .min(burst);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec)
+        }
+    })
+    .unwrap_or(Duration::ZERO)
+}
+
+/// Counts as queued until dropped, which may be because the client went
+/// away while waiting.
+struct Queued(());
+
+impl Queued {
+    fn start() -> Queued {
+        QUEUED_NOW.fetch_add(1, Ordering::Relaxed);
+        Queued(())
+    }
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        QUEUED_NOW.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Wait for this request's turn. Returns how long it waited.
+pub async fn acquire(config: &ProxyConfig) -> Duration {
+    LIMITED.fetch_add(1, Ordering::Relaxed);
+    let started = Instant::now();
+    {
+        let _queued = Queued::start();
+        let _turn = QUEUE.lock().await;
+        loop {
+            let wait = try_take(config);
+            if wait.is_zero() {
+                break;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+    let waited = started.elapsed();
+    // Waiting on the queue lock alone takes a moment.
+    if waited >= Duration::from_millis(10) {
+        let ms = waited.as_millis() as u64;
+        QUEUED_TOTAL.fetch_add(1, Ordering::Relaxed);
+        WAIT_TOTAL_MS.fetch_add(ms, Ordering::Relaxed);
+        WAIT_MAX_MS.fetch_max(ms, Ordering::Relaxed);
+    }
+    waited
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+/// Note the upstream's answer to a prompt request. Returns the backoff
+/// started if it was a 429.
+pub fn on_response(
+    config: &ProxyConfig,
+    status: u16,
+    retry_after: Option<&reqwest::header::HeaderValue>,
+) -> Option<Duration> {
+    if status != 429 {
+        with_bucket(config, |bucket| bucket.backoff = INITIAL_BACKOFF);
+        return None;
+    }
+    back_off(
+        config,
+        retry_after
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after),
+    )
+}
+
+/// A provider rate limit OpenCode reported on its event stream.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RateLimitEvent {
+    pub session: String,
+    pub retry_after: Option<Duration>,
+}
+
+/// The rate limit `data`, one event from OpenCode's event stream, reports,
+/// if it's a `session.error` for a 429 from the provider.
+pub fn rate_limit_event(data: &str) -> Option<RateLimitEvent> {
+    // Most events aren't errors; skip parsing those.
+    if !data.contains("session.error") {
+        return None;
+    }
+    let event: serde_json::Value = serde_json::from_str(data).ok()?;
+    if event.get("type")?.as_str()? != "session.error" {
+        return None;
+    }
+    let properties = event.get("properties")?;
+    let error = properties.pointer("/error/data")?;
+    if error.get("statusCode")?.as_u64()? != 429 {
+        return None;
+    }
+    let retry_after = error
+        .get("responseHeaders")
+        .and_then(|headers| headers.as_object())
+        .and_then(|headers| {
+            headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("retry-after"))
+        })
+        .and_then(|(_, value)| value.as_str())
+        .and_then(parse_retry_after);
+    Some(RateLimitEvent {
+        session: properties
+            .get("sessionID")
+            .and_then(|id| id.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        retry_after,
+    })
+}
+
+/// Whether this is the first time `event` turns up within `SEEN_FOR`.
+/// `event_id` is its SSE id, or its data when it has none.
+pub fn first_sighting(event: &RateLimitEvent, event_id: &str) -> bool {
+    seen_first_at(format!("{}/{}", event.session, event_id), Instant::now())
+}
+
+fn seen_first_at(key: String, now: Instant) -> bool {
+    let Ok(mut seen) = SEEN.lock() else {
+        return true;
+    };
+    seen.retain(|(_, at)| now.saturating_duration_since(*at) < SEEN_FOR);
+    if seen.iter().any(|(k, _)| *k == key) {
+        return false;
+    }
+    seen.push((key, now));
+    true
+}
+
+/// Hold prompts back after a 429, for `retry_after` if the provider said,
+/// or the current backoff. Returns how long.
+pub fn back_off(config: &ProxyConfig, retry_after: Option<Duration>) -> Option<Duration> {
+    UPSTREAM_429.fetch_add(1, Ordering::Relaxed);
+    with_bucket(config, |bucket| {
+        let delay = retry_after.unwrap_or(bucket.backoff).min(MAX_BACKOFF);
+        bucket.backoff = (bucket.backoff * 2).min(MAX_BACKOFF);
+        let until = Instant::now() + delay;
+        bucket.blocked_until = Some(bucket.blocked_until.map_or(until, |u| u.max(until)));
+        delay
+    })
+}
+
+pub fn stats(config: &ProxyConfig) -> LimiterStats {
+    let queued_total = QUEUED_TOTAL.load(Ordering::Relaxed);
+    let backoff_remaining_ms = BUCKET
+        .lock()
+        .ok()
+        .and_then(|b| b.as_ref().and_then(|b| b.blocked_until))
+        .map_or(0, |until| {
+            until.saturating_duration_since(Instant::now()).as_millis() as u64
+        });
+    LimiterStats {
+        rate_per_min: config.rate_limit_per_min,
+        burst: config.rate_limit_burst,
+        limited_requests: LIMITED.load(Ordering::Relaxed),
+        queued: QUEUED_NOW.load(Ordering::Relaxed),
+        queued_total,
+        wait_max_ms: WAIT_MAX_MS.load(Ordering::Relaxed),
+        wait_avg_ms: (queued_total > 0)
+            .then(|| WAIT_TOTAL_MS.load(Ordering::Relaxed) / queued_total),
+        upstream_rate_limited: UPSTREAM_429.load(Ordering::Relaxed),
+        backoff_remaining_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_lets_a_burst_through_then_paces_and_backs_off() {
+        *BUCKET.lock().unwrap() = None;
+        let config = ProxyConfig {
+            rate_limit_per_min: 60,
+            rate_limit_burst: 2,
+            ..ProxyConfig::from_app_config(&crate::AppConfig::default())
+        };
+        assert_eq!(try_take(&config), Duration::ZERO);
+        assert_eq!(try_take(&config), Duration::ZERO);
+        let wait = try_take(&config);
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+
+        assert_eq!(
+            back_off(&config, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+        assert!(try_take(&config) > Duration::from_secs(4));
+        // Without a Retry-After the backoff doubles while 429s keep coming,
+        // and an answer that isn't one resets it.
+        assert_eq!(on_response(&config, 200, None), None);
+        assert_eq!(back_off(&config, None), Some(INITIAL_BACKOFF));
+        assert_eq!(back_off(&config, None), Some(INITIAL_BACKOFF * 2));
+        assert_eq!(on_response(&config, 200, None), None);
+        assert_eq!(back_off(&config, None), Some(INITIAL_BACKOFF));
+        assert_eq!(
+            back_off(&config, Some(Duration::from_secs(600))),
+            Some(MAX_BACKOFF)
+        );
+    }
+
+    #[test]
+    fn provider_429_on_the_event_stream_is_a_rate_limit() {
+        let data = r#"{"type":"session.error","properties":{"sessionID":"ses_1","error":{"name":"APIError","data":{"message":"Too many requests","statusCode":429,"isRetryable":true,"responseHeaders":{"Retry-After":"12"}}}}}"#;
+        assert_eq!(
+            rate_limit_event(data),
+            Some(RateLimitEvent {
+                session: "ses_1".to_string(),
+                retry_after: Some(Duration::from_secs(12)),
+            })
+        );
+    }
+
+    #[test]
+    fn rate_limit_without_retry_after() {
+        let data = r#"{"type":"session.error","properties":{"sessionID":"ses_1","error":{"name":"APIError","data":{"message":"rate limited","statusCode":429,"isRetryable":true}}}}"#;
+        assert_eq!(rate_limit_event(data).map(|e| e.retry_after), Some(None));
+    }
+
+    #[test]
+    fn a_replayed_rate_limit_is_only_acted_on_once() {
+        let data = r#"{"type":"session.error","properties":{"sessionID":"ses_replay","error":{"name":"APIError","data":{"message":"Too many requests","statusCode":429}}}}"#;
+        let event = rate_limit_event(data).unwrap();
+        assert!(first_sighting(&event, "41"));
+        // Another stream, or the same one resumed after Last-Event-ID 40.
+        assert!(!first_sighting(&event, "41"));
+        assert!(first_sighting(&event, "42"));
+
+        let now = Instant::now();
+        assert!(seen_first_at("ses_replay/43".to_string(), now));
+        assert!(!seen_first_at(
+            "ses_replay/43".to_string(),
+            now + Duration::from_secs(1)
+        ));
+        assert!(seen_first_at("ses_replay/43".to_string(), now + SEEN_FOR));
+    }
+
+    #[test]
+    fn other_events_and_errors_are_not_rate_limits() {
+        for data in [
+            r#"{"type":"session.idle","properties":{"sessionID":"ses_1"}}"#,
+            r#"{"type":"session.error","properties":{"sessionID":"ses_1","error":{"name":"APIError","data":{"message":"overloaded","statusCode":529}}}}"#,
+            r#"{"type":"session.error","properties":{"sessionID":"ses_1","error":{"name":"MessageAbortedError","data":{"message":"aborted"}}}}"#,
+            r#"{"type":"message.part.updated","properties":{"part":{"text":"session.error 429"}}}"#,
+            "session.error",
+        ] {
+            assert_eq!(rate_limit_event(data), None, "{}", data);
+        }
+    }
+}
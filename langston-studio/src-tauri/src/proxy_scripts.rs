@@ -0,0 +1,279 @@
+//! The scripts the proxy injects into HTML pages.
+//!
+//! The fetch-override script used to be a string constant compiled into the
+//! binary, so every tweak meant a rebuild and a deployment couldn't adjust
+//! it. Scripts now live in the `inject` resource folder next to a manifest,
+//! `scripts.json`:
+//!
+//! ```json
+//! { "scripts": [{ "name": "fetch-override", "file": "fetch-override.js",
+//!                 "upstreams": ["opencode"], "routes": ["*"] }] }
+//! ```
+//!
+//! `upstreams` limits a script to the OpenCode or Remotion proxy (both when
+//! empty) and `routes` to request paths, where `*` matches anything. Every
+//! matching script is injected, in manifest order, in a `<script>` tag of
+//! its own.
+//!
+//! `injectScriptsDir` in config.json points at another folder laid out the
+//! same way, for working on the scripts. Dev builds notice changes to the
+//! manifest or a script within `HOT_RELOAD_INTERVAL` and reload them; release
+//! builds reload when the proxy settings are reloaded. If the folder can't
+//! be read, the copies bundled at build time are used.
+
+use crate::proxy::Upstream;
+use crate::{expand_home, read_config_file};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+const MANIFEST_FILE: &str = "scripts.json";
+const BUNDLED_MANIFEST: &str = include_str!("../../resources/inject/scripts.json");
+/// The scripts `BUNDLED_MANIFEST` refers to.
+const BUNDLED_SCRIPTS: &[(&str, &str)] = &[
+    (
+        "fetch-override.js",
+        include_str!("../../resources/inject/fetch-override.js"),
+    ),
+    (
+        "prompt-prefill.js",
+        include_str!("../../resources/inject/prompt-prefill.js"),
+    ),
+    (
+        "session-resume.js",
+        include_str!("../../resources/inject/session-resume.js"),
+    ),
+];
+/// How often dev builds check the scripts for changes.
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+static RESOURCE_DIR: OnceLock<PathBuf> = OnceLock::new();
+static LOADED: RwLock<Option<Loaded>> = RwLock::new(None);
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    scripts: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    name: String,
+    file: String,
+    #[serde(default)]
+    upstreams: Vec<String>,
+    #[serde(default = "all_routes")]
+    routes: Vec<String>,
+}
+
+fn all_routes() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+struct Script {
+    upstreams: Vec<String>,
+    routes: Vec<String>,
+    source: String,
+}
+
+impl Script {
+    fn applies_to(&self, upstream: Upstream, path: &str) -> bool {
+        let upstream_matches = self.upstreams.is_empty()
+            || self
+                .upstreams
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(upstream.name()));
+        upstream_matches && self.routes.iter().any(|route| matches(route, path))
+    }
+}
+
+struct Loaded {
+    scripts: Vec<Script>,
+    /// The files read, with their modification times, so dev builds can
+    /// tell when to reload. Empty for the bundled copies.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    checked: Instant,
+}
+
+/// Whether `path` matches `pattern`, where `*` stands for any run of
+/// characters.
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*`: the whole path has to match.
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The folder scripts are read from: `injectScriptsDir`, or the bundled
+/// resources.
+fn scripts_dir() -> Option<PathBuf> {
+    match read_config_file().inject_scripts_dir {
+        Some(dir) if !dir.trim().is_empty() => Some(expand_home(dir.trim())),
+        _ => RESOURCE_DIR.get().cloned(),
+    }
+}
+
+/// The scripts `manifest` lists, with their source from `read`.
+fn parse(
+    manifest: &str,
+    mut read: impl FnMut(&str) -> Result<String, String>,
+) -> Result<Vec<Script>, String> {
+    let manifest: Manifest =
+        serde_json::from_str(manifest).map_err(|e| format!("Invalid {}: {}", MANIFEST_FILE, e))?;
+    manifest
+        .scripts
+        .into_iter()
+        .map(|entry| {
+            let source = read(&entry.file)
+                .map_err(|e| format!("Script {} ({}): {}", entry.name, entry.file, e))?;
+            Ok(Script {
+                upstreams: entry.upstreams,
+                routes: entry.routes,
+                source,
+            })
+        })
+        .collect()
+}
+
+fn load_dir(dir: &Path) -> Result<Loaded, String> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let manifest = fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Failed to read {}: {}", manifest_path.display(), e))?;
+    let mut files = vec![(manifest_path.clone(), modified(&manifest_path))];
+    let scripts = parse(&manifest, |file| {
+        let path = dir.join(file);
+        let time = modified(&path);
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        files.push((path, time));
+        Ok(source)
+    })?;
+    Ok(Loaded {
+        scripts,
+        files,
+        checked: Instant::now(),
+    })
+}
+
+fn load_bundled() -> Loaded {
+    let scripts = parse(BUNDLED_MANIFEST, |file| {
+        BUNDLED_SCRIPTS
+            .iter()
+            .find(|(name, _)| *name == file)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| "not bundled".to_string())
+    })
+    .unwrap_or_default();
+    Loaded {
+        scripts,
+        files: Vec::new(),
+        checked: Instant::now(),
+    }
+}
+
+fn load() -> Loaded {
+    let Some(dir) = scripts_dir() else {
+        return load_bundled();
+    };
+    match load_dir(&dir) {
+        Ok(loaded) => {
+            crate::logging::log_line(
+                "INFO",
+                &format!(
+                    "[proxy] Loaded {} injection script(s) from {}",
+                    loaded.scripts.len(),
+                    dir.display()
+                ),
+            );
+            loaded
+        }
+        Err(e) => {
+            crate::logging::log_line(
+                "WARN",
+                &format!("[proxy] {}; using the bundled injection scripts", e),
+            );
+            load_bundled()
+        }
+    }
+}
+
+/// Where the bundled resources are; the scripts are in its `inject` folder.
+pub fn init(resource_dir: &Path) {
+    let _ = RESOURCE_DIR.set(resource_dir.join("inject"));
+}
+
+/// Read the scripts again, e.g. after `injectScriptsDir` changed.
+pub fn reload() {
+    let loaded = load();
+    if let Ok(mut current) = LOADED.write() {
+        *current = Some(loaded);
+    }
+}
+
+/// In dev builds, reload if a script changed since it was read.
+fn reload_if_changed() {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let changed = {
+        let Ok(mut current) = LOADED.write() else {
+            return;
+        };
+        let Some(loaded) = current.as_mut() else {
+            return;
+        };
+        if loaded.checked.elapsed() < HOT_RELOAD_INTERVAL {
+            return;
+        }
+        loaded.checked = Instant::now();
+        loaded
+            .files
+            .iter()
+            .any(|(path, time)| modified(path) != *time)
+    };
+    if changed {
+        crate::logging::log_line("INFO", "[proxy] Injection scripts changed, reloading");
+        reload();
+    }
+}
+
+/// The scripts to inject into an HTML page at `path` from `upstream`.
+pub fn for_route(upstream: Upstream, path: &str) -> Vec<String> {
+    if LOADED.read().map(|l| l.is_none()).unwrap_or(true) {
+        reload();
+    }
+    reload_if_changed();
+    LOADED
+        .read()
+        .ok()
+        .and_then(|loaded| {
+            loaded.as_ref().map(|loaded| {
+                loaded
+                    .scripts
+                    .iter()
+                    .filter(|script| script.applies_to(upstream, path))
+                    .map(|script| script.source.clone())
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,178 @@
+//! Counters for the proxy, for a connection health indicator and for
+//! debugging slowness reports.
+//!
+//! Kept for the whole session: requests by class (see
+//! `proxy::classify_request`), bytes streamed to the webview, streams open
+//! right now, errors by kind, and the median and 95th percentile time to
+//! first byte over the last `TTFB_SAMPLES` requests. Read them with
+//! `get_proxy_stats` or from the proxy itself at `/__proxy/stats`.
+//!
+//! Upstream requests in flight are counted too, with their peak. Over
+//! HTTP/1 each one holds a connection, so one that starts while more are in
+//! flight than the pool keeps idle has to wait for a new connection; those
+//! are counted as `poolExhausted`.
+//!
+//! `rateLimit` covers the prompts held back by the limiter and the 429s
+//! behind them; see `proxy_limit`.
+
+use crate::{proxy, proxy_limit};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Path the proxy answers with the stats instead of forwarding.
+pub const STATS_PATH: &str = "/__proxy/stats";
+/// TTFB samples the percentiles are computed from.
+const TTFB_SAMPLES: usize = 1000;
+
+#[derive(Default)]
+struct Stats {
+    started: Option<Instant>,
+    requests: BTreeMap<&'static str, u64>,
+    errors: BTreeMap<&'static str, u64>,
+    ttfb_ms: VecDeque<u64>,
+}
+
+static STATS: Mutex<Option<Stats>> = Mutex::new(None);
+static BYTES_STREAMED: AtomicU64 = AtomicU64::new(0);
+static ACTIVE_STREAMS: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_IN_FLIGHT_PEAK: AtomicU64 = AtomicU64::new(0);
+static POOL_EXHAUSTED: AtomicU64 = AtomicU64::new(0);
+
+fn with_stats(f: impl FnOnce(&mut Stats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        let stats = guard.get_or_insert_with(Stats::default);
+        stats.started.get_or_insert_with(Instant::now);
+        f(stats);
+    }
+}
+
+pub fn record_request(class: &'static str) {
+    with_stats(|stats| *stats.requests.entry(class).or_insert(0) += 1);
+}
+
+/// Count an error, e.g. "timeout", "connect", "stream" or "rejected".
+pub fn record_error(kind: &'static str) {
+    with_stats(|stats| *stats.errors.entry(kind).or_insert(0) += 1);
+}
+
+pub fn record_ttfb(ttfb: Duration) {
+    with_stats(|stats| {
+        if stats.ttfb_ms.len() == TTFB_SAMPLES {
+            stats.ttfb_ms.pop_front();
+        }
+        stats.ttfb_ms.push_back(ttfb.as_millis() as u64);
+    });
+}
+
+pub fn record_bytes(bytes: u64) {
+    BYTES_STREAMED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Counts as an active stream until dropped.
+pub struct ActiveStream(());
+
+impl ActiveStream {
+    pub fn start() -> ActiveStream {
+        ACTIVE_STREAMS.fetch_add(1, Ordering::Relaxed);
+        ActiveStream(())
+    }
+}
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        ACTIVE_STREAMS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts as an upstream request in flight until dropped, which for a
+/// streamed response is when its body ends.
+pub struct UpstreamRequest {
+    /// Whether every pooled connection was busy when it started.
+    pub pool_exhausted: bool,
+}
+
+impl UpstreamRequest {
+    /// `pool_size` is how many connections the pool keeps; `multiplexed`
+    /// (HTTP/2) requests share one and never wait for the pool.
+    pub fn start(pool_size: usize, multiplexed: bool) -> UpstreamRequest {
+        let busy = UPSTREAM_IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        UPSTREAM_IN_FLIGHT_PEAK.fetch_max(busy + 1, Ordering::Relaxed);
+        let pool_exhausted = !multiplexed && busy >= pool_size as u64;
+        if pool_exhausted {
+            POOL_EXHAUSTED.fetch_add(1, Ordering::Relaxed);
+        }
+        UpstreamRequest { pool_exhausted }
+    }
+
+    /// Requests that have found the pool exhausted this session.
+    pub fn exhausted_count() -> u64 {
+        POOL_EXHAUSTED.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for UpstreamRequest {
+    fn drop(&mut self) {
+        UPSTREAM_IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyStats {
+    pub uptime_secs: u64,
+    pub total_requests: u64,
+    pub requests: BTreeMap<&'static str, u64>,
+    pub bytes_streamed: u64,
+    pub active_streams: u64,
+    pub upstream_in_flight: u64,
+    pub upstream_in_flight_peak: u64,
+    pub pool_exhausted: u64,
+    pub total_errors: u64,
+    pub errors: BTreeMap<&'static str, u64>,
+    pub ttfb_p50_ms: Option<u64>,
+    pub ttfb_p95_ms: Option<u64>,
+    pub rate_limit: proxy_limit::LimiterStats,
+}
+
+/// The `p`th percentile of sorted `values`, nearest rank.
+fn percentile(sorted: &[u64], p: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+pub fn snapshot() -> ProxyStats {
+    let mut view = ProxyStats {
+        bytes_streamed: BYTES_STREAMED.load(Ordering::Relaxed),
+        active_streams: ACTIVE_STREAMS.load(Ordering::Relaxed),
+        upstream_in_flight: UPSTREAM_IN_FLIGHT.load(Ordering::Relaxed),
+        upstream_in_flight_peak: UPSTREAM_IN_FLIGHT_PEAK.load(Ordering::Relaxed),
+        pool_exhausted: POOL_EXHAUSTED.load(Ordering::Relaxed),
+        rate_limit: proxy_limit::stats(&proxy::config()),
+        ..ProxyStats::default()
+    };
+    with_stats(|stats| {
+        let mut ttfb: Vec<u64> = stats.ttfb_ms.iter().copied().collect();
+        ttfb.sort_unstable();
+        view.uptime_secs = stats.started.map_or(0, |s| s.elapsed().as_secs());
+        view.total_requests = stats.requests.values().sum();
+        view.requests = stats.requests.clone();
+        view.total_errors = stats.errors.values().sum();
+        view.errors = stats.errors.clone();
+        view.ttfb_p50_ms = percentile(&ttfb, 50);
+        view.ttfb_p95_ms = percentile(&ttfb, 95);
+    });
+    view
+}
+
+/// Proxy counters for this session.
+#[tauri::command]
+pub fn get_proxy_stats() -> ProxyStats {
+    snapshot()
+}
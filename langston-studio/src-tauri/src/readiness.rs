@@ -0,0 +1,141 @@
+//! Readiness checks for the OpenCode and Remotion servers.
+//!
+//! Spawning a child only means the process started; the Remotion dev server
+//! and OpenCode typically need another 10-30s before they accept connections.
+//! If the frontend loads the iframes before then it shows a connection error.
+//! These helpers poll each server over HTTP until it answers (any status code
+//! counts) so `setup-complete` is only emitted once both are actually up.
+
+use crate::{ports, read_config_file};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Default time to wait for both servers when config.json doesn't override it.
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(90);
+/// Delay between polls of a server that isn't answering yet.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Timeout for a single probe request.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A server to wait for.
+pub struct Endpoint {
+    pub name: &'static str,
+    pub port: u16,
+}
+
+/// Servers that didn't come up within the startup timeout.
+pub struct StartupTimeout {
+    pub services: Vec<&'static str>,
+    pub timeout: Duration,
+}
+
+impl StartupTimeout {
+    pub fn message(&self) -> String {
+        format!(
+            "Timed out after {}s waiting for {} to start",
+            self.timeout.as_secs(),
+            self.services.join(" and ")
+        )
+    }
+}
+
+/// Wait for both OpenCode and Remotion, using the timeout from config.json.
+pub fn wait_for_backend(app: &AppHandle) -> Result<(), StartupTimeout> {
+    let timeout = read_config_file()
+        .startup_timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_STARTUP_TIMEOUT);
+    let ports = ports::current(app);
+    let endpoints = [
+        Endpoint {
+            name: "OpenCode",
+            port: ports.opencode,
+        },
+        Endpoint {
+            name: "Remotion",
+            port: ports.remotion,
+        },
+    ];
+
+    wait_for_servers(&endpoints, timeout).map_err(|services| StartupTimeout { services, timeout })
+}
+
+/// Whether the server on `port` answers HTTP right now (any status counts).
+pub async fn probe(port: u16) -> bool {
+    let Ok(client) = reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .no_proxy()
+        .build()
+    else {
+        return false;
+    };
+    client
+        .get(format!("http://127.0.0.1:{}/", port))
+        .send()
+        .await
+        .is_ok()
+}
+
+/// Poll every endpoint until it responds or `timeout` elapses.
+/// Returns the names of the servers that never answered on timeout.
+pub fn wait_for_servers(
+    endpoints: &[Endpoint],
+    timeout: Duration,
+) -> Result<(), Vec<&'static str>> {
+    let client = match reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .no_proxy()
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            crate::logging::log_line("ERROR", &format!("Readiness client build error: {}", e));
+            return Err(endpoints.iter().map(|e| e.name).collect());
+        }
+    };
+
+    let started = Instant::now();
+
+    tauri::async_runtime::block_on(async {
+        let mut pending: Vec<&Endpoint> = endpoints.iter().collect();
+        loop {
+            let mut still_pending = Vec::new();
+            for endpoint in pending {
+                let url = format!("http://127.0.0.1:{}/", endpoint.port);
+                match client.get(&url).send().await {
+                    Ok(resp) => crate::logging::log_line(
+                        "INFO",
+                        &format!(
+                            "{} is ready on port {} (HTTP {}, after {:.1}s)",
+                            endpoint.name,
+                            endpoint.port,
+                            resp.status().as_u16(),
+                            started.elapsed().as_secs_f64()
+                        ),
+                    ),
+                    Err(_) => still_pending.push(endpoint),
+                }
+            }
+            pending = still_pending;
+
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            if started.elapsed() >= timeout {
+                let names: Vec<&'static str> = pending.iter().map(|e| e.name).collect();
+                crate::logging::log_line(
+                    "ERROR",
+                    &format!(
+                        "Timed out after {}s waiting for: {}",
+                        timeout.as_secs(),
+                        names.join(", ")
+                    ),
+                );
+                return Err(names);
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+}
@@ -0,0 +1,856 @@
+//! Render queue for final video output.
+//!
+//! Renders shell out to `npx remotion render` in the workspace, one job at a
+//! time. Jobs are kept in a queue managed as Tauri state; a worker thread is
+//! started when work is enqueued and exits once the queue is drained.
+//! Remotion's CLI redraws its progress bar with carriage returns, so output is
+//! split on both `\r` and `\n` and the last `done/total` pair on each line is
+//! turned into a `render-progress` event. Each job is also listed in
+//! `operations` from when it's queued until it finishes, so it can be
+//! cancelled from there as well as with `cancel_render`.
+//!
+//! A job can carry post-steps (see `ffmpeg`): transcodes or an audio
+//! extraction run on the rendered file, in order, before the job is done.
+//! The job stays `rendering` meanwhile, with the step as its stage, and the
+//! files they write are listed in `outputs`.
+//!
+//! A finished job can be uploaded for sharing with `upload_render` (see
+//! `upload`); the upload and its link are kept on the job.
+//!
+//! Single frames (thumbnails for the project browser) don't go through the
+//! queue: `export_still` runs `npx remotion still` directly and returns the
+//! PNG's path once it's written.
+//!
+//! `capture_preview_frame` does the same for throwaway previews (project
+//! switcher thumbnails, before/after comparisons of an AI edit). Those go to
+//! a temp folder under a unique name, so an earlier capture isn't
+//! overwritten, and can come back inline as a data URL. Captures older than
+//! `PREVIEW_MAX_AGE` are cleared out as new ones are taken.
+
+use crate::error::StudioError;
+use crate::ffmpeg::{self, PostStep};
+use crate::operations::Operation;
+use crate::upload::RenderUpload;
+use crate::{
+    expand_home, node_shell_command, notifications, platform, prewarm, read_config_file,
+    resolve_workspace_dir,
+};
+use base64::Engine;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Codecs accepted by `start_render`; passed straight to `--codec`.
+const CODECS: &[&str] = &["h264", "h265", "vp8", "vp9", "prores", "gif"];
+
+/// How long preview captures are kept.
+const PREVIEW_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RenderStatus {
+    Queued,
+    Rendering,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderJob {
+    pub id: u64,
+    pub composition_id: String,
+    pub codec: String,
+    pub output_path: String,
+    pub status: RenderStatus,
+    /// Overall progress from 0.0 to 1.0.
+    pub progress: f64,
+    /// What Remotion is currently doing ("bundling", "rendering", "encoding").
+    pub stage: Option<String>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    /// Run on the rendered file once it's written.
+    pub post_steps: Vec<PostStep>,
+    /// Files the post-steps wrote.
+    pub outputs: Vec<String>,
+    /// The last upload of the job's output.
+    pub upload: Option<RenderUpload>,
+}
+
+#[derive(Default)]
+struct Queue {
+    next_id: u64,
+    jobs: Vec<RenderJob>,
+    pending: VecDeque<u64>,
+    /// The render process of the running job, kept so it can be cancelled.
+    running: Option<(u64, Child)>,
+    worker_active: bool,
+    /// The `operations` entry of each unfinished job.
+    operations: HashMap<u64, Operation>,
+}
+
+/// Render jobs, managed as Tauri state.
+#[derive(Default, Clone)]
+pub struct RenderQueue {
+    shared: Arc<Mutex<Queue>>,
+}
+
+impl RenderQueue {
+    fn update(&self, id: u64, f: impl FnOnce(&mut RenderJob)) -> Option<RenderJob> {
+        let mut queue = self.shared.lock().ok()?;
+        let job = queue.jobs.iter_mut().find(|j| j.id == id)?;
+        f(job);
+        Some(job.clone())
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<RenderJob> {
+        let queue = self.shared.lock().ok()?;
+        queue.jobs.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// Record the state of a job's upload and send the job out.
+    pub(crate) fn set_upload(
+        &self,
+        app: &AppHandle,
+        id: u64,
+        upload: RenderUpload,
+    ) -> Option<RenderJob> {
+        let job = self.update(id, |job| job.upload = Some(upload))?;
+        emit_job(app, &job);
+        Some(job)
+    }
+
+    /// Remove a job's `operations` entry. The caller drops it once the queue
+    /// is unlocked.
+    fn take_operation(&self, id: u64) -> Option<Operation> {
+        self.shared.lock().ok()?.operations.remove(&id)
+    }
+}
+
+/// Composition ids end up in a shell command, so only allow the characters
+/// Remotion itself permits.
+fn valid_composition_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn extension_for(codec: &str) -> &'static str {
+    match codec {
+        "vp8" | "vp9" => "webm",
+        "prores" => "mov",
+        "gif" => "gif",
+        _ => "mp4",
+    }
+}
+
+fn emit_job(app: &AppHandle, job: &RenderJob) {
+    let _ = app.emit("render-progress", job.clone());
+}
+
+#[tauri::command]
+pub fn start_render(
+    app: AppHandle,
+    queue: tauri::State<'_, RenderQueue>,
+    composition_id: String,
+    codec: Option<String>,
+    post_steps: Option<Vec<PostStep>>,
+) -> Result<RenderJob, StudioError> {
+    if !valid_composition_id(&composition_id) {
+        return Err(StudioError::InvalidInput(format!(
+            "Invalid composition id: {}",
+            composition_id
+        )));
+    }
+    let codec = codec.unwrap_or_else(|| "h264".to_string());
+    if !CODECS.contains(&codec.as_str()) {
+        return Err(StudioError::InvalidInput(format!(
+            "Unsupported codec: {}",
+            codec
+        )));
+    }
+    let post_steps = post_steps.unwrap_or_default();
+    for step in &post_steps {
+        step.validate()?;
+    }
+
+    let output_path = resolve_workspace_dir().join("out").join(format!(
+        "{}-{}.{}",
+        composition_id,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        extension_for(&codec)
+    ));
+
+    let (job, start_worker) = {
+        let mut q = queue.shared.lock().map_err(|e| e.to_string())?;
+        q.next_id += 1;
+        let job = RenderJob {
+            id: q.next_id,
+            composition_id,
+            codec,
+            output_path: output_path.to_string_lossy().to_string(),
+            status: RenderStatus::Queued,
+            progress: 0.0,
+            stage: None,
+            error: None,
+            created_at: Local::now().to_rfc3339(),
+            finished_at: None,
+            post_steps,
+            outputs: Vec::new(),
+            upload: None,
+        };
+        let id = job.id;
+        let (handle, jobs) = (app.clone(), queue.inner().clone());
+        let operation = Operation::start(&app, "render", format!("Render {}", job.composition_id))
+            .on_cancel(move || {
+                let _ = cancel(&handle, &jobs, id);
+            });
+        q.operations.insert(id, operation);
+        q.jobs.push(job.clone());
+        q.pending.push_back(job.id);
+        let start_worker = !q.worker_active;
+        q.worker_active = true;
+        (job, start_worker)
+    };
+
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Queued render #{} of {} -> {}",
+            job.id, job.composition_id, job.output_path
+        ),
+    );
+    emit_job(&app, &job);
+
+    if start_worker {
+        let queue = queue.inner().clone();
+        std::thread::spawn(move || run_worker(app, queue));
+    }
+
+    Ok(job)
+}
+
+#[tauri::command]
+pub fn cancel_render(
+    app: AppHandle,
+    queue: tauri::State<'_, RenderQueue>,
+    id: u64,
+) -> Result<(), StudioError> {
+    cancel(&app, &queue, id)
+}
+
+fn cancel(app: &AppHandle, queue: &RenderQueue, id: u64) -> Result<(), StudioError> {
+    let (job, operation) = {
+        let mut q = queue.shared.lock().map_err(|e| e.to_string())?;
+        let status = q
+            .jobs
+            .iter()
+            .find(|j| j.id == id)
+            .map(|j| j.status)
+            .ok_or_else(|| StudioError::NotFound(format!("Render #{}", id)))?;
+
+        match status {
+            RenderStatus::Queued => q.pending.retain(|&p| p != id),
+            RenderStatus::Rendering => {
+                if let Some((running_id, child)) = q.running.as_mut() {
+                    if *running_id == id {
+                        platform::kill_process_tree(child);
+                    }
+                }
+            }
+            _ => {
+                return Err(StudioError::InvalidInput(format!(
+                    "Render #{} has already finished",
+                    id
+                )))
+            }
+        }
+
+        let job = q.jobs.iter_mut().find(|j| j.id == id).unwrap();
+        job.status = RenderStatus::Cancelled;
+        job.finished_at = Some(Local::now().to_rfc3339());
+        let job = job.clone();
+        (job, q.operations.remove(&id))
+    };
+    if let Some(operation) = operation {
+        operation.cancel();
+    }
+
+    crate::logging::log_line("INFO", &format!("Cancelled render #{}", id));
+    emit_job(app, &job);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_renders(queue: tauri::State<'_, RenderQueue>) -> Vec<RenderJob> {
+    queue
+        .shared
+        .lock()
+        .map(|q| q.jobs.clone())
+        .unwrap_or_default()
+}
+
+fn run_worker(app: AppHandle, queue: RenderQueue) {
+    loop {
+        let next = {
+            let Ok(mut q) = queue.shared.lock() else {
+                return;
+            };
+            match q.pending.pop_front() {
+                Some(id) => q
+                    .jobs
+                    .iter()
+                    .find(|j| j.id == id && j.status == RenderStatus::Queued)
+                    .cloned(),
+                None => {
+                    q.worker_active = false;
+                    return;
+                }
+            }
+        };
+
+        if let Some(job) = next {
+            run_job(&app, &queue, job);
+        }
+    }
+}
+
+fn run_job(app: &AppHandle, queue: &RenderQueue, job: RenderJob) {
+    let workspace = resolve_workspace_dir();
+    let _ = std::fs::create_dir_all(workspace.join("out"));
+
+    let script = format!(
+        "npx remotion render src/index.ts {} {:?} --codec={}",
+        job.composition_id, job.output_path, job.codec
+    );
+
+    // Remotion renders with headless Chrome and ffmpeg children; a group
+    // lets cancel take them down too.
+    let mut cmd = node_shell_command(&script);
+    platform::new_process_group(&mut cmd);
+    let spawned = cmd
+        .current_dir(&workspace)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            finish(
+                app,
+                queue,
+                job.id,
+                Err(format!("Failed to start render: {}", e)),
+            );
+            return;
+        }
+    };
+
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Render #{} started (PID: {}): {}",
+            job.id,
+            child.id(),
+            script
+        ),
+    );
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    // Mark the job as rendering in the same critical section that records the
+    // child, so a concurrent cancel either sees it queued or can kill it.
+    let started = {
+        let Ok(mut q) = queue.shared.lock() else {
+            return;
+        };
+        let started = match q.jobs.iter_mut().find(|j| j.id == job.id) {
+            Some(j) if j.status == RenderStatus::Queued => {
+                j.status = RenderStatus::Rendering;
+                Some(j.clone())
+            }
+            _ => {
+                platform::kill_process_tree(&mut child);
+                None
+            }
+        };
+        q.running = Some((job.id, child));
+        started
+    };
+    if let Some(started) = started {
+        emit_job(app, &started);
+    }
+
+    // Remotion writes progress to stderr; keep the tail for error reports.
+    let stderr_tail = Arc::new(Mutex::new(VecDeque::<String>::new()));
+    let stderr_reader = stderr.map(|pipe| {
+        let (app, queue, tail) = (app.clone(), queue.clone(), stderr_tail.clone());
+        std::thread::spawn(move || read_progress(&app, &queue, job.id, pipe, Some(tail)))
+    });
+    if let Some(pipe) = stdout {
+        read_progress(app, queue, job.id, pipe, None);
+    }
+    if let Some(handle) = stderr_reader {
+        let _ = handle.join();
+    }
+
+    let status = {
+        let Ok(mut q) = queue.shared.lock() else {
+            return;
+        };
+        match q.running.take() {
+            Some((_, mut child)) => child.wait().ok(),
+            None => None,
+        }
+    };
+
+    let result = match status {
+        Some(s) if s.success() => Ok(()),
+        Some(s) => {
+            let tail: Vec<String> = stderr_tail
+                .lock()
+                .map(|t| t.iter().cloned().collect())
+                .unwrap_or_default();
+            Err(format!(
+                "remotion render exited with code {:?}: {}",
+                s.code(),
+                tail.join("\n")
+            ))
+        }
+        None => Err("Lost track of the render process".to_string()),
+    };
+    let result = match result {
+        Ok(()) if !job.post_steps.is_empty() => run_post_steps(app, queue, &job),
+        result => result,
+    };
+    finish(app, queue, job.id, result);
+}
+
+/// Run the job's post-steps on the rendered file, one after another.
+fn run_post_steps(app: &AppHandle, queue: &RenderQueue, job: &RenderJob) -> Result<(), String> {
+    let ffmpeg = ffmpeg::locate().map_err(|e| e.to_string())?;
+    let input = PathBuf::from(&job.output_path);
+    let cancelled = || {
+        queue
+            .shared
+            .lock()
+            .map(|q| {
+                q.jobs
+                    .iter()
+                    .any(|j| j.id == job.id && j.status == RenderStatus::Cancelled)
+            })
+            .unwrap_or(false)
+    };
+    for step in &job.post_steps {
+        if cancelled() {
+            break;
+        }
+        let stage = step.stage();
+        let plan = step
+            .plan(&input)
+            .map_err(|e| format!("Couldn't start {}: {}", stage, e))?;
+        if let Some(updated) = queue.update(job.id, |j| j.stage = Some(stage.to_string())) {
+            emit_job(app, &updated);
+        }
+        crate::logging::log_line(
+            "INFO",
+            &format!("Render #{}: {} -> {}", job.id, stage, plan.output.display()),
+        );
+        let mut last_reported = -1.0;
+        ffmpeg::run(&ffmpeg, &plan, cancelled, |progress| {
+            if (progress - last_reported).abs() < 0.01 {
+                return;
+            }
+            last_reported = progress;
+            if let Ok(q) = queue.shared.lock() {
+                if let Some(operation) = q.operations.get(&job.id) {
+                    operation.progress(Some(progress), Some(stage));
+                }
+            }
+        })
+        .map_err(|e| format!("{} failed: {}", stage, e))?;
+        let output = plan.output.to_string_lossy().to_string();
+        if let Some(updated) = queue.update(job.id, |j| j.outputs.push(output)) {
+            emit_job(app, &updated);
+        }
+    }
+    Ok(())
+}
+
+fn finish(app: &AppHandle, queue: &RenderQueue, id: u64, result: Result<(), String>) {
+    if let (Some(operation), Err(e)) = (queue.take_operation(id), &result) {
+        operation.fail(e);
+    }
+    let updated = queue.update(id, |job| {
+        // A cancelled job also exits with an error; keep it marked cancelled.
+        if job.status == RenderStatus::Cancelled {
+            return;
+        }
+        match &result {
+            Ok(()) => {
+                job.status = RenderStatus::Done;
+                job.progress = 1.0;
+            }
+            Err(e) => {
+                job.status = RenderStatus::Failed;
+                job.error = Some(e.clone());
+            }
+        }
+        job.finished_at = Some(Local::now().to_rfc3339());
+    });
+
+    if let Some(job) = updated {
+        let details = serde_json::json!({
+            "renderId": job.id,
+            "compositionId": job.composition_id,
+            "outputPath": job.output_path,
+            "outputs": job.outputs,
+            "error": job.error,
+        });
+        match job.status {
+            RenderStatus::Done => {
+                crate::logging::log_line(
+                    "INFO",
+                    &format!("Render #{} finished: {}", id, job.output_path),
+                );
+                notifications::notify(
+                    "render-complete",
+                    format!("Render of {} finished", job.composition_id),
+                    details,
+                );
+            }
+            RenderStatus::Failed => {
+                let error = job.error.as_deref().unwrap_or("");
+                crate::logging::log_line("ERROR", &format!("Render #{} failed: {}", id, error));
+                notifications::notify(
+                    "render-failed",
+                    format!("Render of {} failed", job.composition_id),
+                    details,
+                );
+            }
+            _ => {}
+        }
+        emit_job(app, &job);
+    }
+}
+
+/// Read a render process pipe, updating the job's progress as we go.
+fn read_progress(
+    app: &AppHandle,
+    queue: &RenderQueue,
+    id: u64,
+    mut pipe: impl Read,
+    tail: Option<Arc<Mutex<VecDeque<String>>>>,
+) {
+    let mut buf = [0u8; 4096];
+    let mut line = Vec::new();
+    let mut last_emitted = -1.0;
+
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        for &byte in &buf[..n] {
+            if byte != b'\r' && byte != b'\n' {
+                line.push(byte);
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let text = strip_ansi(&String::from_utf8_lossy(&line));
+            line.clear();
+
+            if let Some(ref tail) = tail {
+                if let Ok(mut t) = tail.lock() {
+                    t.push_back(text.clone());
+                    if t.len() > 20 {
+                        t.pop_front();
+                    }
+                }
+            }
+
+            let Some((stage, progress)) = parse_progress(&text) else {
+                continue;
+            };
+            // Throttle events to whole-percent changes.
+            if (progress - last_emitted).abs() < 0.01 {
+                continue;
+            }
+            last_emitted = progress;
+            if let Some(job) = queue.update(id, |job| {
+                job.stage = Some(stage.to_string());
+                job.progress = progress;
+            }) {
+                emit_job(app, &job);
+            }
+            if let Ok(q) = queue.shared.lock() {
+                if let Some(operation) = q.operations.get(&id) {
+                    operation.progress(Some(progress), Some(stage));
+                }
+            }
+        }
+    }
+}
+
+/// Turn a line of Remotion CLI output into (stage, overall progress).
+/// Bundling is weighted as the first 10%, rendering up to 90%, and encoding
+/// the remainder.
+fn parse_progress(line: &str) -> Option<(&'static str, f64)> {
+    let lower = line.to_lowercase();
+    let (stage, start, weight) = if lower.contains("bundl") {
+        ("bundling", 0.0, 0.1)
+    } else if lower.contains("encod") || lower.contains("stitch") {
+        ("encoding", 0.9, 0.1)
+    } else if lower.contains("render") {
+        ("rendering", 0.1, 0.8)
+    } else {
+        return None;
+    };
+
+    let fraction = line
+        .split_whitespace()
+        .rev()
+        .find_map(|token| {
+            let token = token.trim_end_matches(|c: char| !c.is_ascii_digit());
+            let (done, total) = token.split_once('/')?;
+            let done: f64 = done.parse().ok()?;
+            let total: f64 = total.parse().ok()?;
+            (total > 0.0).then_some((done / total).min(1.0))
+        })
+        .or_else(|| {
+            let pct = lower.split_whitespace().find(|t| t.ends_with('%'))?;
+            pct.trim_end_matches('%')
+                .parse::<f64>()
+                .ok()
+                .map(|p| p / 100.0)
+        })?;
+
+    Some((stage, start + weight * fraction))
+}
+
+/// Remove ANSI color/cursor escape sequences.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            if chars.peek() == Some(&'[') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Whether a render is queued or running.
+pub fn is_busy(app: &AppHandle) -> bool {
+    app.try_state::<RenderQueue>()
+        .and_then(|queue| {
+            let q = queue.shared.lock().ok()?;
+            Some(q.worker_active || q.running.is_some() || !q.pending.is_empty())
+        })
+        .unwrap_or(false)
+}
+
+/// Kill a running render on shutdown so it doesn't outlive the app.
+pub fn kill_running(app: &AppHandle) {
+    if let Some(queue) = app.try_state::<RenderQueue>() {
+        if let Ok(mut q) = queue.shared.lock() {
+            q.pending.clear();
+            if let Some((_, child)) = q.running.as_mut() {
+                platform::kill_process_tree(child);
+            }
+        }
+    }
+}
+
+/// Whether a render job is running right now.
+pub fn is_rendering(app: &AppHandle) -> bool {
+    app.try_state::<RenderQueue>()
+        .and_then(|queue| queue.shared.lock().ok().map(|q| q.running.is_some()))
+        .unwrap_or(false)
+}
+
+/// Where stills are written: the configured `stillsDir`, or `out/stills` in
+/// the open workspace.
+fn stills_dir() -> PathBuf {
+    match read_config_file().stills_dir {
+        Some(dir) if !dir.trim().is_empty() => expand_home(dir.trim()),
+        _ => resolve_workspace_dir().join("out").join("stills"),
+    }
+}
+
+/// Render a single frame of a composition to PNG and return its path.
+#[tauri::command]
+pub async fn export_still(
+    composition_id: String,
+    frame: Option<u32>,
+) -> Result<String, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        export_still_blocking(&composition_id, frame.unwrap_or(0))
+    })
+    .await?
+}
+
+fn export_still_blocking(composition_id: &str, frame: u32) -> Result<String, StudioError> {
+    if !valid_composition_id(composition_id) {
+        return Err(StudioError::InvalidInput(format!(
+            "Invalid composition id: {}",
+            composition_id
+        )));
+    }
+
+    let dir = stills_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| StudioError::io_at("Failed to create", &dir, e))?;
+    let output_path = dir.join(format!("{}-{}.png", composition_id, frame));
+    render_still(composition_id, frame, &output_path)?;
+
+    let path = output_path.to_string_lossy().to_string();
+    crate::logging::log_line("INFO", &format!("Still written to {}", path));
+    Ok(path)
+}
+
+/// Run `remotion still` for one frame, writing a PNG to `output_path`.
+fn render_still(composition_id: &str, frame: u32, output_path: &Path) -> Result<(), StudioError> {
+    let workspace = resolve_workspace_dir();
+    let script = format!(
+        "npx remotion still src/index.ts {} {:?} --frame={} --image-format=png",
+        composition_id, output_path, frame
+    );
+    crate::logging::log_line(
+        "INFO",
+        &format!("Exporting still: {} frame {}", composition_id, frame),
+    );
+
+    let output = node_shell_command(&script)
+        .current_dir(&workspace)
+        .envs(prewarm::env(&workspace))
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| StudioError::io("Failed to start still export", e))?;
+
+    if !output.status.success() {
+        let stderr = strip_ansi(&String::from_utf8_lossy(&output.stderr));
+        let reason = stderr
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("exit code {:?}", output.status.code()));
+        crate::logging::log_line("ERROR", &format!("Still export failed: {}", reason));
+        return Err(StudioError::Other(format!(
+            "Still export failed: {}",
+            reason
+        )));
+    }
+    if !output_path.is_file() {
+        return Err(StudioError::Other(format!(
+            "Still export finished but {} was not written",
+            output_path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// A captured preview frame.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewFrame {
+    /// The PNG, in a temp folder.
+    pub path: String,
+    /// `data:image/png;base64,...`, when asked for inline.
+    pub data_url: Option<String>,
+}
+
+fn previews_dir() -> PathBuf {
+    std::env::temp_dir().join("langston-previews")
+}
+
+/// Delete captures older than `PREVIEW_MAX_AGE`.
+fn prune_previews(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > PREVIEW_MAX_AGE);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Render a frame of a composition to a temp PNG for a preview, returning
+/// its path and, with `inline`, the image itself as a data URL.
+#[tauri::command]
+pub async fn capture_preview_frame(
+    composition: String,
+    frame: Option<u32>,
+    inline: Option<bool>,
+) -> Result<PreviewFrame, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        capture_preview_frame_blocking(&composition, frame.unwrap_or(0), inline.unwrap_or(false))
+    })
+    .await?
+}
+
+fn capture_preview_frame_blocking(
+    composition_id: &str,
+    frame: u32,
+    inline: bool,
+) -> Result<PreviewFrame, StudioError> {
+    if !valid_composition_id(composition_id) {
+        return Err(StudioError::InvalidInput(format!(
+            "Invalid composition id: {}",
+            composition_id
+        )));
+    }
+
+    let dir = previews_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| StudioError::io_at("Failed to create", &dir, e))?;
+    prune_previews(&dir);
+    let output_path = dir.join(format!(
+        "{}-{}-{}.png",
+        composition_id,
+        frame,
+        Local::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+    render_still(composition_id, frame, &output_path)?;
+
+    let data_url = if inline {
+        let png = std::fs::read(&output_path)
+            .map_err(|e| StudioError::io_at("Failed to read", &output_path, e))?;
+        Some(format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png)
+        ))
+    } else {
+        None
+    };
+    Ok(PreviewFrame {
+        path: output_path.to_string_lossy().to_string(),
+        data_url,
+    })
+}
@@ -0,0 +1,308 @@
+//! Finding and repairing a damaged workspace before setup uses it.
+//!
+//! A half-deleted node_modules, a corrupted .git or a package.json mangled
+//! by hand used to surface much later as confusing failures: Remotion not
+//! starting, auto-saves erroring on every launch. `setup_workspace` now
+//! calls `run` on an existing workspace first, which looks for each of these
+//! and repairs what it finds:
+//!
+//! - package.json that isn't valid JSON, or is missing, is restored from the
+//!   template; one that lost dependencies the app needs gets the template's
+//!   entries back. Either way dependencies are reinstalled.
+//! - node_modules missing packages that package.json lists is removed, so
+//!   it's installed again from scratch (npm trusts its own record of what's
+//!   installed and wouldn't notice).
+//! - A .git that git can't read is first given a fresh index; if that
+//!   doesn't help it's moved out of the way and setup starts a new history.
+//!
+//! Whatever is replaced is kept under `repairs/` in the config directory.
+//! Each repair is logged, shown in `setup-status` and sent as a
+//! `workspace-repaired` event, `{ problem, action }`.
+
+use crate::error::StudioError;
+use crate::{copy_dir_recursive, emit_status, frontend, get_config_dir, git};
+use chrono::Local;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// Files that tell an existing workspace apart from an empty folder.
+const WORKSPACE_MARKERS: &[&str] = &[
+    "package.json",
+    "remotion.config.ts",
+    ".langston-template.json",
+    ".git",
+    "src",
+];
+/// Dependencies the app can't run the workspace without.
+const REQUIRED_DEPENDENCIES: &[&str] = &["remotion", "@remotion/cli", "react", "react-dom"];
+/// How many missing packages are named in a repair's description.
+const MAX_LISTED: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Repair {
+    pub problem: String,
+    pub action: String,
+}
+
+/// What `run` did.
+#[derive(Debug, Clone, Default)]
+pub struct RepairOutcome {
+    pub repairs: Vec<Repair>,
+    /// Whether dependencies need reinstalling.
+    pub dependencies_changed: bool,
+}
+
+impl RepairOutcome {
+    fn record(&mut self, app: &AppHandle, problem: String, action: String) {
+        crate::logging::log_line("WARN", &format!("[repair] {} {}", problem, action));
+        emit_status(app, &format!("Repairing workspace: {}", action), 55);
+        let repair = Repair { problem, action };
+        frontend::emit(app, "workspace-repaired", &repair);
+        self.repairs.push(repair);
+    }
+}
+
+/// Where replaced files from this repair are kept.
+fn backup_dir(workspace: &Path) -> PathBuf {
+    let name = workspace
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "workspace".to_string());
+    get_config_dir().join("repairs").join(format!(
+        "{}-{}",
+        name,
+        Local::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Move `path` into `dir`, copying when a rename can't cross volumes.
+fn set_aside(path: &Path, dir: &Path) -> Result<PathBuf, StudioError> {
+    fs::create_dir_all(dir).map_err(|e| StudioError::io_at("Failed to create", dir, e))?;
+    let name = path.file_name().unwrap_or_default();
+    let target = dir.join(name.to_string_lossy().trim_start_matches('.'));
+    if fs::rename(path, &target).is_ok() {
+        return Ok(target);
+    }
+    if path.is_dir() {
+        copy_dir_recursive(path, &target)
+            .map_err(|e| StudioError::io_at("Failed to copy", path, e))?;
+        fs::remove_dir_all(path).map_err(|e| StudioError::io_at("Failed to remove", path, e))?;
+    } else {
+        fs::copy(path, &target).map_err(|e| StudioError::io_at("Failed to copy", path, e))?;
+        fs::remove_file(path).map_err(|e| StudioError::io_at("Failed to remove", path, e))?;
+    }
+    Ok(target)
+}
+
+fn read_json(path: &Path) -> Option<serde_json::Value> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Every package package.json depends on.
+fn dependency_names(package: &serde_json::Value) -> Vec<String> {
+    ["dependencies", "devDependencies"]
+        .iter()
+        .filter_map(|key| package.get(key)?.as_object())
+        .flat_map(|deps| deps.keys().cloned())
+        .collect()
+}
+
+fn repair_package_json(
+    app: &AppHandle,
+    workspace: &Path,
+    template: &Path,
+    backups: &Path,
+    outcome: &mut RepairOutcome,
+) -> Result<(), StudioError> {
+    let path = workspace.join("package.json");
+    let template_path = template.join("package.json");
+    let problem = match read_json(&path) {
+        Some(package) if package.is_object() => {
+            return restore_dependencies(app, &path, &template_path, package, outcome);
+        }
+        _ if !path.exists() => "package.json is missing.".to_string(),
+        _ => "package.json is not valid JSON.".to_string(),
+    };
+    let mut action = "Restored package.json from the template".to_string();
+    if path.exists() {
+        let kept = set_aside(&path, backups)?;
+        action.push_str(&format!(" (the old one is at {})", kept.display()));
+    }
+    fs::copy(&template_path, &path)
+        .map_err(|e| StudioError::io_at("Failed to restore", &path, e))?;
+    outcome.dependencies_changed = true;
+    outcome.record(app, problem, action);
+    Ok(())
+}
+
+/// Put back the template's entries for `REQUIRED_DEPENDENCIES` that
+/// `package` lost, keeping everything else the user added.
+fn restore_dependencies(
+    app: &AppHandle,
+    path: &Path,
+    template_path: &Path,
+    mut package: serde_json::Value,
+    outcome: &mut RepairOutcome,
+) -> Result<(), StudioError> {
+    let installed = dependency_names(&package);
+    let missing: Vec<&str> = REQUIRED_DEPENDENCIES
+        .iter()
+        .copied()
+        .filter(|name| !installed.iter().any(|dep| dep == name))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let Some(template_deps) = read_json(template_path)
+        .and_then(|template| template.get("dependencies")?.as_object().cloned())
+    else {
+        return Ok(());
+    };
+    let deps = package
+        .as_object_mut()
+        .map(|package| {
+            package
+                .entry("dependencies")
+                .or_insert_with(|| serde_json::json!({}))
+        })
+        .and_then(|deps| deps.as_object_mut());
+    let Some(deps) = deps else {
+        return Ok(());
+    };
+    for name in &missing {
+        if let Some(version) = template_deps.get(*name) {
+            deps.insert(name.to_string(), version.clone());
+        }
+    }
+    let contents = serde_json::to_string_pretty(&package).map_err(|e| e.to_string())?;
+    fs::write(path, contents + "\n").map_err(|e| StudioError::io_at("Failed to write", path, e))?;
+    outcome.dependencies_changed = true;
+    outcome.record(
+        app,
+        format!("package.json is missing {}.", missing.join(", ")),
+        "Added them back from the template".to_string(),
+    );
+    Ok(())
+}
+
+fn repair_node_modules(
+    app: &AppHandle,
+    workspace: &Path,
+    outcome: &mut RepairOutcome,
+) -> Result<(), StudioError> {
+    let node_modules = workspace.join("node_modules");
+    let Some(package) = read_json(&workspace.join("package.json")) else {
+        return Ok(());
+    };
+    if !node_modules.is_dir() {
+        return Ok(());
+    }
+    let missing: Vec<String> = dependency_names(&package)
+        .into_iter()
+        .filter(|name| !node_modules.join(name).join("package.json").is_file())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let mut listed = missing
+        .iter()
+        .take(MAX_LISTED)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if missing.len() > MAX_LISTED {
+        listed.push_str(&format!(" and {} more", missing.len() - MAX_LISTED));
+    }
+    fs::remove_dir_all(&node_modules)
+        .map_err(|e| StudioError::io_at("Failed to remove", &node_modules, e))?;
+    outcome.dependencies_changed = true;
+    outcome.record(
+        app,
+        format!("node_modules is missing {}.", listed),
+        "Reinstalling dependencies".to_string(),
+    );
+    Ok(())
+}
+
+/// Why git can't use the repository, if it can't.
+fn git_problem(workspace: &Path) -> Option<String> {
+    if let Err(e) = git::run_git(workspace, &["status", "--porcelain"]) {
+        return Some(e);
+    }
+    // A branch with commits whose objects are gone still passes `status`.
+    if git::run_git(workspace, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() {
+        if let Err(e) = git::run_git(workspace, &["cat-file", "-e", "HEAD^{tree}"]) {
+            return Some(e);
+        }
+    }
+    None
+}
+
+fn repair_git(
+    app: &AppHandle,
+    workspace: &Path,
+    backups: &Path,
+    outcome: &mut RepairOutcome,
+) -> Result<(), StudioError> {
+    let dot_git = workspace.join(".git");
+    if !dot_git.exists() {
+        return Ok(());
+    }
+    let Some(problem) = git_problem(workspace) else {
+        return Ok(());
+    };
+    let problem = format!("The workspace history can't be read ({}).", problem);
+
+    // A damaged index is the common case and is rebuilt from HEAD.
+    let index = dot_git.join("index");
+    if index.exists() {
+        let _ = fs::remove_file(&index);
+        if git::run_git(workspace, &["reset", "-q"]).is_ok() && git_problem(workspace).is_none() {
+            outcome.record(app, problem, "Rebuilt the git index".to_string());
+            return Ok(());
+        }
+    }
+
+    // `setup` starts a new history when there's no .git.
+    let kept = set_aside(&dot_git, backups)?;
+    outcome.record(
+        app,
+        problem,
+        format!(
+            "Started a new history (the old one is at {})",
+            kept.display()
+        ),
+    );
+    Ok(())
+}
+
+/// Check an existing workspace and repair what's broken. A folder that
+/// isn't a workspace yet is left to be created.
+pub fn run(
+    app: &AppHandle,
+    workspace: &Path,
+    template: &Path,
+) -> Result<RepairOutcome, StudioError> {
+    let mut outcome = RepairOutcome::default();
+    if !WORKSPACE_MARKERS
+        .iter()
+        .any(|marker| workspace.join(marker).exists())
+    {
+        return Ok(outcome);
+    }
+    let backups = backup_dir(workspace);
+    repair_package_json(app, workspace, template, &backups, &mut outcome)?;
+    repair_node_modules(app, workspace, &mut outcome)?;
+    repair_git(app, workspace, &backups, &mut outcome)?;
+    if !outcome.repairs.is_empty() {
+        crate::logging::log_line(
+            "INFO",
+            &format!("[repair] Made {} repair(s)", outcome.repairs.len()),
+        );
+    }
+    Ok(outcome)
+}
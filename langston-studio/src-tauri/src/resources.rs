@@ -0,0 +1,280 @@
+//! CPU and memory use of the OpenCode and Remotion process trees.
+//!
+//! "The app makes my fan spin" used to be impossible to look into: the
+//! servers run as node process trees (Remotion's bundler spawns workers)
+//! that don't show up under the app in Activity Monitor. The monitor now
+//! samples each tree every `SAMPLE_INTERVAL`, summing CPU (percent of one
+//! core, so it can pass 100) and resident memory over the server and
+//! everything it spawned, and keeps the last `HISTORY_LEN` samples.
+//! `get_resource_usage` returns them.
+//!
+//! A tree that stays over `resourceCpuWarnPercent` (default
+//! `DEFAULT_CPU_WARN_PERCENT`) or `resourceMemoryWarnMb` (default
+//! `DEFAULT_MEMORY_WARN_MB`) for `SUSTAINED_SAMPLES` samples in a row gets a
+//! `resource-warning` event, `{ service, resource, value, threshold }`. It
+//! isn't warned about again until it's been back under.
+
+use crate::supervisor::Service;
+use crate::{platform, read_config_file, AppConfig, AppState};
+use chrono::Local;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// Ten minutes of samples.
+const HISTORY_LEN: usize = 120;
+/// Samples in a row over a threshold before warning, so a build spike
+/// doesn't count.
+const SUSTAINED_SAMPLES: usize = 6;
+pub const DEFAULT_CPU_WARN_PERCENT: u64 = 200;
+pub const DEFAULT_MEMORY_WARN_MB: u64 = 4096;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sample {
+    pub at: String,
+    /// Percent of one core, summed over the tree.
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub processes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceUsage {
+    pub service: &'static str,
+    /// `None` while the server isn't running.
+    pub pid: Option<u32>,
+    /// Oldest first.
+    pub history: Vec<Sample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Thresholds {
+    pub cpu_percent: u64,
+    pub memory_mb: u64,
+}
+
+impl Thresholds {
+    pub fn from_config(config: &AppConfig) -> Thresholds {
+        Thresholds {
+            cpu_percent: config
+                .resource_cpu_warn_percent
+                .unwrap_or(DEFAULT_CPU_WARN_PERCENT),
+            memory_mb: config
+                .resource_memory_warn_mb
+                .unwrap_or(DEFAULT_MEMORY_WARN_MB),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    pub sample_interval_secs: u64,
+    pub thresholds: Thresholds,
+    pub services: Vec<ServiceUsage>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceWarning {
+    service: &'static str,
+    /// "cpu" (percent of one core) or "memory" (MB).
+    resource: &'static str,
+    value: u64,
+    threshold: u64,
+}
+
+/// Samples and warning state of one service.
+#[derive(Default)]
+struct Track {
+    pid: Option<u32>,
+    history: VecDeque<Sample>,
+    cpu_warned: bool,
+    memory_warned: bool,
+}
+
+static TRACKS: Mutex<[Option<Track>; 2]> = Mutex::new([None, None]);
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+fn index(service: Service) -> usize {
+    match service {
+        Service::OpenCode => 0,
+        Service::Remotion => 1,
+    }
+}
+
+/// Whether the last `SUSTAINED_SAMPLES` samples are all `over`.
+fn sustained(history: &VecDeque<Sample>, over: impl Fn(&Sample) -> bool) -> bool {
+    history.len() >= SUSTAINED_SAMPLES && history.iter().rev().take(SUSTAINED_SAMPLES).all(over)
+}
+
+fn sample_tree(system: &sysinfo::System, root: u32) -> Sample {
+    let mut pids = platform::descendants_in(system, root);
+    pids.push(root);
+    let (mut cpu_percent, mut memory_bytes, mut processes) = (0.0, 0, 0);
+    for pid in pids {
+        if let Some(process) = system.process(sysinfo::Pid::from_u32(pid)) {
+            cpu_percent += process.cpu_usage();
+            memory_bytes += process.memory();
+            processes += 1;
+        }
+    }
+    Sample {
+        at: Local::now().to_rfc3339(),
+        cpu_percent,
+        memory_bytes,
+        processes,
+    }
+}
+
+/// Record a sample of `service` and return the warnings it brings on.
+fn record(
+    service: Service,
+    pid: Option<u32>,
+    sample: Option<Sample>,
+    thresholds: Thresholds,
+) -> Vec<ResourceWarning> {
+    let Ok(mut tracks) = TRACKS.lock() else {
+        return Vec::new();
+    };
+    let track = tracks[index(service)].get_or_insert_with(Track::default);
+    // A new process is a new history.
+    if track.pid != pid {
+        *track = Track {
+            pid,
+            ..Track::default()
+        };
+    }
+    let Some(sample) = sample else {
+        return Vec::new();
+    };
+    track.history.push_back(sample);
+    while track.history.len() > HISTORY_LEN {
+        track.history.pop_front();
+    }
+
+    let mut warnings = Vec::new();
+    let latest = track.history.back().cloned();
+    let cpu_limit = thresholds.cpu_percent as f32;
+    if sustained(&track.history, |s| s.cpu_percent > cpu_limit) {
+        if !track.cpu_warned {
+            track.cpu_warned = true;
+            warnings.push(ResourceWarning {
+                service: service.id(),
+                resource: "cpu",
+                value: latest.as_ref().map_or(0, |s| s.cpu_percent.round() as u64),
+                threshold: thresholds.cpu_percent,
+            });
+        }
+    } else if latest.as_ref().is_some_and(|s| s.cpu_percent <= cpu_limit) {
+        track.cpu_warned = false;
+    }
+    let memory_limit = thresholds.memory_mb * 1024 * 1024;
+    if sustained(&track.history, |s| s.memory_bytes > memory_limit) {
+        if !track.memory_warned {
+            track.memory_warned = true;
+            warnings.push(ResourceWarning {
+                service: service.id(),
+                resource: "memory",
+                value: latest.as_ref().map_or(0, |s| s.memory_bytes / 1024 / 1024),
+                threshold: thresholds.memory_mb,
+            });
+        }
+    } else if latest
+        .as_ref()
+        .is_some_and(|s| s.memory_bytes <= memory_limit)
+    {
+        track.memory_warned = false;
+    }
+    warnings
+}
+
+/// Start sampling, once.
+pub fn start(app: &AppHandle) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        // CPU use is measured between refreshes, so the first one only sets
+        // the baseline.
+        let mut system = sysinfo::System::new();
+        system.refresh_processes();
+        loop {
+            std::thread::sleep(SAMPLE_INTERVAL);
+            system.refresh_processes();
+
+            let Some(state) = app.try_state::<Mutex<AppState>>() else {
+                continue;
+            };
+            let pids = match state.lock() {
+                Ok(guard) if guard.shutting_down => return,
+                Ok(guard) => [
+                    guard.opencode.as_ref().map(|c| c.id()),
+                    guard.remotion.as_ref().map(|c| c.id()),
+                ],
+                Err(_) => return,
+            };
+            let thresholds = Thresholds::from_config(&read_config_file());
+            for service in Service::ALL {
+                let pid = pids[index(service)];
+                let sample = pid.map(|pid| sample_tree(&system, pid));
+                for warning in record(service, pid, sample, thresholds) {
+                    let (value, threshold) = if warning.resource == "cpu" {
+                        (
+                            format!("{}% CPU", warning.value),
+                            format!("{}%", warning.threshold),
+                        )
+                    } else {
+                        (
+                            format!("{} MB of memory", warning.value),
+                            format!("{} MB", warning.threshold),
+                        )
+                    };
+                    crate::logging::log_line(
+                        "WARN",
+                        &format!(
+                            "[resources] {} has been using {} for {}s (warning at {})",
+                            service.label(),
+                            value,
+                            SAMPLE_INTERVAL.as_secs() * SUSTAINED_SAMPLES as u64,
+                            threshold
+                        ),
+                    );
+                    let _ = app.emit("resource-warning", warning);
+                }
+            }
+        }
+    });
+}
+
+/// Recent CPU and memory use of each server's process tree.
+#[tauri::command]
+pub fn get_resource_usage() -> ResourceUsage {
+    let tracks = TRACKS.lock().ok();
+    let services = Service::ALL
+        .iter()
+        .map(|&service| {
+            let track = tracks.as_ref().and_then(|t| t[index(service)].as_ref());
+            ServiceUsage {
+                service: service.id(),
+                pid: track.and_then(|t| t.pid),
+                history: track
+                    .map(|t| t.history.iter().cloned().collect())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+    ResourceUsage {
+        sample_interval_secs: SAMPLE_INTERVAL.as_secs(),
+        thresholds: Thresholds::from_config(&read_config_file()),
+        services,
+    }
+}
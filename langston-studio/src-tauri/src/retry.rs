@@ -0,0 +1,108 @@
+//! Retrying operations that fail for reasons that may pass.
+//!
+//! On a flaky network one failed `npm install` used to abort the whole
+//! setup. Operations that talk to the network or can trip over another
+//! process now go through `blocking` or `run`, which retry them with
+//! exponential backoff and log each retry. The caller decides which errors
+//! are worth another attempt; for `StudioError` that's usually
+//! `StudioError::is_retryable`.
+//!
+//! `retryAttempts` (default 3) and `retryDelayMs` (default 2000) in
+//! config.json set the policy; the delay doubles after every failure, up to
+//! `MAX_DELAY`.
+
+use crate::{read_config_file, AppConfig};
+use serde::Serialize;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+pub const DEFAULT_DELAY_MS: u64 = 2000;
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Policy {
+    /// Attempts in total, counting the first.
+    pub attempts: u32,
+    /// Wait before the first retry.
+    pub delay_ms: u64,
+}
+
+impl Policy {
+    pub fn from_config(config: &AppConfig) -> Policy {
+        Policy {
+            attempts: config.retry_attempts.unwrap_or(DEFAULT_ATTEMPTS).max(1),
+            delay_ms: config.retry_delay_ms.unwrap_or(DEFAULT_DELAY_MS),
+        }
+    }
+
+    /// The policy in config.json right now.
+    pub fn current() -> Policy {
+        Policy::from_config(&read_config_file())
+    }
+
+    /// Wait after the `failures`th failure.
+    fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u64.saturating_pow(failures.saturating_sub(1));
+        Duration::from_millis(self.delay_ms.saturating_mul(factor)).min(MAX_DELAY)
+    }
+}
+
+fn log_retry(what: &str, attempt: u32, policy: Policy, delay: Duration, error: &dyn Display) {
+    crate::logging::log_line(
+        "WARN",
+        &format!(
+            "{} failed (attempt {} of {}), retrying in {:.1}s: {}",
+            what,
+            attempt,
+            policy.attempts,
+            delay.as_secs_f64(),
+            error
+        ),
+    );
+}
+
+/// Call `f` until it succeeds, fails with an error `retryable` rejects, or
+/// runs out of attempts. Sleeps on the current thread between attempts.
+pub fn blocking<T, E: Display>(
+    what: &str,
+    policy: Policy,
+    retryable: impl Fn(&E) -> bool,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < policy.attempts && retryable(&e) => {
+                let delay = policy.delay(attempt);
+                log_retry(what, attempt, policy, delay, &e);
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// `blocking` for async operations.
+pub async fn run<T, E: Display, F: Future<Output = Result<T, E>>>(
+    what: &str,
+    policy: Policy,
+    retryable: impl Fn(&E) -> bool,
+    mut f: impl FnMut() -> F,
+) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Err(e) if attempt < policy.attempts && retryable(&e) => {
+                let delay = policy.delay(attempt);
+                log_retry(what, attempt, policy, delay, &e);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Managed Node.js runtime.
+//!
+//! Many users don't have Node.js at all. When the doctor finds node or npm
+//! missing (or too old) and nvm isn't there to provide it, setup downloads
+//! the pinned `NODE_VERSION` from nodejs.org into the app's data dir,
+//! checks it against the published SHA-256 sums, and unpacks it with `tar`.
+//!
+//! Once installed, the managed runtime comes first: `get_path_env` puts its
+//! bin dir at the front of PATH and `node_shell_command` (npm install, the
+//! Remotion dev server, renders) prepends it in the login shell, ahead of nvm.
+//!
+//! Otherwise `get_path_env` uses the newest compatible node it can find
+//! among the usual version managers and install locations (nvm, fnm, Volta,
+//! asdf, Homebrew, nvm-windows); see `discovered_node_bin`.
+
+use crate::{emit_status, get_config_dir, platform};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::AppHandle;
+
+/// Oldest Node.js major version the workspace supports.
+pub const MIN_NODE_MAJOR: u64 = 18;
+
+/// Node.js version installed when the user has none.
+pub const NODE_VERSION: &str = "24.11.0";
+const DIST_URL: &str = "https://nodejs.org/dist";
+/// Written once unpacking finished, so a half-extracted runtime is never used.
+const COMPLETE_MARKER: &str = ".complete";
+
+/// `<os>-<arch>` as nodejs.org names its builds.
+fn dist_platform() -> Result<String, String> {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win",
+        "linux" => "linux",
+        other => return Err(format!("No Node.js build for {}", other)),
+    };
+    let arch = match std::env::consts::ARCH {
+        "aarch64" => "arm64",
+        "x86_64" => "x64",
+        other => return Err(format!("No Node.js build for {}", other)),
+    };
+    Ok(format!("{}-{}", os, arch))
+}
+
+fn archive_name(dist_platform: &str) -> String {
+    let ext = if cfg!(windows) { "zip" } else { "tar.gz" };
+    format!("node-v{}-{}.{}", NODE_VERSION, dist_platform, ext)
+}
+
+fn runtime_root() -> PathBuf {
+    get_config_dir().join("runtime")
+}
+
+fn install_dir() -> Option<PathBuf> {
+    let dist_platform = dist_platform().ok()?;
+    Some(runtime_root().join(format!("node-v{}-{}", NODE_VERSION, dist_platform)))
+}
+
+/// The managed runtime's bin dir, if it's installed.
+pub fn node_bin_dir() -> Option<PathBuf> {
+    let dir = install_dir()?;
+    if !dir.join(COMPLETE_MARKER).exists() {
+        return None;
+    }
+    Some(if cfg!(windows) { dir } else { dir.join("bin") })
+}
+
+/// `script`, run with the managed runtime first on PATH if it's installed.
+pub fn with_node_path(script: &str) -> Option<String> {
+    let bin = node_bin_dir()?;
+    Some(if cfg!(windows) {
+        format!("set \"PATH={};%PATH%\" && {}", bin.display(), script)
+    } else {
+        format!("export PATH={:?}:\"$PATH\"; {}", bin, script)
+    })
+}
+
+/// The first dotted number in `output`: "v20.11.1" -> [20, 11, 1],
+/// "git version 2.39.3 (Apple Git-145)" -> [2, 39, 3].
+pub fn parse_version(output: &str) -> Option<Vec<u64>> {
+    let start = output.find(|c: char| c.is_ascii_digit())?;
+    let version: Vec<u64> = output[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    (!version.is_empty()).then_some(version)
+}
+
+/// Subdirectories of `parent`, each joined with `suffix`.
+fn subdirs(parent: &Path, suffix: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| {
+            if suffix.is_empty() {
+                e.path()
+            } else {
+                e.path().join(suffix)
+            }
+        })
+        .collect()
+}
+
+/// Directories that may hold a node binary, with where they come from.
+fn node_candidates(home: &Path) -> Vec<(&'static str, PathBuf)> {
+    let mut candidates = Vec::new();
+    let env_dir =
+        |var: &str, default: PathBuf| std::env::var_os(var).map(PathBuf::from).unwrap_or(default);
+
+    let nvm_dir = env_dir("NVM_DIR", home.join(".nvm"));
+    for dir in subdirs(&nvm_dir.join("versions/node"), "bin") {
+        candidates.push(("nvm", dir));
+    }
+
+    let mut fnm_dirs = vec![home.join(".local/share/fnm"), home.join(".fnm")];
+    if let Some(data) = dirs::data_dir() {
+        fnm_dirs.push(data.join("fnm"));
+    }
+    if let Some(dir) = std::env::var_os("FNM_DIR") {
+        fnm_dirs.insert(0, PathBuf::from(dir));
+    }
+    for fnm in fnm_dirs {
+        let suffix = if cfg!(windows) {
+            "installation"
+        } else {
+            "installation/bin"
+        };
+        for dir in subdirs(&fnm.join("node-versions"), suffix) {
+            candidates.push(("fnm", dir));
+        }
+    }
+
+    candidates.push((
+        "volta",
+        env_dir("VOLTA_HOME", home.join(".volta")).join("bin"),
+    ));
+    candidates.push((
+        "asdf",
+        env_dir("ASDF_DATA_DIR", home.join(".asdf")).join("shims"),
+    ));
+
+    if cfg!(windows) {
+        if let Some(appdata) = dirs::data_dir() {
+            for dir in subdirs(&appdata.join("nvm"), "") {
+                candidates.push(("nvm-windows", dir));
+            }
+        }
+    } else {
+        for prefix in ["/opt/homebrew", "/usr/local"] {
+            let opt = Path::new(prefix).join("opt");
+            let Ok(entries) = std::fs::read_dir(&opt) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name == "node" || name.starts_with("node@") {
+                    candidates.push(("homebrew", entry.path().join("bin")));
+                }
+            }
+            candidates.push(("homebrew", Path::new(prefix).join("bin")));
+        }
+    }
+    candidates
+}
+
+/// The version of the node in `dir`. Version-named directories (nvm, fnm)
+/// are read from the path; anything else is asked.
+fn node_version_in(dir: &Path) -> Option<Vec<u64>> {
+    let node = platform::which("node", &dir.to_string_lossy())?;
+    let named = dir
+        .ancestors()
+        .take(3)
+        .filter_map(|a| a.file_name())
+        .map(|name| name.to_string_lossy())
+        .find(|name| name.starts_with('v') && name[1..].starts_with(|c: char| c.is_ascii_digit()));
+    if let Some(version) = named.and_then(|name| parse_version(&name)) {
+        return Some(version);
+    }
+    let output = std::process::Command::new(&node)
+        .arg("--version")
+        .env("PATH", dir)
+        .output()
+        .ok()?;
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The bin dir of the newest node (at least `MIN_NODE_MAJOR`) installed by
+/// a version manager or Homebrew. Looked up once per session; the choice is
+/// logged.
+pub fn discovered_node_bin() -> Option<PathBuf> {
+    static DISCOVERED: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DISCOVERED
+        .get_or_init(|| {
+            let home = dirs::home_dir().unwrap_or_default();
+            let found: Vec<(&str, PathBuf, Vec<u64>)> = node_candidates(&home)
+                .into_iter()
+                .filter_map(|(source, dir)| {
+                    let version = node_version_in(&dir)?;
+                    Some((source, dir, version))
+                })
+                .collect();
+
+            let best = found
+                .iter()
+                .filter(|(_, _, v)| v.first().copied().unwrap_or(0) >= MIN_NODE_MAJOR)
+                .max_by(|a, b| a.2.cmp(&b.2));
+            let show = |v: &[u64]| {
+                v.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            };
+            match best {
+                Some((source, dir, version)) => {
+                    crate::logging::log_line(
+                        "INFO",
+                        &format!(
+                            "[runtime] Using node v{} from {} ({:?}); {} installs found",
+                            show(version),
+                            source,
+                            dir,
+                            found.len()
+                        ),
+                    );
+                    Some(dir.clone())
+                }
+                None => {
+                    let seen: Vec<String> = found
+                        .iter()
+                        .map(|(source, _, v)| format!("v{} ({})", show(v), source))
+                        .collect();
+                    crate::logging::log_line(
+                        "INFO",
+                        &format!(
+                            "[runtime] No node {}+ found in version managers (found: {:?})",
+                            MIN_NODE_MAJOR, seen
+                        ),
+                    );
+                    None
+                }
+            }
+        })
+        .clone()
+}
+
+/// The expected SHA-256 of `archive` from the release's SHASUMS256.txt.
+async fn expected_sha256(client: &reqwest::Client, archive: &str) -> Result<String, String> {
+    let url = format!("{}/v{}/SHASUMS256.txt", DIST_URL, NODE_VERSION);
+    let sums = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download Node.js checksums: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to download Node.js checksums: {}", e))?;
+    sums.lines()
+        .find_map(|line| {
+            let (hash, name) = line.split_once("  ")?;
+            (name.trim() == archive).then(|| hash.to_string())
+        })
+        .ok_or_else(|| format!("No checksum published for {}", archive))
+}
+
+/// Download `archive` to `dest`, reporting progress, and return its SHA-256.
+async fn download(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    archive: &str,
+    dest: &Path,
+) -> Result<String, String> {
+    let url = format!("{}/v{}/{}", DIST_URL, NODE_VERSION, archive);
+    let mut response = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download Node.js: {}", e))?;
+    let total = response.content_length();
+
+    let mut file =
+        File::create(dest).map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut received: u64 = 0;
+    let mut last_percent = None;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download Node.js: {}", e))?
+    {
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        hasher.update(&chunk);
+        received += chunk.len() as u64;
+
+        if let Some(total) = total.filter(|&t| t > 0) {
+            let percent = (received * 100 / total).min(100);
+            // Status updates are logged, so only send one per 10%.
+            if last_percent != Some(percent / 10) {
+                last_percent = Some(percent / 10);
+                emit_status(
+                    app,
+                    &format!("Downloading Node.js {} ({}%)...", NODE_VERSION, percent),
+                    5 + (percent / 10) as u8,
+                );
+            }
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn extract(archive: &Path, into: &Path) -> Result<(), String> {
+    // bsdtar (macOS, Windows 10+) and GNU tar both handle .tar.gz; bsdtar
+    // also unpacks the Windows .zip.
+    let output = std::process::Command::new("tar")
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(into)
+        .output()
+        .map_err(|e| format!("Failed to run tar: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to unpack Node.js: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Install the managed runtime unless it's already there. Returns its bin dir.
+pub fn provision(app: &AppHandle) -> Result<PathBuf, String> {
+    if let Some(bin) = node_bin_dir() {
+        return Ok(bin);
+    }
+    let dist_platform = dist_platform()?;
+    let archive = archive_name(&dist_platform);
+    let root = runtime_root();
+    let dir = install_dir().ok_or("No Node.js build for this platform")?;
+    let staging = root.join(".staging");
+    let download_path = root.join(&archive);
+
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "[runtime] Installing Node.js {} into {:?}",
+            NODE_VERSION, dir
+        ),
+    );
+    emit_status(app, &format!("Downloading Node.js {}...", NODE_VERSION), 5);
+
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    let result = tauri::async_runtime::block_on(async {
+        let client = reqwest::Client::new();
+        let expected = expected_sha256(&client, &archive).await?;
+        let actual = download(app, &client, &archive, &download_path).await?;
+        if actual != expected {
+            return Err(format!(
+                "Node.js download is corrupt (expected SHA-256 {}, got {})",
+                expected, actual
+            ));
+        }
+        Ok(())
+    })
+    .and_then(|()| {
+        emit_status(app, "Unpacking Node.js...", 15);
+        extract(&download_path, &staging)?;
+        let unpacked = staging.join(dir.file_name().unwrap_or_default());
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::rename(&unpacked, &dir)
+            .map_err(|e| format!("Failed to install Node.js into {}: {}", dir.display(), e))?;
+        File::create(dir.join(COMPLETE_MARKER))
+            .map_err(|e| format!("Failed to finish Node.js install: {}", e))?;
+        Ok(())
+    });
+
+    let _ = std::fs::remove_file(&download_path);
+    let _ = std::fs::remove_dir_all(&staging);
+
+    match result {
+        Ok(()) => {
+            let bin = node_bin_dir().ok_or("Node.js install is incomplete")?;
+            crate::logging::log_line(
+                "INFO",
+                &format!("[runtime] Node.js {} installed: {:?}", NODE_VERSION, bin),
+            );
+            if platform::which("npm", &bin.to_string_lossy()).is_none() {
+                crate::logging::log_line("WARN", "[runtime] npm missing from managed Node.js");
+            }
+            Ok(bin)
+        }
+        Err(e) => {
+            crate::logging::log_line("ERROR", &format!("[runtime] {}", e));
+            Err(e)
+        }
+    }
+}
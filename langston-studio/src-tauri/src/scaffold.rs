@@ -0,0 +1,508 @@
+//! Adding a composition from a blueprint, without asking the AI.
+//!
+//! Blueprints are bundled in `blueprints/<id>/` in the resources: a
+//! `blueprint.json` with a name, description, the composition's size, frame
+//! rate and length and its `defaultProps`, and a `Composition.tsx.hbs`
+//! Handlebars template for the component. The template gets `component`
+//! (the component's name), `id` (the composition id, the same) and `props`.
+//!
+//! `scaffold_composition(name, blueprint, props)` renders the template to
+//! `src/<Component>.tsx`, adds the import and a `<Composition>` with the
+//! blueprint's default props, overridden by `props`, to `src/Root.tsx`, and
+//! commits those two files, leaving anything else pending alone.
+//! `list_blueprints` lists what's available.
+
+use crate::error::StudioError;
+use crate::{git, resolve_workspace_dir};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const ROOT_FILE: &str = "src/Root.tsx";
+const TEMPLATE_FILE: &str = "Composition.tsx.hbs";
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Blueprint {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub duration_in_frames: u32,
+    #[serde(default)]
+    pub default_props: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScaffoldResult {
+    pub composition_id: String,
+    /// Relative to the workspace.
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    /// `None` when the workspace has no history to commit to.
+    pub commit: Option<String>,
+}
+
+fn blueprints_dir(app: &AppHandle) -> Result<PathBuf, StudioError> {
+    app.path()
+        .resource_dir()
+        .map(|dir| dir.join("blueprints"))
+        .map_err(|e| StudioError::Other(format!("Failed to get resource dir: {}", e)))
+}
+
+fn load_blueprint(dir: &Path) -> Option<Blueprint> {
+    let contents = fs::read_to_string(dir.join("blueprint.json")).ok()?;
+    let mut blueprint: Blueprint = serde_json::from_str(&contents).ok()?;
+    blueprint.id = dir.file_name()?.to_string_lossy().into_owned();
+    dir.join(TEMPLATE_FILE).is_file().then_some(blueprint)
+}
+
+/// The bundled blueprints, sorted by id.
+#[tauri::command]
+pub fn list_blueprints(app: AppHandle) -> Result<Vec<Blueprint>, StudioError> {
+    let dir = blueprints_dir(&app)?;
+    let entries = fs::read_dir(&dir).map_err(|e| StudioError::io_at("Failed to read", &dir, e))?;
+    let mut blueprints: Vec<Blueprint> = entries
+        .flatten()
+        .filter_map(|entry| load_blueprint(&entry.path()))
+        .collect();
+    blueprints.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(blueprints)
+}
+
+/// "product launch v2" -> "ProductLaunchV2". Composition ids and component
+/// names are both this.
+fn component_name(name: &str) -> Option<String> {
+    let component: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase());
+            first.into_iter().chain(chars).collect::<String>()
+        })
+        .collect();
+    component
+        .starts_with(|c: char| c.is_ascii_alphabetic())
+        .then_some(component)
+}
+
+/// The blueprint's default props with `overrides` on top. Only props the
+/// blueprint has can be set, to a value of the same JSON type.
+fn merge_props(
+    blueprint: &Blueprint,
+    overrides: serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Map<String, serde_json::Value>, StudioError> {
+    let same_type = |a: &serde_json::Value, b: &serde_json::Value| {
+        std::mem::discriminant(a) == std::mem::discriminant(b)
+    };
+    let mut props = blueprint.default_props.clone();
+    for (key, value) in overrides {
+        match props.get(&key) {
+            Some(default) if same_type(default, &value) => {
+                props.insert(key, value);
+            }
+            Some(default) => {
+                return Err(StudioError::InvalidInput(format!(
+                    "{} must be {}",
+                    key,
+                    match default {
+                        serde_json::Value::String(_) => "a string",
+                        serde_json::Value::Number(_) => "a number",
+                        serde_json::Value::Bool(_) => "true or false",
+                        serde_json::Value::Array(_) => "a list",
+                        _ => "an object",
+                    }
+                )))
+            }
+            None => {
+                return Err(StudioError::InvalidInput(format!(
+                    "{} has no prop {}; it has {}",
+                    blueprint.name,
+                    key,
+                    blueprint
+                        .default_props
+                        .keys()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            }
+        }
+    }
+    Ok(props)
+}
+
+/// Where the import goes: after the last import statement, which may span
+/// lines.
+fn import_insert_at(root: &str) -> usize {
+    let mut offset = 0;
+    let mut in_import = false;
+    let mut after_imports = 0;
+    for line in root.split_inclusive('\n') {
+        offset += line.len();
+        if line.starts_with("import ") {
+            in_import = true;
+        }
+        if in_import && line.trim_end().ends_with(';') {
+            in_import = false;
+            after_imports = offset;
+        }
+    }
+    after_imports
+}
+
+/// `root` with `component` imported and registered as a composition, just
+/// before the fragment `RemotionRoot` returns is closed.
+fn register(
+    root: &str,
+    component: &str,
+    blueprint: &Blueprint,
+    props: &serde_json::Map<String, serde_json::Value>,
+) -> Result<String, StudioError> {
+    if root.contains(&format!("id=\"{}\"", component)) {
+        return Err(StudioError::InvalidInput(format!(
+            "There's already a composition called {}",
+            component
+        )));
+    }
+    let close = root.rfind("</>").ok_or_else(|| {
+        StudioError::Other(format!(
+            "Couldn't find where to add the composition in {}; it no longer returns a <>...</> fragment",
+            ROOT_FILE
+        ))
+    })?;
+    let line_start = root[..close].rfind('\n').map_or(0, |i| i + 1);
+    let outer = &root[line_start..close];
+    if !outer.trim().is_empty() {
+        return Err(StudioError::Other(format!(
+            "Couldn't find where to add the composition in {}",
+            ROOT_FILE
+        )));
+    }
+    let indent = format!("{}  ", outer);
+    let default_props = serde_json::to_string_pretty(props)
+        .map_err(|e| StudioError::Other(e.to_string()))?
+        .replace('\n', &format!("\n{}  ", indent));
+    let composition = format!(
+        "{i}<Composition\n\
+         {i}  id=\"{c}\"\n\
+         {i}  component={{{c}}}\n\
+         {i}  durationInFrames={{{d}}}\n\
+         {i}  fps={{{f}}}\n\
+         {i}  width={{{w}}}\n\
+         {i}  height={{{h}}}\n\
+         {i}  schema={{{c}Schema}}\n\
+         {i}  defaultProps={{{p}}}\n\
+         {i}/>\n",
+        i = indent,
+        c = component,
+        d = blueprint.duration_in_frames,
+        f = blueprint.fps,
+        w = blueprint.width,
+        h = blueprint.height,
+        p = default_props,
+    );
+    let import = format!(
+        "import {{ {c}, {c}Schema }} from \"./{c}\";\n",
+        c = component
+    );
+
+    let import_at = import_insert_at(&root[..line_start]);
+    let mut updated = String::with_capacity(root.len() + composition.len() + import.len());
+    updated.push_str(&root[..import_at]);
+    updated.push_str(&import);
+    updated.push_str(&root[import_at..line_start]);
+    updated.push_str(&composition);
+    updated.push_str(&root[line_start..]);
+    Ok(updated)
+}
+
+fn scaffold(
+    app: &AppHandle,
+    name: &str,
+    blueprint_id: &str,
+    overrides: serde_json::Map<String, serde_json::Value>,
+) -> Result<ScaffoldResult, StudioError> {
+    let component = component_name(name)
+        .filter(|c| c.len() <= MAX_NAME_LEN)
+        .ok_or_else(|| {
+            StudioError::InvalidInput(format!(
+                "A composition needs a name of up to {} letters and digits, starting with a letter",
+                MAX_NAME_LEN
+            ))
+        })?;
+    let not_found = || StudioError::NotFound(format!("Blueprint {}", blueprint_id));
+    if blueprint_id.is_empty() || blueprint_id.contains(['/', '\\', '.']) {
+        return Err(not_found());
+    }
+    let dir = blueprints_dir(app)?.join(blueprint_id);
+    let blueprint = load_blueprint(&dir).ok_or_else(not_found)?;
+    let props = merge_props(&blueprint, overrides)?;
+
+    let workspace = resolve_workspace_dir();
+    let root_path = workspace.join(ROOT_FILE);
+    let component_file = format!("src/{}.tsx", component);
+    let component_path = workspace.join(&component_file);
+    if component_path.exists() {
+        return Err(StudioError::InvalidInput(format!(
+            "{} already exists",
+            component_file
+        )));
+    }
+    let root = fs::read_to_string(&root_path)
+        .map_err(|e| StudioError::io_at("Failed to read", &root_path, e))?;
+    let updated_root = register(&root, &component, &blueprint, &props)?;
+
+    let template = fs::read_to_string(dir.join(TEMPLATE_FILE))
+        .map_err(|e| StudioError::io_at("Failed to read", &dir.join(TEMPLATE_FILE), e))?;
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    // It's TSX, not HTML.
+    handlebars.register_escape_fn(handlebars::no_escape);
+    let source = handlebars
+        .render_template(
+            &template,
+            &serde_json::json!({ "component": component, "id": component, "props": props }),
+        )
+        .map_err(|e| {
+            StudioError::Other(format!(
+                "Blueprint {} failed to render: {}",
+                blueprint_id, e
+            ))
+        })?;
+
+    fs::write(&component_path, source)
+        .map_err(|e| StudioError::io_at("Failed to write", &component_path, e))?;
+    if let Err(e) = fs::write(&root_path, updated_root) {
+        let _ = fs::remove_file(&component_path);
+        return Err(StudioError::io_at("Failed to write", &root_path, e));
+    }
+
+    let commit = if git::has_commits(&workspace) {
+        let message = format!(
+            "Add {} composition from the {} blueprint",
+            component, blueprint.name
+        );
+        let paths = [component_file.as_str(), ROOT_FILE];
+        let result = git::run_git(&workspace, &[&["add", "--"][..], &paths[..]].concat())
+            .and_then(|_| {
+                git::run_git_as_studio(
+                    &workspace,
+                    &[
+                        &["commit", "-q", "-m", message.as_str(), "--"][..],
+                        &paths[..],
+                    ]
+                    .concat(),
+                )
+            })
+            .and_then(|_| git::run_git(&workspace, &["rev-parse", "HEAD"]));
+        match result {
+            Ok(hash) => Some(hash.trim().to_string()),
+            Err(e) => {
+                crate::logging::log_line("WARN", &format!("[scaffold] Couldn't commit: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "[scaffold] Added {} from the {} blueprint",
+            component, blueprint_id
+        ),
+    );
+    Ok(ScaffoldResult {
+        composition_id: component,
+        created: vec![component_file],
+        updated: vec![ROOT_FILE.to_string()],
+        commit,
+    })
+}
+
+/// Add a composition called `name` to the workspace from `blueprint` (see
+/// `list_blueprints`), with `props` overriding its default props, and
+/// commit it.
+#[tauri::command]
+pub async fn scaffold_composition(
+    app: AppHandle,
+    name: String,
+    blueprint: String,
+    props: Option<serde_json::Map<String, serde_json::Value>>,
+) -> Result<ScaffoldResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        scaffold(
+            &app,
+            name.trim(),
+            blueprint.trim(),
+            props.unwrap_or_default(),
+        )
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blueprint() -> Blueprint {
+        serde_json::from_value(serde_json::json!({
+            "name": "Title card",
+            "width": 1920,
+            "height": 1080,
+            "fps": 30,
+            "durationInFrames": 90,
+            "defaultProps": { "title": "Hello", "seconds": 3 },
+        }))
+        .unwrap()
+    }
+
+    const ROOT: &str = r#"import { Composition } from "remotion";
+import {
+  Intro,
+  IntroSchema,
+} from "./Intro";
+
+export const RemotionRoot: React.FC = () => {
+  return (
+    <>
+      <Composition id="Intro" component={Intro} schema={IntroSchema} />
+    </>
+  );
+};
+"#;
+
+    #[test]
+    fn component_names_are_pascal_case() {
+        assert_eq!(
+            component_name("product launch v2").as_deref(),
+            Some("ProductLaunchV2")
+        );
+        assert_eq!(
+            component_name("end-card_alt").as_deref(),
+            Some("EndCardAlt")
+        );
+        assert_eq!(component_name("Intro").as_deref(), Some("Intro"));
+        assert_eq!(component_name("2024 recap"), None);
+        assert_eq!(component_name(" -- "), None);
+        assert_eq!(component_name(""), None);
+    }
+
+    #[test]
+    fn props_override_defaults_of_the_same_type() {
+        let overrides = serde_json::json!({ "title": "Launch" });
+        let props = merge_props(&blueprint(), overrides.as_object().unwrap().clone()).unwrap();
+        assert_eq!(
+            serde_json::Value::Object(props),
+            serde_json::json!({ "title": "Launch", "seconds": 3 })
+        );
+    }
+
+    #[test]
+    fn props_of_another_type_or_unknown_are_refused() {
+        let overrides = serde_json::json!({ "seconds": "3" });
+        let err = merge_props(&blueprint(), overrides.as_object().unwrap().clone()).unwrap_err();
+        assert!(matches!(err, StudioError::InvalidInput(_)));
+        assert_eq!(err.to_string(), "seconds must be a number");
+
+        let overrides = serde_json::json!({ "colour": "red" });
+        let err = merge_props(&blueprint(), overrides.as_object().unwrap().clone()).unwrap_err();
+        assert!(err.to_string().starts_with("Title card has no prop colour"));
+    }
+
+    #[test]
+    fn import_goes_after_a_multi_line_import() {
+        let at = import_insert_at(ROOT);
+        assert!(ROOT[..at].ends_with("} from \"./Intro\";\n"));
+        assert!(ROOT[at..].starts_with("\nexport const RemotionRoot"));
+    }
+
+    #[test]
+    fn import_goes_first_without_imports() {
+        assert_eq!(import_insert_at("export const x = 1;\n"), 0);
+        assert_eq!(import_insert_at(""), 0);
+    }
+
+    #[test]
+    fn registers_the_composition_before_the_fragment_closes() {
+        let props = blueprint().default_props;
+        let updated = register(ROOT, "TitleCard", &blueprint(), &props).unwrap();
+        assert_eq!(
+            updated,
+            r#"import { Composition } from "remotion";
+import {
+  Intro,
+  IntroSchema,
+} from "./Intro";
+import { TitleCard, TitleCardSchema } from "./TitleCard";
+
+export const RemotionRoot: React.FC = () => {
+  return (
+    <>
+      <Composition id="Intro" component={Intro} schema={IntroSchema} />
+      <Composition
+        id="TitleCard"
+        component={TitleCard}
+        durationInFrames={90}
+        fps={30}
+        width={1920}
+        height={1080}
+        schema={TitleCardSchema}
+        defaultProps={{
+          "title": "Hello",
+          "seconds": 3
+        }}
+      />
+    </>
+  );
+};
+"#
+        );
+    }
+
+    #[test]
+    fn a_fragment_in_the_children_is_not_the_outer_one() {
+        let root = r#"export const RemotionRoot: React.FC = () => {
+  return (
+    <>
+      <Folder name="Intros">
+        <>
+          <Composition id="Intro" component={Intro} />
+        </>
+      </Folder>
+    </>
+  );
+};
+"#;
+        let props = blueprint().default_props;
+        let updated = register(root, "TitleCard", &blueprint(), &props).unwrap();
+        assert!(
+            updated.starts_with("import { TitleCard, TitleCardSchema } from \"./TitleCard\";\n")
+        );
+        assert!(updated.contains("      </Folder>\n      <Composition\n        id=\"TitleCard\""));
+        assert!(updated.ends_with("      />\n    </>\n  );\n};\n"));
+    }
+
+    #[test]
+    fn refuses_a_duplicate_or_a_root_without_a_fragment() {
+        let props = blueprint().default_props;
+        let err = register(ROOT, "Intro", &blueprint(), &props).unwrap_err();
+        assert!(matches!(err, StudioError::InvalidInput(_)));
+
+        let root = "export const RemotionRoot = () => <Composition id=\"Intro\" />;\n";
+        assert!(register(root, "TitleCard", &blueprint(), &props).is_err());
+        let root = "export const RemotionRoot = () => <></>;\n";
+        assert!(register(root, "TitleCard", &blueprint(), &props).is_err());
+    }
+}
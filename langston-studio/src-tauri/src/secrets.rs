@@ -0,0 +1,199 @@
+//! API key storage in the system keychain.
+//!
+//! Keys used to live in plaintext in config.json. They are now stored in the
+//! macOS Keychain (Credential Manager on Windows, Secret Service on Linux)
+//! under the app's identifier. Keys found in config.json are moved into the
+//! keychain on startup and removed from the file. Key values never leave the
+//! Rust side; the frontend can only set, replace or delete them.
+//!
+//! Every provider in `providers::PROVIDERS` that takes a key is stored under
+//! its id. The secret key for render uploads (see `upload`) is stored under
+//! `UPLOAD_ACCOUNT`.
+
+use crate::error::StudioError;
+use crate::{providers, update_config_file};
+
+/// Keychain service name; matches the bundle identifier.
+const SERVICE: &str = "co.langston.studio";
+/// Keychain account of the upload bucket's secret access key.
+const UPLOAD_ACCOUNT: &str = "upload-bucket";
+
+/// Top-level config.json fields keys used to live in, by provider. Other
+/// providers' keys are in `providers.<id>.apiKey`.
+const LEGACY_FIELDS: &[(&str, &str)] =
+    &[("anthropic", "anthropicApiKey"), ("openai", "openaiApiKey")];
+
+fn check_provider(provider: &str) -> Result<(), StudioError> {
+    if providers::find(provider).is_some_and(|p| p.key_env.is_some()) {
+        Ok(())
+    } else {
+        Err(StudioError::InvalidInput(format!(
+            "Unknown provider: {}",
+            provider
+        )))
+    }
+}
+
+fn entry(provider: &str) -> Result<keyring::Entry, StudioError> {
+    keyring::Entry::new(SERVICE, provider).map_err(|e| StudioError::Keychain(e.to_string()))
+}
+
+/// Read the stored key for `provider`, if any.
+pub fn get_api_key(provider: &str) -> Option<String> {
+    entry(provider)
+        .ok()?
+        .get_password()
+        .ok()
+        .filter(|k| !k.is_empty())
+}
+
+pub fn store_api_key(provider: &str, key: &str) -> Result<(), StudioError> {
+    check_provider(provider)?;
+    entry(provider)?
+        .set_password(key)
+        .map_err(|e| StudioError::Keychain(format!("failed to store {} key: {}", provider, e)))
+}
+
+pub fn remove_api_key(provider: &str) -> Result<(), StudioError> {
+    check_provider(provider)?;
+    match entry(provider)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(StudioError::Keychain(format!(
+            "failed to delete {} key: {}",
+            provider, e
+        ))),
+    }
+}
+
+fn upload_entry() -> Result<keyring::Entry, StudioError> {
+    keyring::Entry::new(SERVICE, UPLOAD_ACCOUNT).map_err(|e| StudioError::Keychain(e.to_string()))
+}
+
+/// The upload bucket's secret access key, if it's been set.
+pub fn get_upload_secret() -> Option<String> {
+    upload_entry()
+        .ok()?
+        .get_password()
+        .ok()
+        .filter(|k| !k.is_empty())
+}
+
+pub fn store_upload_secret(secret: &str) -> Result<(), StudioError> {
+    upload_entry()?
+        .set_password(secret)
+        .map_err(|e| StudioError::Keychain(format!("failed to store upload key: {}", e)))
+}
+
+pub fn remove_upload_secret() -> Result<(), StudioError> {
+    match upload_entry()?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(StudioError::Keychain(format!(
+            "failed to delete upload key: {}",
+            e
+        ))),
+    }
+}
+
+/// Move any plaintext keys from config.json into the keychain. A key is only
+/// removed from the file once it has been stored successfully.
+pub fn migrate_from_config() {
+    let Ok(contents) = std::fs::read_to_string(crate::get_config_path()) else {
+        return;
+    };
+    let Ok(root) = serde_json::from_str::<serde_json::Value>(&contents) else {
+        return;
+    };
+
+    let mut migrated = Vec::new();
+    for (provider, field) in LEGACY_FIELDS {
+        let Some(key) = root.get(*field).and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if key.is_empty() {
+            migrated.push(*field);
+            continue;
+        }
+        match store_api_key(provider, key) {
+            Ok(()) => {
+                crate::logging::log_line(
+                    "INFO",
+                    &format!("Moved {} API key from config.json to keychain", provider),
+                );
+                migrated.push(*field);
+            }
+            Err(e) => crate::logging::log_line(
+                "WARN",
+                &format!("Keeping {} key in config.json: {}", provider, e),
+            ),
+        }
+    }
+
+    let mut migrated_entries = Vec::new();
+    if let Some(entries) = root.get("providers").and_then(|v| v.as_object()) {
+        for (provider, entry) in entries {
+            let Some(key) = entry.get("apiKey").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            if key.is_empty() {
+                migrated_entries.push(provider.clone());
+                continue;
+            }
+            match store_api_key(provider, key) {
+                Ok(()) => {
+                    crate::logging::log_line(
+                        "INFO",
+                        &format!("Moved {} API key from config.json to keychain", provider),
+                    );
+                    migrated_entries.push(provider.clone());
+                }
+                Err(e) => crate::logging::log_line(
+                    "WARN",
+                    &format!("Keeping {} key in config.json: {}", provider, e),
+                ),
+            }
+        }
+    }
+
+    if migrated.is_empty() && migrated_entries.is_empty() {
+        return;
+    }
+
+    if let Err(e) = update_config_file(|obj| {
+        for field in &migrated {
+            obj.remove(*field);
+        }
+        if let Some(entries) = obj.get_mut("providers").and_then(|v| v.as_object_mut()) {
+            for provider in &migrated_entries {
+                if let Some(entry) = entries.get_mut(provider).and_then(|v| v.as_object_mut()) {
+                    entry.remove("apiKey");
+                }
+            }
+        }
+    }) {
+        crate::logging::log_line(
+            "WARN",
+            &format!("Failed to remove migrated keys from config.json: {}", e),
+        );
+    }
+}
+
+/// Store (or replace) the API key for a provider.
+#[tauri::command]
+pub fn set_api_key(provider: String, key: String) -> Result<(), StudioError> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(StudioError::InvalidInput(
+            "API key cannot be empty".to_string(),
+        ));
+    }
+    store_api_key(&provider, key)?;
+    crate::logging::log_line("INFO", &format!("Stored {} API key", provider));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_api_key(provider: String) -> Result<(), StudioError> {
+    remove_api_key(&provider)?;
+    crate::logging::log_line("INFO", &format!("Deleted {} API key", provider));
+    Ok(())
+}
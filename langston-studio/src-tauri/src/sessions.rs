@@ -0,0 +1,253 @@
+//! Picking up the last OpenCode session after a restart.
+//!
+//! OpenCode keeps its sessions on disk, but the UI opened on a fresh one
+//! after every restart, so the conversation seemed lost. The proxy now
+//! watches OpenCode API requests for the session the UI has open
+//! (`observe_path`) and the last one is saved per workspace in sessions.json
+//! in the config dir. If none was seen, the most recently updated session
+//! is asked for through the proxy on shutdown.
+//!
+//! On the next start the frontend calls `get_resume_session` once
+//! OpenCode's page has loaded and, when it gets a session back, asks the
+//! injected session-resume script to open it. `list_sessions` lists the
+//! workspace's sessions, newest first, for picking another one.
+
+use crate::error::StudioError;
+use crate::{get_config_dir, ports, proxy, resolve_workspace_dir};
+use chrono::Local;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// How long to wait for OpenCode when listing sessions.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Shorter on shutdown, where it holds up quitting.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The last session seen in a request, with the workspace it belongs to.
+static ACTIVE: Mutex<Option<(String, String)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedSession {
+    session_id: String,
+    saved_at: String,
+}
+
+/// The saved session of each workspace, by path.
+type Saved = BTreeMap<String, SavedSession>;
+
+/// A session as OpenCode's `GET /session` returns it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiSession {
+    id: String,
+    #[serde(default)]
+    title: String,
+    /// Set on sessions started by an agent inside another session.
+    #[serde(default, rename = "parentID")]
+    parent_id: Option<String>,
+    #[serde(default)]
+    time: ApiTime,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiTime {
+    #[serde(default)]
+    created: i64,
+    #[serde(default)]
+    updated: i64,
+}
+
+/// Session as returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub title: String,
+    /// Milliseconds since the epoch.
+    pub created: i64,
+    pub updated: i64,
+    /// Whether it's the one that will be resumed.
+    pub last_active: bool,
+}
+
+fn saved_path() -> PathBuf {
+    get_config_dir().join("sessions.json")
+}
+
+fn workspace_key() -> String {
+    resolve_workspace_dir().to_string_lossy().into_owned()
+}
+
+fn load_saved() -> Saved {
+    fs::read_to_string(saved_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save(workspace: String, session_id: &str) -> Result<(), String> {
+    let mut saved = load_saved();
+    saved.insert(
+        workspace,
+        SavedSession {
+            session_id: session_id.to_string(),
+            saved_at: Local::now().to_rfc3339(),
+        },
+    );
+    fs::create_dir_all(get_config_dir())
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    let contents = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+    fs::write(saved_path(), contents).map_err(|e| format!("Failed to write sessions.json: {}", e))
+}
+
+/// The session id in an OpenCode API path such as
+/// `/session/ses_abc123/message`.
+pub(crate) fn session_in_path(path: &str) -> Option<&str> {
+    let mut segments = path.split('/');
+    segments.find(|s| *s == "session")?;
+    segments.next().filter(|id| {
+        id.starts_with("ses") && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Called by the proxy for every request to OpenCode.
+pub fn observe_path(path: &str) {
+    let Some(id) = session_in_path(path) else {
+        return;
+    };
+    {
+        let Ok(active) = ACTIVE.lock() else {
+            return;
+        };
+        // Session ids are unique across workspaces, so the same id means
+        // nothing changed.
+        if active.as_ref().is_some_and(|(_, s)| s == id) {
+            return;
+        }
+    }
+    let workspace = workspace_key();
+    if let Ok(mut active) = ACTIVE.lock() {
+        *active = Some((workspace.clone(), id.to_string()));
+    }
+    // Saved as soon as it changes, so it survives a crash too.
+    if let Err(e) = save(workspace, id) {
+        crate::logging::log_line("WARN", &format!("[sessions] {}", e));
+    }
+}
+
+/// GET `path` from OpenCode's API, through the proxy.
+pub(crate) async fn api_get<T: DeserializeOwned>(
+    app: &AppHandle,
+    path: &str,
+    timeout: Duration,
+) -> Result<T, StudioError> {
+    let url = format!("http://127.0.0.1:{}{}", ports::current(app).proxy, path);
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| StudioError::Network(e.to_string()))?;
+    let response = client
+        .get(&url)
+        .header("x-langston-proxy-token", proxy::token())
+        .send()
+        .await
+        .map_err(|e| StudioError::Network(format!("Couldn't reach OpenCode: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(StudioError::Other(format!(
+            "OpenCode answered HTTP {} for {}",
+            response.status(),
+            path
+        )));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| StudioError::Other(format!("Unexpected answer to {}: {}", path, e)))
+}
+
+async fn fetch(app: &AppHandle, timeout: Duration) -> Result<Vec<ApiSession>, StudioError> {
+    let mut sessions: Vec<ApiSession> = api_get(app, "/session", timeout).await?;
+    sessions.retain(|s| s.parent_id.is_none());
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.time.updated));
+    Ok(sessions)
+}
+
+/// Make sure this workspace's session is saved before OpenCode is stopped.
+/// Called from `shutdown`.
+pub fn save_active(app: &AppHandle) {
+    let workspace = workspace_key();
+    let seen = ACTIVE
+        .lock()
+        .ok()
+        .and_then(|active| active.clone())
+        .is_some_and(|(w, _)| w == workspace);
+    if seen {
+        return;
+    }
+    // On a thread of its own: shutdown may be running on the async runtime.
+    let fetch_app = app.clone();
+    let latest = std::thread::spawn(move || {
+        tauri::async_runtime::block_on(fetch(&fetch_app, SHUTDOWN_TIMEOUT))
+    })
+    .join();
+    let result = match latest {
+        Ok(Ok(sessions)) => match sessions.first() {
+            Some(session) => save(workspace, &session.id).map(|()| Some(session.id.clone())),
+            None => Ok(None),
+        },
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("Session lookup panicked".to_string()),
+    };
+    match result {
+        Ok(Some(id)) => {
+            crate::logging::log_line("INFO", &format!("[sessions] Saved session {}", id))
+        }
+        Ok(None) => {}
+        Err(e) => crate::logging::log_line(
+            "WARN",
+            &format!("[sessions] Couldn't save the open session: {}", e),
+        ),
+    }
+}
+
+/// The workspace's sessions, newest first.
+#[tauri::command]
+pub async fn list_sessions(app: AppHandle) -> Result<Vec<SessionInfo>, StudioError> {
+    let last = load_saved().remove(&workspace_key()).map(|s| s.session_id);
+    Ok(fetch(&app, TIMEOUT)
+        .await?
+        .into_iter()
+        .map(|s| SessionInfo {
+            last_active: last.as_deref() == Some(s.id.as_str()),
+            id: s.id,
+            title: s.title,
+            created: s.time.created,
+            updated: s.time.updated,
+        })
+        .collect())
+}
+
+/// The session to reopen on startup, if one was saved for this workspace and
+/// OpenCode still has it.
+#[tauri::command]
+pub async fn get_resume_session(app: AppHandle) -> Result<Option<SessionInfo>, StudioError> {
+    let sessions = list_sessions(app.clone()).await?;
+    let session = sessions.into_iter().find(|s| s.last_active);
+    if let Some(session) = &session {
+        crate::logging::log_line(
+            "INFO",
+            &format!(
+                "[sessions] Resuming session {} (\"{}\")",
+                session.id, session.title
+            ),
+        );
+    }
+    Ok(session)
+}
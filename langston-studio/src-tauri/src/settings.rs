@@ -0,0 +1,765 @@
+//! Settings screen backend.
+//!
+//! `get_config` returns the current settings (never the API keys themselves,
+//! only whether they're set) and `set_config` applies a partial update:
+//! fields are validated, non-secret fields are written to config.json,
+//! keys go to the keychain, and the subsystems affected by the change are
+//! restarted so the user doesn't have to relaunch the app.
+
+use crate::error::StudioError;
+use crate::supervisor::{self, Service};
+use crate::{
+    autosave, backup, base_workspace_dir, editor, expand_home, get_config_path, idle, load_config,
+    logging, notifications, projects, providers, proxy, proxy_scripts, read_config_file, resources,
+    retry, secrets, setup, telemetry, templates, update_config_file, upload, usage, ServiceEnv,
+    REDACTED_ENV_VALUE,
+};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// Upper bound for `startupTimeoutSecs`; anything longer is almost certainly a typo.
+const MAX_STARTUP_TIMEOUT_SECS: u64 = 600;
+/// Upper bound for `logRetentionDays` and `logKeepSessions`.
+const MAX_LOG_RETENTION: u64 = 3650;
+/// Allowed ranges for the proxy settings.
+const PROXY_CONNECT_TIMEOUT_SECS: (u64, u64) = (1, 300);
+const PROXY_READ_TIMEOUT_SECS: (u64, u64) = (10, 24 * 60 * 60);
+const PROXY_ASSET_TIMEOUT_SECS: (u64, u64) = (1, 600);
+const PROXY_API_TIMEOUT_SECS: (u64, u64) = (1, 60 * 60);
+const PROXY_POOL_MAX_IDLE: (u64, u64) = (0, 100);
+const PROXY_RATE_LIMIT_PER_MIN: (u64, u64) = (0, 600);
+const PROXY_RATE_LIMIT_BURST: (u64, u64) = (1, 100);
+const PROXY_MAX_BODY_BYTES: (u64, u64) = (1024 * 1024, 4 * 1024 * 1024 * 1024);
+/// Upper bound for `autoSaveIntervalMins`.
+const MAX_AUTO_SAVE_INTERVAL_MINS: u64 = 24 * 60;
+/// Upper bound for `idleSuspendMins`.
+const MAX_IDLE_SUSPEND_MINS: u64 = 24 * 60;
+/// Allowed ranges for `resourceCpuWarnPercent` and `resourceMemoryWarnMb`.
+const RESOURCE_CPU_WARN_PERCENT: (u64, u64) = (10, 10_000);
+const RESOURCE_MEMORY_WARN_MB: (u64, u64) = (128, 1024 * 1024);
+/// Allowed ranges for `retryAttempts` and `retryDelayMs`.
+const RETRY_ATTEMPTS: (u64, u64) = (1, 10);
+const RETRY_DELAY_MS: (u64, u64) = (100, 60_000);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigView {
+    config_path: String,
+    workspace_dir: Option<String>,
+    resolved_workspace_dir: String,
+    /// Only used when the default workspace is created.
+    workspace_template: Option<String>,
+    template_registry_url: Option<String>,
+    stills_dir: Option<String>,
+    ffmpeg_path: Option<String>,
+    inject_scripts_dir: Option<String>,
+    backup_remote: Option<String>,
+    upload: Option<upload::UploadConfig>,
+    has_upload_secret: bool,
+    webhooks: Vec<notifications::Webhook>,
+    log_level: String,
+    /// Takes effect on the next launch.
+    human_readable_log: bool,
+    log_retention_days: u32,
+    log_keep_sessions: usize,
+    startup_timeout_secs: Option<u64>,
+    proxy: proxy::ProxyConfig,
+    retry: retry::Policy,
+    remotion_prewarm: bool,
+    /// 0 when auto-save is off.
+    auto_save_interval_mins: u64,
+    telemetry_enabled: bool,
+    desktop_notifications: bool,
+    /// 0 when the Remotion server is never stopped.
+    idle_suspend_mins: u64,
+    resource_thresholds: resources::Thresholds,
+    usage_budget: Option<usage::Budget>,
+    editor: Option<String>,
+    /// Values of secret variables are redacted.
+    env: ServiceEnv,
+    has_anthropic_key: bool,
+    has_openai_key: bool,
+    providers: Vec<providers::ProviderStatus>,
+}
+
+fn config_view() -> ConfigView {
+    let file = read_config_file();
+    let config = load_config();
+    // These read several fields, so take them before the literal moves any out.
+    let proxy = proxy::ProxyConfig::from_app_config(&file);
+    let retry = retry::Policy::from_config(&file);
+    let auto_save_interval_mins = autosave::interval_mins(&file);
+    let resource_thresholds = resources::Thresholds::from_config(&file);
+    ConfigView {
+        config_path: get_config_path().to_string_lossy().to_string(),
+        workspace_dir: file.workspace_dir,
+        resolved_workspace_dir: base_workspace_dir().to_string_lossy().to_string(),
+        workspace_template: file.workspace_template,
+        template_registry_url: file.template_registry_url,
+        stills_dir: file.stills_dir,
+        ffmpeg_path: file.ffmpeg_path,
+        inject_scripts_dir: file.inject_scripts_dir,
+        backup_remote: file.backup_remote,
+        upload: file.upload,
+        has_upload_secret: secrets::get_upload_secret().is_some(),
+        webhooks: file.webhooks.unwrap_or_default(),
+        log_level: logging::level().as_str().to_ascii_lowercase(),
+        human_readable_log: file.human_readable_log.unwrap_or(true),
+        log_retention_days: file
+            .log_retention_days
+            .unwrap_or(logging::DEFAULT_RETENTION_DAYS),
+        log_keep_sessions: file
+            .log_keep_sessions
+            .unwrap_or(logging::DEFAULT_KEEP_SESSIONS),
+        startup_timeout_secs: file.startup_timeout_secs,
+        proxy,
+        retry,
+        remotion_prewarm: file.remotion_prewarm.unwrap_or(false),
+        auto_save_interval_mins,
+        telemetry_enabled: file.telemetry_enabled.unwrap_or(true),
+        desktop_notifications: file.desktop_notifications.unwrap_or(true),
+        idle_suspend_mins: file.idle_suspend_mins.unwrap_or(idle::DEFAULT_IDLE_MINS),
+        resource_thresholds,
+        usage_budget: file.usage_budget,
+        editor: file.editor,
+        env: file.env.unwrap_or_default().redacted(),
+        has_anthropic_key: config.anthropic_api_key.is_some(),
+        has_openai_key: config.openai_api_key.is_some(),
+        providers: providers::statuses(&config),
+    }
+}
+
+#[tauri::command]
+pub fn get_config() -> ConfigView {
+    config_view()
+}
+
+/// A validated change to a single setting.
+enum Change {
+    /// Write (or remove, for `None`) a field in config.json.
+    File(&'static str, Option<serde_json::Value>),
+    /// Store (or delete, for `None`) an API key in the keychain.
+    ApiKey(&'static str, Option<String>),
+    /// Set (or remove, for `None`) a provider's base URL.
+    BaseUrl(&'static str, Option<String>),
+    /// Store (or delete, for `None`) the upload bucket's secret key.
+    UploadSecret(Option<String>),
+}
+
+/// Validate `providers`: `{"<id>": {"apiKey": ..., "baseUrl": ...}}`, where
+/// either field can be left out and null or empty removes it.
+fn validate_providers(
+    value: &serde_json::Value,
+    changes: &mut Vec<Change>,
+    errors: &mut Vec<String>,
+) {
+    let Some(entries) = value.as_object() else {
+        errors.push("providers must be an object keyed by provider id".to_string());
+        return;
+    };
+    for (id, entry) in entries {
+        let Some(provider) = providers::find(id) else {
+            errors.push(format!("Unknown provider: {}", id));
+            continue;
+        };
+        let Some(entry) = entry.as_object() else {
+            errors.push(format!("providers.{} must be an object", id));
+            continue;
+        };
+        for (field, value) in entry {
+            let value = match value {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(s) if s.trim().is_empty() => None,
+                serde_json::Value::String(s) => Some(s.trim().to_string()),
+                _ => {
+                    errors.push(format!(
+                        "providers.{}.{} must be a string or null",
+                        id, field
+                    ));
+                    continue;
+                }
+            };
+            match field.as_str() {
+                "apiKey" if provider.key_env.is_none() => {
+                    errors.push(format!("{} doesn't take an API key", provider.name))
+                }
+                "apiKey"
+                    if value
+                        .as_ref()
+                        .is_some_and(|k| k.chars().any(char::is_whitespace)) =>
+                {
+                    errors.push(format!(
+                        "providers.{}.apiKey must not contain whitespace",
+                        id
+                    ))
+                }
+                "apiKey" => changes.push(Change::ApiKey(provider.id, value)),
+                "baseUrl" if provider.base_url_env.is_none() => {
+                    errors.push(format!("{} doesn't take a base URL", provider.name))
+                }
+                "baseUrl"
+                    if value.as_ref().is_some_and(|url| {
+                        !(url.starts_with("http://") || url.starts_with("https://"))
+                    }) =>
+                {
+                    errors.push(format!("providers.{}.baseUrl must be an http(s) URL", id))
+                }
+                "baseUrl" => changes.push(Change::BaseUrl(provider.id, value)),
+                other => errors.push(format!("Unknown provider setting: {}", other)),
+            }
+        }
+    }
+}
+
+/// Validate a directory setting. Empty or null clears it; otherwise it must be
+/// an absolute path (after `~` expansion) that isn't an existing file.
+fn validate_dir(
+    field: &'static str,
+    value: &serde_json::Value,
+    changes: &mut Vec<Change>,
+    errors: &mut Vec<String>,
+) {
+    match value {
+        serde_json::Value::Null => changes.push(Change::File(field, None)),
+        serde_json::Value::String(p) if p.trim().is_empty() => {
+            changes.push(Change::File(field, None))
+        }
+        serde_json::Value::String(p) => {
+            let dir = expand_home(p.trim());
+            if !dir.is_absolute() {
+                errors.push(format!("{} must be an absolute path: {}", field, p));
+            } else if dir.exists() && !dir.is_dir() {
+                errors.push(format!("{} is not a directory: {}", field, p));
+            } else {
+                changes.push(Change::File(field, Some(serde_json::json!(p.trim()))));
+            }
+        }
+        _ => errors.push(format!("{} must be a string or null", field)),
+    }
+}
+
+/// Validate `env`: `{"opencode": {...}, "remotion": {...}}` with string
+/// values. A secret left as shown by `get_config` (redacted) keeps its
+/// current value.
+fn validate_env(value: &serde_json::Value) -> Result<Option<serde_json::Value>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let mut env: ServiceEnv = serde_json::from_value(value.clone()).map_err(|_| {
+        "env must look like {\"opencode\": {\"NAME\": \"value\"}, \"remotion\": {...}}".to_string()
+    })?;
+    let current = read_config_file().env.unwrap_or_default();
+    for (vars, current_vars) in [
+        (&mut env.opencode, &current.opencode),
+        (&mut env.remotion, &current.remotion),
+    ] {
+        for (name, value) in vars.iter_mut() {
+            let valid = !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !name.starts_with(|c: char| c.is_ascii_digit());
+            if !valid {
+                return Err(format!("env: {:?} is not a valid variable name", name));
+            }
+            if name.eq_ignore_ascii_case("PATH") {
+                return Err("env: PATH is managed by the app".to_string());
+            }
+            if value.contains('\0') {
+                return Err(format!("env: {} contains a NUL character", name));
+            }
+            if value == REDACTED_ENV_VALUE {
+                match current_vars.get(name) {
+                    Some(existing) => *value = existing.clone(),
+                    None => return Err(format!("env: {} needs a value", name)),
+                }
+            }
+        }
+    }
+    let empty = env.opencode.is_empty() && env.remotion.is_empty();
+    Ok((!empty).then(|| serde_json::to_value(env).unwrap_or_default()))
+}
+
+fn validate(patch: &serde_json::Map<String, serde_json::Value>) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    let mut errors = Vec::new();
+
+    for (field, value) in patch {
+        match field.as_str() {
+            "workspaceDir" => validate_dir("workspaceDir", value, &mut changes, &mut errors),
+            "workspaceTemplate" => match value {
+                serde_json::Value::Null => changes.push(Change::File("workspaceTemplate", None)),
+                serde_json::Value::String(t) if t.trim().is_empty() => {
+                    changes.push(Change::File("workspaceTemplate", None))
+                }
+                serde_json::Value::String(t) if templates::valid_id(t.trim()) => changes.push(
+                    Change::File("workspaceTemplate", Some(serde_json::json!(t.trim()))),
+                ),
+                serde_json::Value::String(t) => {
+                    errors.push(format!("workspaceTemplate is not a template id: {}", t))
+                }
+                _ => errors.push("workspaceTemplate must be a string or null".to_string()),
+            },
+            "templateRegistryUrl" => match value {
+                serde_json::Value::Null => changes.push(Change::File("templateRegistryUrl", None)),
+                serde_json::Value::String(url) if url.trim().is_empty() => {
+                    changes.push(Change::File("templateRegistryUrl", None))
+                }
+                serde_json::Value::String(url) if url.trim().starts_with("https://") => {
+                    changes.push(Change::File(
+                        "templateRegistryUrl",
+                        Some(serde_json::json!(url.trim())),
+                    ))
+                }
+                serde_json::Value::String(url) => {
+                    errors.push(format!("templateRegistryUrl must be an https URL: {}", url))
+                }
+                _ => errors.push("templateRegistryUrl must be a string or null".to_string()),
+            },
+            "stillsDir" => validate_dir("stillsDir", value, &mut changes, &mut errors),
+            "ffmpegPath" => match value {
+                serde_json::Value::Null => changes.push(Change::File("ffmpegPath", None)),
+                serde_json::Value::String(p) if p.trim().is_empty() => {
+                    changes.push(Change::File("ffmpegPath", None))
+                }
+                serde_json::Value::String(p) if expand_home(p.trim()).is_file() => changes.push(
+                    Change::File("ffmpegPath", Some(serde_json::json!(p.trim()))),
+                ),
+                serde_json::Value::String(p) => {
+                    errors.push(format!("ffmpegPath is not a file: {}", p))
+                }
+                _ => errors.push("ffmpegPath must be a string or null".to_string()),
+            },
+            "injectScriptsDir" => {
+                validate_dir("injectScriptsDir", value, &mut changes, &mut errors)
+            }
+            "backupRemote" => match value {
+                serde_json::Value::Null => changes.push(Change::File("backupRemote", None)),
+                serde_json::Value::String(url) if url.trim().is_empty() => {
+                    changes.push(Change::File("backupRemote", None))
+                }
+                serde_json::Value::String(url) if backup::valid_remote_url(url.trim()) => changes
+                    .push(Change::File(
+                        "backupRemote",
+                        Some(serde_json::json!(url.trim())),
+                    )),
+                serde_json::Value::String(url) => {
+                    errors.push(format!("backupRemote is not a valid git URL: {}", url))
+                }
+                _ => errors.push("backupRemote must be a string or null".to_string()),
+            },
+            "upload" => match value {
+                serde_json::Value::Null => changes.push(Change::File("upload", None)),
+                v => match serde_json::from_value::<upload::UploadConfig>(v.clone()) {
+                    Ok(config) => {
+                        let problems = config.problems();
+                        if problems.is_empty() {
+                            changes.push(Change::File("upload", Some(v.clone())))
+                        } else {
+                            errors.extend(problems)
+                        }
+                    }
+                    Err(_) => errors.push(
+                        "upload must look like {\"endpoint\": ..., \"bucket\": ..., \"accessKeyId\": ...}"
+                            .to_string(),
+                    ),
+                },
+            },
+            "webhooks" => match value {
+                serde_json::Value::Null => changes.push(Change::File("webhooks", None)),
+                v => match serde_json::from_value::<Vec<notifications::Webhook>>(v.clone()) {
+                    Ok(webhooks) => {
+                        let problems: Vec<String> =
+                            webhooks.iter().flat_map(|w| w.problems()).collect();
+                        if !problems.is_empty() {
+                            errors.extend(problems)
+                        } else if webhooks.is_empty() {
+                            changes.push(Change::File("webhooks", None))
+                        } else {
+                            changes.push(Change::File("webhooks", Some(v.clone())))
+                        }
+                    }
+                    Err(_) => errors.push(
+                        "webhooks must be a list of {\"url\": ..., \"events\": [...]}"
+                            .to_string(),
+                    ),
+                },
+            },
+            "usageBudget" => match value {
+                serde_json::Value::Null => changes.push(Change::File("usageBudget", None)),
+                v => match serde_json::from_value::<usage::Budget>(v.clone()) {
+                    Ok(budget) => {
+                        let problems = budget.problems();
+                        if !problems.is_empty() {
+                            errors.extend(problems)
+                        } else if budget == usage::Budget::default() {
+                            changes.push(Change::File("usageBudget", None))
+                        } else {
+                            changes.push(Change::File("usageBudget", Some(v.clone())))
+                        }
+                    }
+                    Err(_) => errors.push(
+                        "usageBudget must look like {\"dailyUsd\": ..., \"monthlyUsd\": ...}"
+                            .to_string(),
+                    ),
+                },
+            },
+            "uploadSecretAccessKey" => match value {
+                serde_json::Value::Null => changes.push(Change::UploadSecret(None)),
+                serde_json::Value::String(k) if k.trim().is_empty() => {
+                    changes.push(Change::UploadSecret(None))
+                }
+                serde_json::Value::String(k) => {
+                    changes.push(Change::UploadSecret(Some(k.trim().to_string())))
+                }
+                _ => errors.push("uploadSecretAccessKey must be a string or null".to_string()),
+            },
+            "logLevel" => match value {
+                serde_json::Value::Null => changes.push(Change::File("logLevel", None)),
+                serde_json::Value::String(l) if logging::Level::parse(l).is_some() => changes.push(
+                    Change::File("logLevel", Some(serde_json::json!(l.to_ascii_lowercase()))),
+                ),
+                _ => errors.push(
+                    "logLevel must be one of \"debug\", \"info\", \"warn\" or \"error\""
+                        .to_string(),
+                ),
+            },
+            "humanReadableLog" => match value {
+                serde_json::Value::Null => changes.push(Change::File("humanReadableLog", None)),
+                serde_json::Value::Bool(b) => {
+                    changes.push(Change::File("humanReadableLog", Some(serde_json::json!(b))))
+                }
+                _ => errors.push("humanReadableLog must be true, false or null".to_string()),
+            },
+            "logRetentionDays" | "logKeepSessions" => {
+                let field = if field == "logRetentionDays" {
+                    "logRetentionDays"
+                } else {
+                    "logKeepSessions"
+                };
+                match value {
+                    serde_json::Value::Null => changes.push(Change::File(field, None)),
+                    v => match v.as_u64() {
+                        Some(n) if (1..=MAX_LOG_RETENTION).contains(&n) => {
+                            changes.push(Change::File(field, Some(serde_json::json!(n))))
+                        }
+                        _ => errors.push(format!(
+                            "{} must be a whole number between 1 and {}",
+                            field, MAX_LOG_RETENTION
+                        )),
+                    },
+                }
+            }
+            "startupTimeoutSecs" => match value {
+                serde_json::Value::Null => changes.push(Change::File("startupTimeoutSecs", None)),
+                v => match v.as_u64() {
+                    Some(secs) if (1..=MAX_STARTUP_TIMEOUT_SECS).contains(&secs) => changes.push(
+                        Change::File("startupTimeoutSecs", Some(serde_json::json!(secs))),
+                    ),
+                    _ => errors.push(format!(
+                        "startupTimeoutSecs must be a whole number between 1 and {}",
+                        MAX_STARTUP_TIMEOUT_SECS
+                    )),
+                },
+            },
+            "autoSaveIntervalMins" => match value {
+                serde_json::Value::Null => changes.push(Change::File("autoSaveIntervalMins", None)),
+                v => match v.as_u64() {
+                    Some(mins) if mins <= MAX_AUTO_SAVE_INTERVAL_MINS => changes.push(
+                        Change::File("autoSaveIntervalMins", Some(serde_json::json!(mins))),
+                    ),
+                    _ => errors.push(format!(
+                        "autoSaveIntervalMins must be a whole number between 0 (off) and {}",
+                        MAX_AUTO_SAVE_INTERVAL_MINS
+                    )),
+                },
+            },
+            "idleSuspendMins" => match value {
+                serde_json::Value::Null => changes.push(Change::File("idleSuspendMins", None)),
+                v => match v.as_u64() {
+                    Some(mins) if mins <= MAX_IDLE_SUSPEND_MINS => changes.push(Change::File(
+                        "idleSuspendMins",
+                        Some(serde_json::json!(mins)),
+                    )),
+                    _ => errors.push(format!(
+                        "idleSuspendMins must be a whole number between 0 (never) and {}",
+                        MAX_IDLE_SUSPEND_MINS
+                    )),
+                },
+            },
+            "proxyConnectTimeoutSecs"
+            | "proxyReadTimeoutSecs"
+            | "proxyAssetTimeoutSecs"
+            | "proxyApiTimeoutSecs"
+            | "proxyPoolMaxIdle"
+            | "proxyMaxBodyBytes"
+            | "proxyRateLimitPerMin"
+            | "proxyRateLimitBurst" => {
+                let (field, (min, max)) = match field.as_str() {
+                    "proxyConnectTimeoutSecs" => {
+                        ("proxyConnectTimeoutSecs", PROXY_CONNECT_TIMEOUT_SECS)
+                    }
+                    "proxyReadTimeoutSecs" => ("proxyReadTimeoutSecs", PROXY_READ_TIMEOUT_SECS),
+                    "proxyAssetTimeoutSecs" => ("proxyAssetTimeoutSecs", PROXY_ASSET_TIMEOUT_SECS),
+                    "proxyApiTimeoutSecs" => ("proxyApiTimeoutSecs", PROXY_API_TIMEOUT_SECS),
+                    "proxyPoolMaxIdle" => ("proxyPoolMaxIdle", PROXY_POOL_MAX_IDLE),
+                    "proxyRateLimitPerMin" => ("proxyRateLimitPerMin", PROXY_RATE_LIMIT_PER_MIN),
+                    "proxyRateLimitBurst" => ("proxyRateLimitBurst", PROXY_RATE_LIMIT_BURST),
+                    _ => ("proxyMaxBodyBytes", PROXY_MAX_BODY_BYTES),
+                };
+                match value {
+                    serde_json::Value::Null => changes.push(Change::File(field, None)),
+                    v => match v.as_u64() {
+                        Some(n) if (min..=max).contains(&n) => {
+                            changes.push(Change::File(field, Some(serde_json::json!(n))))
+                        }
+                        _ => errors.push(format!(
+                            "{} must be a whole number between {} and {}",
+                            field, min, max
+                        )),
+                    },
+                }
+            }
+            "retryAttempts"
+            | "retryDelayMs"
+            | "resourceCpuWarnPercent"
+            | "resourceMemoryWarnMb" => {
+                let (field, (min, max)) = match field.as_str() {
+                    "retryAttempts" => ("retryAttempts", RETRY_ATTEMPTS),
+                    "retryDelayMs" => ("retryDelayMs", RETRY_DELAY_MS),
+                    "resourceCpuWarnPercent" => {
+                        ("resourceCpuWarnPercent", RESOURCE_CPU_WARN_PERCENT)
+                    }
+                    _ => ("resourceMemoryWarnMb", RESOURCE_MEMORY_WARN_MB),
+                };
+                match value {
+                    serde_json::Value::Null => changes.push(Change::File(field, None)),
+                    v => match v.as_u64() {
+                        Some(n) if (min..=max).contains(&n) => {
+                            changes.push(Change::File(field, Some(serde_json::json!(n))))
+                        }
+                        _ => errors.push(format!(
+                            "{} must be a whole number between {} and {}",
+                            field, min, max
+                        )),
+                    },
+                }
+            }
+            "proxyRequireToken"
+            | "proxyHttp2"
+            | "telemetryEnabled"
+            | "remotionPrewarm"
+            | "desktopNotifications" => {
+                let field = match field.as_str() {
+                    "proxyRequireToken" => "proxyRequireToken",
+                    "proxyHttp2" => "proxyHttp2",
+                    "telemetryEnabled" => "telemetryEnabled",
+                    "desktopNotifications" => "desktopNotifications",
+                    _ => "remotionPrewarm",
+                };
+                match value {
+                    serde_json::Value::Null => changes.push(Change::File(field, None)),
+                    serde_json::Value::Bool(b) => {
+                        changes.push(Change::File(field, Some(serde_json::json!(b))))
+                    }
+                    _ => errors.push(format!("{} must be true, false or null", field)),
+                }
+            }
+            "editor" => match value {
+                serde_json::Value::Null => changes.push(Change::File("editor", None)),
+                serde_json::Value::String(e) if editor::find(e).is_some() => {
+                    changes.push(Change::File("editor", Some(serde_json::json!(e))))
+                }
+                serde_json::Value::String(e) if editor::EDITORS.iter().any(|ed| ed.id == e) => {
+                    errors.push(format!("editor {} is not installed", e))
+                }
+                _ => errors.push("editor must be \"code\", \"cursor\" or null".to_string()),
+            },
+            "providers" => validate_providers(value, &mut changes, &mut errors),
+            "env" => match validate_env(value) {
+                Ok(env) => changes.push(Change::File("env", env)),
+                Err(e) => errors.push(e),
+            },
+            "anthropicApiKey" | "openaiApiKey" => {
+                let provider = if field == "anthropicApiKey" {
+                    "anthropic"
+                } else {
+                    "openai"
+                };
+                match value {
+                    serde_json::Value::Null => changes.push(Change::ApiKey(provider, None)),
+                    serde_json::Value::String(k) if k.trim().is_empty() => {
+                        changes.push(Change::ApiKey(provider, None))
+                    }
+                    serde_json::Value::String(k) if k.trim().chars().any(char::is_whitespace) => {
+                        errors.push(format!("{} must not contain whitespace", field))
+                    }
+                    serde_json::Value::String(k) => {
+                        changes.push(Change::ApiKey(provider, Some(k.trim().to_string())))
+                    }
+                    _ => errors.push(format!("{} must be a string or null", field)),
+                }
+            }
+            other => errors.push(format!("Unknown setting: {}", other)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(changes)
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// `obj[key]` as an object, created (or replacing anything else there) if
+/// needed.
+fn object_field<'a>(
+    obj: &'a mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+) -> Option<&'a mut serde_json::Map<String, serde_json::Value>> {
+    if !obj.get(key).is_some_and(|value| value.is_object()) {
+        obj.insert(key.to_string(), serde_json::json!({}));
+    }
+    obj.get_mut(key)?.as_object_mut()
+}
+
+/// Validate and apply a partial settings update. Nothing is written unless
+/// every field is valid. Returns the updated settings.
+#[tauri::command]
+pub async fn set_config(
+    app: AppHandle,
+    patch: serde_json::Map<String, serde_json::Value>,
+) -> Result<ConfigView, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || set_config_blocking(&app, &patch)).await?
+}
+
+fn set_config_blocking(
+    app: &AppHandle,
+    patch: &serde_json::Map<String, serde_json::Value>,
+) -> Result<ConfigView, StudioError> {
+    let changes = validate(patch).map_err(StudioError::InvalidInput)?;
+    let before = read_config_file();
+    let mut keys_changed = false;
+    let mut file_changes = Vec::new();
+    let mut base_urls = Vec::new();
+
+    for change in changes {
+        match change {
+            Change::ApiKey(provider, Some(key)) => {
+                secrets::store_api_key(provider, &key)?;
+                keys_changed = true;
+            }
+            Change::ApiKey(provider, None) => {
+                secrets::remove_api_key(provider)?;
+                keys_changed = true;
+            }
+            Change::UploadSecret(Some(secret)) => secrets::store_upload_secret(&secret)?,
+            Change::UploadSecret(None) => secrets::remove_upload_secret()?,
+            Change::File(field, value) => file_changes.push((field, value)),
+            Change::BaseUrl(provider, value) => {
+                base_urls.push((provider, value));
+                keys_changed = true;
+            }
+        }
+    }
+
+    if !file_changes.is_empty() || !base_urls.is_empty() {
+        update_config_file(|obj| {
+            for (provider, value) in base_urls {
+                let Some(entry) = object_field(obj, "providers")
+                    .and_then(|entries| object_field(entries, provider))
+                else {
+                    continue;
+                };
+                match value {
+                    Some(url) => {
+                        entry.insert("baseUrl".to_string(), serde_json::json!(url));
+                    }
+                    None => {
+                        entry.remove("baseUrl");
+                    }
+                }
+            }
+            for (field, value) in file_changes {
+                match value {
+                    Some(v) => {
+                        obj.insert(field.to_string(), v);
+                    }
+                    None => {
+                        obj.remove(field);
+                    }
+                }
+            }
+        })?;
+    }
+
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Settings updated: {}",
+            patch.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    );
+
+    let after = read_config_file();
+    logging::set_level(
+        after
+            .log_level
+            .as_deref()
+            .and_then(logging::Level::parse)
+            .unwrap_or(logging::Level::Info),
+    );
+    telemetry::set_enabled(after.telemetry_enabled.unwrap_or(true));
+
+    if after.inject_scripts_dir != before.inject_scripts_dir {
+        proxy_scripts::reload();
+    }
+    let proxy_changed =
+        proxy::ProxyConfig::from_app_config(&after) != proxy::ProxyConfig::from_app_config(&before);
+    if proxy_changed {
+        if let Err(e) = proxy::reload_proxy_config() {
+            crate::logging::log_line("ERROR", &format!("Proxy settings not applied: {}", e));
+        }
+    }
+
+    // The default workspace moved and it's the one open: set it up and
+    // respawn both servers there.
+    let workspace_moved =
+        after.workspace_dir != before.workspace_dir && projects::active_project_dir().is_none();
+
+    let reload = if workspace_moved {
+        supervisor::relaunch(app, &Service::ALL, "Workspace directory changed", |app| {
+            setup::prepare_workspace_blocking(app)
+        })
+    } else {
+        // Provider keys and URLs only affect OpenCode; extra variables the
+        // service they're set for.
+        let before_env = before.env.unwrap_or_default();
+        let after_env = after.env.clone().unwrap_or_default();
+        let mut services = Vec::new();
+        if keys_changed || after_env.opencode != before_env.opencode {
+            services.push(Service::OpenCode);
+        }
+        if after_env.remotion != before_env.remotion {
+            services.push(Service::Remotion);
+        }
+        if services.is_empty() {
+            Ok(())
+        } else {
+            supervisor::relaunch(
+                app,
+                &services,
+                "Provider settings or environment changed, restarting",
+                |_| Ok(()),
+            )
+        }
+    };
+
+    // Back up existing history right away to a newly configured remote.
+    if after.backup_remote.is_some() && after.backup_remote != before.backup_remote {
+        backup::schedule_push(app);
+    }
+
+    let view = config_view();
+    let _ = app.emit("config-changed", view.clone());
+    reload?;
+    Ok(view)
+}
@@ -0,0 +1,513 @@
+//! Startup: everything between the window opening and `setup-complete`.
+//!
+//! Setup used to run on a raw thread, one blocking call after another, so a
+//! hung `git` or installer stalled it forever and nothing could stop it. It
+//! now runs as a task on Tauri's async runtime. Each blocking step goes
+//! through `step`, which runs it on the blocking pool with a time limit and
+//! gives up on it when `cancel_setup` is called; git runs on
+//! `tokio::process` and is killed when it takes longer than `GIT_TIMEOUT`.
+//! Steps that don't depend on each other run side by side through
+//! `concurrently`.
+//!
+//! The OpenCode and Remotion children stay `std::process` children, since
+//! the supervisor and the shutdown path manage them synchronously.
+
+use crate::error::StudioError;
+use crate::phase::{self, Phase};
+use crate::{
+    autosave, backup, doctor, emit_status, frontend, get_config_path, get_path_env, idle,
+    key_validation, load_config, local_models, notifications, npm, onboarding, ports, prewarm,
+    providers, proxy, readiness, resolve_workspace_dir, resources, retry, runtime, secrets,
+    setup_workspace, spawn_opencode, spawn_remotion, start_proxy, supervisor, telemetry, template,
+    template_dir, transcripts, updater, watcher, workspace_lock, AppState,
+};
+use futures_util::future::{BoxFuture, FutureExt};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+const PROVISION_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// Covers copying the template and `npm install`, which also has its own
+/// stall detection.
+const WORKSPACE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+/// Includes installing the opencode CLI when it's missing.
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const GIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// Whether the last setup run failed, so `retry_setup` may start another.
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+fn cancel_signal() -> &'static watch::Sender<bool> {
+    static CANCEL: OnceLock<watch::Sender<bool>> = OnceLock::new();
+    CANCEL.get_or_init(|| watch::channel(false).0)
+}
+
+/// Whether setup is running.
+pub fn running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Whether `cancel_setup` has been called on the setup that's running.
+pub fn cancel_requested() -> bool {
+    *cancel_signal().borrow()
+}
+
+/// Run `f` on the blocking pool. Fails if it takes longer than `timeout` or
+/// setup is cancelled first; `f` itself keeps running in that case, so
+/// steps that start processes must clean up after themselves.
+async fn step<T: Send + 'static>(
+    name: &str,
+    timeout: Option<Duration>,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, StudioError> {
+    telemetry::breadcrumb("setup", name);
+    let mut cancelled = cancel_signal().subscribe();
+    if *cancelled.borrow() {
+        return Err(StudioError::Cancelled(name.to_string()));
+    }
+    let task = tauri::async_runtime::spawn_blocking(f);
+    let run = async {
+        let joined = match timeout {
+            Some(limit) => {
+                tokio::time::timeout(limit, task)
+                    .await
+                    .map_err(|_| StudioError::Timeout {
+                        what: name.to_string(),
+                        secs: limit.as_secs(),
+                    })?
+            }
+            None => task.await,
+        };
+        joined.map_err(|e| StudioError::Other(format!("{} failed: {}", name, e)))
+    };
+    tokio::select! {
+        result = run => result,
+        _ = cancelled.wait_for(|cancelled| *cancelled) => {
+            Err(StudioError::Cancelled(name.to_string()))
+        }
+    }
+}
+
+/// Run git in `workspace`, giving up after `GIT_TIMEOUT`. Failures to run
+/// it at all are logged.
+async fn git(workspace: &Path, path_env: &str, args: &[&str]) -> Result<Output, StudioError> {
+    let mut command = tokio::process::Command::new("git");
+    command
+        .args(args)
+        .current_dir(workspace)
+        .env("PATH", path_env)
+        .env("GIT_AUTHOR_NAME", "Langston Studio")
+        .env("GIT_AUTHOR_EMAIL", "studio@langston.co")
+        .env("GIT_COMMITTER_NAME", "Langston Studio")
+        .env("GIT_COMMITTER_EMAIL", "studio@langston.co")
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    let what = format!("git {}", args.join(" "));
+    let result = match tokio::time::timeout(GIT_TIMEOUT, command.output()).await {
+        Ok(Ok(output)) => return Ok(output),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(StudioError::GitMissing(format!("{}: git not found", what)))
+        }
+        Ok(Err(e)) => Err(StudioError::io(format!("Couldn't run {}", what), e)),
+        Err(_) => Err(StudioError::Timeout {
+            what,
+            secs: GIT_TIMEOUT.as_secs(),
+        }),
+    };
+    if let Err(e) = &result {
+        crate::logging::log_line("WARN", &e.to_string());
+    }
+    result
+}
+
+async fn git_ok(workspace: &Path, path_env: &str, args: &[&str]) -> bool {
+    git(workspace, path_env, args)
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// `git_ok`, retried when git times out or another git process holds the
+/// index lock.
+async fn git_ok_retrying(workspace: &Path, path_env: &str, args: &[&str]) -> bool {
+    let what = format!("git {}", args.join(" "));
+    retry::run(
+        &what,
+        retry::Policy::current(),
+        |e: &StudioError| e.is_retryable() || e.to_string().contains("index.lock"),
+        || async move {
+            let output = git(workspace, path_env, args).await?;
+            if output.status.success() {
+                Ok(())
+            } else {
+                Err(StudioError::Other(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ))
+            }
+        },
+    )
+    .await
+    .is_ok()
+}
+
+async fn git_auto_save(app: &AppHandle, workspace: &Path, path_env: &str, message: &str) {
+    let has_changes = git(workspace, path_env, &["status", "--porcelain"])
+        .await
+        .is_ok_and(|output| !output.stdout.is_empty());
+    if !has_changes {
+        crate::logging::log_line("INFO", "No changes to auto-save");
+        return;
+    }
+
+    crate::logging::log_line("INFO", &format!("Auto-saving changes: {}", message));
+    git_ok_retrying(workspace, path_env, &["add", "-A"]).await;
+    if git_ok_retrying(workspace, path_env, &["commit", "-m", message]).await {
+        backup::schedule_push(app);
+    }
+}
+
+async fn git_init(app: &AppHandle, workspace: &Path, path_env: &str) {
+    git_ok(workspace, path_env, &["init"]).await;
+    git_ok(workspace, path_env, &["add", "-A"]).await;
+    if git_ok(
+        workspace,
+        path_env,
+        &["commit", "-m", "Initial workspace setup"],
+    )
+    .await
+    {
+        backup::schedule_push(app);
+    }
+}
+
+/// Report a failed setup. `setup-error` carries the error's code and hint
+/// along with the message.
+fn fail(app: &AppHandle, error: &StudioError) {
+    let msg = error.to_string();
+    telemetry::report_error("setup", &msg);
+    phase::fail(app, &msg);
+    frontend::emit(app, "setup-error", error);
+    notifications::notify(
+        "setup-error",
+        format!("Setup failed: {}", msg),
+        serde_json::json!({ "code": error.code(), "workspace": resolve_workspace_dir() }),
+    );
+}
+
+/// Run independent setup tasks at the same time. Until they've all
+/// finished, `setup-status` lists the ones still running at `progress`.
+/// Stops at the first that fails.
+async fn concurrently(
+    app: &AppHandle,
+    progress: u8,
+    tasks: Vec<(&'static str, BoxFuture<'_, Result<(), StudioError>>)>,
+) -> Result<(), StudioError> {
+    let mut running: Vec<&str> = tasks.iter().map(|(name, _)| *name).collect();
+    let mut pending: FuturesUnordered<_> = tasks
+        .into_iter()
+        .map(|(name, task)| async move { task.await.map(|()| name) })
+        .collect();
+    while !running.is_empty() {
+        let status = running.join(", ");
+        let mut chars = status.chars();
+        let status: String = chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default();
+        emit_status(app, &format!("{}...", status), progress);
+        let Some(done) = pending.next().await else {
+            break;
+        };
+        let done = done?;
+        running.retain(|name| *name != done);
+    }
+    Ok(())
+}
+
+/// Stop servers an earlier session left on our ports.
+async fn release_ports(app: &AppHandle) -> Result<(), StudioError> {
+    let current = ports::current(app);
+    let release = |port| {
+        let handle = app.clone();
+        step("Cleaning up old processes", None, move || {
+            ports::release(&handle, port)
+        })
+    };
+    let (opencode, remotion) = tokio::join!(release(current.opencode), release(current.remotion));
+    opencode?;
+    remotion?;
+    Ok(())
+}
+
+/// Lock the workspace against other copies of the app (see
+/// `workspace_lock`), auto-save it, bring it up to date (or create it) and
+/// start watching it. Cleaning up old processes, the auto-save and checking the
+/// template don't depend on each other and run at the same time.
+async fn prepare_workspace(app: &AppHandle) -> Result<(), StudioError> {
+    let workspace = resolve_workspace_dir();
+    let path_env = get_path_env();
+
+    // Before anything else: the ports released next may be another copy's.
+    let handle = app.clone();
+    let dir = workspace.clone();
+    step("Locking the workspace", None, move || {
+        workspace_lock::acquire(&handle, &dir)
+    })
+    .await
+    .and_then(|locked| locked)?;
+
+    let mut template_current = false;
+    let mut tasks: Vec<(&'static str, BoxFuture<'_, Result<(), StudioError>>)> =
+        vec![("cleaning up old processes", release_ports(app).boxed())];
+    if workspace.join("package.json").exists() {
+        tasks.push((
+            "saving progress",
+            async {
+                git_auto_save(app, &workspace, &path_env, "Auto-save on session start").await;
+                Ok(())
+            }
+            .boxed(),
+        ));
+        let handle = app.clone();
+        let dir = workspace.clone();
+        let template_current = &mut template_current;
+        tasks.push((
+            "checking the workspace template",
+            async move {
+                *template_current = step("Checking the workspace template", None, move || {
+                    template_dir(&handle)
+                        .is_ok_and(|template| template::is_current(&template, &dir))
+                })
+                .await?;
+                Ok(())
+            }
+            .boxed(),
+        ));
+    }
+    concurrently(app, 15, tasks).await?;
+
+    let handle = app.clone();
+    let created = step("Workspace setup", Some(WORKSPACE_TIMEOUT), move || {
+        setup_workspace(&handle, template_current)
+    })
+    .await
+    .and_then(|created| created)
+    .map_err(|e| {
+        // A timed-out install is still running on the blocking pool.
+        npm::cancel_npm_install();
+        crate::logging::log_line("ERROR", &format!("Workspace setup failed: {}", e));
+        e
+    })?;
+    // Imported projects may come without history.
+    if created || !workspace.join(".git").exists() {
+        emit_status(app, "Initializing version control...", 90);
+        git_init(app, &workspace, &path_env).await;
+    }
+    watcher::watch(app, &workspace);
+    emit_status(
+        app,
+        if created {
+            "Setup complete!"
+        } else {
+            "Workspace ready"
+        },
+        100,
+    );
+    crate::logging::log_line("INFO", "Workspace setup complete");
+    Ok(())
+}
+
+/// `prepare_workspace` for callers on the blocking pool, such as switching
+/// projects or moving the workspace.
+pub fn prepare_workspace_blocking(app: &AppHandle) -> Result<(), String> {
+    tauri::async_runtime::block_on(prepare_workspace(app)).map_err(String::from)
+}
+
+/// Set up the workspace, start the servers and proxies, and send
+/// `setup-complete` once both servers answer.
+pub async fn run(app: AppHandle) {
+    RUNNING.store(true, Ordering::SeqCst);
+    FAILED.store(false, Ordering::SeqCst);
+    if let Err(e) = run_steps(&app).await {
+        FAILED.store(true, Ordering::SeqCst);
+        fail(&app, &e);
+    }
+    RUNNING.store(false, Ordering::SeqCst);
+    // Workspace steps run again later, e.g. when switching projects.
+    cancel_signal().send_replace(false);
+}
+
+async fn run_steps(app: &AppHandle) -> Result<(), StudioError> {
+    // Events sent before the UI listens are lost.
+    step("Waiting for the UI", None, frontend::wait_until_ready).await?;
+
+    crate::logging::log_line("INFO", "Starting workspace setup...");
+    secrets::migrate_from_config();
+
+    // Without a key OpenCode can still use a local model server, which also
+    // lets onboarding skip asking for keys.
+    if !providers::any_key(&load_config()) {
+        local_models::detect().await;
+    }
+
+    // New installs first need keys, Node.js and a workspace.
+    let handle = app.clone();
+    step("Onboarding", None, move || {
+        onboarding::wait_until_complete(&handle)
+    })
+    .await?;
+
+    let config = load_config();
+    let config_path = get_config_path();
+    crate::logging::log_line("INFO", &format!("Config path: {:?}", config_path));
+    crate::logging::log_line("INFO", &format!("Config exists: {}", config_path.exists()));
+    for provider in providers::statuses(&config) {
+        crate::logging::log_line(
+            "INFO",
+            &format!("{} configured: {}", provider.name, provider.configured),
+        );
+    }
+
+    key_validation::validate_in_background(app, &config);
+
+    phase::set(app, Phase::InstallingDeps);
+    emit_status(app, "Checking dependencies...", 5);
+    let handle = app.clone();
+    let mut preflight = step("Dependency check", Some(PREFLIGHT_TIMEOUT), move || {
+        doctor::preflight(&handle)
+    })
+    .await?;
+    if !preflight.ok && preflight.needs_node() {
+        let handle = app.clone();
+        step("Installing Node.js", Some(PROVISION_TIMEOUT), move || {
+            runtime::provision(&handle)
+        })
+        .await
+        .and_then(|provisioned| provisioned.map_err(StudioError::from))
+        .map_err(|e| StudioError::NodeMissing(format!("{}\n{}", preflight.summary(), e)))?;
+        let handle = app.clone();
+        preflight = step("Dependency check", Some(PREFLIGHT_TIMEOUT), move || {
+            doctor::preflight(&handle)
+        })
+        .await?;
+    }
+    if !preflight.ok {
+        return Err(preflight.error());
+    }
+
+    // After an update the migration in `prepare_workspace` brings the
+    // workspace up to the new template.
+    updater::after_launch(app);
+    prepare_workspace(app).await?;
+    let workspace = resolve_workspace_dir();
+    prewarm::start(&workspace);
+    autosave::start(app);
+
+    phase::set(app, Phase::StartingServers);
+    let handle = app.clone();
+    let spawn_dir = workspace.clone();
+    let (opencode, remotion) = step("Starting servers", Some(SPAWN_TIMEOUT), move || {
+        let opencode = spawn_opencode(&handle, &spawn_dir, &config);
+        let remotion = spawn_remotion(&handle, &spawn_dir);
+        (opencode, remotion)
+    })
+    .await?;
+    let (opencode, remotion) = match (opencode, remotion) {
+        (Ok(opencode), Ok(remotion)) => (opencode, remotion),
+        (Err(e), other) | (other, Err(e)) => {
+            // Don't leave the one that did start running without an owner.
+            if let Ok(mut child) = other {
+                crate::platform::kill_process_tree(&mut child);
+            }
+            return Err(e);
+        }
+    };
+
+    // Start the reverse proxies that sit between the webview and the
+    // servers, preventing WKWebView timeout kills on long-running streaming
+    // responses.
+    start_proxy(app, proxy::Upstream::OpenCode);
+    start_proxy(app, proxy::Upstream::Remotion);
+
+    if let Some(state) = app.try_state::<Mutex<AppState>>() {
+        let mut guard = state.lock().map_err(|e| e.to_string())?;
+        guard.opencode = Some(opencode);
+        guard.remotion = Some(remotion);
+    }
+
+    supervisor::start(app.clone());
+    idle::start(app);
+    resources::start(app);
+    transcripts::start(app);
+
+    // The children take a while to start listening; hold setup-complete back
+    // until both actually answer.
+    emit_status(app, "Starting servers...", 100);
+    let handle = app.clone();
+    let ready = step("Waiting for servers", None, move || {
+        readiness::wait_for_backend(&handle)
+    })
+    .await?;
+    match ready {
+        Ok(()) => {
+            ports::remember_listeners(app);
+            phase::set(app, Phase::Ready);
+            frontend::emit(app, "servers-ready", ());
+            frontend::emit(app, "setup-complete", ());
+        }
+        Err(timeout) => {
+            let msg = timeout.message();
+            telemetry::report_error("setup", &msg);
+            phase::fail(app, &msg);
+            frontend::emit(
+                app,
+                "setup-timeout",
+                serde_json::json!({
+                    "message": msg,
+                    "services": timeout.services,
+                    "timeoutSecs": timeout.timeout.as_secs(),
+                }),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Abort setup: the step in progress is given up on and a running
+/// `npm install` is killed. Returns whether setup was running.
+#[tauri::command]
+pub fn cancel_setup() -> bool {
+    if !RUNNING.load(Ordering::SeqCst) {
+        return false;
+    }
+    crate::logging::log_line("INFO", "Cancelling setup");
+    cancel_signal().send_replace(true);
+    npm::cancel_npm_install();
+    true
+}
+
+/// Run setup again after it failed. Workspace creation picks up from its
+/// checkpoint (see `checkpoint`), so an interrupted install doesn't copy
+/// the template again.
+#[tauri::command]
+pub fn retry_setup(app: AppHandle) -> Result<(), StudioError> {
+    if RUNNING.load(Ordering::SeqCst) {
+        return Err(StudioError::InvalidInput(
+            "Setup is already running".to_string(),
+        ));
+    }
+    if !FAILED.load(Ordering::SeqCst) {
+        return Err(StudioError::InvalidInput(
+            "Setup hasn't failed, so there's nothing to retry".to_string(),
+        ));
+    }
+    crate::logging::log_line("INFO", "Retrying setup");
+    tauri::async_runtime::spawn(run(app));
+    Ok(())
+}
@@ -0,0 +1,296 @@
+//! Snapshots of the workspace before operations that replace its files.
+//!
+//! A template update once overwrote a user's edited remotion.config.ts with
+//! nothing to go back to. Template migrations, restores and imports now call
+//! `take` first: pending changes are committed and the commit is tagged
+//! `snapshot/<timestamp>-<reason>`. Tags are cheap, survive later commits
+//! and restores, and don't show up as extra commits in `git_log`.
+//!
+//! `list_snapshots` returns them newest first and `restore_snapshot` rolls
+//! the workspace back to one through the same path as `git_restore`. Only
+//! the newest `MAX_SNAPSHOTS` are kept.
+//!
+//! Checkpoints are the user's own kind of snapshot, for moments like
+//! "version shown to client". `create_checkpoint` commits the workspace with
+//! their label and tags it `checkpoint/<timestamp>-<label>`. The tag is
+//! annotated so the label is kept exactly as typed, and checkpoints are
+//! never pruned. `list_checkpoints` returns them newest first.
+
+use crate::error::StudioError;
+use crate::git::{self, RestoreResult};
+use crate::resolve_workspace_dir;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+use std::path::Path;
+use tauri::AppHandle;
+
+const TAG_PREFIX: &str = "snapshot/";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+/// Length of a timestamp in `TIMESTAMP_FORMAT`.
+const TIMESTAMP_LEN: usize = 15;
+const MAX_SNAPSHOTS: usize = 20;
+const CHECKPOINT_PREFIX: &str = "checkpoint/";
+const MAX_LABEL_LEN: usize = 200;
+/// How much of a checkpoint's label goes into its tag name.
+const MAX_SLUG_LEN: usize = 40;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// The tag, e.g. "snapshot/20240501-093000-template-update".
+    pub name: String,
+    pub commit: String,
+    /// When it was taken, RFC 3339.
+    pub created: Option<String>,
+    /// What it was taken before: "template-update", "restore", "import" or
+    /// "discard-experiment".
+    pub reason: String,
+}
+
+fn parse_tag(name: &str, commit: &str) -> Option<Snapshot> {
+    let rest = name.strip_prefix(TAG_PREFIX)?;
+    let timestamp = rest.get(..TIMESTAMP_LEN)?;
+    let reason = rest.get(TIMESTAMP_LEN..)?.strip_prefix('-')?;
+    let created = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()
+        .and_then(|t| Local.from_local_datetime(&t).single())
+        .map(|t| t.to_rfc3339());
+    Some(Snapshot {
+        name: name.to_string(),
+        commit: commit.to_string(),
+        created,
+        reason: reason.to_string(),
+    })
+}
+
+fn list(workspace: &Path) -> Result<Vec<Snapshot>, String> {
+    if !workspace.join(".git").exists() {
+        return Ok(Vec::new());
+    }
+    let output = git::run_git(
+        workspace,
+        &[
+            "for-each-ref",
+            "--sort=-refname",
+            "--format=%(refname:short) %(objectname)",
+            "refs/tags/snapshot/",
+        ],
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let (name, commit) = line.split_once(' ')?;
+            parse_tag(name, commit)
+        })
+        .collect())
+}
+
+/// Delete all but the newest `MAX_SNAPSHOTS`.
+fn prune(workspace: &Path) {
+    let Ok(snapshots) = list(workspace) else {
+        return;
+    };
+    for snapshot in snapshots.iter().skip(MAX_SNAPSHOTS) {
+        if let Err(e) = git::run_git(workspace, &["tag", "-d", &snapshot.name]) {
+            crate::logging::log_line(
+                "WARN",
+                &format!("[snapshots] Couldn't delete {}: {}", snapshot.name, e),
+            );
+        }
+    }
+}
+
+fn take_snapshot(workspace: &Path, reason: &str) -> Result<Snapshot, String> {
+    git::commit_all(
+        workspace,
+        &format!("Auto-save before {}", reason.replace('-', " ")),
+    )?;
+    let commit = git::run_git(workspace, &["rev-parse", "HEAD"])?
+        .trim()
+        .to_string();
+    let name = format!(
+        "{}{}-{}",
+        TAG_PREFIX,
+        Local::now().format(TIMESTAMP_FORMAT),
+        reason
+    );
+    git::run_git(workspace, &["tag", "-f", &name, &commit])?;
+    parse_tag(&name, &commit).ok_or_else(|| format!("Invalid snapshot name: {}", name))
+}
+
+/// Snapshot `workspace` before `reason` ("template-update", "restore",
+/// "import" or "discard-experiment"). A workspace without history yet is skipped, and a failure is
+/// logged rather than stopping the operation that asked for it.
+pub fn take(workspace: &Path, reason: &str) -> Option<Snapshot> {
+    if !workspace.join(".git").exists() {
+        return None;
+    }
+    match take_snapshot(workspace, reason) {
+        Ok(snapshot) => {
+            crate::logging::log_line(
+                "INFO",
+                &format!("[snapshots] Took {} at {}", snapshot.name, snapshot.commit),
+            );
+            prune(workspace);
+            Some(snapshot)
+        }
+        Err(e) => {
+            crate::logging::log_line(
+                "WARN",
+                &format!("[snapshots] Couldn't snapshot before {}: {}", reason, e),
+            );
+            None
+        }
+    }
+}
+
+/// Snapshots of the open workspace, newest first.
+#[tauri::command]
+pub async fn list_snapshots() -> Result<Vec<Snapshot>, StudioError> {
+    tauri::async_runtime::spawn_blocking(|| list(&resolve_workspace_dir()))
+        .await?
+        .map_err(Into::into)
+}
+
+/// Roll the workspace back to a snapshot. Works like `git_restore`,
+/// including its `restore-progress` events.
+#[tauri::command]
+pub async fn restore_snapshot(app: AppHandle, name: String) -> Result<RestoreResult, StudioError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let workspace = resolve_workspace_dir();
+        let snapshot = list(&workspace)?
+            .into_iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| StudioError::NotFound(format!("Snapshot {}", name)))?;
+        git::restore(&app, &snapshot.commit).map_err(StudioError::from)
+    })
+    .await?
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// The tag, e.g. "checkpoint/20240501-093000-version-shown-to-client".
+    pub name: String,
+    pub commit: String,
+    /// When it was created, RFC 3339.
+    pub created: Option<String>,
+    /// The label as the user typed it.
+    pub label: String,
+}
+
+/// `label` reduced to lowercase letters, digits and dashes for a ref name.
+pub(crate) fn slug(label: &str) -> String {
+    let words: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let slug = words.split_whitespace().collect::<Vec<_>>().join("-");
+    let slug = slug
+        .get(..MAX_SLUG_LEN)
+        .unwrap_or(&slug)
+        .trim_end_matches('-');
+    if slug.is_empty() {
+        "checkpoint".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+fn list_checkpoints_in(workspace: &Path) -> Result<Vec<Checkpoint>, String> {
+    if !workspace.join(".git").exists() {
+        return Ok(Vec::new());
+    }
+    // `%(*objectname)` is the commit an annotated tag points at.
+    let output = git::run_git(
+        workspace,
+        &[
+            "for-each-ref",
+            "--sort=-refname",
+            "--format=%(refname:short)%00%(*objectname)%00%(creatordate:iso-strict)%00%(contents:subject)",
+            "refs/tags/checkpoint/",
+        ],
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\0');
+            let name = fields.next()?;
+            let commit = fields.next().filter(|c| !c.is_empty())?;
+            let created = fields.next().filter(|c| !c.is_empty());
+            Some(Checkpoint {
+                name: name.to_string(),
+                commit: commit.to_string(),
+                created: created.map(str::to_string),
+                label: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect())
+}
+
+fn create(workspace: &Path, label: &str) -> Result<Checkpoint, String> {
+    git::commit_all(workspace, &format!("Checkpoint: {}", label))?;
+    let commit = git::run_git(workspace, &["rev-parse", "HEAD"])?
+        .trim()
+        .to_string();
+    let name = format!(
+        "{}{}-{}",
+        CHECKPOINT_PREFIX,
+        Local::now().format(TIMESTAMP_FORMAT),
+        slug(label)
+    );
+    git::run_git_as_studio(workspace, &["tag", "-a", &name, "-m", label, &commit])
+        .map_err(|e| format!("Couldn't tag the checkpoint: {}", e))?;
+    Ok(Checkpoint {
+        name,
+        commit,
+        created: Some(Local::now().to_rfc3339()),
+        label: label.to_string(),
+    })
+}
+
+/// Commit the workspace as a checkpoint labelled `label` and tag it. With
+/// nothing to commit, the current commit is tagged.
+#[tauri::command]
+pub async fn create_checkpoint(app: AppHandle, label: String) -> Result<Checkpoint, StudioError> {
+    let label = label.trim().to_string();
+    if label.is_empty() || label.contains('\n') || label.chars().count() > MAX_LABEL_LEN {
+        return Err(StudioError::InvalidInput(format!(
+            "A checkpoint needs a one-line label of up to {} characters",
+            MAX_LABEL_LEN
+        )));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let workspace = resolve_workspace_dir();
+        if !workspace.join(".git").exists() {
+            return Err(StudioError::InvalidInput(
+                "The workspace has no history yet".to_string(),
+            ));
+        }
+        let checkpoint = create(&workspace, &label)?;
+        crate::logging::log_line(
+            "INFO",
+            &format!(
+                "[snapshots] Created {} at {}",
+                checkpoint.name, checkpoint.commit
+            ),
+        );
+        crate::backup::schedule_push(&app);
+        Ok(checkpoint)
+    })
+    .await?
+}
+
+/// Checkpoints of the open workspace, newest first.
+#[tauri::command]
+pub async fn list_checkpoints() -> Result<Vec<Checkpoint>, StudioError> {
+    tauri::async_runtime::spawn_blocking(|| list_checkpoints_in(&resolve_workspace_dir()))
+        .await?
+        .map_err(Into::into)
+}
@@ -0,0 +1,491 @@
+//! Supervisor for the OpenCode and Remotion child processes.
+//!
+//! Both servers are spawned once during setup, but either one can die later
+//! (an OOM, a bad plugin, a syntax error that takes the dev server down).
+//! Without supervision the iframe is left pointing at a dead port until the
+//! whole app is restarted.
+//!
+//! The supervisor polls both children held in `AppState`. When one exits
+//! without the app asking it to, a crash report is written (see `crash`) and
+//! a `process-crashed` event is emitted with the exit code, signal, report
+//! path and the last lines the process printed. The process is then
+//! respawned after an exponential backoff, followed by a
+//! `process-restarted` event. A process that keeps crashing is given up on
+//! after `MAX_CONSECUTIVE_RESTARTS` attempts so we don't spin forever.
+
+use crate::error::StudioError;
+use crate::phase::{self, Phase};
+use crate::{crash, telemetry, tray};
+use crate::{
+    load_config, platform, ports, readiness, resolve_workspace_dir, restart_proxies,
+    spawn_opencode, spawn_remotion, AppState,
+};
+use std::process::Child;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How often the children are checked for an unexpected exit.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Delay before the first restart; doubled on every consecutive crash.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound for the restart delay.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A process that stays up this long is considered healthy again and its
+/// backoff is reset.
+const STABLE_UPTIME: Duration = Duration::from_secs(120);
+/// Consecutive crashes after which we stop restarting and leave it to the user.
+const MAX_CONSECUTIVE_RESTARTS: u32 = 5;
+/// How long a child gets to exit on SIGTERM during a manual restart.
+const RESTART_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    OpenCode,
+    Remotion,
+}
+
+impl Service {
+    pub const ALL: [Service; 2] = [Service::OpenCode, Service::Remotion];
+
+    /// Identifier used in event payloads.
+    pub fn id(self) -> &'static str {
+        match self {
+            Service::OpenCode => "opencode",
+            Service::Remotion => "remotion",
+        }
+    }
+
+    /// Human-readable name used in log lines.
+    pub fn label(self) -> &'static str {
+        match self {
+            Service::OpenCode => "OpenCode",
+            Service::Remotion => "Remotion",
+        }
+    }
+
+    /// The port the service is currently listening on.
+    pub fn port(self, app: &AppHandle) -> u16 {
+        let ports = ports::current(app);
+        match self {
+            Service::OpenCode => ports.opencode,
+            Service::Remotion => ports.remotion,
+        }
+    }
+
+    pub fn child_mut(self, state: &mut AppState) -> &mut Option<Child> {
+        match self {
+            Service::OpenCode => &mut state.opencode,
+            Service::Remotion => &mut state.remotion,
+        }
+    }
+
+    fn spawn(self, app: &AppHandle) -> Result<Child, StudioError> {
+        let workspace = resolve_workspace_dir();
+        match self {
+            Service::OpenCode => spawn_opencode(app, &workspace, &load_config()),
+            Service::Remotion => spawn_remotion(app, &workspace),
+        }
+    }
+}
+
+/// Restart bookkeeping for a single supervised process.
+struct Tracker {
+    service: Service,
+    consecutive_restarts: u32,
+    started_at: Instant,
+    restart_at: Option<Instant>,
+    gave_up: bool,
+}
+
+impl Tracker {
+    fn new(service: Service) -> Self {
+        Self {
+            service,
+            consecutive_restarts: 0,
+            started_at: Instant::now(),
+            restart_at: None,
+            gave_up: false,
+        }
+    }
+
+    fn backoff(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.consecutive_restarts);
+        INITIAL_BACKOFF.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+/// Start supervising the children currently stored in `AppState`.
+/// Runs on its own thread for the lifetime of the app.
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut trackers = [
+            Tracker::new(Service::OpenCode),
+            Tracker::new(Service::Remotion),
+        ];
+        let mut seen_generation = 0;
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let Some(state) = app.try_state::<Mutex<AppState>>() else {
+                continue;
+            };
+
+            let (shutting_down, restarting, generation) = match state.lock() {
+                Ok(g) => (g.shutting_down, g.restarting, g.services_generation),
+                Err(_) => return,
+            };
+
+            if shutting_down {
+                return;
+            }
+
+            // `restart_services` owns the children while it runs; afterwards
+            // start from a clean slate, including services we had given up on.
+            if restarting {
+                continue;
+            }
+            if generation != seen_generation {
+                seen_generation = generation;
+                trackers = [
+                    Tracker::new(Service::OpenCode),
+                    Tracker::new(Service::Remotion),
+                ];
+            }
+
+            for tracker in trackers.iter_mut() {
+                if tracker.gave_up {
+                    continue;
+                }
+
+                match tracker.restart_at {
+                    Some(due) if Instant::now() >= due => restart(&app, &state, tracker),
+                    Some(_) => {}
+                    None => check(&app, &state, tracker),
+                }
+            }
+        }
+    });
+}
+
+/// Kill both children, clean up their ports and spawn them again, then wait
+/// for both to accept connections. The proxies are cycled too. Backs the
+/// `restart_services` command.
+pub fn restart_all(app: &AppHandle) -> Result<(), String> {
+    relaunch(
+        app,
+        &Service::ALL,
+        "Manual restart of backend services requested",
+        |app| {
+            restart_proxies(app);
+            Ok(())
+        },
+    )
+}
+
+/// Stop `services`, run `prepare` (e.g. setting up a different workspace)
+/// while they're down, then spawn them again and wait until both servers
+/// answer.
+pub fn relaunch(
+    app: &AppHandle,
+    services: &[Service],
+    reason: &str,
+    prepare: impl FnOnce(&AppHandle) -> Result<(), String>,
+) -> Result<(), String> {
+    let Some(state) = app.try_state::<Mutex<AppState>>() else {
+        return Err("App state not initialized".to_string());
+    };
+
+    // Record the servers the children started before killing the children;
+    // afterwards they can't be traced back to us.
+    ports::remember_listeners(app);
+
+    let children = {
+        let mut guard = state.lock().map_err(|e| e.to_string())?;
+        if guard.restarting {
+            return Err("Services are already restarting".to_string());
+        }
+        guard.restarting = true;
+        guard.services_generation += 1;
+        services
+            .iter()
+            .map(|&service| (service, service.child_mut(&mut guard).take()))
+            .collect::<Vec<_>>()
+    };
+
+    crate::logging::log_line("INFO", reason);
+    telemetry::breadcrumb("process", reason);
+    phase::set(app, Phase::Recovering);
+
+    let mut running = Vec::new();
+    for (service, child) in children {
+        if let Some(child) = child {
+            crate::logging::log_line(
+                "INFO",
+                &format!("Stopping {} (PID: {})", service.label(), child.id()),
+            );
+            running.push(child);
+        }
+    }
+    let forced = platform::stop_process_trees(&mut running, RESTART_GRACE);
+    if !forced.is_empty() {
+        crate::logging::log_line(
+            "WARN",
+            &format!("Force-killed processes that ignored SIGTERM: {:?}", forced),
+        );
+    }
+
+    for service in services {
+        ports::release(app, service.port(app));
+    }
+    std::thread::sleep(Duration::from_millis(500));
+
+    // Respawn even if `prepare` failed so the user isn't left without
+    // servers; `prepare` is responsible for restoring the previous setup.
+    let prepared = prepare(app);
+    let spawned = spawn_all(app, &state, services);
+
+    if let Ok(mut guard) = state.lock() {
+        guard.restarting = false;
+    }
+    let result = prepared
+        .and(spawned)
+        .and_then(|()| readiness::wait_for_backend(app).map_err(|timeout| timeout.message()));
+    match &result {
+        Ok(()) => {
+            ports::remember_listeners(app);
+            phase::set(app, Phase::Ready);
+            crate::frontend::emit(app, "servers-ready", ());
+        }
+        Err(e) => phase::fail(app, e),
+    }
+    result
+}
+
+/// Stop `service` and leave it stopped: the supervisor only restarts
+/// children that exit on their own. `relaunch` starts it again.
+pub fn stop(app: &AppHandle, service: Service, reason: &str) -> Result<(), String> {
+    let Some(state) = app.try_state::<Mutex<AppState>>() else {
+        return Err("App state not initialized".to_string());
+    };
+    ports::remember_listeners(app);
+    let child = {
+        let mut guard = state.lock().map_err(|e| e.to_string())?;
+        if guard.restarting || guard.shutting_down {
+            return Err("Services are restarting".to_string());
+        }
+        service.child_mut(&mut guard).take()
+    };
+    let Some(child) = child else {
+        return Ok(());
+    };
+    crate::logging::log_line("INFO", &format!("{} (PID: {})", reason, child.id()));
+    telemetry::breadcrumb("process", reason);
+    let forced = platform::stop_process_trees(&mut [child], RESTART_GRACE);
+    if !forced.is_empty() {
+        crate::logging::log_line(
+            "WARN",
+            &format!("Force-killed processes that ignored SIGTERM: {:?}", forced),
+        );
+    }
+    ports::release(app, service.port(app));
+    tray::refresh(app);
+    Ok(())
+}
+
+fn spawn_all(app: &AppHandle, state: &Mutex<AppState>, services: &[Service]) -> Result<(), String> {
+    for &service in services {
+        let mut child = service.spawn(app)?;
+        let pid = child.id();
+        {
+            let mut guard = state.lock().map_err(|e| e.to_string())?;
+            if guard.shutting_down {
+                platform::kill_process_tree(&mut child);
+                return Err("App is shutting down".to_string());
+            }
+            *service.child_mut(&mut guard) = Some(child);
+        }
+        let _ = app.emit(
+            "process-restarted",
+            serde_json::json!({
+                "service": service.id(),
+                "pid": pid,
+                "attempt": 0,
+                "manual": true,
+            }),
+        );
+    }
+    Ok(())
+}
+
+/// Check whether the process exited, and if so schedule a restart.
+fn check(app: &AppHandle, state: &Mutex<AppState>, tracker: &mut Tracker) {
+    let service = tracker.service;
+
+    // Take the child out of the state while holding the lock, and do all
+    // logging after it is released.
+    let exited = {
+        let Ok(mut guard) = state.lock() else {
+            return;
+        };
+        if guard.shutting_down {
+            return;
+        }
+        let slot = service.child_mut(&mut guard);
+        match slot.as_mut().map(|child| (child.id(), child.try_wait())) {
+            Some((pid, Ok(Some(status)))) => {
+                *slot = None;
+                Some((pid, status))
+            }
+            _ => None,
+        }
+    };
+
+    let Some((pid, status)) = exited else {
+        return;
+    };
+
+    let uptime = tracker.started_at.elapsed();
+    let (crash, report) = crash::report(service, pid, status, uptime);
+    if uptime >= STABLE_UPTIME {
+        tracker.consecutive_restarts = 0;
+    }
+
+    let will_restart = tracker.consecutive_restarts < MAX_CONSECUTIVE_RESTARTS;
+    let msg = format!(
+        "{} (PID: {}) exited unexpectedly with {}",
+        service.label(),
+        pid,
+        crash.describe()
+    );
+    crate::logging::log_line("ERROR", &msg);
+    let report_path = match report {
+        Ok(path) => {
+            crate::logging::log_line(
+                "INFO",
+                &format!("Crash report written to {}", path.display()),
+            );
+            Some(path.to_string_lossy().into_owned())
+        }
+        Err(e) => {
+            crate::logging::log_line("WARN", &e);
+            None
+        }
+    };
+
+    let _ = app.emit(
+        "process-crashed",
+        serde_json::json!({
+            "service": service.id(),
+            "pid": pid,
+            "exitCode": crash.exit_code,
+            "signal": crash.signal,
+            "signalName": crash.signal_name,
+            "uptimeSecs": crash.uptime_secs,
+            "outputTail": crash.output_tail,
+            "reportPath": report_path,
+            "restarts": tracker.consecutive_restarts,
+            "willRestart": will_restart,
+        }),
+    );
+
+    if will_restart {
+        phase::set(app, Phase::Recovering);
+        schedule_restart(tracker);
+    } else {
+        tracker.gave_up = true;
+        phase::fail(app, &msg);
+        crate::logging::log_line(
+            "ERROR",
+            &format!(
+                "{} crashed {} times in a row, giving up on automatic restarts",
+                service.label(),
+                MAX_CONSECUTIVE_RESTARTS
+            ),
+        );
+    }
+}
+
+fn schedule_restart(tracker: &mut Tracker) {
+    let delay = tracker.backoff();
+    crate::logging::log_line(
+        "INFO",
+        &format!(
+            "Restarting {} in {:.0}s (attempt {})",
+            tracker.service.label(),
+            delay.as_secs_f64(),
+            tracker.consecutive_restarts + 1
+        ),
+    );
+    tracker.restart_at = Some(Instant::now() + delay);
+}
+
+fn restart(app: &AppHandle, state: &Mutex<AppState>, tracker: &mut Tracker) {
+    let service = tracker.service;
+    tracker.restart_at = None;
+    tracker.consecutive_restarts += 1;
+
+    match service.spawn(app) {
+        Ok(mut child) => {
+            let pid = child.id();
+            {
+                let Ok(mut guard) = state.lock() else {
+                    return;
+                };
+                // A manual restart may have started while we were spawning;
+                // it owns the children now.
+                if guard.shutting_down || guard.restarting {
+                    platform::kill_process_tree(&mut child);
+                    return;
+                }
+                *service.child_mut(&mut guard) = Some(child);
+            }
+            tracker.started_at = Instant::now();
+
+            crate::logging::log_line(
+                "INFO",
+                &format!("{} restarted with PID: {}", service.label(), pid),
+            );
+            telemetry::breadcrumb(
+                "process",
+                &format!("{} restarted with PID {}", service.label(), pid),
+            );
+            phase::set(app, Phase::Ready);
+            let _ = app.emit(
+                "process-restarted",
+                serde_json::json!({
+                    "service": service.id(),
+                    "pid": pid,
+                    "attempt": tracker.consecutive_restarts,
+                    "manual": false,
+                }),
+            );
+        }
+        Err(e) => {
+            let msg = format!("Failed to restart {}: {}", service.label(), e);
+            crate::logging::log_line("ERROR", &msg);
+            if tracker.consecutive_restarts < MAX_CONSECUTIVE_RESTARTS {
+                schedule_restart(tracker);
+            } else {
+                tracker.gave_up = true;
+                phase::fail(app, &msg);
+                let _ = app.emit(
+                    "process-crashed",
+                    serde_json::json!({
+                        "service": service.id(),
+                        "pid": null,
+                        "exitCode": null,
+                        "signal": null,
+                        "signalName": null,
+                        "uptimeSecs": null,
+                        "outputTail": [],
+                        "reportPath": null,
+                        "restarts": tracker.consecutive_restarts,
+                        "willRestart": false,
+                    }),
+                );
+            }
+        }
+    }
+}
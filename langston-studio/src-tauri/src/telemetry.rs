@@ -0,0 +1,139 @@
+//! Error reporting to Sentry.
+//!
+//! Reports used to be a bare message with no context and couldn't be turned
+//! off. Now:
+//!
+//! - each setup step, phase change and process event leaves a breadcrumb,
+//!   so a report shows what led up to it;
+//! - the last `RECENT_LOG_LINES` log lines are attached to every report;
+//! - `report_error` tags reports with where they came from;
+//! - server crashes carry their crash report as an attachment;
+//! - nothing is sent when `telemetryEnabled` is false in config.json.
+//!   `set_telemetry_enabled` changes it without a restart.
+
+use crate::error::StudioError;
+use crate::{read_config_file, update_config_file};
+use sentry::IntoDsn;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const SENTRY_DSN: &str = "https://3a30fa628bbd0e5f55d9d25f394076c0@o4506593499873280.ingest.us.sentry.io/4510817219444736";
+/// Log lines attached to each report.
+const RECENT_LOG_LINES: usize = 100;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Set up the Sentry client. Keep the guard for the life of the app; events
+/// are only sent while telemetry is enabled.
+pub fn init(version: &str, username: &str) -> sentry::ClientInitGuard {
+    set_enabled(read_config_file().telemetry_enabled.unwrap_or(true));
+
+    let guard = sentry::init((
+        SENTRY_DSN.into_dsn().expect("Invalid Sentry DSN"),
+        sentry::ClientOptions {
+            release: Some(format!("langston-studio@{}", version).into()),
+            environment: Some("production".into()),
+            before_send: Some(Arc::new(|mut event| {
+                if !enabled() {
+                    return None;
+                }
+                let lines: Vec<String> = RECENT_LOGS
+                    .lock()
+                    .map(|logs| logs.iter().cloned().collect())
+                    .unwrap_or_default();
+                event
+                    .extra
+                    .insert("recentLogs".to_string(), lines.join("\n").into());
+                Some(event)
+            })),
+            before_breadcrumb: Some(Arc::new(|crumb| enabled().then_some(crumb))),
+            ..Default::default()
+        },
+    ));
+
+    sentry::configure_scope(|scope| {
+        scope.set_user(Some(sentry::User {
+            username: Some(username.to_string()),
+            ..Default::default()
+        }));
+        scope.set_tag("platform", std::env::consts::OS);
+    });
+    guard
+}
+
+/// Keep a log line for the next report; see `logging`.
+pub fn remember_log_line(level: &str, message: &str) {
+    if let Ok(mut logs) = RECENT_LOGS.lock() {
+        if logs.len() == RECENT_LOG_LINES {
+            logs.pop_front();
+        }
+        logs.push_back(format!("[{}] {}", level, message));
+    }
+}
+
+/// Leave a breadcrumb, e.g. `breadcrumb("setup", "Dependency check")`.
+pub fn breadcrumb(category: &str, message: &str) {
+    sentry::add_breadcrumb(sentry::Breadcrumb {
+        category: Some(category.to_string()),
+        message: Some(message.to_string()),
+        level: sentry::Level::Info,
+        ..Default::default()
+    });
+}
+
+/// Report an error, tagged with the subsystem it came from.
+pub fn report_error(context: &str, message: &str) {
+    sentry::with_scope(
+        |scope| scope.set_tag("context", context),
+        || sentry::capture_message(message, sentry::Level::Error),
+    );
+}
+
+/// Report a server crash with its crash report attached (see `crash`).
+pub fn report_crash(
+    context: &str,
+    message: &str,
+    tags: &[(&str, String)],
+    report_name: &str,
+    report: Vec<u8>,
+) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("context", context);
+            for (key, value) in tags {
+                scope.set_tag(key, value);
+            }
+            scope.add_attachment(sentry::protocol::Attachment {
+                buffer: report,
+                filename: report_name.to_string(),
+                content_type: Some("text/plain".to_string()),
+                ..Default::default()
+            });
+        },
+        || sentry::capture_message(message, sentry::Level::Error),
+    );
+}
+
+/// Turn error reporting on or off. Saved in config.json.
+#[tauri::command]
+pub fn set_telemetry_enabled(enabled: bool) -> Result<(), StudioError> {
+    update_config_file(|obj| {
+        obj.insert("telemetryEnabled".to_string(), enabled.into());
+    })?;
+    set_enabled(enabled);
+    crate::logging::log_line(
+        "INFO",
+        &format!("Telemetry {}", if enabled { "enabled" } else { "disabled" }),
+    );
+    Ok(())
+}
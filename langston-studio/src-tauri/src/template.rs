@@ -0,0 +1,369 @@
+//! Workspace template versioning and migrations.
+//!
+//! The bundled template carries a manifest, `.langston-template.json`:
+//!
+//! - `version`: the template's current version
+//! - `sync`: files copied over on every launch, because the app depends on
+//!   them matching the template (OpenCode config, Remotion config, AGENTS.md)
+//! - `migrations`: one entry per version, each listing files to `update`
+//!   (overwrite from the template), `add` (copy only if the workspace doesn't
+//!   have them, so user edits survive) and `remove`
+//!
+//! A workspace records the template version it's at in its own copy of the
+//! manifest; workspaces from before versioning count as version 0. On launch
+//! `migrate` applies every migration newer than the workspace, in order, and
+//! commits each one separately so it can be reverted from the history. The
+//! caller reruns `npm install` when a migration changed package.json.
+//!
+//! Synced files used to be overwritten on every launch, losing any changes
+//! the user made to them. The workspace manifest now also records a hash of
+//! each synced file as it was last written. A file that still matches it is
+//! updated as before; an edited one is kept while the template's copy hasn't
+//! changed. When both changed, the user's version is saved next to it as
+//! `<file>.user.bak` before the template's replaces it, and a
+//! `config-conflict` event tells the UI.
+
+use crate::{backup, copy_dir_recursive, frontend, git, snapshots};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+
+const MANIFEST_FILE: &str = ".langston-template.json";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Migration {
+    version: u32,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    update: Vec<String>,
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    sync: Vec<String>,
+    #[serde(default)]
+    migrations: Vec<Migration>,
+}
+
+/// What's written to the workspace's copy of the manifest.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct WorkspaceVersion {
+    #[serde(default)]
+    version: u32,
+    /// SHA-256 of each synced file as it was last written from the template.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    synced: BTreeMap<String, String>,
+}
+
+/// A synced file the user had changed and the template replaced.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigConflict {
+    pub path: String,
+    /// Where the user's version was saved.
+    pub backup_path: String,
+}
+
+/// What `migrate` did.
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOutcome {
+    /// Versions applied, oldest first.
+    pub applied: Vec<u32>,
+    /// Whether package.json or package-lock.json changed, so dependencies
+    /// need reinstalling.
+    pub dependencies_changed: bool,
+}
+
+fn read_manifest(dir: &Path) -> Option<Manifest> {
+    let contents = fs::read_to_string(dir.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// The template version `dir` (the bundled template or a workspace) is at.
+pub fn version(dir: &Path) -> u32 {
+    read_manifest(dir).map_or(0, |m| m.version)
+}
+
+fn read_workspace_version(workspace: &Path) -> WorkspaceVersion {
+    fs::read_to_string(workspace.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn write_workspace_version(workspace: &Path, record: &WorkspaceVersion) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(record).map_err(|e| e.to_string())?;
+    fs::write(workspace.join(MANIFEST_FILE), json + "\n")
+        .map_err(|e| format!("Failed to record template version: {}", e))
+}
+
+fn write_version(workspace: &Path, version: u32) -> Result<(), String> {
+    let mut record = read_workspace_version(workspace);
+    record.version = version;
+    write_workspace_version(workspace, &record)
+}
+
+/// SHA-256 of the file at `path`, `None` if it can't be read.
+fn file_hash(path: &Path) -> Option<String> {
+    let contents = fs::read(path).ok()?;
+    Some(format!("{:x}", Sha256::digest(&contents)))
+}
+
+/// Paths from the manifest must stay inside the workspace.
+fn safe_relative(path: &str) -> bool {
+    let path = Path::new(path);
+    !path.as_os_str().is_empty()
+        && path.is_relative()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+fn copy_from_template(template: &Path, workspace: &Path, path: &str) -> Result<(), String> {
+    let src = template.join(path);
+    let dst = workspace.join(path);
+    let copied = if src.is_dir() {
+        copy_dir_recursive(&src, &dst)
+    } else {
+        dst.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::copy(&src, &dst).map(|_| ()))
+    };
+    copied.map_err(|e| format!("Failed to update {}: {}", path, e))
+}
+
+fn is_dependency_file(path: &str) -> bool {
+    path == "package.json" || path == "package-lock.json"
+}
+
+/// What `sync_files` does with one synced file.
+enum SyncAction {
+    /// The workspace already has the template's version, and it's recorded.
+    Nothing,
+    /// The workspace has the template's version; record its hash.
+    Record,
+    /// Edited, but the template has nothing new to bring in.
+    Keep,
+    /// Unedited (or a directory, which isn't tracked): replace it.
+    Copy,
+    /// Edited, and the template changed too.
+    BackUpAndCopy,
+}
+
+fn sync_action(
+    template: &Path,
+    workspace: &Path,
+    record: &WorkspaceVersion,
+    path: &str,
+) -> (SyncAction, Option<String>) {
+    let src = template.join(path);
+    if !src.exists() {
+        return (SyncAction::Nothing, None);
+    }
+    let Some(new) = src.is_file().then(|| file_hash(&src)).flatten() else {
+        return (SyncAction::Copy, None);
+    };
+    let last = record.synced.get(path);
+    let action = match file_hash(&workspace.join(path)) {
+        Some(current) if current == new && last == Some(&new) => SyncAction::Nothing,
+        Some(current) if current == new => SyncAction::Record,
+        Some(current) if last == Some(&new) && last != Some(&current) => SyncAction::Keep,
+        Some(current) if last != Some(&current) => SyncAction::BackUpAndCopy,
+        _ => SyncAction::Copy,
+    };
+    (action, Some(new))
+}
+
+/// Copy the files in `sync` from the template, keeping or backing up the
+/// user's edits (see the module docs). Returns whether any dependency file
+/// was copied, and the files whose edits were backed up.
+fn sync_files(
+    template: &Path,
+    workspace: &Path,
+    manifest: &Manifest,
+) -> Result<(bool, Vec<ConfigConflict>), String> {
+    let mut record = read_workspace_version(workspace);
+    let mut dependencies_changed = false;
+    let mut conflicts = Vec::new();
+    for path in manifest.sync.iter().filter(|p| safe_relative(p)) {
+        let (action, new) = sync_action(template, workspace, &record, path);
+        match action {
+            SyncAction::Keep => continue,
+            SyncAction::Nothing | SyncAction::Record => {}
+            SyncAction::Copy => {
+                copy_from_template(template, workspace, path)?;
+                dependencies_changed |= is_dependency_file(path);
+            }
+            SyncAction::BackUpAndCopy => {
+                let backup_path = format!("{}.user.bak", path);
+                fs::copy(workspace.join(path), workspace.join(&backup_path))
+                    .map_err(|e| format!("Failed to back up {}: {}", path, e))?;
+                copy_from_template(template, workspace, path)?;
+                dependencies_changed |= is_dependency_file(path);
+                conflicts.push(ConfigConflict {
+                    path: path.clone(),
+                    backup_path,
+                });
+            }
+        }
+        if let Some(new) = new {
+            record.synced.insert(path.clone(), new);
+        }
+    }
+    write_workspace_version(workspace, &record)?;
+    Ok((dependencies_changed, conflicts))
+}
+
+/// Whether `migrate` has nothing to do: no migrations pending and no synced
+/// file to write. Only reads, so setup checks it alongside other work and
+/// skips `migrate` when it can.
+pub fn is_current(template: &Path, workspace: &Path) -> bool {
+    let Some(manifest) = read_manifest(template) else {
+        return false;
+    };
+    if version(workspace) < manifest.version {
+        return false;
+    }
+    let record = read_workspace_version(workspace);
+    manifest
+        .sync
+        .iter()
+        .filter(|p| safe_relative(p))
+        .all(|path| {
+            matches!(
+                sync_action(template, workspace, &record, path).0,
+                SyncAction::Nothing | SyncAction::Keep
+            )
+        })
+}
+
+/// Apply one migration. Returns whether it touched a dependency file.
+fn apply(template: &Path, workspace: &Path, migration: &Migration) -> Result<bool, String> {
+    let mut dependencies_changed = false;
+    for path in migration.update.iter().filter(|p| safe_relative(p)) {
+        if template.join(path).exists() {
+            copy_from_template(template, workspace, path)?;
+            dependencies_changed |= is_dependency_file(path);
+        }
+    }
+    for path in migration.add.iter().filter(|p| safe_relative(p)) {
+        if template.join(path).exists() && !workspace.join(path).exists() {
+            copy_from_template(template, workspace, path)?;
+            dependencies_changed |= is_dependency_file(path);
+        }
+    }
+    for path in migration.remove.iter().filter(|p| safe_relative(p)) {
+        let target = workspace.join(path);
+        let removed = if target.is_dir() {
+            fs::remove_dir_all(&target)
+        } else if target.exists() {
+            fs::remove_file(&target)
+        } else {
+            Ok(())
+        };
+        removed.map_err(|e| format!("Failed to remove {}: {}", path, e))?;
+        dependencies_changed |= is_dependency_file(path);
+    }
+    Ok(dependencies_changed)
+}
+
+/// Commit, logging rather than failing: a workspace without git history can
+/// still be migrated.
+fn commit(app: &AppHandle, workspace: &Path, message: &str) {
+    match git::commit_all(workspace, message) {
+        Ok(Some(_)) => backup::schedule_push(app),
+        Ok(None) => {}
+        Err(e) => crate::logging::log_line(
+            "WARN",
+            &format!("[template] Couldn't commit \"{}\": {}", message, e),
+        ),
+    }
+}
+
+/// Bring an existing workspace up to the bundled template's version, then
+/// refresh the always-synced files.
+pub fn migrate(
+    app: &AppHandle,
+    template: &Path,
+    workspace: &Path,
+) -> Result<MigrationOutcome, String> {
+    let Some(manifest) = read_manifest(template) else {
+        return Err(format!(
+            "Template manifest missing or invalid: {:?}",
+            template.join(MANIFEST_FILE)
+        ));
+    };
+    let current = version(workspace);
+    let mut outcome = MigrationOutcome::default();
+
+    if current > manifest.version {
+        crate::logging::log_line("WARN",
+            &format!(
+                "[template] Workspace is at template version {}, newer than this app's {}; not migrating",
+                current, manifest.version
+            ),
+        );
+    } else {
+        let mut pending: Vec<&Migration> = manifest
+            .migrations
+            .iter()
+            .filter(|m| m.version > current && m.version <= manifest.version)
+            .collect();
+        pending.sort_by_key(|m| m.version);
+        if current < manifest.version {
+            snapshots::take(workspace, "template-update");
+        }
+
+        for migration in pending {
+            crate::logging::log_line(
+                "INFO",
+                &format!(
+                    "[template] Applying migration {}: {}",
+                    migration.version, migration.description
+                ),
+            );
+            outcome.dependencies_changed |= apply(template, workspace, migration)?;
+            write_version(workspace, migration.version)?;
+            commit(
+                app,
+                workspace,
+                &format!(
+                    "Template migration {}: {}",
+                    migration.version, migration.description
+                ),
+            );
+            outcome.applied.push(migration.version);
+        }
+        if current < manifest.version {
+            write_version(workspace, manifest.version)?;
+        }
+    }
+
+    let (dependencies_changed, conflicts) = sync_files(template, workspace, &manifest)?;
+    outcome.dependencies_changed |= dependencies_changed;
+    for conflict in &conflicts {
+        crate::logging::log_line(
+            "WARN",
+            &format!(
+                "[template] {} had local changes and was replaced; they were saved to {}",
+                conflict.path, conflict.backup_path
+            ),
+        );
+        frontend::emit(app, "config-conflict", conflict);
+    }
+    commit(app, workspace, "Update app config");
+    Ok(outcome)
+}
@@ -0,0 +1,498 @@
+//! Starter templates to create a workspace or project from.
+//!
+//! Every workspace starts as a copy of the bundled workspace template, which
+//! `template` keeps up to date. A starter template is an overlay on top of
+//! it: a folder with a `template.json` (`{ "name", "description" }`) and the
+//! files it adds or replaces, usually `src/Root.tsx` and a composition. So
+//! workspaces made from any of them still get the template migrations.
+//!
+//! The starters bundled with the app are in `templates/` in the resources.
+//! More can come from a registry: `templateRegistryUrl` in config.json
+//! points at a JSON index,
+//!
+//! ```json
+//! { "templates": [{ "id": "podcast", "name": "Podcast clip",
+//!   "description": "...", "url": "https://.../podcast.zip",
+//!   "sha256": "..." }] }
+//! ```
+//!
+//! Templates include package.json scripts that `npm install` runs, so the
+//! registry and every template have to be served over https, and an entry
+//! without the `sha256` of its zip is left out.
+//!
+//! `list_templates` fetches it (or uses the copy cached in the config dir)
+//! and lists it after the bundled ones. A remote template is downloaded and
+//! unpacked to `templates/<id>` in the config dir the first time a workspace
+//! is created from it. The `sha256` it was installed from is kept next to it
+//! in `templates/<id>.sha256`, and it's downloaded again once the registry
+//! lists a different one.
+//!
+//! `create_project` takes the template to use, and `workspaceTemplate` in
+//! config.json picks one for the default workspace. Either only matters
+//! when the workspace is created: `setup_workspace` calls `apply` right
+//! after copying the base template.
+
+use crate::error::StudioError;
+use crate::{get_config_dir, import, read_config_file, retry};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// The base template on its own.
+pub const BLANK: &str = "blank";
+const MANIFEST: &str = "template.json";
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// A template as the registry lists it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteTemplate {
+    id: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    /// A zip of the template folder.
+    url: String,
+    /// Of the zip, in hex.
+    #[serde(default)]
+    sha256: String,
+}
+
+impl RemoteTemplate {
+    fn usable(&self) -> bool {
+        valid_id(&self.id)
+            && self.id != BLANK
+            && self.url.starts_with("https://")
+            && self.sha256.len() == 64
+            && self.sha256.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryIndex {
+    #[serde(default)]
+    templates: Vec<RemoteTemplate>,
+}
+
+/// The last index fetched, in `templates/registry.json` in the config dir.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedIndex {
+    url: String,
+    fetched_at: String,
+    templates: Vec<RemoteTemplate>,
+}
+
+/// Template as returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// "bundled" or "remote".
+    pub source: &'static str,
+    /// Whether it can be used without downloading it first.
+    pub installed: bool,
+}
+
+/// Template ids are also folder names: lowercase letters, digits, `-` and
+/// `_`.
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn bundled_dir(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .resource_dir()
+        .ok()
+        .map(|dir| dir.join("templates"))
+}
+
+fn remote_dir() -> PathBuf {
+    get_config_dir().join("templates")
+}
+
+fn cache_path() -> PathBuf {
+    remote_dir().join("registry.json")
+}
+
+/// `templateRegistryUrl`, unless it isn't https (only possible by editing
+/// config.json by hand).
+fn registry_url() -> Option<String> {
+    read_config_file()
+        .template_registry_url
+        .map(|url| url.trim().to_string())
+        .filter(|url| url.starts_with("https://"))
+}
+
+fn read_manifest(dir: &Path) -> Option<Manifest> {
+    let contents = fs::read_to_string(dir.join(MANIFEST)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn blank_info() -> TemplateInfo {
+    TemplateInfo {
+        id: BLANK.to_string(),
+        name: "Blank".to_string(),
+        description: "The welcome video and nothing else, to start from scratch.".to_string(),
+        source: "bundled",
+        installed: true,
+    }
+}
+
+/// The bundled starters, sorted by id.
+fn bundled(app: &AppHandle) -> Vec<(TemplateInfo, PathBuf)> {
+    let Some(entries) = bundled_dir(app).and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut templates: Vec<(TemplateInfo, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry.file_name().to_string_lossy().into_owned();
+            let manifest = read_manifest(&entry.path()).filter(|_| valid_id(&id))?;
+            Some((
+                TemplateInfo {
+                    id,
+                    name: manifest.name,
+                    description: manifest.description,
+                    source: "bundled",
+                    installed: true,
+                },
+                entry.path(),
+            ))
+        })
+        .collect();
+    templates.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+    templates
+}
+
+/// The cached index, if it's for the registry configured now.
+fn cached_index() -> Vec<RemoteTemplate> {
+    let Some(url) = registry_url() else {
+        return Vec::new();
+    };
+    fs::read_to_string(cache_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str::<CachedIndex>(&contents).ok())
+        .filter(|cache| cache.url == url)
+        .map(|cache| {
+            cache
+                .templates
+                .into_iter()
+                .filter(RemoteTemplate::usable)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn save_index(url: &str, templates: &[RemoteTemplate]) -> Result<(), String> {
+    fs::create_dir_all(remote_dir())
+        .map_err(|e| format!("Failed to create templates directory: {}", e))?;
+    let cache = CachedIndex {
+        url: url.to_string(),
+        fetched_at: Local::now().to_rfc3339(),
+        templates: templates.to_vec(),
+    };
+    let contents = serde_json::to_string_pretty(&cache).map_err(|e| e.to_string())?;
+    fs::write(cache_path(), contents).map_err(|e| format!("Failed to write registry.json: {}", e))
+}
+
+async fn get(url: &str) -> Result<reqwest::Response, StudioError> {
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| StudioError::Network(e.to_string()))?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| StudioError::Network(format!("Couldn't reach {}: {}", url, e)))?;
+    let status = response.status();
+    if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(StudioError::Network(format!(
+            "{} answered HTTP {}",
+            url, status
+        )));
+    }
+    if !status.is_success() {
+        return Err(StudioError::Other(format!(
+            "{} answered HTTP {}",
+            url, status
+        )));
+    }
+    Ok(response)
+}
+
+/// Fetch the registry's index and cache it. Entries with an unusable id,
+/// no https URL or no checksum are left out.
+async fn fetch_index(url: &str) -> Result<Vec<RemoteTemplate>, StudioError> {
+    let index: RegistryIndex = retry::run(
+        "Template registry",
+        retry::Policy::current(),
+        StudioError::is_retryable,
+        || async {
+            get(url).await?.json().await.map_err(|e| {
+                StudioError::Other(format!("{} isn't a template registry: {}", url, e))
+            })
+        },
+    )
+    .await?;
+    let (templates, unusable): (Vec<RemoteTemplate>, Vec<RemoteTemplate>) = index
+        .templates
+        .into_iter()
+        .partition(RemoteTemplate::usable);
+    if !unusable.is_empty() {
+        crate::logging::log_line(
+            "WARN",
+            &format!(
+                "[templates] Left out {} registry entries without an https URL and sha256: {}",
+                unusable.len(),
+                unusable
+                    .iter()
+                    .map(|t| t.id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        );
+    }
+    if let Err(e) = save_index(url, &templates) {
+        crate::logging::log_line("WARN", &format!("[templates] {}", e));
+    }
+    Ok(templates)
+}
+
+/// The registry's templates: fetched when `refresh` is set or nothing is
+/// cached yet, otherwise from the cache. Falls back to the cache when the
+/// registry can't be reached.
+async fn remote(refresh: bool) -> Vec<RemoteTemplate> {
+    let Some(url) = registry_url() else {
+        return Vec::new();
+    };
+    let cached = cached_index();
+    if !refresh && !cached.is_empty() {
+        return cached;
+    }
+    match fetch_index(&url).await {
+        Ok(templates) => templates,
+        Err(e) => {
+            crate::logging::log_line(
+                "WARN",
+                &format!("[templates] Couldn't fetch the template registry: {}", e),
+            );
+            cached
+        }
+    }
+}
+
+fn installed_dir(id: &str) -> Option<PathBuf> {
+    let dir = remote_dir().join(id);
+    dir.join(MANIFEST).is_file().then_some(dir)
+}
+
+/// Where the `sha256` of the zip `id` was installed from is kept.
+fn installed_sha_path(id: &str) -> PathBuf {
+    remote_dir().join(format!("{}.sha256", id))
+}
+
+/// The installed copy of `id`, unless the cached index lists a different
+/// `sha256` for it than it was installed from.
+fn current_install(id: &str) -> Option<PathBuf> {
+    let dir = installed_dir(id)?;
+    let Some(template) = cached_index().into_iter().find(|t| t.id == id) else {
+        return Some(dir);
+    };
+    let installed = fs::read_to_string(installed_sha_path(id)).unwrap_or_default();
+    if installed.trim().eq_ignore_ascii_case(&template.sha256) {
+        return Some(dir);
+    }
+    crate::logging::log_line(
+        "INFO",
+        &format!("[templates] {} changed in the registry, reinstalling", id),
+    );
+    None
+}
+
+/// The templates a workspace can be created from: blank, the bundled ones,
+/// then those from `templateRegistryUrl`. `refresh` fetches the registry's
+/// index again instead of using the cached one.
+#[tauri::command]
+pub async fn list_templates(app: AppHandle, refresh: Option<bool>) -> Vec<TemplateInfo> {
+    let mut templates = vec![blank_info()];
+    templates.extend(bundled(&app).into_iter().map(|(info, _)| info));
+    for template in remote(refresh.unwrap_or(false)).await {
+        if templates.iter().any(|t| t.id == template.id) {
+            continue;
+        }
+        templates.push(TemplateInfo {
+            installed: installed_dir(&template.id).is_some(),
+            id: template.id,
+            name: template.name,
+            description: template.description,
+            source: "remote",
+        });
+    }
+    templates
+}
+
+/// Whether `id` is a template `apply` can find, downloading it if needed.
+pub fn exists(app: &AppHandle, id: &str) -> bool {
+    id == BLANK
+        || bundled(app).iter().any(|(t, _)| t.id == id)
+        || installed_dir(id).is_some()
+        || cached_index().iter().any(|t| t.id == id)
+}
+
+/// The folder to unpack to: the archive's root, or its only subfolder, as
+/// archives often wrap everything in one.
+fn template_root(dir: &Path) -> Option<PathBuf> {
+    if dir.join(MANIFEST).is_file() {
+        return Some(dir.to_path_buf());
+    }
+    let subdirs: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| entry.file_name() != "__MACOSX")
+        .map(|entry| entry.path())
+        .collect();
+    match subdirs.as_slice() {
+        [only] if only.join(MANIFEST).is_file() => Some(only.clone()),
+        _ => None,
+    }
+}
+
+/// Unpack a downloaded template to `templates/<id>` in the config dir,
+/// replacing an older copy. Symlinks and entries that would land outside it
+/// are left out (see `import::extract_zip`).
+fn unpack(template: &RemoteTemplate, bytes: Vec<u8>) -> Result<PathBuf, String> {
+    let staging = remote_dir().join(format!(".{}.unpacking", template.id));
+    let _ = fs::remove_dir_all(&staging);
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(bytes))
+        .map_err(|e| format!("{} is not a valid zip: {}", template.url, e))?;
+    let result = import::extract_zip(&mut zip, &staging)
+        .map_err(|e| format!("Failed to extract {}: {}", template.url, e))
+        .and_then(|_| {
+            let root = template_root(&staging)
+                .ok_or_else(|| format!("No {} in {}", MANIFEST, template.url))?;
+            let dest = remote_dir().join(&template.id);
+            let _ = fs::remove_dir_all(&dest);
+            fs::rename(&root, &dest).map_err(|e| format!("Failed to move template: {}", e))?;
+            fs::write(installed_sha_path(&template.id), &template.sha256)
+                .map_err(|e| format!("Failed to record the template's checksum: {}", e))?;
+            Ok(dest)
+        });
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// Download and unpack a template from the registry.
+async fn install(id: &str) -> Result<PathBuf, StudioError> {
+    let template = match cached_index().into_iter().find(|t| t.id == id) {
+        Some(template) => template,
+        None => remote(true)
+            .await
+            .into_iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| StudioError::NotFound(format!("Template {}", id)))?,
+    };
+    crate::logging::log_line(
+        "INFO",
+        &format!("[templates] Downloading {} from {}", id, template.url),
+    );
+    let bytes = retry::run(
+        "Template download",
+        retry::Policy::current(),
+        StudioError::is_retryable,
+        || async {
+            get(&template.url)
+                .await?
+                .bytes()
+                .await
+                .map_err(|e| StudioError::Network(format!("Download of {} failed: {}", id, e)))
+        },
+    )
+    .await?;
+    let actual = format!("{:x}", Sha256::digest(&bytes));
+    if !actual.eq_ignore_ascii_case(&template.sha256) {
+        return Err(StudioError::Other(format!(
+            "Template {} doesn't match its checksum (expected {}, got {})",
+            id, template.sha256, actual
+        )));
+    }
+    fs::create_dir_all(remote_dir())
+        .map_err(|e| StudioError::io_at("Failed to create", &remote_dir(), e))?;
+    Ok(unpack(&template, bytes.to_vec())?)
+}
+
+/// Copy a template over the workspace, leaving out its `template.json`, the
+/// base template's manifest (migrations go by the base template) and
+/// anything that's reinstalled or version control.
+fn overlay(src: &Path, dst: &Path, top: bool) -> std::io::Result<usize> {
+    fs::create_dir_all(dst)?;
+    let mut copied = 0;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if (top && (name == MANIFEST || name == ".langston-template.json"))
+            || name == "node_modules"
+            || name == ".git"
+        {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let dst_path = dst.join(&name);
+        if file_type.is_dir() {
+            copied += overlay(&entry.path(), &dst_path, false)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &dst_path)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// Put template `id` over a workspace that was just copied from the base
+/// template. Remote templates are downloaded first if they haven't been, or
+/// if the registry has a different version now (see `current_install`).
+pub fn apply(app: &AppHandle, id: &str, workspace: &Path) -> Result<(), StudioError> {
+    if id == BLANK {
+        return Ok(());
+    }
+    let dir = match bundled(app).into_iter().find(|(t, _)| t.id == id) {
+        Some((_, dir)) => dir,
+        None => match current_install(id) {
+            Some(dir) => dir,
+            None => {
+                // On a thread of its own: setup may be running on the async
+                // runtime.
+                let id = id.to_string();
+                std::thread::spawn(move || tauri::async_runtime::block_on(install(&id)))
+                    .join()
+                    .map_err(|_| StudioError::Other("Template download panicked".to_string()))??
+            }
+        },
+    };
+    let copied = overlay(&dir, workspace, true)
+        .map_err(|e| StudioError::io("Failed to apply the template", e))?;
+    crate::logging::log_line(
+        "INFO",
+        &format!("[templates] Applied {} ({} files)", id, copied),
+    );
+    Ok(())
+}
@@ -0,0 +1,474 @@
+//! Archive of OpenCode prompts and responses, per project.
+//!
+//! "That prompt where it built the lower-third animation" could only be
+//! found by scrolling through old sessions one by one. The proxy now marks
+//! every session it sees a request for (`observe_path`), and every
+//! `SYNC_INTERVAL` the messages of those sessions are read from OpenCode's
+//! API and the ones not archived yet are appended to
+//! `transcripts/<project id>.ndjson` in the config dir: one JSON object per
+//! message with its session, role, time and text (tool calls and other
+//! parts are left out). An answer still being written is picked up on a
+//! later sync. `sync_now` runs a last sync on shutdown. The tokens and cost
+//! of each answer are handed to `usage`.
+//!
+//! `search_transcripts` finds messages containing every word of a query,
+//! newest first.
+
+use crate::error::StudioError;
+use crate::projects::{self, DEFAULT_PROJECT_ID};
+use crate::sessions::{self, api_get};
+use crate::{get_config_dir, resolve_workspace_dir, usage, AppState};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Shorter on shutdown, where it holds up quitting.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+/// Characters of context on each side of a match in a snippet.
+const SNIPPET_CONTEXT: usize = 80;
+
+/// Sessions with activity since their last sync, and their project.
+static PENDING: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+/// The project each session seen so far belongs to.
+static SESSION_PROJECTS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+/// Ids of the messages already archived, by project; loaded on first use.
+static ARCHIVED: Mutex<Option<HashMap<String, HashSet<String>>>> = Mutex::new(None);
+/// Serializes syncs, so a message isn't archived twice.
+static SYNC_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// One archived message, as stored and as returned to the frontend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptEntry {
+    pub message_id: String,
+    pub session_id: String,
+    pub project: String,
+    /// "user" or "assistant".
+    pub role: String,
+    pub at: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TranscriptHit {
+    #[serde(flatten)]
+    pub entry: TranscriptEntry,
+    /// The text around the first match.
+    pub snippet: String,
+}
+
+/// A message as OpenCode's `GET /session/:id/message` returns it.
+#[derive(Debug, Deserialize)]
+struct ApiMessage {
+    info: ApiMessageInfo,
+    #[serde(default)]
+    parts: Vec<ApiPart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiMessageInfo {
+    id: String,
+    role: String,
+    #[serde(default)]
+    time: ApiMessageTime,
+    /// On answers.
+    #[serde(default, rename = "providerID")]
+    provider_id: Option<String>,
+    #[serde(default)]
+    cost: Option<f64>,
+    #[serde(default)]
+    tokens: Option<ApiTokens>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiTokens {
+    #[serde(default)]
+    input: u64,
+    #[serde(default)]
+    output: u64,
+    #[serde(default)]
+    reasoning: u64,
+    #[serde(default)]
+    cache: ApiCacheTokens,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiCacheTokens {
+    #[serde(default)]
+    read: u64,
+    #[serde(default)]
+    write: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiMessageTime {
+    #[serde(default)]
+    created: i64,
+    /// Unset while an answer is still being written.
+    #[serde(default)]
+    completed: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiPart {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+    /// Text OpenCode added itself, such as file contents.
+    #[serde(default)]
+    synthetic: Option<bool>,
+}
+
+fn transcripts_dir() -> PathBuf {
+    get_config_dir().join("transcripts")
+}
+
+fn archive_path(project: &str) -> PathBuf {
+    transcripts_dir().join(format!("{}.ndjson", project))
+}
+
+fn current_project() -> String {
+    projects::id_for_path(&resolve_workspace_dir())
+        .unwrap_or_else(|| DEFAULT_PROJECT_ID.to_string())
+}
+
+/// Called by the proxy for every request to OpenCode.
+pub fn observe_path(path: &str) {
+    let Some(session) = sessions::session_in_path(path) else {
+        return;
+    };
+    let project = {
+        let Ok(mut known) = SESSION_PROJECTS.lock() else {
+            return;
+        };
+        let known = known.get_or_insert_with(HashMap::new);
+        match known.get(session) {
+            Some(project) => project.clone(),
+            None => {
+                let project = current_project();
+                known.insert(session.to_string(), project.clone());
+                project
+            }
+        }
+    };
+    if let Ok(mut pending) = PENDING.lock() {
+        pending
+            .get_or_insert_with(HashMap::new)
+            .insert(session.to_string(), project);
+    }
+}
+
+fn read_archive(project: &str) -> Vec<TranscriptEntry> {
+    let Ok(file) = fs::File::open(archive_path(project)) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
+fn append(project: &str, entries: &[TranscriptEntry]) -> Result<(), StudioError> {
+    fs::create_dir_all(transcripts_dir())
+        .map_err(|e| StudioError::io_at("Failed to create", &transcripts_dir(), e))?;
+    let path = archive_path(project);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| StudioError::io_at("Failed to open", &path, e))?;
+    for entry in entries {
+        let line = serde_json::to_string(entry).map_err(|e| StudioError::Other(e.to_string()))?;
+        writeln!(file, "{}", line).map_err(|e| StudioError::io_at("Failed to write", &path, e))?;
+    }
+    Ok(())
+}
+
+fn text_of(parts: &[ApiPart]) -> String {
+    parts
+        .iter()
+        .filter(|p| p.kind == "text" && p.synthetic != Some(true))
+        .filter_map(|p| p.text.as_deref())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The tokens and cost of an answer, for `usage`.
+fn usage_of(info: &ApiMessageInfo, project: &str) -> Option<usage::Record> {
+    let tokens = info.tokens.as_ref()?;
+    let date = Local
+        .timestamp_millis_opt(info.time.created)
+        .single()?
+        .date_naive();
+    Some(usage::Record {
+        date,
+        project: project.to_string(),
+        provider: info
+            .provider_id
+            .clone()
+            .unwrap_or_else(|| "unknown".to_string()),
+        usage: usage::Usage {
+            input_tokens: tokens.input,
+            output_tokens: tokens.output,
+            reasoning_tokens: tokens.reasoning,
+            cache_read_tokens: tokens.cache.read,
+            cache_write_tokens: tokens.cache.write,
+            cost_usd: info.cost.unwrap_or(0.0),
+            messages: 1,
+        },
+    })
+}
+
+fn timestamp(millis: i64) -> String {
+    Local
+        .timestamp_millis_opt(millis)
+        .single()
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Archive the new messages of one session. Returns whether it has an
+/// answer still being written.
+async fn sync_session(
+    app: &AppHandle,
+    session: &str,
+    project: &str,
+    timeout: Duration,
+) -> Result<bool, StudioError> {
+    let messages: Vec<ApiMessage> =
+        api_get(app, &format!("/session/{}/message", session), timeout).await?;
+    let mut unfinished = false;
+    let mut new = Vec::new();
+    let mut inserted = Vec::new();
+    let mut usage = Vec::new();
+    {
+        let Ok(mut archived) = ARCHIVED.lock() else {
+            return Ok(false);
+        };
+        let archived = archived
+            .get_or_insert_with(HashMap::new)
+            .entry(project.to_string())
+            .or_insert_with(|| {
+                read_archive(project)
+                    .into_iter()
+                    .map(|e| e.message_id)
+                    .collect()
+            });
+        for message in messages {
+            if archived.contains(&message.info.id) {
+                continue;
+            }
+            if message.info.role == "assistant" && message.info.time.completed.is_none() {
+                unfinished = true;
+                continue;
+            }
+            archived.insert(message.info.id.clone());
+            inserted.push(message.info.id.clone());
+            if message.info.role == "assistant" {
+                if let Some(record) = usage_of(&message.info, project) {
+                    usage.push(record);
+                }
+            }
+            let text = text_of(&message.parts);
+            if text.is_empty() {
+                continue;
+            }
+            new.push(TranscriptEntry {
+                message_id: message.info.id,
+                session_id: session.to_string(),
+                project: project.to_string(),
+                role: message.info.role,
+                at: timestamp(message.info.time.created),
+                text,
+            });
+        }
+    }
+    if !new.is_empty() {
+        if let Err(e) = append(project, &new) {
+            // Forget them so the next sync tries again.
+            if let Ok(mut archived) = ARCHIVED.lock() {
+                if let Some(ids) = archived.as_mut().and_then(|a| a.get_mut(project)) {
+                    for id in &inserted {
+                        ids.remove(id);
+                    }
+                }
+            }
+            return Err(e);
+        }
+    }
+    usage::record(app, &usage);
+    Ok(unfinished)
+}
+
+/// Archive the new messages of every session with activity since the last
+/// sync.
+async fn sync(app: &AppHandle, timeout: Duration) {
+    let pending = PENDING
+        .lock()
+        .ok()
+        .and_then(|mut p| p.take())
+        .unwrap_or_default();
+    if pending.is_empty() {
+        return;
+    }
+    let _sync = SYNC_LOCK.lock().await;
+    for (session, project) in pending {
+        let retry = match sync_session(app, &session, &project, timeout).await {
+            Ok(unfinished) => unfinished,
+            Err(e) => {
+                crate::logging::log_line(
+                    "WARN",
+                    &format!("[transcripts] Couldn't archive session {}: {}", session, e),
+                );
+                true
+            }
+        };
+        if retry {
+            if let Ok(mut p) = PENDING.lock() {
+                p.get_or_insert_with(HashMap::new)
+                    .entry(session)
+                    .or_insert(project);
+            }
+        }
+    }
+}
+
+/// Start archiving, once.
+pub fn start(app: &AppHandle) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SYNC_INTERVAL);
+        let shutting_down = app
+            .try_state::<Mutex<AppState>>()
+            .and_then(|state| state.lock().ok().map(|g| g.shutting_down))
+            .unwrap_or(true);
+        if shutting_down {
+            return;
+        }
+        tauri::async_runtime::block_on(sync(&app, TIMEOUT));
+    });
+}
+
+/// Archive what's left before OpenCode is stopped. Called from `shutdown`.
+pub fn sync_now(app: &AppHandle) {
+    let app = app.clone();
+    // On a thread of its own: shutdown may be running on the async runtime.
+    let _ =
+        std::thread::spawn(move || tauri::async_runtime::block_on(sync(&app, SHUTDOWN_TIMEOUT)))
+            .join();
+}
+
+fn snippet(text: &str, at: usize, len: usize) -> String {
+    let start = text[..at]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(0, |(i, _)| i);
+    let end = text[at + len..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(text.len(), |(i, _)| at + len + i);
+    let mut snippet = text[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// Archived messages containing every word of `query`, ignoring case,
+/// newest first. Searches the open project unless `project` names another
+/// one, or `"*"` for all of them.
+#[tauri::command]
+pub fn search_transcripts(
+    query: String,
+    project: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<TranscriptHit>, StudioError> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Err(StudioError::InvalidInput(
+            "Enter something to search for".to_string(),
+        ));
+    }
+    let projects: Vec<String> = match project.as_deref() {
+        Some("*") => fs::read_dir(transcripts_dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| {
+                        let name = e.file_name().to_string_lossy().into_owned();
+                        name.strip_suffix(".ndjson").map(str::to_string)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Some(id)
+            if id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            vec![id.to_string()]
+        }
+        Some(id) => {
+            return Err(StudioError::InvalidInput(format!(
+                "Invalid project id: {}",
+                id
+            )))
+        }
+        None => vec![current_project()],
+    };
+
+    let mut hits: Vec<TranscriptHit> = Vec::new();
+    for project in projects {
+        for entry in read_archive(&project) {
+            // Lowercasing can change byte offsets outside ASCII, so the
+            // snippet falls back to the start of the text there.
+            let lower = entry.text.to_lowercase();
+            if !terms.iter().all(|t| lower.contains(t.as_str())) {
+                continue;
+            }
+            let (at, len) = match lower.find(terms[0].as_str()) {
+                Some(at)
+                    if lower.len() == entry.text.len()
+                        && entry.text.is_char_boundary(at)
+                        && entry.text.is_char_boundary(at + terms[0].len()) =>
+                {
+                    (at, terms[0].len())
+                }
+                _ => (0, 0),
+            };
+            hits.push(TranscriptHit {
+                snippet: snippet(&entry.text, at, len),
+                entry,
+            });
+        }
+    }
+    hits.sort_by(|a, b| b.entry.at.cmp(&a.entry.at));
+    hits.truncate(limit.unwrap_or(DEFAULT_SEARCH_LIMIT));
+    Ok(hits)
+}
@@ -0,0 +1,160 @@
+//! Menu bar (tray) item.
+//!
+//! With the window minimized there was no way to tell whether OpenCode and
+//! Remotion were still up. The tray menu shows each server's status, kept
+//! current by `refresh` on every phase change, and has the actions people
+//! reached for the window for: restart the services, open the logs folder,
+//! reveal the workspace and quit. Quitting goes through the same cleanup as
+//! closing the window.
+
+use crate::phase::{self, Phase};
+use crate::{get_logs_dir, platform, resolve_workspace_dir, shutdown, supervisor};
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Wry};
+
+const TRAY_ID: &str = "main";
+
+/// The status lines, kept so `refresh` can update them.
+struct TrayStatus {
+    opencode: MenuItem<Wry>,
+    remotion: MenuItem<Wry>,
+}
+
+fn status_text(name: &str, phase: Phase, pid: Option<u32>) -> String {
+    let status = match (phase, pid) {
+        (Phase::Recovering, _) => "Restarting".to_string(),
+        (Phase::Error, _) | (Phase::Ready, None) => "Stopped".to_string(),
+        (Phase::Ready, Some(pid)) => format!("Running (PID {})", pid),
+        _ => "Starting".to_string(),
+    };
+    format!("{}: {}", name, status)
+}
+
+fn tooltip(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Ready => "Langston Studio: running",
+        Phase::Recovering => "Langston Studio: restarting services",
+        Phase::Error => "Langston Studio: services stopped",
+        _ => "Langston Studio: starting",
+    }
+}
+
+/// Add the tray item. Called once from the setup hook.
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let opencode = MenuItem::with_id(
+        app,
+        "opencode-status",
+        "OpenCode: Starting",
+        false,
+        None::<&str>,
+    )?;
+    let remotion = MenuItem::with_id(
+        app,
+        "remotion-status",
+        "Remotion: Starting",
+        false,
+        None::<&str>,
+    )?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &opencode,
+            &remotion,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "show", "Show Langston Studio", true, None::<&str>)?,
+            &MenuItem::with_id(app, "restart", "Restart Services", true, None::<&str>)?,
+            &MenuItem::with_id(app, "open-logs", "Open Logs Folder", true, None::<&str>)?,
+            &MenuItem::with_id(
+                app,
+                "open-workspace",
+                "Open Workspace in Finder",
+                true,
+                None::<&str>,
+            )?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(
+                app,
+                "quit",
+                "Quit Langston Studio",
+                true,
+                Some("CmdOrCtrl+Q"),
+            )?,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip(tooltip(Phase::default()))
+        .on_menu_event(on_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(TrayStatus { opencode, remotion });
+    Ok(())
+}
+
+/// Bring the status lines and tooltip up to date with the app state.
+pub fn refresh(app: &AppHandle) {
+    let Some(status) = app.try_state::<TrayStatus>() else {
+        return;
+    };
+    let view = phase::view(app);
+    let _ = status
+        .opencode
+        .set_text(status_text("OpenCode", view.phase, view.opencode_pid));
+    let _ = status
+        .remotion
+        .set_text(status_text("Remotion", view.phase, view.remotion_pid));
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some(tooltip(view.phase)));
+    }
+}
+
+/// Bring the main window back and to the front.
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        "show" => show_main_window(app),
+        "restart" => {
+            let app = app.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = supervisor::restart_all(&app) {
+                    crate::logging::log_line("ERROR", &format!("[tray] Restart failed: {}", e));
+                }
+            });
+        }
+        "open-logs" => {
+            if let Err(e) = platform::open_path(&get_logs_dir()) {
+                crate::logging::log_line(
+                    "WARN",
+                    &format!("[tray] Couldn't open logs folder: {}", e),
+                );
+            }
+        }
+        "open-workspace" => {
+            if let Err(e) = platform::open_path(&resolve_workspace_dir()) {
+                crate::logging::log_line("WARN", &format!("[tray] Couldn't open workspace: {}", e));
+            }
+        }
+        "quit" => {
+            // Cleanup blocks while the children stop; keep it off the
+            // event loop.
+            let app = app.clone();
+            std::thread::spawn(move || {
+                shutdown(&app, "Quit from menu bar");
+                app.exit(0);
+            });
+        }
+        _ => {}
+    }
+}
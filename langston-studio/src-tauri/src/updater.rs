@@ -0,0 +1,205 @@
+//! Self-update through tauri-plugin-updater.
+//!
+//! Release builds are given the manifest URL and the public key releases
+//! are signed with (`LANGSTON_UPDATE_ENDPOINT` and `LANGSTON_UPDATE_PUBKEY`
+//! at build time); builds without them, like local dev builds, never
+//! update. A background check runs `FIRST_CHECK_DELAY` after launch and
+//! every `CHECK_INTERVAL` after that and sends `update-available`. The UI
+//! can also call `check_for_update`, and `install_update` downloads the
+//! update (sending `update-progress`), installs it and relaunches.
+//!
+//! Rollouts are staged with an optional `rolloutPercent` in the manifest:
+//! each install falls in a fixed bucket from 0 to 99 and only sees the
+//! update once the percentage is above its bucket.
+//!
+//! The workspace template migration runs on every launch, so the relaunch
+//! after an update brings the workspace up to the new template. `after_launch`
+//! notices the version change and sends `app-updated`.
+
+use crate::error::StudioError;
+use crate::{frontend, get_username, read_config_file, telemetry, update_config_file};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+const ENDPOINT: Option<&str> = option_env!("LANGSTON_UPDATE_ENDPOINT");
+pub const PUBKEY: Option<&str> = option_env!("LANGSTON_UPDATE_PUBKEY");
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    pub date: Option<String>,
+}
+
+impl UpdateInfo {
+    fn from_update(update: &Update) -> UpdateInfo {
+        UpdateInfo {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            notes: update.body.clone(),
+            date: update.date.map(|date| date.to_string()),
+        }
+    }
+}
+
+/// This install's rollout bucket, 0 to 99. Stable across launches.
+fn rollout_bucket() -> u64 {
+    let digest = Sha256::digest(format!("co.langston.studio:{}", get_username()).as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes) % 100
+}
+
+/// The newest update this install should get, if any.
+async fn find_update(app: &AppHandle) -> Result<Option<Update>, StudioError> {
+    let (Some(endpoint), Some(_)) = (ENDPOINT, PUBKEY) else {
+        return Err("Updates aren't set up for this build".into());
+    };
+    let url = tauri::Url::parse(endpoint).map_err(|e| format!("Invalid update URL: {}", e))?;
+    let updater = app
+        .updater_builder()
+        .endpoints(vec![url])
+        .and_then(|builder| builder.build())
+        .map_err(|e| e.to_string())?;
+    let Some(update) = updater
+        .check()
+        .await
+        .map_err(|e| StudioError::Network(e.to_string()))?
+    else {
+        return Ok(None);
+    };
+
+    let percent = update
+        .raw_json
+        .get("rolloutPercent")
+        .and_then(|p| p.as_u64())
+        .unwrap_or(100);
+    if rollout_bucket() >= percent {
+        crate::logging::log_line(
+            "INFO",
+            &format!(
+                "[updater] {} is rolling out to {}% of installs, not this one yet",
+                update.version, percent
+            ),
+        );
+        return Ok(None);
+    }
+    Ok(Some(update))
+}
+
+/// Check for updates in the background for as long as the app runs.
+pub fn start(app: AppHandle) {
+    if ENDPOINT.is_none() || PUBKEY.is_none() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_CHECK_DELAY).await;
+        loop {
+            match find_update(&app).await {
+                Ok(Some(update)) => {
+                    crate::logging::log_line(
+                        "INFO",
+                        &format!("[updater] Update available: {}", update.version),
+                    );
+                    frontend::emit(&app, "update-available", UpdateInfo::from_update(&update));
+                }
+                Ok(None) => {}
+                Err(e) => crate::logging::log_line(
+                    "WARN",
+                    &format!("[updater] Update check failed: {}", e),
+                ),
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Note a version change since the last launch and send `app-updated`.
+pub fn after_launch(app: &AppHandle) {
+    let current = env!("CARGO_PKG_VERSION");
+    let previous = read_config_file().last_run_version;
+    if previous.as_deref() == Some(current) {
+        return;
+    }
+    if let Err(e) = update_config_file(|obj| {
+        obj.insert("lastRunVersion".to_string(), current.into());
+    }) {
+        crate::logging::log_line("WARN", &format!("[updater] Couldn't record version: {}", e));
+    }
+    // A fresh install has nothing to report.
+    let Some(previous) = previous else {
+        return;
+    };
+    let msg = format!("Updated from {} to {}", previous, current);
+    telemetry::breadcrumb("updater", &msg);
+    crate::logging::log_line("INFO", &format!("[updater] {}", msg));
+    frontend::emit(
+        app,
+        "app-updated",
+        serde_json::json!({ "from": previous, "to": current }),
+    );
+}
+
+/// Check for a newer version now.
+#[tauri::command]
+pub async fn check_for_update(app: AppHandle) -> Result<Option<UpdateInfo>, StudioError> {
+    Ok(find_update(&app)
+        .await?
+        .map(|update| UpdateInfo::from_update(&update)))
+}
+
+/// Download and install the available update, then relaunch. Sends
+/// `update-progress` while downloading.
+#[tauri::command]
+pub async fn install_update(app: AppHandle) -> Result<(), StudioError> {
+    let Some(update) = find_update(&app).await? else {
+        return Err("No update available".into());
+    };
+    crate::logging::log_line("INFO", &format!("[updater] Installing {}", update.version));
+
+    let mut downloaded: u64 = 0;
+    let mut last_step = None;
+    let progress_app = app.clone();
+    update
+        .download_and_install(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                let percent = total.map(|total| downloaded * 100 / total.max(1));
+                // One event per percent (or per MB when the size is unknown)
+                // is plenty for a progress bar.
+                let step = percent.unwrap_or(downloaded >> 20);
+                if last_step != Some(step) {
+                    last_step = Some(step);
+                    let _ = progress_app.emit(
+                        "update-progress",
+                        serde_json::json!({
+                            "downloaded": downloaded,
+                            "total": total,
+                            "percent": percent,
+                        }),
+                    );
+                }
+            },
+            || {},
+        )
+        .await
+        .map_err(|e| StudioError::Network(format!("Update failed: {}", e)))?;
+
+    crate::logging::log_line(
+        "INFO",
+        &format!("[updater] Installed {}, relaunching", update.version),
+    );
+    let handle = app.clone();
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        crate::shutdown(&handle, "Relaunching after update")
+    })
+    .await;
+    app.restart();
+}
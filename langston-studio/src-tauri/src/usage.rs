@@ -0,0 +1,284 @@
+//! Token use and cost, per day, project and provider.
+//!
+//! The first anyone heard of a heavy week was the provider's invoice.
+//! OpenCode puts the tokens and estimated cost of each answer on the
+//! message, so when `transcripts` archives a finished answer it's also
+//! added up here: per day, per project and per provider, in usage.json in
+//! the config dir. `get_usage_stats` returns the last days of it.
+//!
+//! `usageBudget` in config.json sets a soft budget in US dollars:
+//!
+//! ```json
+//! "usageBudget": { "dailyUsd": 10, "monthlyUsd": 150 }
+//! ```
+//!
+//! Nothing is blocked. The first time the day's or the month's estimated
+//! cost reaches its budget, a `budget-warning` event is sent,
+//! `{ period, spentUsd, budgetUsd }` with period "day" or "month". Costs
+//! are OpenCode's estimates from public pricing, so they won't match the
+//! bill exactly; local models cost nothing.
+
+use crate::{get_config_dir, read_config_file};
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+const DEFAULT_STATS_DAYS: u32 = 30;
+const MAX_STATS_DAYS: u32 = 366;
+
+/// Serializes updates of usage.json.
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Budget {
+    #[serde(default)]
+    pub daily_usd: Option<f64>,
+    #[serde(default)]
+    pub monthly_usd: Option<f64>,
+}
+
+impl Budget {
+    /// What's wrong with it, for `set_config`.
+    pub fn problems(&self) -> Vec<String> {
+        [
+            ("usageBudget.dailyUsd", self.daily_usd),
+            ("usageBudget.monthlyUsd", self.monthly_usd),
+        ]
+        .into_iter()
+        .filter_map(|(field, value)| match value {
+            Some(v) if !v.is_finite() || v <= 0.0 => {
+                Some(format!("{} must be a positive amount", field))
+            }
+            _ => None,
+        })
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub reasoning_tokens: u64,
+    #[serde(default)]
+    pub cache_read_tokens: u64,
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    /// Estimated.
+    #[serde(default)]
+    pub cost_usd: f64,
+    /// Answers counted.
+    #[serde(default)]
+    pub messages: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+        self.cost_usd += other.cost_usd;
+        self.messages += other.messages;
+    }
+}
+
+/// One answer's usage, as `transcripts` finds it.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub date: NaiveDate,
+    pub project: String,
+    pub provider: String,
+    pub usage: Usage,
+}
+
+/// usage.json: date -> project -> provider -> usage.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Store {
+    #[serde(default)]
+    days: BTreeMap<String, BTreeMap<String, BTreeMap<String, Usage>>>,
+    /// Budget periods already warned about, e.g. "day:2026-03-14".
+    #[serde(default)]
+    warned: Vec<String>,
+}
+
+impl Store {
+    /// Total over the days whose key starts with `prefix`.
+    fn total(&self, prefix: &str) -> Usage {
+        let mut total = Usage::default();
+        for (_, projects) in self.days.iter().filter(|(day, _)| day.starts_with(prefix)) {
+            for usage in projects.values().flat_map(|p| p.values()) {
+                total.add(usage);
+            }
+        }
+        total
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DayUsage {
+    pub date: String,
+    pub total: Usage,
+    pub projects: BTreeMap<String, Usage>,
+    pub providers: BTreeMap<String, Usage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageStats {
+    /// Oldest first; days without use are left out.
+    pub days: Vec<DayUsage>,
+    pub today: Usage,
+    pub this_month: Usage,
+    pub budget: Option<Budget>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BudgetWarning {
+    /// "day" or "month".
+    period: &'static str,
+    spent_usd: f64,
+    budget_usd: f64,
+}
+
+fn store_path() -> PathBuf {
+    get_config_dir().join("usage.json")
+}
+
+fn load() -> Store {
+    fs::read_to_string(store_path())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save(store: &Store) -> Result<(), String> {
+    fs::create_dir_all(get_config_dir())
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    let contents = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    fs::write(store_path(), contents).map_err(|e| format!("Failed to write usage.json: {}", e))
+}
+
+/// The budget periods that have just been reached.
+fn check_budget(store: &mut Store, budget: &Budget) -> Vec<BudgetWarning> {
+    let today = Local::now().date_naive();
+    let day = today.format("%Y-%m-%d").to_string();
+    let month = today.format("%Y-%m").to_string();
+    // Only the current periods matter.
+    store
+        .warned
+        .retain(|w| *w == format!("day:{}", day) || *w == format!("month:{}", month));
+
+    let mut warnings = Vec::new();
+    for (period, prefix, limit) in [
+        ("day", &day, budget.daily_usd),
+        ("month", &month, budget.monthly_usd),
+    ] {
+        let Some(limit) = limit else {
+            continue;
+        };
+        let key = format!("{}:{}", period, prefix);
+        let spent = store.total(prefix).cost_usd;
+        if spent >= limit && !store.warned.contains(&key) {
+            store.warned.push(key);
+            warnings.push(BudgetWarning {
+                period,
+                spent_usd: spent,
+                budget_usd: limit,
+            });
+        }
+    }
+    warnings
+}
+
+/// Add up `records` and warn if a budget has been reached.
+pub fn record(app: &AppHandle, records: &[Record]) {
+    if records.is_empty() {
+        return;
+    }
+    let Ok(_lock) = STORE_LOCK.lock() else {
+        return;
+    };
+    let mut store = load();
+    for record in records {
+        store
+            .days
+            .entry(record.date.format("%Y-%m-%d").to_string())
+            .or_default()
+            .entry(record.project.clone())
+            .or_default()
+            .entry(record.provider.clone())
+            .or_default()
+            .add(&record.usage);
+    }
+    let budget = read_config_file().usage_budget.unwrap_or_default();
+    let warnings = check_budget(&mut store, &budget);
+    if let Err(e) = save(&store) {
+        crate::logging::log_line("WARN", &format!("[usage] {}", e));
+        return;
+    }
+    for warning in warnings {
+        crate::logging::log_line(
+            "WARN",
+            &format!(
+                "[usage] Estimated cost this {} is ${:.2}, over the ${:.2} budget",
+                warning.period, warning.spent_usd, warning.budget_usd
+            ),
+        );
+        let _ = app.emit("budget-warning", warning);
+    }
+}
+
+/// Token use and estimated cost over the last `days` days (default
+/// `DEFAULT_STATS_DAYS`), today and this month.
+#[tauri::command]
+pub fn get_usage_stats(days: Option<u32>) -> UsageStats {
+    let days = days.unwrap_or(DEFAULT_STATS_DAYS).clamp(1, MAX_STATS_DAYS);
+    let today = Local::now().date_naive();
+    let first = (today - chrono::Duration::days(i64::from(days) - 1))
+        .format("%Y-%m-%d")
+        .to_string();
+    let store = load();
+    let per_day = store
+        .days
+        .range(first..)
+        .map(|(date, projects)| {
+            let mut day = DayUsage {
+                date: date.clone(),
+                total: Usage::default(),
+                projects: BTreeMap::new(),
+                providers: BTreeMap::new(),
+            };
+            for (project, providers) in projects {
+                for (provider, usage) in providers {
+                    day.total.add(usage);
+                    day.projects.entry(project.clone()).or_default().add(usage);
+                    day.providers
+                        .entry(provider.clone())
+                        .or_default()
+                        .add(usage);
+                }
+            }
+            day
+        })
+        .collect();
+    UsageStats {
+        days: per_day,
+        today: store.total(&today.format("%Y-%m-%d").to_string()),
+        this_month: store.total(&today.format("%Y-%m").to_string()),
+        budget: read_config_file().usage_budget,
+    }
+}
@@ -0,0 +1,138 @@
+//! Workspace file watcher.
+//!
+//! OpenCode edits files behind the frontend's back; until now the only sign
+//! was the Remotion preview reloading. Setup starts a watcher on the open
+//! workspace (replacing the previous one when the workspace changes). File
+//! system events are collected until things have been quiet for `DEBOUNCE`,
+//! then sent as one `workspace-changed` event:
+//!
+//! `{ workspace, changes: [{ path, kind }] }`
+//!
+//! where `path` is relative to the workspace and `kind` is "created",
+//! "modified", "removed" or "renamed". Dependencies, git internals and
+//! build output are ignored.
+
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How long events must stop arriving before a batch is sent.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// Top-level directories whose changes aren't interesting.
+const IGNORED_DIRS: &[&str] = &["node_modules", ".git", "out", "dist", "build", ".cache"];
+const IGNORED_FILES: &[&str] = &[".DS_Store"];
+
+/// The active watcher. Dropping it stops the watch and ends its thread.
+static WATCHER: Mutex<Option<RecommendedWatcher>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Change {
+    path: String,
+    kind: &'static str,
+}
+
+fn change_kind(kind: &EventKind) -> Option<&'static str> {
+    match kind {
+        EventKind::Create(_) => Some("created"),
+        EventKind::Remove(_) => Some("removed"),
+        EventKind::Modify(ModifyKind::Name(_)) => Some("renamed"),
+        EventKind::Modify(_) => Some("modified"),
+        _ => None,
+    }
+}
+
+/// `path` relative to `workspace`, unless it's somewhere we ignore.
+fn relevant_path(workspace: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(workspace).ok()?;
+    let first = relative.components().next()?.as_os_str().to_string_lossy();
+    if IGNORED_DIRS.contains(&first.as_ref()) {
+        return None;
+    }
+    let name = relative.file_name()?.to_string_lossy();
+    if IGNORED_FILES.contains(&name.as_ref()) || name.ends_with('~') {
+        return None;
+    }
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Watch `workspace`, replacing any earlier watcher.
+pub fn watch(app: &AppHandle, workspace: &Path) {
+    let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            crate::logging::log_line("WARN", &format!("[watcher] Couldn't create watcher: {}", e));
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(workspace, RecursiveMode::Recursive) {
+        crate::logging::log_line(
+            "WARN",
+            &format!("[watcher] Couldn't watch {:?}: {}", workspace, e),
+        );
+        return;
+    }
+    // Replacing the old watcher drops its sender, which ends its thread.
+    if let Ok(mut current) = WATCHER.lock() {
+        *current = Some(watcher);
+    }
+    crate::logging::log_line("INFO", &format!("[watcher] Watching {:?}", workspace));
+
+    let app = app.clone();
+    let workspace: PathBuf = workspace.to_path_buf();
+    std::thread::spawn(move || {
+        // Latest kind per path; a file created then modified in one batch
+        // is still reported as created.
+        let mut pending: BTreeMap<String, &'static str> = BTreeMap::new();
+        loop {
+            let timeout = if pending.is_empty() {
+                Duration::from_secs(3600)
+            } else {
+                DEBOUNCE
+            };
+            match rx.recv_timeout(timeout) {
+                Ok(Ok(event)) => {
+                    let Some(kind) = change_kind(&event.kind) else {
+                        continue;
+                    };
+                    for path in &event.paths {
+                        if let Some(path) = relevant_path(&workspace, path) {
+                            let entry = pending.entry(path).or_insert(kind);
+                            if *entry != "created" || kind == "removed" {
+                                *entry = kind;
+                            }
+                        }
+                    }
+                }
+                Ok(Err(e)) => {
+                    crate::logging::log_line("WARN", &format!("[watcher] {}", e));
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if pending.is_empty() {
+                        continue;
+                    }
+                    let changes: Vec<Change> = std::mem::take(&mut pending)
+                        .into_iter()
+                        .map(|(path, kind)| Change { path, kind })
+                        .collect();
+                    crate::autosave::note_change();
+                    let _ = app.emit(
+                        "workspace-changed",
+                        serde_json::json!({
+                            "workspace": workspace.to_string_lossy(),
+                            "changes": changes,
+                        }),
+                    );
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+}
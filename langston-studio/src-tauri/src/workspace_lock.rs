@@ -0,0 +1,212 @@
+//! Keeping a second copy of the app out of a workspace that's in use.
+//!
+//! Launching the app twice used to have both copies set up the same
+//! workspace: each released the other's ports as "left over from an earlier
+//! session" and both auto-saved into the same git history. Setup now takes
+//! a lock first, `.langston-studio.lock` in the workspace, recording the
+//! process holding it and when it last confirmed it's still running:
+//!
+//! ```json
+//! { "pid": 4242, "startedAt": "...", "heartbeat": "..." }
+//! ```
+//!
+//! The holder refreshes `heartbeat` every `HEARTBEAT_INTERVAL`. A lock whose
+//! process is gone, or whose heartbeat is older than `STALE_AFTER` (the
+//! process id may have been reused after a crash), is stale and taken over.
+//! A live one stops setup with `WorkspaceLocked` and a `workspace-locked`
+//! event, `{ workspace, pid, startedAt }`; `take_over_workspace` runs setup
+//! again regardless, for when the other copy has hung. The lock is removed
+//! when the app shuts down cleanly or switches to another workspace.
+
+use crate::error::StudioError;
+use crate::{frontend, git, platform, setup};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+pub(crate) const LOCK_FILE: &str = ".langston-studio.lock";
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const STALE_AFTER: Duration = Duration::from_secs(2 * 60);
+
+/// The workspace whose lock this process holds.
+static HELD: Mutex<Option<PathBuf>> = Mutex::new(None);
+static HEARTBEAT_STARTED: AtomicBool = AtomicBool::new(false);
+/// Set by `take_over_workspace` for the next `acquire`.
+static TAKE_OVER: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Lock {
+    pid: u32,
+    started_at: String,
+    heartbeat: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceLocked<'a> {
+    workspace: String,
+    pid: u32,
+    started_at: &'a str,
+}
+
+fn path(workspace: &Path) -> PathBuf {
+    workspace.join(LOCK_FILE)
+}
+
+fn read(workspace: &Path) -> Option<Lock> {
+    let contents = fs::read_to_string(path(workspace)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn write(workspace: &Path, lock: &Lock) -> Result<(), StudioError> {
+    let lock_path = path(workspace);
+    let contents = serde_json::to_string_pretty(lock).map_err(|e| e.to_string())?;
+    fs::write(&lock_path, contents)
+        .map_err(|e| StudioError::io_at("Failed to write", &lock_path, e))
+}
+
+/// Whether the process holding `lock` is gone or has stopped refreshing it.
+fn is_stale(lock: &Lock) -> bool {
+    if !platform::pid_alive(lock.pid) {
+        return true;
+    }
+    DateTime::parse_from_rfc3339(&lock.heartbeat)
+        .map(|heartbeat| {
+            let age = Local::now().signed_duration_since(heartbeat);
+            age.to_std().is_ok_and(|age| age > STALE_AFTER)
+        })
+        .unwrap_or(true)
+}
+
+/// Refresh the heartbeat of whichever lock is held, for the rest of the
+/// app's life.
+fn start_heartbeat() {
+    if HEARTBEAT_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(move || loop {
+        std::thread::sleep(HEARTBEAT_INTERVAL);
+        let Some(workspace) = HELD.lock().ok().and_then(|held| held.clone()) else {
+            continue;
+        };
+        match read(&workspace) {
+            Some(mut lock) if lock.pid == std::process::id() => {
+                lock.heartbeat = Local::now().to_rfc3339();
+                let _ = write(&workspace, &lock);
+            }
+            other => {
+                let holder = other
+                    .map(|lock| format!("process {}", lock.pid))
+                    .unwrap_or_else(|| "nobody".to_string());
+                crate::logging::log_line(
+                    "WARN",
+                    &format!(
+                        "[lock] The lock on {} is now held by {}; no longer refreshing it",
+                        workspace.display(),
+                        holder
+                    ),
+                );
+                if let Ok(mut held) = HELD.lock() {
+                    *held = None;
+                }
+            }
+        }
+    });
+}
+
+/// Lock `workspace` for this process, releasing the one held before. Fails
+/// with `WorkspaceLocked` when another running copy of the app has it.
+pub fn acquire(app: &AppHandle, workspace: &Path) -> Result<(), StudioError> {
+    let take_over = TAKE_OVER.swap(false, Ordering::SeqCst);
+    let pid = std::process::id();
+    if let Some(lock) = read(workspace) {
+        if lock.pid != pid && !take_over && !is_stale(&lock) {
+            crate::logging::log_line(
+                "WARN",
+                &format!(
+                    "[lock] {} is in use by process {} (since {})",
+                    workspace.display(),
+                    lock.pid,
+                    lock.started_at
+                ),
+            );
+            frontend::emit(
+                app,
+                "workspace-locked",
+                WorkspaceLocked {
+                    workspace: workspace.to_string_lossy().to_string(),
+                    pid: lock.pid,
+                    started_at: &lock.started_at,
+                },
+            );
+            return Err(StudioError::WorkspaceLocked { pid: lock.pid });
+        }
+        if lock.pid != pid {
+            crate::logging::log_line(
+                "INFO",
+                &format!(
+                    "[lock] Taking over the lock on {} from process {}{}",
+                    workspace.display(),
+                    lock.pid,
+                    if take_over { " as asked" } else { " (stale)" }
+                ),
+            );
+        }
+    }
+
+    let previous = HELD.lock().ok().and_then(|held| held.clone());
+    if let Some(previous) = previous.filter(|previous| previous != workspace) {
+        release_at(&previous);
+    }
+    fs::create_dir_all(workspace)
+        .map_err(|e| StudioError::io_at("Failed to create", workspace, e))?;
+    let now = Local::now().to_rfc3339();
+    write(
+        workspace,
+        &Lock {
+            pid,
+            started_at: now.clone(),
+            heartbeat: now,
+        },
+    )?;
+    // Workspaces created from the current template ignore it already.
+    git::exclude(workspace, LOCK_FILE);
+    if let Ok(mut held) = HELD.lock() {
+        *held = Some(workspace.to_path_buf());
+    }
+    start_heartbeat();
+    Ok(())
+}
+
+/// Remove the lock on `workspace` if it's still this process's.
+fn release_at(workspace: &Path) {
+    if read(workspace).is_some_and(|lock| lock.pid == std::process::id()) {
+        let _ = fs::remove_file(path(workspace));
+    }
+}
+
+/// Give up the lock this process holds, on shutdown.
+pub fn release() {
+    let held = HELD.lock().ok().and_then(|mut held| held.take());
+    if let Some(workspace) = held {
+        release_at(&workspace);
+    }
+}
+
+/// Run setup again, taking the workspace over from the copy of the app
+/// that has it locked.
+#[tauri::command]
+pub fn take_over_workspace(app: AppHandle) -> Result<(), StudioError> {
+    TAKE_OVER.store(true, Ordering::SeqCst);
+    let retried = setup::retry_setup(app);
+    if retried.is_err() {
+        TAKE_OVER.store(false, Ordering::SeqCst);
+    }
+    retried
+}